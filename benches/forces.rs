@@ -0,0 +1,59 @@
+/*
+ Force kernel and full-step benchmarks: "cargo bench". Every solver at
+ N = 256, 1k, 4k and 16k, serial and on all cores, plus whole leapfrog steps.
+ The stars are the first N of input/input16k, so runs compare like with like.
+ */
+#[macro_use]
+extern crate criterion;
+extern crate nbabel;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+
+use nbabel::*;
+use nbabel::solver;
+
+const SIZES: [usize; 4] = [256, 1024, 4096, 16384];
+
+fn stars(n: usize) -> Vec<Star> {
+	let text = std::fs::read_to_string("input/input16k").expect("Could not read input/input16k");
+	read_stars(&text.lines().take(n).collect::<Vec<_>>().join("\n"))
+}
+
+fn thread_counts() -> Vec<usize> {
+	let all = std::thread::available_parallelism().map_or(1, |x| x.get());
+	if all > 1 { vec![1, all] } else { vec![1] }
+}
+
+fn forces(c: &mut Criterion) {
+	for &name in ["direct", "tree", "auto"].iter() {
+		let mut group = c.benchmark_group(format!("forces/{}", name));
+		group.sample_size(10);
+		for &n in SIZES.iter() {
+			group.throughput(Throughput::Elements(n as u64));
+			for &threads in thread_counts().iter() {
+				let mut p = Params::default();
+				p.threads = threads;
+				let mut solver = solver::by_name::<f64>(name, 0.5).expect("Unknown solver");
+				let mut s = stars(n);
+				group.bench_with_input(BenchmarkId::new(format!("{} threads", threads), n), &n, |b, _| {
+					b.iter(|| solver.accelerations(&mut s, &p))
+				});
+			}
+		}
+		group.finish();
+	}
+}
+
+fn steps(c: &mut Criterion) {
+	let mut group = c.benchmark_group("step");
+	group.sample_size(10);
+	for &n in SIZES[..3].iter() {
+		let mut sim = Simulation::new(stars(n), Params::default());
+		group.throughput(Throughput::Elements(n as u64));
+		group.bench_function(BenchmarkId::from_parameter(n), |b| b.iter(|| sim.step()));
+	}
+	group.finish();
+}
+
+criterion_group!(benches, forces, steps);
+criterion_main!(benches);
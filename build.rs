@@ -0,0 +1,20 @@
+/*
+ Records the git commit being built as NBABEL_GIT for metadata.rs, with
+ "-dirty" when the tree has uncommitted changes. Builds outside a checkout
+ leave it unset.
+ */
+use std::process::Command;
+
+fn git(args: &[&str]) -> Option<String> {
+	let out = Command::new("git").args(args).output().ok()?;
+	if out.status.success() { String::from_utf8(out.stdout).ok() } else { None }
+}
+
+fn main() {
+	println!("cargo:rerun-if-changed=.git/HEAD");
+	println!("cargo:rerun-if-changed=.git/index");
+	if let Some(hash) = git(&["rev-parse", "--short=12", "HEAD"]) {
+		let dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|x| !x.trim().is_empty());
+		println!("cargo:rustc-env=NBABEL_GIT={}{}", hash.trim(), if dirty { "-dirty" } else { "" });
+	}
+}
@@ -0,0 +1,58 @@
+// Draws the x-y projection of a King cluster integrated by nbabel in the browser
+import init, { Demo } from "./pkg/nbabel.js";
+
+const sky = document.getElementById("sky");
+const ctx = sky.getContext("2d");
+const info = document.getElementById("info");
+const value = id => Number(document.getElementById(id).value);
+
+let demo = null;
+let e0 = 0;
+let running = true;
+
+function reset() {
+	if (demo) {
+		demo.free();
+	}
+	try {
+		demo = new Demo(value("n"), value("seed"), 1e-3, 0.01);
+	} catch (error) {
+		info.textContent = error;
+		demo = null;
+		return;
+	}
+	e0 = demo.energy();
+}
+
+function draw() {
+	const r = demo.positions();
+	const scale = sky.width/6;
+	ctx.fillStyle = "#000";
+	ctx.fillRect(0, 0, sky.width, sky.height);
+	ctx.fillStyle = "#fff";
+	for (let i = 0; i < r.length; i += 3) {
+		ctx.fillRect(sky.width/2 + scale*r[i], sky.height/2 - scale*r[i + 1], 2, 2);
+	}
+	const de = (demo.energy() - e0)/e0;
+	info.textContent = `t = ${demo.time().toFixed(3)}  N = ${demo.count()}  dE/E = ${de.toExponential(2)}`;
+}
+
+function frame() {
+	if (demo) {
+		if (running) {
+			demo.step(value("rate"));
+		}
+		draw();
+	}
+	requestAnimationFrame(frame);
+}
+
+document.getElementById("reset").onclick = reset;
+document.getElementById("pause").onclick = event => {
+	running = !running;
+	event.target.textContent = running ? "pause" : "run";
+};
+
+await init();
+reset();
+requestAnimationFrame(frame);
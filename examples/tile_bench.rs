@@ -0,0 +1,42 @@
+/*
+ Times one force evaluation for a range of tile sizes, including the untiled
+ loop (tile >= N).
+
+ cargo run --release --example tile_bench [INPUT] [THREADS]
+ */
+extern crate nbabel;
+
+use std::env;
+use std::fs;
+use std::time::Instant;
+
+use nbabel::*;
+
+fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	let path = args.first().map_or("input/input8k", |x| x.as_str());
+	let threads: usize = args.get(1).map_or(1, |x| x.parse().expect("THREADS must be a number"));
+	let mut s: Vec<Star> = read_stars(&fs::read_to_string(path).expect("Could not read input"));
+	let n = s.len();
+	let mut p = Params::default();
+	p.threads = threads;
+
+	println!("N = {}, {} thread(s)", n, threads);
+	let mut untiled = 0.0;
+	for &tile in &[n, 16, 32, 64, 128, 256, 512] {
+		pairs::set_tile_size(tile);
+		acceleration(&mut s, &p);
+		let mut best = std::f64::INFINITY;
+		for _ in 0..3 {
+			let clock = Instant::now();
+			acceleration(&mut s, &p);
+			best = best.min(clock.elapsed().as_secs_f64());
+		}
+		if tile == n {
+			untiled = best;
+			println!("untiled    {:.4} s", best);
+		} else {
+			println!("tile {:4}  {:.4} s  ({:.2}x)", tile, best, untiled/best);
+		}
+	}
+}
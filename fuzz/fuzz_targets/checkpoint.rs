@@ -0,0 +1,14 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate nbabel;
+
+use nbabel::checkpoint;
+use nbabel::dd::DoubleDouble;
+
+fuzz_target!(|data: &[u8]| {
+	if let Ok(text) = std::str::from_utf8(data) {
+		let _ = checkpoint::read::<f64>(text);
+		let _ = checkpoint::read::<DoubleDouble>(text);
+	}
+});
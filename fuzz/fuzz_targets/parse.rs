@@ -0,0 +1,15 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate nbabel;
+
+use nbabel::columns::Columns;
+
+// The input parser must turn any text into stars or an error, never panic
+fuzz_target!(|data: &[u8]| {
+	if let Ok(text) = std::str::from_utf8(data) {
+		let _ = nbabel::parse_stars::<f64>(text);
+		let _ = nbabel::parse_stars::<f32>(text);
+		let _ = Columns::read(text);
+	}
+});
@@ -0,0 +1,14 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate nbabel;
+
+use nbabel::fuzz;
+
+// Random but valid systems, a few steps each, invariants checked throughout
+fuzz_target!(|data: &[u8]| {
+	let (s, p) = fuzz::random_system(data);
+	if let Err(msg) = fuzz::check(s, p, 5) {
+		panic!("{}", msg);
+	}
+});
@@ -0,0 +1,47 @@
+/*
+ C interface to nbabel, see src/ffi.rs. Link with -lnbabel (the cdylib from
+ "cargo build --release", in target/release).
+
+ All values are doubles in N-body units (G = 1). Positions, velocities and
+ accelerations are arrays of 3n doubles: x, y, z of each star in turn.
+ Functions returning int give NBABEL_OK or a negative NBABEL_E* code.
+ */
+#ifndef NBABEL_H
+#define NBABEL_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define NBABEL_OK 0
+#define NBABEL_ENULL (-1)
+#define NBABEL_EINVAL (-2)
+#define NBABEL_EPANIC (-3)
+
+typedef struct NBabelSimulation NBabelSimulation;
+
+/* v may be NULL for stars at rest; returns NULL on bad arguments */
+NBabelSimulation *nbabel_create(size_t n, const double *m, const double *r, const double *v, double dt, double eps);
+void nbabel_destroy(NBabelSimulation *sim);
+
+/* Replaces the stars, keeping the time and settings */
+int nbabel_set_particles(NBabelSimulation *sim, size_t n, const double *m, const double *r, const double *v);
+int nbabel_step(NBabelSimulation *sim, size_t steps);
+
+size_t nbabel_count(const NBabelSimulation *sim);
+double nbabel_time(const NBabelSimulation *sim);
+/* Any of m, r, v and a may be NULL; n must be nbabel_count(sim) */
+int nbabel_get_state(const NBabelSimulation *sim, size_t n, double *m, double *r, double *v, double *a);
+/* e[0] total, e[1] kinetic, e[2] potential */
+int nbabel_energy(const NBabelSimulation *sim, double *e);
+
+/* The gravity kernel alone: accelerations of n stars, softened with eps */
+int nbabel_accelerations(size_t n, const double *m, const double *r, double eps, double *a);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
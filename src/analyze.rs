@@ -0,0 +1,177 @@
+/*
+ "nbabel analyze SNAPSHOT..." summarizes snapshot files (the input format,
+ checkpoints included) as one CSV row each. The files are streamed line by
+ line, so archives far larger than RAM can be processed:
+
+ - pass 1: star count, total mass, centre of mass and its velocity, kinetic
+   energy in the centre-of-mass frame;
+ - pass 2: smallest and largest distance from the centre of mass;
+ - pass 3: a logarithmic histogram of mass against that distance, from which
+   the Lagrangian radii are read off (to about 0.25% in radius).
+
+ Potential energy, the virial ratio, the density centre and the bound count
+ need all pairs of stars and so the whole snapshot in memory; --full adds
+ them at O(N) memory and O(N^2) time.
+ */
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+
+use diagnostics;
+use {energies, read_stars, Params, Star};
+
+// Histogram bins per decade of radius
+static BINS_PER_DECADE: f64 = 1000.0;
+
+fn parse_star(line: &str) -> Option<Result<Star, String>> {
+	let line = line.trim();
+	if line.is_empty() || line.starts_with('#') {
+		return None;
+	}
+	let x: Result<Vec<f64>, _> = line.split_whitespace().map(|x| x.parse::<f64>()).collect();
+	Some(match x {
+		Ok(ref x) if x.len() >= 8 => Ok(Star { m: x[1], r: x[2..5].to_vec(), v: x[5..8].to_vec(), a: vec![0.0; 3], a0: vec![0.0; 3] }),
+		_ => Err(format!("Bad star line '{}'", line)),
+	})
+}
+
+// Calls f for every star in the file, returns the checkpoint time if there is one
+fn stream<F: FnMut(&Star)>(path: &str, mut f: F) -> Result<Option<f64>, String> {
+	let file = File::open(path).map_err(|x| format!("{}: {}", path, x))?;
+	let mut t = None;
+	for line in BufReader::new(file).lines() {
+		let line = line.map_err(|x| format!("{}: {}", path, x))?;
+		if line.starts_with("# nbabel checkpoint") {
+			let words: Vec<&str> = line.split_whitespace().collect();
+			if let Some(k) = words.iter().position(|&w| w == "t") {
+				t = words.get(k + 2).and_then(|x| x.parse().ok());
+			}
+		}
+		if let Some(star) = parse_star(&line) {
+			f(&star?);
+		}
+	}
+	Ok(t)
+}
+
+pub struct Summary {
+	pub t: Option<f64>,
+	pub n: usize,
+	pub mass: f64,
+	pub com: [f64; 3],
+	pub vcom: [f64; 3],
+	pub kinetic: f64,
+	pub radii: Vec<f64>,
+	// Only with --full: W, Q, density centre, bound count
+	pub full: Option<(f64, f64, Vec<f64>, usize)>,
+}
+
+pub fn summarize(path: &str, fractions: &[f64], full: bool) -> Result<Summary, String> {
+	// Pass 1: moments
+	let (mut n, mut mass) = (0, 0.0);
+	let mut mr = [0.0; 3];
+	let mut mv = [0.0; 3];
+	let mut mv2 = 0.0;
+	let t = stream(path, |x| {
+		n += 1;
+		mass += x.m;
+		for i in 0..3 {
+			mr[i] += x.m*x.r[i];
+			mv[i] += x.m*x.v[i];
+		}
+		mv2 += x.m*(x.v[0]*x.v[0] + x.v[1]*x.v[1] + x.v[2]*x.v[2]);
+	})?;
+	if n == 0 || mass <= 0.0 {
+		return Err(format!("{}: no stars with mass", path));
+	}
+	let com = [mr[0]/mass, mr[1]/mass, mr[2]/mass];
+	let vcom = [mv[0]/mass, mv[1]/mass, mv[2]/mass];
+	let kinetic = 0.5*(mv2 - mass*(vcom[0]*vcom[0] + vcom[1]*vcom[1] + vcom[2]*vcom[2]));
+
+	// Pass 2: radial extent, pass 3: mass histogram in log radius
+	let distance = |x: &Star| ((x.r[0] - com[0]).powi(2) + (x.r[1] - com[1]).powi(2) + (x.r[2] - com[2]).powi(2)).sqrt();
+	let mut rmin = f64::INFINITY;
+	let mut rmax: f64 = 0.0;
+	stream(path, |x| {
+		let d = distance(x);
+		if d > 0.0 {
+			rmin = rmin.min(d);
+		}
+		rmax = rmax.max(d);
+	})?;
+	let lo = if rmin.is_finite() { rmin.log10() } else { 0.0 };
+	let bins = (((rmax.max(rmin).log10() - lo)*BINS_PER_DECADE).ceil() as usize).max(1) + 1;
+	let mut histogram = vec![0.0; bins];
+	stream(path, |x| {
+		let d = distance(x);
+		let b = if d > 0.0 { ((d.log10() - lo)*BINS_PER_DECADE).max(0.0) as usize } else { 0 };
+		histogram[b.min(bins - 1)] += x.m;
+	})?;
+	let mut radii = vec![];
+	let mut menc = 0.0;
+	let mut f = 0;
+	for (b, m) in histogram.iter().enumerate() {
+		menc += m;
+		while f < fractions.len() && menc >= fractions[f]*mass {
+			radii.push(10f64.powf(lo + (b + 1) as f64/BINS_PER_DECADE).min(rmax));
+			f += 1;
+		}
+	}
+	while radii.len() < fractions.len() {
+		radii.push(rmax);
+	}
+
+	let full = if full {
+		let text = std::fs::read_to_string(path).map_err(|x| format!("{}: {}", path, x))?;
+		let s: Vec<Star> = read_stars(&text);
+		let e = energies(&s, &Params::default());
+		Some((e[2], diagnostics::virial_ratio(&e), diagnostics::density_center(&s), diagnostics::bound_count(&s)))
+	} else {
+		None
+	};
+	Ok(Summary { t: t, n: n, mass: mass, com: com, vcom: vcom, kinetic: kinetic, radii: radii, full: full })
+}
+
+pub fn main(args: &[String]) {
+	let usage = "Usage: nbabel analyze [--full] SNAPSHOT...";
+	let mut full = false;
+	let mut files = vec![];
+	for arg in args {
+		match arg.as_str() {
+			"--full" => full = true,
+			_ => files.push(arg.clone()),
+		}
+	}
+	if files.is_empty() {
+		panic!("{}", usage);
+	}
+
+	let fractions = &diagnostics::FRACTIONS;
+	let mut header = String::from("file,t,N,M,xcm,ycm,zcm,vxcm,vycm,vzcm,T");
+	for f in fractions.iter() {
+		header += &format!(",r{}", (f*100.0).round());
+	}
+	if full {
+		header += ",W,Q,xd,yd,zd,n_bound";
+	}
+	println!("{}", header);
+	for path in &files {
+		let x = match summarize(path, fractions, full) {
+			Ok(x) => x,
+			Err(msg) => {
+				warn!("Skipping {}", msg);
+				continue;
+			},
+		};
+		let mut row = format!("{},{},{},{},{},{},{},{},{},{},{}", path, x.t.map_or(String::new(), |t| t.to_string()), x.n, x.mass,
+			x.com[0], x.com[1], x.com[2], x.vcom[0], x.vcom[1], x.vcom[2], x.kinetic);
+		for r in &x.radii {
+			row += &format!(",{}", r);
+		}
+		if let Some((w, q, ref center, bound)) = x.full {
+			row += &format!(",{},{},{},{},{},{}", w, q, center[0], center[1], center[2], bound);
+		}
+		println!("{}", row);
+	}
+	let _ = io::Write::flush(&mut io::stdout());
+}
@@ -0,0 +1,140 @@
+/*
+ Picks the thread count and pair-loop tile size for a run by timing a few
+ force evaluations on the actual stars, and remembers the choice in a
+ per-machine cache so later runs of a similar size start with it.
+
+ The cache is a text file, NBABEL_TUNE_FILE or else autotune under
+ $XDG_CACHE_HOME/nbabel or ~/.cache/nbabel, with one line per setting:
+
+ solver precision n cores threads tile
+
+ where n is the star count rounded up to a power of two and cores what the
+ machine offered when it was measured, so a changed machine tunes again.
+ */
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bench;
+use pairs;
+use solver;
+use timing::Clock;
+use {thread_count, Params, Real, Star};
+
+// Below this many stars a run is too short for the tuning to pay off
+pub static MIN_N: usize = 1024;
+
+// Tile sides tried for the direct pair loop, around pairs::BLOCK
+static TILES: [usize; 4] = [32, 64, 128, 256];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+	pub threads: usize,
+	pub tile: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Key {
+	pub solver: String,
+	pub precision: String,
+	pub n: usize,
+	pub cores: usize,
+}
+
+impl Key {
+	pub fn new(solver: &str, precision: &str, n: usize) -> Key {
+		Key { solver: String::from(solver), precision: String::from(precision), n: n.next_power_of_two(), cores: thread_count() }
+	}
+}
+
+// Where the cache lives on this machine, if anywhere
+pub fn cache_path() -> Option<PathBuf> {
+	if let Ok(x) = env::var("NBABEL_TUNE_FILE") {
+		return Some(PathBuf::from(x));
+	}
+	let dir = env::var("XDG_CACHE_HOME").map(PathBuf::from)
+		.or_else(|_| env::var("HOME").map(|x| Path::new(&x).join(".cache")))
+		.ok()?;
+	Some(dir.join("nbabel").join("autotune"))
+}
+
+fn parse_line(line: &str) -> Option<(Key, Tuning)> {
+	let x: Vec<&str> = line.split_whitespace().collect();
+	if x.len() != 6 || line.starts_with('#') {
+		return None;
+	}
+	let key = Key { solver: String::from(x[0]), precision: String::from(x[1]), n: x[2].parse().ok()?, cores: x[3].parse().ok()? };
+	Some((key, Tuning { threads: x[4].parse().ok()?, tile: x[5].parse().ok()? }))
+}
+
+// The cached setting for key; a missing or unreadable cache has none
+pub fn load(path: &Path, key: &Key) -> Option<Tuning> {
+	let text = fs::read_to_string(path).ok()?;
+	text.lines().filter_map(parse_line).find(|x| x.0 == *key).map(|x| x.1)
+}
+
+// Stores the setting for key, replacing an older one
+pub fn save(path: &Path, key: &Key, x: Tuning) -> io::Result<()> {
+	let text = fs::read_to_string(path).unwrap_or_default();
+	let mut lines: Vec<String> = text.lines().filter(|l| parse_line(l).map_or(true, |y| y.0 != *key)).map(String::from).collect();
+	if lines.is_empty() {
+		lines.push(String::from("# nbabel autotune: solver precision n cores threads tile"));
+	}
+	lines.push(format!("{} {} {} {} {} {}", key.solver, key.precision, key.n, key.cores, x.threads, x.tile));
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	fs::write(path, lines.join("\n") + "\n")
+}
+
+// Seconds of the faster of two force evaluations after a warm-up
+fn time<R: Real>(s: &Vec<Star<R>>, p: &Params<R>, name: &str, theta: f64) -> f64 {
+	let mut s = s.clone();
+	let mut solver = solver::by_name::<R>(name, theta).expect("Solver name was checked when parsing");
+	solver.accelerations(&mut s, p);
+	let mut best = std::f64::INFINITY;
+	for _ in 0..2 {
+		let clock = Clock::start();
+		solver.accelerations(&mut s, p);
+		best = best.min(clock.seconds());
+	}
+	best
+}
+
+/*
+ Doubles the thread count up to every core, stopping once more threads stop
+ helping, then tries the tile sizes at the best count. The tree does not use
+ the tiled pair loop, so it keeps the default tile. Leaves the tile size of
+ the process at the winner.
+ */
+pub fn tune<R: Real>(s: &Vec<Star<R>>, p: &Params<R>, name: &str, theta: f64) -> Tuning {
+	let mut p = p.clone();
+	pairs::set_tile_size(pairs::BLOCK);
+	let mut best = Tuning { threads: 1, tile: pairs::BLOCK };
+	let mut fastest = std::f64::INFINITY;
+	for threads in bench::thread_counts(&format!("1..{}", thread_count())).expect("At least one thread") {
+		p.threads = threads;
+		let seconds = time(s, &p, name, theta);
+		verbose!("Autotune: {} threads, tile {}: {:.4} s", threads, pairs::BLOCK, seconds);
+		if seconds >= fastest {
+			break;
+		}
+		fastest = seconds;
+		best.threads = threads;
+	}
+	if name != "tree" {
+		p.threads = best.threads;
+		for &tile in TILES.iter().filter(|&&x| x != pairs::BLOCK) {
+			pairs::set_tile_size(tile);
+			let seconds = time(s, &p, name, theta);
+			verbose!("Autotune: {} threads, tile {}: {:.4} s", best.threads, tile, seconds);
+			if seconds < fastest {
+				fastest = seconds;
+				best.tile = tile;
+			}
+		}
+	}
+	pairs::set_tile_size(best.tile);
+	best
+}
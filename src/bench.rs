@@ -0,0 +1,167 @@
+/*
+ "nbabel bench" times each solver on Plummer spheres of a range of sizes and
+ thread counts, so solvers, thread counts and machines can be compared
+ without setting up a run:
+
+ nbabel bench [-n 1024,4096,16384] [--solver direct,tree,auto] [--threads 1..16]
+              [--steps K] [--repeat R] [--seed S]
+
+ Thread counts are a list, where A..B doubles from A up to B (1..12 is 1, 2,
+ 4, 8, 12); the default is 1 up to every core. Every combination builds a
+ fresh Simulation, whose initial force evaluation is the untimed warm-up,
+ and then the best of R runs of K steps counts. The result is CSV on stdout,
+
+ solver,n,threads,seconds_per_step,pairs_per_second,speedup,efficiency
+
+ with the pair rate counted as N(N-1)/2 per step for every solver, so it is
+ the speed-up over direct summation that the tree shows there. Speed-up and
+ parallel efficiency are relative to the fewest threads measured for the same
+ solver and N. The recommended settings for each N go to the log.
+ */
+use std::io;
+use std::io::Write;
+
+use generate;
+use rng::Rng;
+use solver;
+use timing::Clock;
+use {thread_count, Params, Simulation};
+
+// A recommendation may be this much slower than the fastest when it needs fewer threads
+static SLACK: f64 = 1.1;
+
+// One measured combination
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+	pub solver: String,
+	pub n: usize,
+	pub threads: usize,
+	pub seconds: f64,
+}
+
+/*
+ Thread counts from "4", "1,2,6" or "1..16", ascending and without
+ duplicates. None for anything else, zero included.
+ */
+pub fn thread_counts(spec: &str) -> Option<Vec<usize>> {
+	let mut counts = vec![];
+	for item in spec.split(',') {
+		let item = item.trim();
+		if let Some(at) = item.find("..") {
+			let lo: usize = item[..at].trim().parse().ok()?;
+			let hi: usize = item[at + 2..].trim().parse().ok()?;
+			if lo == 0 || hi < lo {
+				return None;
+			}
+			let mut k = lo;
+			while k < hi {
+				counts.push(k);
+				k *= 2;
+			}
+			counts.push(hi);
+		} else {
+			counts.push(item.parse().ok().filter(|&x| x > 0)?);
+		}
+	}
+	counts.sort();
+	counts.dedup();
+	Some(counts)
+}
+
+/*
+ The setting to use for each N, in the order the sizes were measured: the
+ fewest threads that come within SLACK of the fastest time of any solver, so
+ cores that barely help are left free.
+ */
+pub fn recommend(rows: &[Row]) -> Vec<Row> {
+	let mut picks: Vec<Row> = vec![];
+	for row in rows {
+		if picks.iter().any(|x| x.n == row.n) {
+			continue;
+		}
+		let same: Vec<&Row> = rows.iter().filter(|x| x.n == row.n).collect();
+		let best = same.iter().map(|x| x.seconds).fold(std::f64::INFINITY, f64::min);
+		let pick = same.iter().filter(|x| x.seconds <= SLACK*best)
+			.min_by(|a, b| a.threads.cmp(&b.threads).then(a.seconds.partial_cmp(&b.seconds).unwrap()))
+			.expect("The fastest row is within the slack");
+		picks.push((*pick).clone());
+	}
+	picks
+}
+
+fn list<T: std::str::FromStr>(x: Option<&String>, what: &str) -> Vec<T> {
+	x.expect(what).split(',').map(|x| x.trim().parse().ok().expect(what)).collect()
+}
+
+pub fn main(args: &[String]) {
+	let usage = "Usage: nbabel bench [-n N,N,...] [--solver direct,tree,auto] [--threads 1..T] [--steps K] [--repeat R] [--seed S]";
+	let mut sizes: Vec<usize> = vec![1024, 4096];
+	let mut solvers: Vec<String> = vec![String::from("direct"), String::from("tree")];
+	let mut threads: Vec<usize> = thread_counts(&format!("1..{}", thread_count())).expect("At least one thread");
+	let mut steps: usize = 10;
+	let mut repeat: usize = 3;
+	let mut seed: u64 = 1;
+	let mut it = args.iter();
+	while let Some(arg) = it.next() {
+		match arg.as_str() {
+			"-n" | "--n" => sizes = list(it.next(), "-n needs star counts N,N,..."),
+			"--solver" => solvers = list(it.next(), "--solver needs solver names"),
+			"--threads" => threads = it.next().and_then(|x| thread_counts(x)).expect("--threads needs thread counts T,T,... or A..B, at least 1"),
+			"--steps" => steps = it.next().and_then(|x| x.parse().ok()).filter(|&x| x > 0).expect("--steps needs at least 1 step"),
+			"--repeat" => repeat = it.next().and_then(|x| x.parse().ok()).filter(|&x| x > 0).expect("--repeat needs at least 1 run"),
+			"--seed" => seed = it.next().and_then(|x| x.parse().ok()).expect("--seed needs a number"),
+			_ => panic!("Unknown argument: {}\n{}", arg, usage),
+		}
+	}
+	for name in &solvers {
+		if solver::by_name::<f64>(name, solver::THETA).is_none() {
+			panic!("Unknown solver '{}', use direct, tree or auto", name);
+		}
+	}
+
+	let stdout = io::stdout();
+	let mut out = io::BufWriter::new(stdout.lock());
+	writeln!(out, "solver,n,threads,seconds_per_step,pairs_per_second,speedup,efficiency").expect("Could not write output");
+	let mut rows: Vec<Row> = vec![];
+	for &n in &sizes {
+		let s = generate::plummer(n, &mut Rng::new(seed));
+		let pairs = (n*n.saturating_sub(1)/2) as f64;
+		for name in &solvers {
+			let mut base: Option<(usize, f64)> = None;
+			for &t in &threads {
+				let mut p: Params = Params::default();
+				p.threads = t;
+				let mut best = std::f64::INFINITY;
+				for _ in 0..repeat {
+					let solver = solver::by_name::<f64>(name, solver::THETA).expect("Solver name was checked above");
+					let mut sim = Simulation::with_solver(s.clone(), p.clone(), solver);
+					let clock = Clock::start();
+					for _ in 0..steps {
+						sim.step();
+					}
+					best = best.min(clock.seconds()/steps as f64);
+				}
+				let (t0, s0) = *base.get_or_insert((t, best));
+				let speedup = s0/best;
+				info!("{} with N = {} on {} threads: {:.4} s per step", name, n, t, best);
+				writeln!(out, "{},{},{},{},{},{},{}", name, n, t, best, pairs/best, speedup, speedup*t0 as f64/t as f64).expect("Could not write output");
+				out.flush().expect("Could not write output");
+				rows.push(Row { solver: name.clone(), n: n, threads: t, seconds: best });
+			}
+		}
+	}
+
+	for x in recommend(&rows) {
+		info!("N = {}: --solver {} --threads {} ({:.4} s per step)", x.n, x.solver, x.threads, x.seconds);
+	}
+	// Where the tree starts to pay off, against AUTO_MIN_N for --solver auto
+	let fastest = |name: &str, n: usize| rows.iter().filter(|x| x.solver == name && x.n == n).map(|x| x.seconds).fold(std::f64::INFINITY, f64::min);
+	let mut measured: Vec<usize> = sizes.clone();
+	measured.sort();
+	if solvers.iter().any(|x| x == "direct") && solvers.iter().any(|x| x == "tree") {
+		match measured.iter().find(|&&n| fastest("tree", n) < fastest("direct", n)) {
+			Some(n) => info!("The tree beats direct summation from N = {} here (--solver auto switches at {})", n, solver::AUTO_MIN_N),
+			None => info!("Direct summation is faster at every N measured"),
+		}
+	}
+}
@@ -0,0 +1,263 @@
+/*
+ Bound pairs: detection and a catalog that follows every binary through the
+ run. A binary is a pair of mutual nearest neighbours with negative two-body
+ energy. Each gets a persistent ID when it is first found; the catalog logs
+ its formation, its semi-major axis and eccentricity at every check
+ (hardening history), exchanges (one member replaced by a third star) and
+ disruption, to DIR/binaries.csv, and writes one summary line per binary to
+ DIR/binary_catalog.csv at the end. Formation, exchange and disruption also
+ go to the run log at -v, and statistics() sums the run up.
+ */
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use columns::Columns;
+use orbit;
+use {pairs, Real, Star};
+
+pub struct Pair {
+	pub i: usize,
+	pub j: usize,
+	// Semi-major axis, eccentricity and two-body energy per reduced mass
+	pub a: f64,
+	pub e: f64,
+	pub energy: f64,
+}
+
+/*
+ Semi-major axis, eccentricity and specific energy of the relative orbit of
+ stars i and j (G = 1). The energy is positive for unbound pairs.
+ */
+pub fn two_body<R: Real>(s: &Vec<Star<R>>, i: usize, j: usize) -> (f64, f64, f64) {
+	let (x, mu) = orbit::of_pair(s, i, j);
+	(x.a, x.e, -mu/(2.0*x.a))
+}
+
+// Mutual nearest neighbours with negative pair energy, ordered by i
+pub fn find_pairs<R: Real>(s: &Vec<Star<R>>) -> Vec<Pair> {
+	let mut nearest: Vec<(R, usize)> = vec![(R::infinity(), 0); s.len()];
+	pairs::for_each_pair(s, |i, j, _, r2| {
+		if r2 < nearest[i].0 {
+			nearest[i] = (r2, j);
+		}
+		if r2 < nearest[j].0 {
+			nearest[j] = (r2, i);
+		}
+	});
+
+	let mut found = vec![];
+	for i in 0..s.len() {
+		let j = nearest[i].1;
+		if j > i && nearest[j].1 == i && nearest[i].0 < R::infinity() {
+			let (a, e, energy) = two_body(s, i, j);
+			if energy < 0.0 {
+				found.push(Pair { i: i, j: j, a: a, e: e, energy: energy });
+			}
+		}
+	}
+	found
+}
+
+pub struct Binary {
+	pub id: usize,
+	pub i: usize,
+	pub j: usize,
+	pub t_form: f64,
+	// Time it was last seen, or disrupted
+	pub t_end: f64,
+	pub a_form: f64,
+	pub a: f64,
+	pub e: f64,
+	pub exchanges: usize,
+	pub disrupted: bool,
+}
+
+/*
+ Binaries formed and disrupted over the run, exchanges, and the semi-major
+ axes and eccentricities of those bound at the last check (NaN if none).
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Statistics {
+	pub formed: usize,
+	pub disrupted: usize,
+	pub bound: usize,
+	pub exchanges: usize,
+	pub min_a: f64,
+	pub median_a: f64,
+	pub median_e: f64,
+}
+
+impl fmt::Display for Statistics {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} formed, {} disrupted, {} bound, {} exchanges", self.formed, self.disrupted, self.bound, self.exchanges)?;
+		if self.bound > 0 {
+			write!(f, "; bound: a min {:.4e}, median {:.4e}, median e {:.3}", self.min_a, self.median_a, self.median_e)?;
+		}
+		Ok(())
+	}
+}
+
+pub static EVENTS_HEADER: &'static str = "t,id,event,i,j,a,e";
+pub static CATALOG_HEADER: &'static str = "id,i,j,t_form,t_end,a_form,a_final,e_final,exchanges,status";
+
+pub struct Catalog {
+	pub binaries: Vec<Binary>,
+	// Indices into binaries of the ones currently bound
+	active: Vec<usize>,
+	events: Option<BufWriter<File>>,
+	out_dir: Option<Box<Path>>,
+	// Ids and extra input columns, written for both members in the catalog
+	pub columns: Columns,
+}
+
+impl Catalog {
+	pub fn new(out_dir: Option<&Path>) -> io::Result<Catalog> {
+		let events = match out_dir {
+			Some(dir) => {
+				let mut f = BufWriter::new(File::create(dir.join("binaries.csv"))?);
+				writeln!(f, "{}", EVENTS_HEADER)?;
+				Some(f)
+			},
+			None => None,
+		};
+		Ok(Catalog { binaries: vec![], active: vec![], events: events, out_dir: out_dir.map(|x| x.into()), columns: Columns::default() })
+	}
+
+	fn log(&mut self, t: f64, b: usize, event: &str) -> io::Result<()> {
+		let x = &self.binaries[b];
+		if event != "update" {
+			verbose!("Binary {} ({}, {}) {} at t = {}: a = {}, e = {}", x.id, x.i, x.j, event, t, x.a, x.e);
+		}
+		if let Some(ref mut f) = self.events {
+			writeln!(f, "{},{},{},{},{},{},{}", t, x.id, event, x.i, x.j, x.a, x.e)?;
+		}
+		Ok(())
+	}
+
+	/*
+	 Matches the pairs bound now against the active binaries. The same two
+	 stars keep their ID. A binary that lost one member while that member's
+	 partner is now bound to a third star is an exchange and keeps its ID;
+	 otherwise a vanished binary is disrupted and a new pair forms a new one.
+	 */
+	pub fn update<R: Real>(&mut self, t: f64, s: &Vec<Star<R>>) -> io::Result<()> {
+		let found = find_pairs(s);
+		let mut matched: Vec<Option<usize>> = vec![None; found.len()];
+		let mut still: Vec<bool> = vec![false; self.active.len()];
+
+		for (k, p) in found.iter().enumerate() {
+			for (slot, &b) in self.active.iter().enumerate() {
+				let x = &self.binaries[b];
+				if x.i == p.i && x.j == p.j {
+					matched[k] = Some(b);
+					still[slot] = true;
+				}
+			}
+		}
+		for (k, p) in found.iter().enumerate() {
+			if matched[k].is_some() {
+				continue;
+			}
+			for (slot, &b) in self.active.iter().enumerate() {
+				let x = &self.binaries[b];
+				let shares = x.i == p.i || x.i == p.j || x.j == p.i || x.j == p.j;
+				if !still[slot] && shares {
+					matched[k] = Some(b);
+					still[slot] = true;
+					self.binaries[b].exchanges += 1;
+					self.binaries[b].i = p.i;
+					self.binaries[b].j = p.j;
+					self.binaries[b].a = p.a;
+					self.binaries[b].e = p.e;
+					self.log(t, b, "exchange")?;
+					break;
+				}
+			}
+		}
+
+		for slot in 0..self.active.len() {
+			if !still[slot] {
+				let b = self.active[slot];
+				self.binaries[b].disrupted = true;
+				self.binaries[b].t_end = t;
+				self.log(t, b, "disrupt")?;
+			}
+		}
+
+		let mut active = vec![];
+		for (k, p) in found.iter().enumerate() {
+			let b = match matched[k] {
+				Some(b) => {
+					self.binaries[b].a = p.a;
+					self.binaries[b].e = p.e;
+					self.binaries[b].t_end = t;
+					self.log(t, b, "update")?;
+					b
+				},
+				None => {
+					let id = self.binaries.len();
+					self.binaries.push(Binary { id: id, i: p.i, j: p.j, t_form: t, t_end: t, a_form: p.a, a: p.a, e: p.e, exchanges: 0, disrupted: false });
+					self.log(t, id, "form")?;
+					id
+				},
+			};
+			active.push(b);
+		}
+		self.active = active;
+		Ok(())
+	}
+
+	pub fn active_count(&self) -> usize {
+		self.active.len()
+	}
+
+	pub fn statistics(&self) -> Statistics {
+		let mut a: Vec<f64> = self.active.iter().map(|&b| self.binaries[b].a).collect();
+		let mut e: Vec<f64> = self.active.iter().map(|&b| self.binaries[b].e).collect();
+		a.sort_by(|x, y| x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal));
+		e.sort_by(|x, y| x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal));
+		let median = |x: &Vec<f64>| if x.is_empty() { std::f64::NAN } else { x[x.len()/2] };
+		Statistics {
+			formed: self.binaries.len(),
+			disrupted: self.binaries.iter().filter(|x| x.disrupted).count(),
+			bound: self.active.len(),
+			exchanges: self.binaries.iter().map(|x| x.exchanges).sum(),
+			min_a: a.first().cloned().unwrap_or(std::f64::NAN),
+			median_a: median(&a),
+			median_e: median(&e),
+		}
+	}
+
+	// Flushes the event log and writes binary_catalog.csv
+	pub fn finish(&mut self) -> io::Result<()> {
+		if let Some(ref mut f) = self.events {
+			f.flush()?;
+		}
+		if let Some(ref dir) = self.out_dir {
+			let mut f = BufWriter::new(File::create(dir.join("binary_catalog.csv"))?);
+			write!(f, "{},i_id,j_id", CATALOG_HEADER)?;
+			for member in &["i", "j"] {
+				for name in &self.columns.names {
+					write!(f, ",{}_{}", member, name)?;
+				}
+			}
+			writeln!(f)?;
+			for x in &self.binaries {
+				write!(f, "{},{},{},{},{},{},{},{},{},{}", x.id, x.i, x.j, x.t_form, x.t_end, x.a_form, x.a, x.e,
+					x.exchanges, if x.disrupted { "disrupted" } else { "bound" })?;
+				write!(f, ",{},{}", self.columns.id(x.i), self.columns.id(x.j))?;
+				for &k in &[x.i, x.j] {
+					for value in self.columns.row(k) {
+						write!(f, ",{}", value)?;
+					}
+				}
+				writeln!(f)?;
+			}
+			f.flush()?;
+		}
+		Ok(())
+	}
+}
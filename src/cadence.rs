@@ -0,0 +1,127 @@
+/*
+ Decides how many steps to wait between diagnostics. The fixed cadence is the
+ classic "every 10 steps"; the adaptive one halves the interval whenever one of
+ the triggers fires and doubles it again while nothing interesting happens.
+ With every set the diagnostics go by simulation time instead, at multiples
+ of every, and the run shortens the steps that would jump over one.
+ */
+use diagnostics;
+use {Real, Star};
+
+pub struct Triggers {
+	// |d(dE)/dt| between two diagnostics
+	pub de_rate: Option<f64>,
+	// Smallest pair separation
+	pub rmin: Option<f64>,
+	// Relative change of the 10% Lagrangian radius per unit time
+	pub clump: Option<f64>,
+}
+
+pub struct Cadence {
+	pub interval: usize,
+	pub min: usize,
+	pub max: usize,
+	pub triggers: Option<Triggers>,
+	pub every: Option<f64>,
+	last: Option<(f64, f64, f64)>,
+}
+
+impl Cadence {
+	pub fn fixed(interval: usize) -> Cadence {
+		Cadence { interval: interval, min: interval, max: interval, triggers: None, every: None, last: None }
+	}
+
+	// Sets the interval by hand, back to counting steps; an adaptive cadence widens its bounds to take it
+	pub fn set_interval(&mut self, interval: usize) {
+		self.interval = interval;
+		self.every = None;
+		if self.triggers.is_none() {
+			self.min = interval;
+			self.max = interval;
+		} else {
+			self.min = self.min.min(interval);
+			self.max = self.max.max(interval);
+		}
+	}
+
+	// The first multiple of every after t, allowing for rounding in t
+	pub fn next_time(&self, t: f64) -> Option<f64> {
+		self.every.map(|x| ((t/x + 1e-9).floor() + 1.0)*x)
+	}
+
+	/*
+	 Parses "de=1e-6,rmin=1e-3,clump=0.5,min=1,max=100". Triggers that are not
+	 mentioned stay off.
+	 */
+	pub fn parse(spec: &str) -> Result<Cadence, String> {
+		let mut c = Cadence::fixed(10);
+		c.min = 1;
+		c.max = 100;
+		let mut triggers = Triggers { de_rate: None, rmin: None, clump: None };
+		for item in spec.split(',') {
+			if item == "" {
+				continue;
+			}
+			let mut kv = item.splitn(2, '=');
+			let key = kv.next().unwrap_or("");
+			let value = kv.next().ok_or(format!("Missing value for '{}'", key))?;
+			let bad = format!("Bad value for '{}': {}", key, value);
+			match key {
+				"de" => triggers.de_rate = Some(value.parse().map_err(|_| bad.clone())?),
+				"rmin" => triggers.rmin = Some(value.parse().map_err(|_| bad.clone())?),
+				"clump" => triggers.clump = Some(value.parse().map_err(|_| bad.clone())?),
+				"min" => c.min = value.parse().map_err(|_| bad.clone())?,
+				"max" => c.max = value.parse().map_err(|_| bad.clone())?,
+				_ => return Err(format!("Unknown cadence trigger '{}'", key)),
+			}
+		}
+		if c.min == 0 || c.min > c.max {
+			return Err(format!("Need 0 < min <= max, got min={} max={}", c.min, c.max));
+		}
+		c.interval = c.interval.max(c.min).min(c.max);
+		c.triggers = Some(triggers);
+		Ok(c)
+	}
+
+	/*
+	 Feeds the state at a diagnostic step and adapts the interval. Returns the
+	 names of the triggers that fired.
+	 */
+	pub fn update<R: Real>(&mut self, t: f64, de: f64, s: &Vec<Star<R>>) -> Vec<&'static str> {
+		let mut fired = vec![];
+		let triggers = match self.triggers {
+			Some(ref x) => x,
+			None => return fired,
+		};
+
+		let r10 = if triggers.clump.is_some() { diagnostics::lagrangian_radii(s, &diagnostics::density_center(s), &[0.1])[0].to_f64() } else { 0.0 };
+		if let Some((t0, de0, r0)) = self.last {
+			let span = t - t0;
+			if span > 0.0 {
+				if let Some(x) = triggers.de_rate {
+					if ((de - de0)/span).abs() > x {
+						fired.push("de");
+					}
+				}
+				if let Some(x) = triggers.clump {
+					if r0 > 0.0 && ((r10 - r0)/r0/span).abs() > x {
+						fired.push("clump");
+					}
+				}
+			}
+		}
+		if let Some(x) = triggers.rmin {
+			if diagnostics::min_separation(s).to_f64() < x {
+				fired.push("rmin");
+			}
+		}
+		self.last = Some((t, de, r10));
+
+		if fired.is_empty() {
+			self.interval = (self.interval*2).min(self.max);
+		} else {
+			self.interval = (self.interval/2).max(self.min);
+		}
+		fired
+	}
+}
@@ -0,0 +1,128 @@
+/*
+ A massive central object, e.g. the black hole of a nuclear star cluster.
+ One star is flagged as central and its interactions are never softened: the
+ force solver computes all forces as usual and the softened star-hole terms
+ are then swapped for exact ones, so this works with every solver and
+ kernel. energies() gets the same correction.
+
+ With substeps > 1 the star-hole forces are also integrated with a smaller
+ step (an impulse multiple-timestep scheme): a half kick with the remaining
+ forces, substeps velocity-Verlet steps of dt/substeps under the star-hole
+ forces alone, the full force evaluation, and another half kick. Stars on
+ tight orbits around the hole are then resolved without shrinking dt for
+ everything else.
+
+ "--central N" flags the N-th star of the input (counting from 0), or
+ "--central heaviest" the most massive one; "--central-substeps K" sets the
+ substeps.
+ */
+use {Params, Real, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Central {
+	pub index: usize,
+	pub substeps: usize,
+}
+
+impl Central {
+	pub fn new(index: usize) -> Central {
+		Central { index: index, substeps: 1 }
+	}
+
+	// Parses a star index or "heaviest"
+	pub fn pick<R: Real>(spec: &str, s: &Vec<Star<R>>) -> Result<Central, String> {
+		let index = if spec == "heaviest" {
+			(0..s.len()).fold(None, |best: Option<usize>, i| match best {
+				Some(b) if s[b].m >= s[i].m => Some(b),
+				_ => Some(i),
+			}).ok_or(String::from("--central heaviest needs at least one star"))?
+		} else {
+			spec.parse().map_err(|_| format!("--central needs a star index or heaviest, got '{}'", spec))?
+		};
+		if index >= s.len() {
+			return Err(format!("--central {} is out of range for {} stars", index, s.len()));
+		}
+		Ok(Central::new(index))
+	}
+
+	/*
+	 Accelerations of all stars from their interactions with the central
+	 object, softened as p would soften them or, with p None, exact.
+	 */
+	pub fn accelerations<R: Real>(&self, s: &Vec<Star<R>>, p: Option<&Params<R>>) -> Vec<[R; 3]> {
+		let b = self.index;
+		let eps = p.map(|p| p.star_eps(s));
+		let mut a = vec![[R::zero(); 3]; s.len()];
+		for i in 0..s.len() {
+			if i == b {
+				continue;
+			}
+			let d = [s[i].r[0] - s[b].r[0], s[i].r[1] - s[b].r[1], s[i].r[2] - s[b].r[2]];
+			let mut r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+			if let (Some(p), Some(eps)) = (p, eps.as_ref()) {
+				r2 += p.pair_eps2(eps[i], eps[b]);
+			}
+			let apre = R::one()/(r2*r2.sqrt());
+			for k in 0..3 {
+				a[i][k] -= s[b].m*apre*d[k];
+				a[b][k] += s[i].m*apre*d[k];
+			}
+		}
+		a
+	}
+
+	// Replaces the softened star-hole terms in the accelerations by exact ones
+	pub fn unsoften<R: Real>(&self, s: &mut Vec<Star<R>>, p: &Params<R>) {
+		if p.eps == R::zero() {
+			return;
+		}
+		let soft = self.accelerations(s, Some(p));
+		let exact = self.accelerations(s, None);
+		for (i, star) in s.iter_mut().enumerate() {
+			for k in 0..3 {
+				star.a[k] += exact[i][k] - soft[i][k];
+			}
+		}
+	}
+
+	// Exact minus softened star-hole potential energy, to add to W
+	pub fn energy_correction<R: Real>(&self, s: &Vec<Star<R>>, p: &Params<R>) -> R {
+		let b = self.index;
+		let eps = p.star_eps(s);
+		let mut w = R::zero();
+		for i in 0..s.len() {
+			if i == b {
+				continue;
+			}
+			let d = [s[i].r[0] - s[b].r[0], s[i].r[1] - s[b].r[1], s[i].r[2] - s[b].r[2]];
+			let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+			w += s[i].m*s[b].m*(R::one()/(r2 + p.pair_eps2(eps[i], eps[b])).sqrt() - R::one()/r2.sqrt());
+		}
+		w
+	}
+
+	/*
+	 The inner loop of a sub-cycled step: substeps velocity-Verlet steps of
+	 dt/substeps under the star-hole forces only. The half kicks with the
+	 other forces around it are up to the caller.
+	 */
+	pub fn subcycle<R: Real>(&self, s: &mut Vec<Star<R>>, dt: R) {
+		let h = dt/R::from_f64(self.substeps as f64);
+		let half = R::from_f64(0.5)*h;
+		let mut a = self.accelerations(s, None);
+		for _ in 0..self.substeps {
+			for (i, star) in s.iter_mut().enumerate() {
+				for k in 0..3 {
+					star.v[k] += half*a[i][k];
+					star.r[k] += h*star.v[k];
+				}
+			}
+			a = self.accelerations(s, None);
+			for (i, star) in s.iter_mut().enumerate() {
+				for k in 0..3 {
+					star.v[k] += half*a[i][k];
+				}
+			}
+		}
+	}
+}
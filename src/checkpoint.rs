@@ -0,0 +1,85 @@
+/*
+ Checkpoints are plain input files with a header line carrying the clock, so
+ a checkpoint can be fed back to nbabel like any other input:
+
+ # nbabel checkpoint t = 0.5 steps = 500
+ 0 m x y z vx vy vz
+
+ Extra input columns follow each star, with their "# columns:" line. A
+ checkpoint of a run also carries the run's metadata.rs lines ("# meta ...")
+ after the header.
+
+ Runs with --units write the stars back in the input units, after a
+ "# units:" line and the time in Myr. t is then in the G = 1 time unit of the
+ input masses and lengths, so the checkpoint resumes with the same --units
+ whether or not --normalize is given.
+ */
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use units::Units;
+use {parse_stars, write_stars_with, NBodyError, Real, Simulation, Star};
+
+pub fn write<R: Real>(path: &Path, sim: &Simulation<R>) -> io::Result<()> {
+	write_units(path, sim, None)
+}
+
+pub fn write_units<R: Real>(path: &Path, sim: &Simulation<R>, units: Option<&Units>) -> io::Result<()> {
+	let mut f = BufWriter::new(File::create(path)?);
+	match units {
+		Some(u) => {
+			writeln!(f, "# nbabel checkpoint t = {:e} steps = {}", u.input_time(sim.t.to_f64()), sim.steps)?;
+			if let Some(ref x) = sim.metadata {
+				x.write_comments(&mut f)?;
+			}
+			writeln!(f, "# units: {}", u.names.join(","))?;
+			writeln!(f, "# t = {:e} Myr", u.myr(sim.t.to_f64()))?;
+			write_stars_with(&mut f, &u.to_input(&sim.s), &sim.columns)?;
+		},
+		None => {
+			writeln!(f, "# nbabel checkpoint t = {:e} steps = {}", sim.t, sim.steps)?;
+			if let Some(ref x) = sim.metadata {
+				x.write_comments(&mut f)?;
+			}
+			write_stars_with(&mut f, &sim.s, &sim.columns)?;
+		},
+	}
+	f.flush()
+}
+
+// Column of the n-th word of a line, counting from 1
+fn column(line: &str, n: usize) -> usize {
+	let word = line.split_whitespace().nth(n).unwrap_or("");
+	line[..word.as_ptr() as usize - line.as_ptr() as usize].chars().count() + 1
+}
+
+// The time and step count of a checkpoint header, zero without one
+pub fn read_clock<R: Real>(text: &str) -> Result<(R, usize), NBodyError> {
+	let mut t = R::zero();
+	let mut steps = 0;
+	if let Some(header) = text.lines().next() {
+		if header.starts_with("# nbabel checkpoint") {
+			let words: Vec<&str> = header.split_whitespace().collect();
+			for w in 0..words.len() {
+				if words[w] == "t" && w + 2 < words.len() {
+					t = words[w + 2].parse().map_err(|_| NBodyError::parse(1, column(header, w + 2), format!("bad checkpoint time '{}'", words[w + 2])))?;
+				}
+				if words[w] == "steps" && w + 2 < words.len() {
+					steps = words[w + 2].parse().map_err(|_| NBodyError::parse(1, column(header, w + 2), format!("bad checkpoint step count '{}'", words[w + 2])))?;
+				}
+			}
+		}
+	}
+	Ok((t, steps))
+}
+
+/*
+ Returns the stars, the time and the step count. Files without a checkpoint
+ header are read as an ordinary input starting at t = 0.
+ */
+pub fn read<R: Real>(text: &str) -> Result<(Vec<Star<R>>, R, usize), NBodyError> {
+	let (t, steps) = read_clock(text)?;
+	Ok((parse_stars(text)?, t, steps))
+}
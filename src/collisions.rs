@@ -0,0 +1,169 @@
+/*
+ Physical collisions between stars with radii. After every step, pairs that
+ are closer than the sum of their radii and still approaching either
+
+   merge:  become one star at their centre of mass with their total mass and
+           momentum and radius (r_i^3 + r_j^3)^(1/3) (volume kept), or
+   bounce: have the normal component of their relative velocity reversed
+           and multiplied by the restitution coefficient (1 is elastic);
+           momentum is kept either way.
+
+ Radii come from an extra input column named "radius", with a default for
+ stars without one. A merged star takes the place and the extra columns of
+ the first of the two, the second is removed, so the indices of later stars
+ shift down by one; its radius column is updated. Merging and inelastic
+ bounces take kinetic energy out of the system, which the events report and
+ dE will show.
+
+ "--collisions merge|bounce|off", "--restitution E" and "--radius R" for the
+ default radius (0, i.e. point masses, unless given).
+ */
+use columns::Columns;
+use pairs;
+use {Real, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Mode {
+	Off,
+	Merge,
+	Bounce,
+}
+
+impl Mode {
+	pub fn parse(name: &str) -> Option<Mode> {
+		match name {
+			"off" => Some(Mode::Off),
+			"merge" => Some(Mode::Merge),
+			"bounce" => Some(Mode::Bounce),
+			_ => None,
+		}
+	}
+}
+
+pub struct Collisions {
+	pub mode: Mode,
+	pub restitution: f64,
+	pub radii: Vec<f64>,
+}
+
+// What happened to a colliding pair (i, j), i < j
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+	// j was merged into i and removed
+	Merged(usize, usize),
+	Bounced(usize, usize),
+}
+
+impl Collisions {
+	// Radii from the "radius" column, default for stars without one
+	pub fn new(mode: Mode, restitution: f64, n: usize, columns: &Columns, default: f64) -> Collisions {
+		let radii = (0..n).map(|i| columns.value(i, "radius").unwrap_or(default)).collect();
+		Collisions { mode: mode, restitution: restitution, radii: radii }
+	}
+
+	// Overlapping pairs that are still approaching, closest first
+	pub fn detect<R: Real>(&self, s: &Vec<Star<R>>) -> Vec<(usize, usize)> {
+		let radii = &self.radii;
+		let mut found: Vec<(usize, usize, f64)> = vec![];
+		pairs::for_each_pair(s, |i, j, rij, r2| {
+			let reach = radii[i] + radii[j];
+			if reach > 0.0 && r2.to_f64() < reach*reach {
+				let approach: f64 = (0..3).map(|k| (rij[k]*(s[i].v[k] - s[j].v[k])).to_f64()).sum();
+				if approach < 0.0 {
+					found.push((i, j, r2.to_f64()));
+				}
+			}
+		});
+		found.sort_by(|a, b| a.2.partial_cmp(&b.2).expect("NaN separation"));
+		found.iter().map(|x| (x.0, x.1)).collect()
+	}
+
+	/*
+	 Handles every collision in s and returns what happened with a message
+	 for the event log. Pairs involving a star that already merged in this
+	 call wait for the next step.
+	 */
+	pub fn resolve<R: Real>(&mut self, s: &mut Vec<Star<R>>, columns: &mut Columns) -> Vec<(Outcome, String)> {
+		if self.mode == Mode::Off {
+			return vec![];
+		}
+		let mut pairs = self.detect(s);
+		let mut done = vec![];
+		while !pairs.is_empty() {
+			let (i, j) = pairs.remove(0);
+			let before = kinetic(s);
+			let outcome = match self.mode {
+				Mode::Merge => {
+					self.merge(s, columns, i, j);
+					// Later pairs refer to the old indices
+					pairs.retain(|&(a, b)| a != i && a != j && b != i && b != j);
+					for x in pairs.iter_mut() {
+						*x = (shift(x.0, j), shift(x.1, j));
+					}
+					Outcome::Merged(i, j)
+				},
+				_ => {
+					bounce(s, i, j, R::from_f64(self.restitution));
+					Outcome::Bounced(i, j)
+				},
+			};
+			let lost = before - kinetic(s);
+			let msg = match outcome {
+				Outcome::Merged(i, j) => format!("stars {} and {} merged into a star of mass {} and radius {}, kinetic energy lost {:e}", i, j, s[i].m, self.radii[i], lost),
+				Outcome::Bounced(i, j) => format!("stars {} and {} bounced, kinetic energy lost {:e}", i, j, lost),
+			};
+			done.push((outcome, msg));
+		}
+		done
+	}
+
+	fn merge<R: Real>(&mut self, s: &mut Vec<Star<R>>, columns: &mut Columns, i: usize, j: usize) {
+		let m = s[i].m + s[j].m;
+		for k in 0..3 {
+			if m > R::zero() {
+				s[i].r[k] = (s[i].m*s[i].r[k] + s[j].m*s[j].r[k])/m;
+				s[i].v[k] = (s[i].m*s[i].v[k] + s[j].m*s[j].v[k])/m;
+			}
+		}
+		s[i].m = m;
+		s.remove(j);
+		self.radii[i] = (self.radii[i].powi(3) + self.radii[j].powi(3)).cbrt();
+		self.radii.remove(j);
+		if let Some(k) = columns.names.iter().position(|x| x == "radius") {
+			if let Some(row) = columns.rows.get_mut(i) {
+				row[k] = self.radii[i].to_string();
+			}
+		}
+		columns.remove(j);
+	}
+}
+
+// Index of a star after star j was removed
+pub fn shift(index: usize, j: usize) -> usize {
+	if index > j { index - 1 } else { index }
+}
+
+fn kinetic<R: Real>(s: &Vec<Star<R>>) -> f64 {
+	s.iter().map(|x| 0.5*x.m.to_f64()*(x.v[0].powi(2) + x.v[1].powi(2) + x.v[2].powi(2)).to_f64()).sum()
+}
+
+// Reverses and scales the normal relative velocity of i and j, keeping momentum
+fn bounce<R: Real>(s: &mut Vec<Star<R>>, i: usize, j: usize, restitution: R) {
+	let m = s[i].m + s[j].m;
+	if !(m > R::zero()) {
+		return;
+	}
+	let d: Vec<R> = (0..3).map(|k| s[i].r[k] - s[j].r[k]).collect();
+	let d2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+	if d2 == R::zero() {
+		return;
+	}
+	let vn = (0..3).fold(R::zero(), |x, k| x + (s[i].v[k] - s[j].v[k])*d[k])/d2;
+	// Change in the relative velocity, shared by mass
+	let dv = (R::one() + restitution)*vn;
+	let (fi, fj) = (s[j].m/m, s[i].m/m);
+	for k in 0..3 {
+		s[i].v[k] -= fi*dv*d[k];
+		s[j].v[k] += fj*dv*d[k];
+	}
+}
@@ -0,0 +1,160 @@
+/*
+ Columnar output for dataframe tools (polars, pandas, duckdb): with
+ --parquet a run writes
+
+ - snapshots.parquet: the stars at every diagnostic, one row per star and
+   snapshot with t, step, id (the star's id, see columns.rs), m, x, y, z,
+   vx, vy, vz, with --accelerations ax, ay, az and the jerk jx, jy, jz, and
+   the extra input columns (species as text), one row group per snapshot so
+   readers can skip to the times they want;
+ - diagnostics.parquet: the diagnostics time series at the end of the run,
+   the columns of diagnostics.csv and lagrangian.csv side by side.
+
+ Both are Snappy-compressed Parquet written through Arrow record batches,
+ and both carry the run's metadata (metadata.rs) as key-value pairs in the
+ file footer. A Parquet file is only readable once its footer is written, so
+ finish() has to run; interrupted runs get there like finished ones.
+ */
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, Float64Array, StringArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use parquet::format::KeyValue;
+
+use columns::{Columns, SPECIES};
+use diagnostics::{jerks, lagrangian_header, History, Sample, CSV_HEADER};
+use metadata::Metadata;
+use {Params, Real, Star};
+
+fn arrow_error(x: ArrowError) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, x.to_string())
+}
+
+fn parquet_error(x: ParquetError) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, x.to_string())
+}
+
+fn schema(names: &[String]) -> SchemaRef {
+	Arc::new(Schema::new(names.iter().map(|x| {
+		let kind = match x.as_str() {
+			"step" | "id" | "n_bound" => DataType::UInt64,
+			x if x == SPECIES => DataType::Utf8,
+			_ => DataType::Float64,
+		};
+		Field::new(x.as_str(), kind, false)
+	}).collect::<Vec<Field>>()))
+}
+
+fn writer(path: &Path, schema: &SchemaRef, metadata: Option<&Metadata>) -> io::Result<ArrowWriter<File>> {
+	let pairs = metadata.map(|x| x.pairs().into_iter().map(|(k, v)| KeyValue::new(String::from(k), String::from(v))).collect());
+	let props = WriterProperties::builder()
+		.set_compression(Compression::SNAPPY)
+		.set_key_value_metadata(pairs)
+		.build();
+	ArrowWriter::try_new(File::create(path)?, schema.clone(), Some(props)).map_err(parquet_error)
+}
+
+fn floats(x: Vec<f64>) -> ArrayRef {
+	Arc::new(Float64Array::from(x))
+}
+
+fn integers(x: Vec<u64>) -> ArrayRef {
+	Arc::new(UInt64Array::from(x))
+}
+
+pub struct Parquet {
+	dir: PathBuf,
+	metadata: Option<Metadata>,
+	extra: Vec<String>,
+	accelerations: bool,
+	schema: SchemaRef,
+	snapshots: ArrowWriter<File>,
+	last: Option<f64>,
+}
+
+impl Parquet {
+	// snapshots.parquet in dir, with the extra columns of the input and optionally accelerations and jerks
+	pub fn new(dir: &Path, columns: &Columns, metadata: Option<&Metadata>, accelerations: bool) -> io::Result<Parquet> {
+		let mut names: Vec<String> = ["t", "step", "id", "m", "x", "y", "z", "vx", "vy", "vz"].iter().map(|x| String::from(*x)).collect();
+		if accelerations {
+			names.extend(["ax", "ay", "az", "jx", "jy", "jz"].iter().map(|x| String::from(*x)));
+		}
+		names.extend(columns.names.iter().cloned());
+		let schema = schema(&names);
+		let snapshots = writer(&dir.join("snapshots.parquet"), &schema, metadata)?;
+		Ok(Parquet { dir: dir.to_path_buf(), metadata: metadata.cloned(), extra: columns.names.clone(), accelerations: accelerations, schema: schema, snapshots: snapshots, last: None })
+	}
+
+	// Adds the stars at time t as a row group, unless t is already the last snapshot
+	pub fn write<R: Real>(&mut self, t: f64, step: usize, s: &Vec<Star<R>>, p: &Params<R>, columns: &Columns) -> io::Result<()> {
+		if self.last == Some(t) {
+			return Ok(());
+		}
+		let n = s.len();
+		let mut arrays = vec![floats(vec![t; n]), integers(vec![step as u64; n]), integers((0..n).map(|i| columns.id(i)).collect()), floats(s.iter().map(|x| x.m.to_f64()).collect())];
+		for k in 0..3 {
+			arrays.push(floats(s.iter().map(|x| x.r[k].to_f64()).collect()));
+		}
+		for k in 0..3 {
+			arrays.push(floats(s.iter().map(|x| x.v[k].to_f64()).collect()));
+		}
+		if self.accelerations {
+			for k in 0..3 {
+				arrays.push(floats(s.iter().map(|x| x.a[k].to_f64()).collect()));
+			}
+			let jerk = jerks(s, p);
+			for k in 0..3 {
+				arrays.push(floats(jerk.iter().map(|x| x[k].to_f64()).collect()));
+			}
+		}
+		for (j, name) in self.extra.iter().enumerate() {
+			if name == SPECIES {
+				arrays.push(Arc::new(StringArray::from((0..n).map(|i| columns.species(i).unwrap_or("")).collect::<Vec<&str>>())));
+				continue;
+			}
+			// Columns::read checked these are numbers
+			arrays.push(floats((0..n).map(|i| columns.rows.get(i).and_then(|row| row.get(j)).and_then(|x| x.parse().ok()).unwrap_or(std::f64::NAN)).collect()));
+		}
+		let batch = RecordBatch::try_new(self.schema.clone(), arrays).map_err(arrow_error)?;
+		self.snapshots.write(&batch).map_err(parquet_error)?;
+		self.snapshots.flush().map_err(parquet_error)?;
+		self.last = Some(t);
+		Ok(())
+	}
+
+	// Closes snapshots.parquet and writes diagnostics.parquet from the whole history
+	pub fn finish(self, history: Option<&History>) -> io::Result<()> {
+		self.snapshots.close().map_err(parquet_error)?;
+		let h = match history {
+			Some(h) => h,
+			None => return Ok(()),
+		};
+		let mut names: Vec<String> = CSV_HEADER.split(',').map(String::from).collect();
+		names.extend(lagrangian_header(&h.fractions).split(',').skip(1).map(String::from));
+		let schema = schema(&names);
+		let x = &h.samples;
+		let column = |f: &dyn Fn(&Sample) -> f64| floats(x.iter().map(f).collect());
+		let mut arrays = vec![
+			column(&|x| x.t), column(&|x| x.e[0]), column(&|x| x.e[1]), column(&|x| x.e[2]), column(&|x| x.de), column(&|x| x.q),
+			integers(x.iter().map(|x| x.n_bound as u64).collect()),
+			column(&|x| x.center[0]), column(&|x| x.center[1]), column(&|x| x.center[2]), column(&|x| x.core_radius), column(&|x| x.core_density),
+		];
+		for k in 0..h.fractions.len() {
+			arrays.push(column(&|x| x.radii[k]));
+		}
+		let batch = RecordBatch::try_new(schema.clone(), arrays).map_err(arrow_error)?;
+		let mut w = writer(&self.dir.join("diagnostics.parquet"), &schema, self.metadata.as_ref())?;
+		w.write(&batch).map_err(parquet_error)?;
+		w.close().map_err(parquet_error)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,138 @@
+/*
+ What the input says about each star besides the physics: its id, and the
+ extra columns after the eight the physics uses, e.g. metallicity or age.
+ Extra values are kept as the text they were read as, so integers stay
+ integers and floats round-trip exactly, and written back unchanged next to
+ their star in checkpoints and the binary catalog.
+
+ Names come from a header line listing all columns,
+
+ # columns: id m x y z vx vy vz feh age
+
+ and default to col8, col9, ... without one. Every extra column has to hold
+ numbers, except one named "species", which may tag the stars with any word
+ (dm, disk, bulge) to tell mixed populations apart.
+
+ The id of the first column stays with its star through merges into every
+ output. Ids are non-negative integers; a star without one (the reference
+ inputs give every star -1) gets its position in the input instead.
+ */
+use std::collections::HashSet;
+
+pub static HEADER: &'static str = "# columns:";
+pub static BASE: [&'static str; 8] = ["id", "m", "x", "y", "z", "vx", "vy", "vz"];
+// The extra column that may hold words
+pub static SPECIES: &'static str = "species";
+
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Columns {
+	pub names: Vec<String>,
+	// One row per star, in input order
+	pub rows: Vec<Vec<String>>,
+	// One id per star; empty numbers the stars from 0
+	pub ids: Vec<u64>,
+}
+
+impl Columns {
+	/*
+	 Collects the extra columns of every star line. Each must parse as a
+	 number and every star must have the same number of them.
+	 */
+	pub fn read(text: &str) -> Result<Columns, String> {
+		let mut names: Option<Vec<String>> = None;
+		let mut rows: Vec<Vec<String>> = vec![];
+		let mut ids: Vec<u64> = vec![];
+		for line in text.split("\n") {
+			if let Some(rest) = line.strip_prefix(HEADER) {
+				let all: Vec<String> = rest.split_whitespace().map(String::from).collect();
+				if all.len() < BASE.len() {
+					return Err(format!("Column header '{}' has fewer than {} columns", line, BASE.len()));
+				}
+				names = Some(all[BASE.len()..].to_vec());
+				continue;
+			}
+			if line.trim() == "" || line.starts_with('#') {
+				continue;
+			}
+			let extra: Vec<String> = line.split_whitespace().skip(BASE.len()).map(String::from).collect();
+			if !rows.is_empty() && rows[0].len() != extra.len() {
+				return Err(format!("Star {} has {} extra columns, the first star has {}", rows.len(), extra.len(), rows[0].len()));
+			}
+			ids.push(line.split_whitespace().next().and_then(|x| x.parse().ok()).unwrap_or(rows.len() as u64));
+			rows.push(extra);
+		}
+		let width = rows.first().map_or(0, |x| x.len());
+		let names = match names {
+			Some(names) => {
+				if names.len() != width {
+					return Err(format!("Column header names {} extra columns, the stars have {}", names.len(), width));
+				}
+				names
+			},
+			None => (0..width).map(|k| format!("col{}", BASE.len() + k)).collect(),
+		};
+		for row in &rows {
+			for (name, x) in names.iter().zip(row.iter()) {
+				if name != SPECIES && x.parse::<f64>().is_err() {
+					return Err(format!("Extra column value '{}' is not a number", x));
+				}
+			}
+		}
+		Ok(Columns { names: names, rows: rows, ids: ids })
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.names.is_empty()
+	}
+
+	// The "# columns:" line describing a file written with these columns
+	pub fn header(&self) -> String {
+		let mut all: Vec<&str> = BASE.to_vec();
+		all.extend(self.names.iter().map(|x| x.as_str()));
+		format!("{} {}", HEADER, all.join(" "))
+	}
+
+	// Extra values of star i, empty when there are none
+	pub fn row(&self, i: usize) -> &[String] {
+		self.rows.get(i).map_or(&[], |x| x.as_slice())
+	}
+
+	pub fn value(&self, i: usize, name: &str) -> Option<f64> {
+		let k = self.names.iter().position(|x| x == name)?;
+		self.row(i).get(k).and_then(|x| x.parse().ok())
+	}
+
+	// Id of star i, its index when the ids are not known
+	pub fn id(&self, i: usize) -> u64 {
+		self.ids.get(i).cloned().unwrap_or(i as u64)
+	}
+
+	// Species tag of star i, if there is a species column
+	pub fn species(&self, i: usize) -> Option<&str> {
+		let k = self.names.iter().position(|x| x == SPECIES)?;
+		self.row(i).get(k).map(|x| x.as_str())
+	}
+
+	// Forgets star j, which was removed from the stars
+	pub fn remove(&mut self, j: usize) {
+		if j < self.rows.len() {
+			self.rows.remove(j);
+		}
+		if j < self.ids.len() {
+			self.ids.remove(j);
+		}
+	}
+
+	/*
+	 Appends the stars of other, for stars put together from several inputs.
+	 Ids that are already taken move up past the largest one so far.
+	 */
+	pub fn extend(&mut self, other: Columns, n: usize, m: usize) {
+		let mine: Vec<u64> = (0..n).map(|i| self.id(i)).collect();
+		let theirs: Vec<u64> = (0..m).map(|i| other.id(i)).collect();
+		let taken: HashSet<u64> = mine.iter().cloned().collect();
+		let offset = if theirs.iter().any(|x| taken.contains(x)) { mine.iter().max().map_or(0, |x| x + 1) } else { 0 };
+		self.ids = mine.into_iter().chain(theirs.into_iter().map(|x| x + offset)).collect();
+		self.rows.extend(other.rows);
+	}
+}
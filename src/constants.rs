@@ -0,0 +1,112 @@
+/*
+ Physical constants in SI units. The integrator itself works in N-body units
+ (G = 1); everything that converts to or from physical units takes its
+ numbers from a Constants value so there is one audited place for them.
+
+ Presets: "codata2018" (default) and "codata2014" for G; the astronomical
+ values follow the IAU 2012/2015 resolutions in both (exact au, parsec from
+ the au, nominal solar mass parameter, Julian year). The solar mass is the
+ IAU mass parameter divided by G, so it moves with the chosen G.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Constants {
+	// m^3 kg^-1 s^-2
+	pub g: f64,
+	// m/s
+	pub c: f64,
+	// kg
+	pub msun: f64,
+	// m
+	pub au: f64,
+	pub pc: f64,
+	// s
+	pub year: f64,
+}
+
+// IAU 2015 B3 nominal solar mass parameter, m^3 s^-2
+pub static GM_SUN: f64 = 1.3271244e20;
+// IAU 2012 B2, exact
+pub static AU: f64 = 149597870700.0;
+pub static C: f64 = 299792458.0;
+// Julian year
+pub static YEAR: f64 = 365.25*86400.0;
+
+impl Constants {
+	fn with_g(g: f64) -> Constants {
+		Constants {
+			g: g,
+			c: C,
+			msun: GM_SUN/g,
+			au: AU,
+			pc: AU*648000.0/std::f64::consts::PI,
+			year: YEAR,
+		}
+	}
+
+	pub fn codata2018() -> Constants {
+		Constants::with_g(6.67430e-11)
+	}
+
+	pub fn codata2014() -> Constants {
+		Constants::with_g(6.67408e-11)
+	}
+
+	pub fn preset(name: &str) -> Option<Constants> {
+		match name {
+			"codata2018" => Some(Constants::codata2018()),
+			"codata2014" => Some(Constants::codata2014()),
+			_ => None,
+		}
+	}
+
+	/*
+	 Parses "PRESET,NAME=VALUE,..." where the preset is optional, e.g.
+	 "codata2014" or "G=6.674e-11,year=3.15576e7". Names are G, c, msun, au,
+	 pc and year. Overriding G does not change msun; set both if needed.
+	 */
+	pub fn parse(spec: &str) -> Result<Constants, String> {
+		let mut x = Constants::default();
+		for (n, item) in spec.split(',').enumerate() {
+			let item = item.trim();
+			if item == "" {
+				continue;
+			}
+			if !item.contains('=') {
+				if n > 0 {
+					return Err(format!("Preset '{}' must come first", item));
+				}
+				x = Constants::preset(item).ok_or(format!("Unknown constants preset '{}', use codata2018 or codata2014", item))?;
+				continue;
+			}
+			let mut kv = item.splitn(2, '=');
+			let name = kv.next().unwrap_or("");
+			let value: f64 = kv.next().unwrap_or("").parse().map_err(|_| format!("Bad value in '{}'", item))?;
+			match name {
+				"G" => x.g = value,
+				"c" => x.c = value,
+				"msun" => x.msun = value,
+				"au" => x.au = value,
+				"pc" => x.pc = value,
+				"year" => x.year = value,
+				_ => return Err(format!("Unknown constant '{}', use G, c, msun, au, pc or year", name)),
+			}
+		}
+		Ok(x)
+	}
+
+	// N-body time unit in seconds for the given length (m) and mass (kg) units
+	pub fn time_unit(&self, length: f64, mass: f64) -> f64 {
+		(length.powi(3)/(self.g*mass)).sqrt()
+	}
+
+	// N-body velocity unit in m/s
+	pub fn velocity_unit(&self, length: f64, mass: f64) -> f64 {
+		length/self.time_unit(length, mass)
+	}
+}
+
+impl Default for Constants {
+	fn default() -> Constants {
+		Constants::codata2018()
+	}
+}
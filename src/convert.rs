@@ -0,0 +1,1011 @@
+/*
+ Snapshot formats and "nbabel convert INPUT [--from FORMAT] --to FORMAT
+ OUTPUT", which translates between them. The formats are
+
+ - text: the input format, with the checkpoint header for the clock;
+ - csv: a header row id,m,x,y,z,vx,vy,vz plus the extra columns, the clock
+   in a checkpoint header line above it;
+ - json: {"t", "steps", "columns", "stars": [{"id", "m", "x", ...}]};
+ - gadget: Gadget-2 binary snapshot (SnapFormat 1, little endian), all
+   stars as type 1 in single precision;
+ - tipsy: standard (big-endian) Tipsy, all stars as dark matter in single
+   precision;
+ - hdf5: the Gadget/SWIFT layout, /Header and /PartType1 with Coordinates,
+   Velocities, Masses, ParticleIDs and a dataset per extra column (needs a
+   build with --features hdf5);
+ - nbody: Aarseth's NBODY input (dat.10), m x y z vx vy vz per star, with
+   the N and time lines of the ACS tutorials when present;
+ - starlab: Starlab's "(Particle" tree, binaries and all.
+
+ Without --from or --to the format follows the file extension (.txt, .dat,
+ .csv, .json, .gadget, .tipsy or .std, .h5 or .hdf5, .10, .dyn), "-" being
+ text on stdin or stdout. Ids, the time, the step count and the extra
+ columns go wherever the target format has room for them: text, csv, json
+ and hdf5 keep everything; Gadget keeps integer ids and the time, Starlab
+ the ids and the time, Tipsy only the time and NBODY nothing (those two
+ number the stars by their order). What gets lost is logged.
+ */
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::process;
+
+use checkpoint;
+use columns::{Columns, BASE, HEADER, SPECIES};
+use status::{json_number, json_string};
+use {parse_stars, NBodyError, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+	Text,
+	Csv,
+	Json,
+	Gadget,
+	Tipsy,
+	Hdf5,
+	Nbody,
+	Starlab,
+}
+
+impl Format {
+	pub fn parse(name: &str) -> Option<Format> {
+		match name {
+			"text" => Some(Format::Text),
+			"csv" => Some(Format::Csv),
+			"json" => Some(Format::Json),
+			"gadget" => Some(Format::Gadget),
+			"tipsy" => Some(Format::Tipsy),
+			"hdf5" => Some(Format::Hdf5),
+			"nbody" => Some(Format::Nbody),
+			"starlab" => Some(Format::Starlab),
+			_ => None,
+		}
+	}
+
+	// Whether the format is text, which read_str() takes
+	pub fn is_text(self) -> bool {
+		match self {
+			Format::Gadget | Format::Tipsy | Format::Hdf5 => false,
+			_ => true,
+		}
+	}
+
+	// By file extension, text for anything unknown
+	pub fn of_path(path: &str) -> Format {
+		match path.rsplit('.').next().unwrap_or("") {
+			"csv" => Format::Csv,
+			"json" => Format::Json,
+			"gadget" => Format::Gadget,
+			"tipsy" | "std" => Format::Tipsy,
+			"h5" | "hdf5" => Format::Hdf5,
+			"10" => Format::Nbody,
+			"dyn" => Format::Starlab,
+			_ => Format::Text,
+		}
+	}
+}
+
+/*
+ A set of stars with everything that describes it: the ids of the input
+ (kept as text, NBabel inputs use -1 throughout), the clock and the extra
+ columns.
+ */
+#[derive(Clone)]
+pub struct Snapshot {
+	pub t: f64,
+	pub steps: usize,
+	pub ids: Vec<String>,
+	pub s: Vec<Star>,
+	pub columns: Columns,
+}
+
+impl Snapshot {
+	// Stars numbered from 0, at t = 0
+	pub fn new(s: Vec<Star>) -> Snapshot {
+		Snapshot { t: 0.0, steps: 0, ids: (0..s.len()).map(|i| i.to_string()).collect(), s: s, columns: Columns::default() }
+	}
+
+	// The ids as integers, or None when one of them is not
+	fn integer_ids(&self) -> Option<Vec<u64>> {
+		self.ids.iter().map(|x| x.parse().ok()).collect()
+	}
+}
+
+fn star(m: f64, r: [f64; 3], v: [f64; 3]) -> Star {
+	Star { m: m, r: r.to_vec(), v: v.to_vec(), a: vec![0.0; 3], a0: vec![0.0; 3] }
+}
+
+fn clock_header(x: &Snapshot) -> String {
+	format!("# nbabel checkpoint t = {:e} steps = {}", x.t, x.steps)
+}
+
+fn text_lines(text: &str) -> impl Iterator<Item = &str> {
+	text.split("\n").filter(|x| x.trim() != "" && !x.starts_with('#'))
+}
+
+pub fn read_text(text: &str) -> Result<Snapshot, NBodyError> {
+	let (t, steps) = checkpoint::read_clock::<f64>(text)?;
+	let s = parse_stars(text)?;
+	let mut columns = Columns::read(text).map_err(NBodyError::Config)?;
+	// Kept as text in the snapshot instead, whatever they are
+	columns.ids.clear();
+	let ids = text_lines(text).map(|x| String::from(x.split_whitespace().next().unwrap_or(""))).collect();
+	Ok(Snapshot { t: t, steps: steps, ids: ids, s: s, columns: columns })
+}
+
+pub fn write_text<W: Write>(w: &mut W, x: &Snapshot) -> io::Result<()> {
+	if x.t != 0.0 || x.steps != 0 {
+		writeln!(w, "{}", clock_header(x))?;
+	}
+	if !x.columns.is_empty() {
+		writeln!(w, "{}", x.columns.header())?;
+	}
+	for (i, star) in x.s.iter().enumerate() {
+		write!(w, "{} {:e} {:e} {:e} {:e} {:e} {:e} {:e}", x.ids[i], star.m, star.r[0], star.r[1], star.r[2], star.v[0], star.v[1], star.v[2])?;
+		for value in x.columns.row(i) {
+			write!(w, " {}", value)?;
+		}
+		writeln!(w)?;
+	}
+	Ok(())
+}
+
+/*
+ CSV goes through the text reader: the header row becomes a "# columns:"
+ line and the commas spaces.
+ */
+pub fn read_csv(text: &str) -> Result<Snapshot, NBodyError> {
+	let mut lines = vec![];
+	let mut header = false;
+	for line in text.split("\n") {
+		if line.starts_with('#') || line.trim() == "" {
+			lines.push(String::from(line));
+		} else if !header {
+			header = true;
+			let names: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+			if names.len() < BASE.len() || names[..BASE.len()] != BASE[..] {
+				return Err(NBodyError::parse(lines.len() + 1, 1, format!("expected a header row starting with {}", BASE.join(","))));
+			}
+			lines.push(format!("{} {}", HEADER, names.join(" ")));
+		} else {
+			lines.push(line.split(',').map(|x| x.trim()).collect::<Vec<&str>>().join(" "));
+		}
+	}
+	read_text(&lines.join("\n"))
+}
+
+pub fn write_csv<W: Write>(w: &mut W, x: &Snapshot) -> io::Result<()> {
+	writeln!(w, "{}", clock_header(x))?;
+	let mut names: Vec<&str> = BASE.to_vec();
+	names.extend(x.columns.names.iter().map(|x| x.as_str()));
+	writeln!(w, "{}", names.join(","))?;
+	for (i, star) in x.s.iter().enumerate() {
+		write!(w, "{},{:e},{:e},{:e},{:e},{:e},{:e},{:e}", x.ids[i], star.m, star.r[0], star.r[1], star.r[2], star.v[0], star.v[1], star.v[2])?;
+		for value in x.columns.row(i) {
+			write!(w, ",{}", value)?;
+		}
+		writeln!(w)?;
+	}
+	Ok(())
+}
+
+// JSON values, numbers kept as their text so extra columns round-trip exactly
+#[derive(Clone, Debug, PartialEq)]
+enum Json {
+	Null,
+	Bool(bool),
+	Number(String),
+	Str(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	fn get(&self, key: &str) -> Option<&Json> {
+		match *self {
+			Json::Object(ref x) => x.iter().find(|x| x.0 == key).map(|x| &x.1),
+			_ => None,
+		}
+	}
+
+	fn number(&self) -> Option<&str> {
+		match *self {
+			Json::Number(ref x) => Some(x),
+			_ => None,
+		}
+	}
+}
+
+struct JsonParser<'a> {
+	text: &'a str,
+	at: usize,
+}
+
+impl<'a> JsonParser<'a> {
+	fn error(&self, what: &str) -> NBodyError {
+		let before = &self.text.as_bytes()[..self.at.min(self.text.len())];
+		let line = before.iter().filter(|&&c| c == b'\n').count() + 1;
+		let column = before.iter().rev().take_while(|&&c| c != b'\n').count() + 1;
+		NBodyError::parse(line, column, format!("JSON: {}", what))
+	}
+
+	fn skip(&mut self) {
+		while self.at < self.text.len() && (self.text.as_bytes()[self.at] as char).is_whitespace() {
+			self.at += 1;
+		}
+	}
+
+	fn expect(&mut self, c: u8) -> Result<(), NBodyError> {
+		self.skip();
+		if self.text.as_bytes().get(self.at) != Some(&c) {
+			return Err(self.error(&format!("expected '{}'", c as char)));
+		}
+		self.at += 1;
+		Ok(())
+	}
+
+	fn string(&mut self) -> Result<String, NBodyError> {
+		self.expect(b'"')?;
+		let mut out = String::new();
+		let start = self.at;
+		let text: &'a str = self.text;
+		let mut chars = text[start..].char_indices();
+		while let Some((i, c)) = chars.next() {
+			match c {
+				'"' => {
+					self.at = start + i + 1;
+					return Ok(out);
+				},
+				'\\' => match chars.next().map(|x| x.1) {
+					Some('n') => out.push('\n'),
+					Some('t') => out.push('\t'),
+					Some('r') => out.push('\r'),
+					Some('b') => out.push('\u{8}'),
+					Some('f') => out.push('\u{c}'),
+					Some('u') => {
+						let hex: String = (0..4).filter_map(|_| chars.next().map(|x| x.1)).collect();
+						let code = u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32);
+						out.push(code.ok_or_else(|| self.error(&format!("bad escape \\u{}", hex)))?);
+					},
+					Some(c) => out.push(c),
+					None => break,
+				},
+				c => out.push(c),
+			}
+		}
+		Err(self.error("unterminated string"))
+	}
+
+	fn value(&mut self) -> Result<Json, NBodyError> {
+		self.skip();
+		let bytes: &'a [u8] = self.text.as_bytes();
+		let rest = &bytes[self.at.min(bytes.len())..];
+		match rest.first() {
+			Some(b'{') => {
+				self.at += 1;
+				let mut members = vec![];
+				self.skip();
+				if bytes.get(self.at) == Some(&b'}') {
+					self.at += 1;
+					return Ok(Json::Object(members));
+				}
+				loop {
+					let key = self.string()?;
+					self.expect(b':')?;
+					members.push((key, self.value()?));
+					self.skip();
+					match bytes.get(self.at) {
+						Some(b',') => self.at += 1,
+						Some(b'}') => {
+							self.at += 1;
+							return Ok(Json::Object(members));
+						},
+						_ => return Err(self.error("expected ',' or '}'")),
+					}
+				}
+			},
+			Some(b'[') => {
+				self.at += 1;
+				let mut items = vec![];
+				self.skip();
+				if bytes.get(self.at) == Some(&b']') {
+					self.at += 1;
+					return Ok(Json::Array(items));
+				}
+				loop {
+					items.push(self.value()?);
+					self.skip();
+					match bytes.get(self.at) {
+						Some(b',') => self.at += 1,
+						Some(b']') => {
+							self.at += 1;
+							return Ok(Json::Array(items));
+						},
+						_ => return Err(self.error("expected ',' or ']'")),
+					}
+				}
+			},
+			Some(b'"') => Ok(Json::Str(self.string()?)),
+			_ if rest.starts_with(b"null") => {
+				self.at += 4;
+				Ok(Json::Null)
+			},
+			_ if rest.starts_with(b"true") => {
+				self.at += 4;
+				Ok(Json::Bool(true))
+			},
+			_ if rest.starts_with(b"false") => {
+				self.at += 5;
+				Ok(Json::Bool(false))
+			},
+			_ => {
+				let n = rest.iter().take_while(|&&c| c == b'-' || c == b'+' || c == b'.' || c == b'e' || c == b'E' || c.is_ascii_digit()).count();
+				let x = String::from_utf8_lossy(&rest[..n]).into_owned();
+				if n == 0 || x.parse::<f64>().is_err() {
+					return Err(self.error("expected a value"));
+				}
+				self.at += n;
+				Ok(Json::Number(x))
+			},
+		}
+	}
+}
+
+// Extra column values are numbers in JSON; ones JSON cannot spell (like "1.") go through f64
+fn json_value(x: &str) -> String {
+	let digits = |x: &str| !x.is_empty() && x.bytes().all(|c| c.is_ascii_digit());
+	let (mantissa, exponent) = match x.find(|c: char| c == 'e' || c == 'E') {
+		Some(k) => (&x[..k], Some(x[k + 1..].trim_start_matches(|c: char| c == '+' || c == '-'))),
+		None => (x, None),
+	};
+	let mantissa = mantissa.strip_prefix('-').unwrap_or(mantissa);
+	let (int, frac) = match mantissa.find('.') {
+		Some(k) => (&mantissa[..k], Some(&mantissa[k + 1..])),
+		None => (mantissa, None),
+	};
+	let valid = digits(int) && (int == "0" || !int.starts_with('0')) && frac.map_or(true, digits) && exponent.map_or(true, digits);
+	match x.parse::<f64>() {
+		_ if valid => String::from(x),
+		Ok(y) => json_number(y),
+		// A species tag
+		Err(_) => json_string(x),
+	}
+}
+
+pub fn write_json<W: Write>(w: &mut W, x: &Snapshot) -> io::Result<()> {
+	let mut names: Vec<&str> = BASE.to_vec();
+	names.extend(x.columns.names.iter().map(|x| x.as_str()));
+	let quoted: Vec<String> = names.iter().map(|x| json_string(x)).collect();
+	writeln!(w, "{{\n  \"t\": {},\n  \"steps\": {},\n  \"columns\": [{}],\n  \"stars\": [", json_number(x.t), x.steps, quoted.join(", "))?;
+	for (i, star) in x.s.iter().enumerate() {
+		let mut fields = vec![format!("\"id\": {}", json_string(&x.ids[i])), format!("\"m\": {}", json_number(star.m))];
+		for k in 0..3 {
+			fields.push(format!("{}: {}", json_string(BASE[2 + k]), json_number(star.r[k])));
+		}
+		for k in 0..3 {
+			fields.push(format!("{}: {}", json_string(BASE[5 + k]), json_number(star.v[k])));
+		}
+		for (name, value) in x.columns.names.iter().zip(x.columns.row(i)) {
+			fields.push(format!("{}: {}", json_string(name), json_value(value)));
+		}
+		writeln!(w, "    {{{}}}{}", fields.join(", "), if i + 1 < x.s.len() { "," } else { "" })?;
+	}
+	writeln!(w, "  ]\n}}")
+}
+
+pub fn read_json(text: &str) -> Result<Snapshot, NBodyError> {
+	let mut parser = JsonParser { text: text, at: 0 };
+	let root = parser.value()?;
+	let bad = |what: &str| NBodyError::Config(format!("JSON snapshot: {}", what));
+	let t = match root.get("t") {
+		Some(x) => x.number().and_then(|x| x.parse().ok()).ok_or_else(|| bad("t is not a number"))?,
+		None => 0.0,
+	};
+	let steps = match root.get("steps") {
+		Some(x) => x.number().and_then(|x| x.parse().ok()).ok_or_else(|| bad("steps is not a count"))?,
+		None => 0,
+	};
+	let extra: Vec<String> = match root.get("columns") {
+		Some(&Json::Array(ref names)) => names.iter().skip(BASE.len()).map(|x| match *x {
+			Json::Str(ref x) => Ok(x.clone()),
+			_ => Err(bad("column names must be strings")),
+		}).collect::<Result<_, _>>()?,
+		Some(_) => return Err(bad("columns is not a list")),
+		None => vec![],
+	};
+	let stars = match root.get("stars") {
+		Some(&Json::Array(ref x)) => x,
+		_ => return Err(bad("no list of stars")),
+	};
+	let mut x = Snapshot { t: t, steps: steps, ids: vec![], s: vec![], columns: Columns { names: extra.clone(), ..Columns::default() } };
+	for (i, item) in stars.iter().enumerate() {
+		let number = |name: &str| -> Result<f64, NBodyError> {
+			item.get(name).and_then(|x| x.number()).and_then(|x| x.parse().ok()).ok_or_else(|| bad(&format!("star {} has no number {}", i, name)))
+		};
+		let id = match item.get("id") {
+			Some(&Json::Str(ref x)) => x.clone(),
+			Some(&Json::Number(ref x)) => x.clone(),
+			_ => i.to_string(),
+		};
+		let m = number("m")?;
+		if !(m >= 0.0) {
+			return Err(bad(&format!("star {} has mass {}", i, m)));
+		}
+		x.s.push(star(m, [number("x")?, number("y")?, number("z")?], [number("vx")?, number("vy")?, number("vz")?]));
+		x.ids.push(id);
+		let row = extra.iter().map(|name| {
+			match item.get(name) {
+				Some(&Json::Str(ref x)) if name == SPECIES => Ok(x.clone()),
+				x => x.and_then(|x| x.number()).map(String::from).ok_or_else(|| bad(&format!("star {} has no number {}", i, name))),
+			}
+		}).collect::<Result<Vec<String>, _>>()?;
+		x.columns.rows.push(row);
+	}
+	if extra.is_empty() {
+		x.columns.rows.clear();
+	}
+	Ok(x)
+}
+
+// A number as Fortran may write it, 1.5D-03 included
+fn fortran_number(word: &str) -> Option<f64> {
+	word.replace(&['D', 'd'][..], "e").parse().ok().filter(|x: &f64| x.is_finite())
+}
+
+/*
+ NBODY input as Aarseth's codes read it (dat.10, fort.10): a star per line,
+ m x y z vx vy vz, numbered from 1 in order. The variant of the ACS and
+ nbody_sh1 tutorials, which opens with a line holding N and one holding the
+ time, is read too.
+ */
+pub fn read_nbody(text: &str) -> Result<Snapshot, NBodyError> {
+	let mut x = Snapshot::new(vec![]);
+	let mut header = vec![];
+	for (number, line) in text.split("\n").enumerate() {
+		if line.trim() == "" || line.starts_with('#') {
+			continue;
+		}
+		let words: Vec<&str> = line.split_whitespace().collect();
+		let values: Vec<f64> = match words.iter().map(|w| fortran_number(w)).collect() {
+			Some(x) => x,
+			None => return Err(NBodyError::parse(number + 1, 1, format!("'{}' is not a line of numbers", line.trim()))),
+		};
+		if values.len() == 1 && x.s.is_empty() && header.len() < 2 {
+			header.push(values[0]);
+			continue;
+		}
+		if values.len() != 7 {
+			return Err(NBodyError::parse(number + 1, 1, format!("expected 7 columns (m x y z vx vy vz), found {}", values.len())));
+		}
+		if values[0] < 0.0 {
+			return Err(NBodyError::parse(number + 1, 1, format!("negative mass {}", values[0])));
+		}
+		x.s.push(star(values[0], [values[1], values[2], values[3]], [values[4], values[5], values[6]]));
+	}
+	if let Some(&n) = header.first() {
+		if n != x.s.len() as f64 {
+			return Err(NBodyError::Config(format!("NBODY header says {} stars, found {}", n, x.s.len())));
+		}
+	}
+	x.t = header.get(1).cloned().unwrap_or(0.0);
+	x.ids = (1..x.s.len() + 1).map(|i| i.to_string()).collect();
+	Ok(x)
+}
+
+// Plain dat.10, which has room for neither the ids, the time nor extra columns
+pub fn write_nbody<W: Write>(w: &mut W, x: &Snapshot) -> io::Result<()> {
+	if !x.columns.is_empty() {
+		warn!("NBODY input has no room for the extra columns {}, they are left out", x.columns.names.join(" "));
+	}
+	if x.t != 0.0 {
+		warn!("NBODY input has no time, t = {} is left out", x.t);
+	}
+	for star in &x.s {
+		writeln!(w, "{:e} {:e} {:e} {:e} {:e} {:e} {:e}", star.m, star.r[0], star.r[1], star.r[2], star.v[0], star.v[1], star.v[2])?;
+	}
+	Ok(())
+}
+
+// A particle of a Starlab tree, with its position and velocity relative to its parent
+#[derive(Default)]
+struct Particle {
+	id: Option<String>,
+	name: Option<String>,
+	m: f64,
+	r: [f64; 3],
+	v: [f64; 3],
+	t: Option<f64>,
+	children: Vec<Particle>,
+}
+
+// Adds the leaves under x to out, at positions relative to the root
+fn leaves(x: Particle, r: [f64; 3], v: [f64; 3], out: &mut Snapshot) {
+	let r = [r[0] + x.r[0], r[1] + x.r[1], r[2] + x.r[2]];
+	let v = [v[0] + x.v[0], v[1] + x.v[1], v[2] + x.v[2]];
+	if x.children.is_empty() {
+		let name = x.name.filter(|x| x.parse::<f64>().is_ok());
+		out.ids.push(name.or(x.id).unwrap_or_else(|| out.s.len().to_string()));
+		out.s.push(star(x.m, r, v));
+	}
+	for child in x.children {
+		leaves(child, r, v, out);
+	}
+}
+
+/*
+ Starlab's tree format: nested "(Particle" ... ")Particle" blocks, the
+ dynamics of each in a "(Dynamics" block of "key = value" lines, positions
+ and velocities of children relative to their parent. The stars are the
+ leaves, so the members of a binary come out at their own positions; their
+ ids are the names where those are numbers and else the "i" indices. The time is the root's
+ system_time. Log, Hydro and Star blocks are skipped, and of a file with
+ several snapshots only the first is read.
+ */
+pub fn read_starlab(text: &str) -> Result<Snapshot, NBodyError> {
+	let mut stack: Vec<Particle> = vec![];
+	// Depth of the blocks other than Particle and Dynamics around the line
+	let mut skipped = 0;
+	let mut dynamics = false;
+	let mut root = None;
+	for (number, line) in text.split("\n").enumerate() {
+		let line = line.trim();
+		let bad = |what: String| NBodyError::parse(number + 1, 1, what);
+		if line == "(Particle" && skipped == 0 && !dynamics {
+			stack.push(Particle::default());
+		} else if line == ")Particle" && skipped == 0 && !dynamics {
+			let x = stack.pop().ok_or_else(|| bad(String::from(")Particle without (Particle")))?;
+			match stack.last_mut() {
+				Some(parent) => parent.children.push(x),
+				None => {
+					root = Some(x);
+					break;
+				},
+			}
+		} else if line == "(Dynamics" && skipped == 0 {
+			dynamics = true;
+		} else if line == ")Dynamics" && skipped == 0 {
+			dynamics = false;
+		} else if line.starts_with('(') {
+			skipped += 1;
+		} else if line.starts_with(')') {
+			if skipped == 0 {
+				return Err(bad(format!("{} without a matching opening line", line)));
+			}
+			skipped -= 1;
+		} else if skipped == 0 && line.contains('=') {
+			let x = stack.last_mut().ok_or_else(|| bad(String::from("expected (Particle")))?;
+			let at = line.find('=').expect("Checked above");
+			let (key, value) = (line[..at].trim(), line[at + 1..].trim());
+			let numbers = || -> Result<Vec<f64>, NBodyError> {
+				value.split_whitespace().map(fortran_number).collect::<Option<Vec<f64>>>().ok_or_else(|| bad(format!("'{}' is not a number", value)))
+			};
+			let vector = || -> Result<[f64; 3], NBodyError> {
+				let x = numbers()?;
+				if x.len() != 3 {
+					return Err(bad(format!("{} needs 3 components, found {}", key, x.len())));
+				}
+				Ok([x[0], x[1], x[2]])
+			};
+			match (dynamics, key) {
+				(false, "i") => x.id = Some(String::from(value)),
+				(false, "name") => x.name = Some(String::from(value)),
+				(true, "m") => x.m = *numbers()?.first().ok_or_else(|| bad(String::from("m needs a value")))?,
+				(true, "r") => x.r = vector()?,
+				(true, "v") => x.v = vector()?,
+				(true, "system_time") | (true, "t") => x.t = numbers()?.first().cloned(),
+				_ => (),
+			}
+		}
+	}
+	let root = match root {
+		Some(x) => x,
+		None => return Err(NBodyError::Config(String::from("No complete (Particle ... )Particle tree in the input"))),
+	};
+	let mut x = Snapshot::new(vec![]);
+	x.ids.clear();
+	x.t = root.t.unwrap_or(0.0);
+	// The root's own position and velocity are those of the system, the stars are relative to it
+	if root.children.is_empty() {
+		leaves(root, [0.0; 3], [0.0; 3], &mut x);
+	} else {
+		for child in root.children {
+			leaves(child, [0.0; 3], [0.0; 3], &mut x);
+		}
+	}
+	Ok(x)
+}
+
+// A flat Starlab tree: the root and a leaf per star, numbered from 1
+pub fn write_starlab<W: Write>(w: &mut W, x: &Snapshot) -> io::Result<()> {
+	if !x.columns.is_empty() {
+		warn!("Starlab has no room for the extra columns {}, they are left out", x.columns.names.join(" "));
+	}
+	let block = |w: &mut W, name: Option<(usize, &str)>, n: usize, dynamics: &[String]| -> io::Result<()> {
+		writeln!(w, "(Particle")?;
+		if let Some((i, id)) = name {
+			writeln!(w, "  i = {}", i)?;
+			if id != i.to_string() {
+				writeln!(w, "  name = {}", id)?;
+			}
+		}
+		writeln!(w, "  N = {}\n(Log\n)Log\n(Dynamics", n)?;
+		for line in dynamics {
+			writeln!(w, "  {}", line)?;
+		}
+		writeln!(w, ")Dynamics\n(Hydro\n)Hydro\n(Star\n)Star")
+	};
+	let mass: f64 = x.s.iter().map(|x| x.m).sum();
+	block(w, None, x.s.len(), &[format!("system_time = {:e}", x.t), format!("m = {:e}", mass), String::from("r = 0 0 0"), String::from("v = 0 0 0")])?;
+	for (i, star) in x.s.iter().enumerate() {
+		block(w, Some((i + 1, &x.ids[i])), 1, &[
+			format!("m = {:e}", star.m),
+			format!("r = {:e} {:e} {:e}", star.r[0], star.r[1], star.r[2]),
+			format!("v = {:e} {:e} {:e}", star.v[0], star.v[1], star.v[2]),
+		])?;
+		writeln!(w, ")Particle")?;
+	}
+	writeln!(w, ")Particle")
+}
+
+/*
+ The format of a run's input: starlab when it opens with "(Particle", nbody
+ when its star lines have the 7 columns m x y z vx vy vz, text otherwise.
+ */
+pub fn detect(text: &str) -> Format {
+	if text_lines(text).next().map(|x| x.trim()) == Some("(Particle") {
+		return Format::Starlab;
+	}
+	match text_lines(text).find(|x| x.split_whitespace().count() > 1) {
+		Some(x) if x.split_whitespace().count() == 7 => Format::Nbody,
+		_ => Format::Text,
+	}
+}
+
+// Reads fixed-size fields from a binary file, little or big endian
+struct Bytes<'a> {
+	data: &'a [u8],
+	at: usize,
+	big: bool,
+}
+
+impl<'a> Bytes<'a> {
+	fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+		if self.at + n > self.data.len() {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("file ends at byte {}, {} more expected", self.data.len(), self.at + n - self.data.len())));
+		}
+		self.at += n;
+		let data: &'a [u8] = self.data;
+		Ok(&data[self.at - n..self.at])
+	}
+
+	fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+		let mut x = [0; N];
+		x.copy_from_slice(self.take(N)?);
+		if self.big {
+			x.reverse();
+		}
+		Ok(x)
+	}
+
+	fn u32(&mut self) -> io::Result<u32> {
+		Ok(u32::from_le_bytes(self.array()?))
+	}
+
+	fn f32(&mut self) -> io::Result<f64> {
+		Ok(f32::from_le_bytes(self.array()?) as f64)
+	}
+
+	fn f64(&mut self) -> io::Result<f64> {
+		Ok(f64::from_le_bytes(self.array()?))
+	}
+}
+
+fn invalid(what: String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+// A Fortran record: its length before and after the data
+fn gadget_record(out: &mut Vec<u8>, data: &[u8]) {
+	out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+	out.extend_from_slice(data);
+	out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+}
+
+fn gadget_block<'a>(b: &mut Bytes<'a>, name: &str, size: usize) -> io::Result<&'a [u8]> {
+	let n = b.u32()? as usize;
+	if n != size {
+		return Err(invalid(format!("Gadget {} block has {} bytes, expected {}", name, n, size)));
+	}
+	let data = b.take(n)?;
+	if b.u32()? as usize != n {
+		return Err(invalid(format!("Gadget {} block is not closed", name)));
+	}
+	Ok(data)
+}
+
+pub fn write_gadget(x: &Snapshot) -> Vec<u8> {
+	let n = x.s.len();
+	let ids = x.integer_ids().filter(|x| x.iter().all(|&id| id <= std::u32::MAX as u64));
+	if ids.is_none() {
+		warn!("Gadget ids are 32-bit integers, numbering the stars from 0 instead");
+	}
+	if !x.columns.is_empty() {
+		warn!("Gadget has no room for the extra columns {}, they are left out", x.columns.names.join(" "));
+	}
+	let mut header = vec![];
+	for k in 0..6 {
+		header.extend_from_slice(&(if k == 1 { n as u32 } else { 0 }).to_le_bytes());
+	}
+	// Individual masses for every type
+	header.extend_from_slice(&[0; 48]);
+	header.extend_from_slice(&x.t.to_le_bytes());
+	// Redshift, the star formation and feedback flags
+	header.extend_from_slice(&[0; 16]);
+	for k in 0..6 {
+		header.extend_from_slice(&(if k == 1 { n as u32 } else { 0 }).to_le_bytes());
+	}
+	// Cooling flag, then one file
+	header.extend_from_slice(&0u32.to_le_bytes());
+	header.extend_from_slice(&1u32.to_le_bytes());
+	// Box size, Omega0, OmegaLambda, and h = 1
+	header.extend_from_slice(&[0; 24]);
+	header.extend_from_slice(&1.0f64.to_le_bytes());
+	header.resize(256, 0);
+
+	let mut out = vec![];
+	gadget_record(&mut out, &header);
+	let floats = |f: &dyn Fn(&Star) -> Vec<f64>| -> Vec<u8> {
+		x.s.iter().flat_map(|star| f(star)).flat_map(|v| (v as f32).to_le_bytes().to_vec()).collect()
+	};
+	gadget_record(&mut out, &floats(&|star: &Star| star.r[..3].to_vec()));
+	gadget_record(&mut out, &floats(&|star: &Star| star.v[..3].to_vec()));
+	let ids: Vec<u8> = match ids {
+		Some(ids) => ids.iter().flat_map(|&id| (id as u32).to_le_bytes().to_vec()).collect(),
+		None => (0..n as u32).flat_map(|id| id.to_le_bytes().to_vec()).collect(),
+	};
+	gadget_record(&mut out, &ids);
+	gadget_record(&mut out, &floats(&|star: &Star| vec![star.m]));
+	out
+}
+
+pub fn read_gadget(data: &[u8]) -> io::Result<Snapshot> {
+	let mut b = Bytes { data: data, at: 0, big: false };
+	let header = gadget_block(&mut b, "header", 256)?;
+	let mut h = Bytes { data: header, at: 0, big: false };
+	let counts: Vec<usize> = (0..6).map(|_| h.u32().map(|x| x as usize)).collect::<io::Result<_>>()?;
+	let masses: Vec<f64> = (0..6).map(|_| h.f64()).collect::<io::Result<_>>()?;
+	let t = h.f64()?;
+	let n: usize = counts.iter().sum();
+	let pos = gadget_block(&mut b, "position", 12*n)?;
+	let vel = gadget_block(&mut b, "velocity", 12*n)?;
+	let ids = gadget_block(&mut b, "id", 4*n)?;
+	// Only types without a fixed mass have a mass entry per star
+	let variable: usize = (0..6).filter(|&k| masses[k] == 0.0).map(|k| counts[k]).sum();
+	let mass = if variable > 0 { gadget_block(&mut b, "mass", 4*variable)? } else { &[][..] };
+
+	let (mut pos, mut vel, mut ids, mut mass) = (Bytes { data: pos, at: 0, big: false }, Bytes { data: vel, at: 0, big: false }, Bytes { data: ids, at: 0, big: false }, Bytes { data: mass, at: 0, big: false });
+	let mut x = Snapshot { t: t, steps: 0, ids: vec![], s: vec![], columns: Columns::default() };
+	for k in 0..6 {
+		for _ in 0..counts[k] {
+			let m = if masses[k] == 0.0 { mass.f32()? } else { masses[k] };
+			let r = [pos.f32()?, pos.f32()?, pos.f32()?];
+			let v = [vel.f32()?, vel.f32()?, vel.f32()?];
+			x.s.push(star(m, r, v));
+			x.ids.push(ids.u32()?.to_string());
+		}
+	}
+	Ok(x)
+}
+
+pub fn write_tipsy(x: &Snapshot) -> Vec<u8> {
+	if !x.columns.is_empty() {
+		warn!("Tipsy has no room for the extra columns {}, they are left out", x.columns.names.join(" "));
+	}
+	if x.ids.iter().enumerate().any(|(i, id)| *id != i.to_string()) {
+		warn!("Tipsy numbers the stars by their order, the input ids are left out");
+	}
+	let n = x.s.len() as u32;
+	let mut out = vec![];
+	out.extend_from_slice(&x.t.to_be_bytes());
+	// nbodies, ndim, nsph, ndark, nstar and padding
+	for &k in &[n, 3, 0, n, 0, 0] {
+		out.extend_from_slice(&k.to_be_bytes());
+	}
+	for star in &x.s {
+		// mass, pos, vel, eps, phi
+		let values = [star.m, star.r[0], star.r[1], star.r[2], star.v[0], star.v[1], star.v[2], 0.0, 0.0];
+		for &v in values.iter() {
+			out.extend_from_slice(&(v as f32).to_be_bytes());
+		}
+	}
+	out
+}
+
+pub fn read_tipsy(data: &[u8]) -> io::Result<Snapshot> {
+	let mut b = Bytes { data: data, at: 0, big: true };
+	let t = b.f64()?;
+	let n = b.u32()? as usize;
+	let ndim = b.u32()?;
+	let (gas, dark, stars) = (b.u32()? as usize, b.u32()? as usize, b.u32()? as usize);
+	if ndim != 3 || gas + dark + stars != n {
+		return Err(invalid(format!("not a standard Tipsy file: {} dimensions, {} + {} + {} of {} bodies", ndim, gas, dark, stars, n)));
+	}
+	b.take(4)?;
+	let mut x = Snapshot { t: t, steps: 0, ids: (0..n).map(|i| i.to_string()).collect(), s: vec![], columns: Columns::default() };
+	// Gas, dark matter and stars differ in what follows mass, position and velocity
+	for &(count, rest) in &[(gas, 5), (dark, 2), (stars, 4)] {
+		for _ in 0..count {
+			let m = b.f32()?;
+			let r = [b.f32()?, b.f32()?, b.f32()?];
+			let v = [b.f32()?, b.f32()?, b.f32()?];
+			b.take(4*rest)?;
+			x.s.push(star(m, r, v));
+		}
+	}
+	Ok(x)
+}
+
+#[cfg(feature = "hdf5")]
+fn hdf5_error(x: hdf5::Error) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, x.to_string())
+}
+
+#[cfg(feature = "hdf5")]
+pub fn write_hdf5(path: &str, x: &Snapshot) -> io::Result<()> {
+	let n = x.s.len();
+	let file = hdf5::File::create(path).map_err(hdf5_error)?;
+	let header = file.create_group("Header").map_err(hdf5_error)?;
+	header.new_attr::<f64>().shape(()).create("Time").and_then(|a| a.write_scalar(&x.t)).map_err(hdf5_error)?;
+	header.new_attr::<u64>().shape(()).create("Steps").and_then(|a| a.write_scalar(&(x.steps as u64))).map_err(hdf5_error)?;
+	let counts: Vec<u64> = (0..6).map(|k| if k == 1 { n as u64 } else { 0 }).collect();
+	header.new_attr::<u64>().shape(6).create("NumPart_ThisFile").and_then(|a| a.write_raw(&counts)).map_err(hdf5_error)?;
+	header.new_attr::<u64>().shape(6).create("NumPart_Total").and_then(|a| a.write_raw(&counts)).map_err(hdf5_error)?;
+
+	let stars = file.create_group("PartType1").map_err(hdf5_error)?;
+	let vectors = |name: &str, velocity: bool| -> hdf5::Result<()> {
+		let values: Vec<f64> = x.s.iter().flat_map(|star| if velocity { star.v[..3].to_vec() } else { star.r[..3].to_vec() }).collect();
+		stars.new_dataset::<f64>().shape((n, 3)).create(name)?.write_raw(&values)
+	};
+	vectors("Coordinates", false).map_err(hdf5_error)?;
+	vectors("Velocities", true).map_err(hdf5_error)?;
+	let masses: Vec<f64> = x.s.iter().map(|star| star.m).collect();
+	stars.new_dataset::<f64>().shape(n).create("Masses").and_then(|d| d.write_raw(&masses)).map_err(hdf5_error)?;
+	let ids = x.integer_ids().unwrap_or_else(|| {
+		warn!("HDF5 ParticleIDs are integers, numbering the stars from 0 instead");
+		(0..n as u64).collect()
+	});
+	stars.new_dataset::<u64>().shape(n).create("ParticleIDs").and_then(|d| d.write_raw(&ids)).map_err(hdf5_error)?;
+	for (k, name) in x.columns.names.iter().enumerate() {
+		let values: Vec<f64> = (0..n).map(|i| x.columns.row(i)[k].parse().unwrap_or(std::f64::NAN)).collect();
+		stars.new_dataset::<f64>().shape(n).create(name.as_str()).and_then(|d| d.write_raw(&values)).map_err(hdf5_error)?;
+	}
+	Ok(())
+}
+
+#[cfg(feature = "hdf5")]
+pub fn read_hdf5(path: &str) -> io::Result<Snapshot> {
+	let file = hdf5::File::open(path).map_err(hdf5_error)?;
+	let header = file.group("Header").map_err(hdf5_error)?;
+	let t: f64 = header.attr("Time").and_then(|a| a.read_scalar()).map_err(hdf5_error)?;
+	let steps: u64 = header.attr("Steps").and_then(|a| a.read_scalar()).unwrap_or(0);
+	let stars = file.group("PartType1").map_err(hdf5_error)?;
+	let read = |name: &str| -> io::Result<Vec<f64>> { stars.dataset(name).and_then(|d| d.read_raw::<f64>()).map_err(hdf5_error) };
+	let (r, v, m) = (read("Coordinates")?, read("Velocities")?, read("Masses")?);
+	let n = m.len();
+	if r.len() != 3*n || v.len() != 3*n {
+		return Err(invalid(format!("HDF5 PartType1 has {} masses but {} coordinates and {} velocities", n, r.len()/3, v.len()/3)));
+	}
+	let ids: Vec<String> = match stars.dataset("ParticleIDs").and_then(|d| d.read_raw::<u64>()) {
+		Ok(x) => x.iter().map(|x| x.to_string()).collect(),
+		Err(_) => (0..n).map(|i| i.to_string()).collect(),
+	};
+	let known = ["Coordinates", "Velocities", "Masses", "ParticleIDs"];
+	let mut columns = Columns::default();
+	let mut values = vec![];
+	for name in stars.member_names().map_err(hdf5_error)? {
+		if known.contains(&name.as_str()) {
+			continue;
+		}
+		let x = read(&name)?;
+		if x.len() == n {
+			columns.names.push(name);
+			values.push(x);
+		}
+	}
+	if !columns.names.is_empty() {
+		columns.rows = (0..n).map(|i| values.iter().map(|x| format!("{:e}", x[i])).collect()).collect();
+	}
+	let s = (0..n).map(|i| star(m[i], [r[3*i], r[3*i + 1], r[3*i + 2]], [v[3*i], v[3*i + 1], v[3*i + 2]])).collect();
+	Ok(Snapshot { t: t, steps: steps as usize, ids: ids, s: s, columns: columns })
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn no_hdf5() -> NBodyError {
+	NBodyError::Config(String::from("HDF5 needs a build with --features hdf5"))
+}
+
+fn read_bytes(path: &str) -> Result<Vec<u8>, NBodyError> {
+	let mut data = vec![];
+	let result = if path == "-" { io::stdin().read_to_end(&mut data).map(|_| ()) } else { fs::File::open(path).and_then(|mut f| f.read_to_end(&mut data)).map(|_| ()) };
+	result.map_err(|x| NBodyError::io(&format!("Could not read {}", path), x))?;
+	Ok(data)
+}
+
+fn text(data: Vec<u8>, path: &str) -> Result<String, NBodyError> {
+	String::from_utf8(data).map_err(|_| NBodyError::Config(format!("{} is not a text file", path)))
+}
+
+// Reads a snapshot in one of the text formats
+pub fn read_str(text: &str, format: Format) -> Result<Snapshot, NBodyError> {
+	match format {
+		Format::Text => read_text(text),
+		Format::Csv => read_csv(text),
+		Format::Json => read_json(text),
+		Format::Nbody => read_nbody(text),
+		Format::Starlab => read_starlab(text),
+		Format::Gadget | Format::Tipsy | Format::Hdf5 => Err(NBodyError::Config(format!("{:?} is a binary format", format))),
+	}
+}
+
+// Reads a snapshot from path ("-" for stdin)
+pub fn read(path: &str, format: Format) -> Result<Snapshot, NBodyError> {
+	let io = |x: io::Error| NBodyError::io(&format!("Could not read {}", path), x);
+	match format {
+		Format::Text | Format::Csv | Format::Json | Format::Nbody | Format::Starlab => read_str(&text(read_bytes(path)?, path)?, format),
+		Format::Gadget => read_gadget(&read_bytes(path)?).map_err(io),
+		Format::Tipsy => read_tipsy(&read_bytes(path)?).map_err(io),
+		#[cfg(feature = "hdf5")]
+		Format::Hdf5 => read_hdf5(path).map_err(io),
+		#[cfg(not(feature = "hdf5"))]
+		Format::Hdf5 => Err(no_hdf5()),
+	}
+}
+
+// Writes a snapshot to path ("-" for stdout)
+pub fn write(path: &str, format: Format, x: &Snapshot) -> Result<(), NBodyError> {
+	let mut data = vec![];
+	match format {
+		Format::Text => write_text(&mut data, x).expect("Writing to memory"),
+		Format::Csv => write_csv(&mut data, x).expect("Writing to memory"),
+		Format::Json => write_json(&mut data, x).expect("Writing to memory"),
+		Format::Gadget => data = write_gadget(x),
+		Format::Tipsy => data = write_tipsy(x),
+		Format::Nbody => write_nbody(&mut data, x).expect("Writing to memory"),
+		Format::Starlab => write_starlab(&mut data, x).expect("Writing to memory"),
+		#[cfg(feature = "hdf5")]
+		Format::Hdf5 => return write_hdf5(path, x).map_err(|e| NBodyError::io(&format!("Could not write {}", path), e)),
+		#[cfg(not(feature = "hdf5"))]
+		Format::Hdf5 => return Err(no_hdf5()),
+	}
+	let result = if path == "-" { io::stdout().write_all(&data) } else { fs::write(path, &data) };
+	result.map_err(|e| NBodyError::io(&format!("Could not write {}", path), e))
+}
+
+pub fn main(args: &[String]) {
+	let usage = "Usage: nbabel convert INPUT [--from text|csv|json|gadget|tipsy|hdf5|nbody|starlab] --to FORMAT OUTPUT";
+	let mut paths = vec![];
+	let mut from: Option<Format> = None;
+	let mut to: Option<Format> = None;
+	let mut it = args.iter();
+	while let Some(arg) = it.next() {
+		match arg.as_str() {
+			"--from" => from = Some(it.next().and_then(|x| Format::parse(x)).expect("--from needs text, csv, json, gadget, tipsy, hdf5, nbody or starlab")),
+			"--to" => to = Some(it.next().and_then(|x| Format::parse(x)).expect("--to needs text, csv, json, gadget, tipsy, hdf5, nbody or starlab")),
+			_ if arg.starts_with("--") => panic!("Unknown argument: {}\n{}", arg, usage),
+			_ => paths.push(arg.clone()),
+		}
+	}
+	if paths.len() != 2 {
+		panic!("{}", usage);
+	}
+	let from = from.unwrap_or_else(|| Format::of_path(&paths[0]));
+	let to = to.unwrap_or_else(|| Format::of_path(&paths[1]));
+	let result = read(&paths[0], from).and_then(|x| {
+		info!("{} stars at t = {} from {} ({:?})", x.s.len(), x.t, paths[0], from);
+		write(&paths[1], to, &x)
+	});
+	if let Err(x) = result {
+		error!("{}", x);
+		process::exit(x.exit_code());
+	}
+}
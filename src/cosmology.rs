@@ -0,0 +1,112 @@
+/*
+ Comoving coordinates for structure formation toys: --comoving
+ omega_m=OM,omega_lambda=OL[,h0=H][,z=Z] together with --periodic L.
+ Positions are comoving (the physical ones are a(t) times them) and the
+ velocities are dx/dt, in a background that expands as
+
+   H(a) = (da/dt)/a = H0 sqrt(OM/a^3 + (1 - OM - OL)/a^2 + OL)
+
+ from a = 1/(1 + Z) at t = 0 (Z = 0 by default). The stars then move as
+
+   d^2x/dt^2 = g(x)/a^3 - 2 H dx/dt,
+
+ with g the periodic force of the comoving positions, mean density removed,
+ and the -2 H dx/dt the Hubble drag. Written for the momentum p = a^2 dx/dt
+ that is dp/dt = g/a and dx/dt = p/a^2, so a kick-drift-kick step only
+ needs the integrals of 1/a and 1/a^2 over the step, taken along the
+ Friedmann solution, and the drag is exact (Quinn et al. 1997).
+
+ With G = 1 the matter in the box fixes the expansion: a mean comoving
+ density rho = M/L^3 needs H0^2 = 8 pi rho/(3 OM). That is the default H0;
+ with any other the expansion does not match the matter, which main.rs
+ warns about.
+ */
+use std::f64::consts::PI;
+
+// Runge-Kutta substeps of the Friedmann equation per step
+static SUBSTEPS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cosmology {
+	pub omega_m: f64,
+	pub omega_lambda: f64,
+	// None until known: the default follows from the box
+	pub h0: Option<f64>,
+	// Redshift at t = 0
+	pub z: f64,
+	// Scale factor now, advanced by Simulation::step()
+	pub a: f64,
+}
+
+impl Cosmology {
+	// Parses "omega_m=OM,omega_lambda=OL[,h0=H][,z=Z]" for --comoving
+	pub fn parse(spec: &str) -> Result<Cosmology, String> {
+		let (mut omega_m, mut omega_lambda, mut h0, mut z) = (None, None, None, 0.0);
+		for item in spec.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+			let mut kv = item.splitn(2, '=');
+			let key = kv.next().unwrap_or("");
+			let value: f64 = kv.next().and_then(|x| x.trim().parse().ok()).ok_or(format!("--comoving needs KEY=NUMBER, got '{}'", item))?;
+			match key {
+				"omega_m" => omega_m = Some(value),
+				"omega_lambda" => omega_lambda = Some(value),
+				"h0" => h0 = Some(value),
+				"z" => z = value,
+				_ => return Err(format!("Unknown --comoving parameter '{}', use omega_m, omega_lambda, h0 or z", key)),
+			}
+		}
+		let (omega_m, omega_lambda) = match (omega_m, omega_lambda) {
+			(Some(m), Some(l)) => (m, l),
+			_ => return Err(String::from("--comoving needs omega_m and omega_lambda")),
+		};
+		if !(omega_m > 0.0) || !(omega_lambda >= 0.0) || h0.map_or(false, |x| !(x > 0.0)) || !(z > -1.0) {
+			return Err(String::from("--comoving needs omega_m > 0, omega_lambda >= 0, h0 > 0 and z > -1"));
+		}
+		Ok(Cosmology { omega_m: omega_m, omega_lambda: omega_lambda, h0: h0, z: z, a: 1.0/(1.0 + z) })
+	}
+
+	// The H0 that matches mass in a box of side l with G = 1
+	pub fn matching_h0(&self, mass: f64, l: f64) -> f64 {
+		(8.0*PI*mass/(3.0*self.omega_m*l*l*l)).sqrt()
+	}
+
+	pub fn hubble(&self, a: f64) -> f64 {
+		let curvature = 1.0 - self.omega_m - self.omega_lambda;
+		self.h0.expect("H0 is set before integrating")*(self.omega_m/(a*a*a) + curvature/(a*a) + self.omega_lambda).sqrt()
+	}
+
+	/*
+	 The scale factor dt after a, with the integrals of 1/a and 1/a^2 over
+	 that time by Simpson's rule, the midpoints found by Runge-Kutta half steps.
+	 */
+	pub fn advance(&self, a: f64, dt: f64) -> (f64, f64, f64) {
+		let h = dt/SUBSTEPS as f64;
+		let rk4 = |a: f64, h: f64| {
+			let rate = |a: f64| a*self.hubble(a);
+			let k1 = rate(a);
+			let k2 = rate(a + 0.5*h*k1);
+			let k3 = rate(a + 0.5*h*k2);
+			let k4 = rate(a + h*k3);
+			a + h/6.0*(k1 + 2.0*k2 + 2.0*k3 + k4)
+		};
+		let (mut a, mut kick, mut drift) = (a, 0.0, 0.0);
+		for _ in 0..SUBSTEPS {
+			let mid = rk4(a, 0.5*h);
+			let next = rk4(mid, 0.5*h);
+			kick += h/6.0*(1.0/a + 4.0/mid + 1.0/next);
+			drift += h/6.0*(1.0/(a*a) + 4.0/(mid*mid) + 1.0/(next*next));
+			a = next;
+		}
+		(a, kick, drift)
+	}
+
+	// Scale factor at time t, in pieces short enough for advance()
+	pub fn scale_at(&self, t: f64) -> f64 {
+		let pieces = 100;
+		(0..pieces).fold(1.0/(1.0 + self.z), |a, _| self.advance(a, t/pieces as f64).0)
+	}
+
+	// Redshift of the current scale factor
+	pub fn redshift(&self) -> f64 {
+		1.0/self.a - 1.0
+	}
+}
@@ -0,0 +1,113 @@
+/*
+ Diagnostics of many runs in one SQLite database, for comparing runs with
+ SQL. With --sqlite PATH a run adds itself under its run ID (--run-id, or
+ start time, host and process by default) to four tables:
+
+ runs(run_id, start, host, version, git, input_sha256, command, config, n,
+      steps, t, outcome, wall_seconds)
+ diagnostics(run_id, t, E, kinetic, potential, dE, Q, n_bound, xc, yc, zc,
+             rc, rho_c)
+ lagrangian(run_id, t, fraction, radius)
+ timings(run_id, step, t, wall_seconds, forces, integration, diagnostics, io)
+
+ diagnostics and lagrangian get a row (a row per fraction) for every
+ diagnostics sample, timings the cumulative seconds per phase at every
+ diagnostic step; they join on run_id and t. Rows replace older ones with
+ the same key, so a run resumed from a checkpoint under the same --run-id
+ carries on its series. The run row is written at the start with outcome
+ "running" and completed at the end. SQL names are not case sensitive, so
+ the T and W of diagnostics.csv are called kinetic and potential here.
+ */
+use std::path::Path;
+
+use rusqlite::{params, Connection};
+
+use diagnostics::Sample;
+use metadata::Metadata;
+use timing::{Phase, Times};
+
+static SCHEMA: &'static str = "
+CREATE TABLE IF NOT EXISTS runs (
+	run_id TEXT PRIMARY KEY, start TEXT, host TEXT, version TEXT, git TEXT, input_sha256 TEXT,
+	command TEXT, config TEXT, n INTEGER, steps INTEGER, t REAL, outcome TEXT, wall_seconds REAL
+);
+CREATE TABLE IF NOT EXISTS diagnostics (
+	run_id TEXT, t REAL, E REAL, kinetic REAL, potential REAL, dE REAL, Q REAL, n_bound INTEGER,
+	xc REAL, yc REAL, zc REAL, rc REAL, rho_c REAL,
+	PRIMARY KEY (run_id, t)
+);
+CREATE TABLE IF NOT EXISTS lagrangian (
+	run_id TEXT, t REAL, fraction REAL, radius REAL,
+	PRIMARY KEY (run_id, t, fraction)
+);
+CREATE TABLE IF NOT EXISTS timings (
+	run_id TEXT, step INTEGER, t REAL, wall_seconds REAL,
+	forces REAL, integration REAL, diagnostics REAL, io REAL,
+	PRIMARY KEY (run_id, step)
+);
+";
+
+fn error(x: rusqlite::Error) -> String {
+	format!("SQLite: {}", x)
+}
+
+pub struct Database {
+	connection: Connection,
+	pub run_id: String,
+	// Samples of the history already stored
+	stored: usize,
+}
+
+// Run ID when none is given: start time, host and process
+pub fn default_run_id(metadata: &Metadata) -> String {
+	format!("{}-{}-{}", metadata.start, metadata.host, std::process::id())
+}
+
+impl Database {
+	// Opens or creates the database at path and registers the run
+	pub fn open(path: &Path, run_id: &str, metadata: &Metadata, n: usize) -> Result<Database, String> {
+		let connection = Connection::open(path).map_err(error)?;
+		connection.execute_batch(SCHEMA).map_err(error)?;
+		connection.execute(
+			"INSERT INTO runs (run_id, start, host, version, git, input_sha256, command, config, n, outcome) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 'running')
+			 ON CONFLICT (run_id) DO UPDATE SET outcome = 'running'",
+			params![run_id, metadata.start, metadata.host, metadata.version, metadata.git, metadata.input_sha256, metadata.command, metadata.config, n as i64],
+		).map_err(error)?;
+		Ok(Database { connection: connection, run_id: String::from(run_id), stored: 0 })
+	}
+
+	// Stores the samples that came in since the last call
+	pub fn samples(&mut self, samples: &[Sample], fractions: &[f64]) -> Result<(), String> {
+		let tx = self.connection.transaction().map_err(error)?;
+		for x in &samples[self.stored.min(samples.len())..] {
+			tx.execute(
+				"INSERT OR REPLACE INTO diagnostics VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+				params![self.run_id, x.t, x.e[0], x.e[1], x.e[2], x.de, x.q, x.n_bound as i64, x.center[0], x.center[1], x.center[2], x.core_radius, x.core_density],
+			).map_err(error)?;
+			for (f, r) in fractions.iter().zip(x.radii.iter()) {
+				tx.execute("INSERT OR REPLACE INTO lagrangian VALUES (?1, ?2, ?3, ?4)", params![self.run_id, x.t, f, r]).map_err(error)?;
+			}
+		}
+		tx.commit().map_err(error)?;
+		self.stored = samples.len();
+		Ok(())
+	}
+
+	// The cumulative time per phase at a diagnostic step
+	pub fn timings(&mut self, step: usize, t: f64, wall_seconds: f64, run: &Times) -> Result<(), String> {
+		self.connection.execute(
+			"INSERT OR REPLACE INTO timings VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+			params![self.run_id, step as i64, t, wall_seconds, run.get(Phase::Forces), run.get(Phase::Integration), run.get(Phase::Diagnostics), run.get(Phase::Io)],
+		).map_err(error)?;
+		Ok(())
+	}
+
+	// How the run ended
+	pub fn finish(&mut self, steps: usize, t: f64, outcome: &str, wall_seconds: f64) -> Result<(), String> {
+		self.connection.execute(
+			"UPDATE runs SET steps = ?2, t = ?3, outcome = ?4, wall_seconds = ?5 WHERE run_id = ?1",
+			params![self.run_id, steps as i64, t, outcome, wall_seconds],
+		).map_err(error)?;
+		Ok(())
+	}
+}
@@ -0,0 +1,272 @@
+/*
+ Double-double arithmetic: an unevaluated sum hi + lo of two f64s, good for
+ about 32 significant digits. Slow, but it plugs into the generic code as
+ another Real for precision studies. The algorithms are the usual ones from
+ Dekker (1971) and the QD library of Hida, Li & Bailey.
+ */
+use std::cmp::Ordering;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use Real;
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct DoubleDouble {
+	pub hi: f64,
+	pub lo: f64,
+}
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+	let s = a + b;
+	let bb = s - a;
+	(s, (a - (s - bb)) + (b - bb))
+}
+
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+	let s = a + b;
+	(s, b - (s - a))
+}
+
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+	let p = a*b;
+	(p, a.mul_add(b, -p))
+}
+
+impl DoubleDouble {
+	pub fn new(hi: f64, lo: f64) -> DoubleDouble {
+		let (hi, lo) = quick_two_sum(hi, lo);
+		DoubleDouble { hi: hi, lo: lo }
+	}
+
+	fn floor(self) -> DoubleDouble {
+		let hi = self.hi.floor();
+		if hi == self.hi {
+			DoubleDouble::new(hi, self.lo.floor())
+		} else {
+			DoubleDouble { hi: hi, lo: 0.0 }
+		}
+	}
+}
+
+impl Add for DoubleDouble {
+	type Output = DoubleDouble;
+	fn add(self, b: DoubleDouble) -> DoubleDouble {
+		let (s, e) = two_sum(self.hi, b.hi);
+		let (t, f) = two_sum(self.lo, b.lo);
+		let (s, e) = quick_two_sum(s, e + t);
+		DoubleDouble::new(s, e + f)
+	}
+}
+
+impl Sub for DoubleDouble {
+	type Output = DoubleDouble;
+	fn sub(self, b: DoubleDouble) -> DoubleDouble {
+		self + (-b)
+	}
+}
+
+impl Mul for DoubleDouble {
+	type Output = DoubleDouble;
+	fn mul(self, b: DoubleDouble) -> DoubleDouble {
+		let (p, e) = two_prod(self.hi, b.hi);
+		DoubleDouble::new(p, e + (self.hi*b.lo + self.lo*b.hi))
+	}
+}
+
+impl Div for DoubleDouble {
+	type Output = DoubleDouble;
+	fn div(self, b: DoubleDouble) -> DoubleDouble {
+		let q1 = self.hi/b.hi;
+		let r = self - b*DoubleDouble::from_f64(q1);
+		let q2 = r.hi/b.hi;
+		let r = r - b*DoubleDouble::from_f64(q2);
+		let q3 = r.hi/b.hi;
+		DoubleDouble::new(q1, q2) + DoubleDouble::from_f64(q3)
+	}
+}
+
+impl Neg for DoubleDouble {
+	type Output = DoubleDouble;
+	fn neg(self) -> DoubleDouble {
+		DoubleDouble { hi: -self.hi, lo: -self.lo }
+	}
+}
+
+impl AddAssign for DoubleDouble {
+	fn add_assign(&mut self, b: DoubleDouble) {
+		*self = *self + b;
+	}
+}
+
+impl SubAssign for DoubleDouble {
+	fn sub_assign(&mut self, b: DoubleDouble) {
+		*self = *self - b;
+	}
+}
+
+impl MulAssign for DoubleDouble {
+	fn mul_assign(&mut self, b: DoubleDouble) {
+		*self = *self*b;
+	}
+}
+
+impl DivAssign for DoubleDouble {
+	fn div_assign(&mut self, b: DoubleDouble) {
+		*self = *self/b;
+	}
+}
+
+impl PartialOrd for DoubleDouble {
+	fn partial_cmp(&self, b: &DoubleDouble) -> Option<Ordering> {
+		match self.hi.partial_cmp(&b.hi) {
+			Some(Ordering::Equal) => self.lo.partial_cmp(&b.lo),
+			x => x,
+		}
+	}
+}
+
+impl Sum for DoubleDouble {
+	fn sum<I: Iterator<Item = DoubleDouble>>(iter: I) -> DoubleDouble {
+		iter.fold(DoubleDouble::default(), |a, b| a + b)
+	}
+}
+
+impl FromStr for DoubleDouble {
+	type Err = String;
+
+	// Decimal literals like "-1.25e-3", accumulated digit by digit in double-double
+	fn from_str(text: &str) -> Result<DoubleDouble, String> {
+		let bad = || format!("Invalid number: {}", text);
+		let t = text.trim();
+		let (negative, t) = match t.chars().next() {
+			Some('-') => (true, &t[1..]),
+			Some('+') => (false, &t[1..]),
+			_ => (false, t),
+		};
+		let (mantissa, exponent) = match t.find(|ch| ch == 'e' || ch == 'E') {
+			Some(idx) => (&t[..idx], t[idx + 1..].parse::<i32>().map_err(|_| bad())?),
+			None => (t, 0),
+		};
+
+		let ten = DoubleDouble::from_f64(10.0);
+		let mut x = DoubleDouble::default();
+		let mut scale = exponent;
+		let mut seen_point = false;
+		let mut digits = 0;
+		for ch in mantissa.chars() {
+			match ch {
+				'0'..='9' => {
+					x = x*ten + DoubleDouble::from_f64((ch as u8 - b'0') as f64);
+					digits += 1;
+					if seen_point {
+						scale -= 1;
+					}
+				},
+				'.' if !seen_point => seen_point = true,
+				_ => return Err(bad()),
+			}
+		}
+		if digits == 0 {
+			return Err(bad());
+		}
+		x = if scale >= 0 { x*ten.powi(scale) } else { x/ten.powi(-scale) };
+		Ok(if negative { -x } else { x })
+	}
+}
+
+impl fmt::LowerExp for DoubleDouble {
+	// 32 significant digits in scientific notation
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if !self.hi.is_finite() || self.hi == 0.0 {
+			return fmt::LowerExp::fmt(&self.hi, f);
+		}
+		let ten = DoubleDouble::from_f64(10.0);
+		let mut x = self.abs();
+		let mut exponent = x.hi.abs().log10().floor() as i32;
+		x = if exponent >= 0 { x/ten.powi(exponent) } else { x*ten.powi(-exponent) };
+		if x.hi >= 10.0 {
+			x = x/ten;
+			exponent += 1;
+		} else if x.hi < 1.0 {
+			x = x*ten;
+			exponent -= 1;
+		}
+
+		let mut digits = String::new();
+		for idx in 0..32 {
+			let d = x.floor();
+			let d = d.hi.max(0.0).min(9.0);
+			digits.push((b'0' + d as u8) as char);
+			if idx == 0 {
+				digits.push('.');
+			}
+			x = (x - DoubleDouble::from_f64(d))*ten;
+		}
+		write!(f, "{}{}e{}", if self.hi < 0.0 { "-" } else { "" }, digits, exponent)
+	}
+}
+
+impl fmt::Display for DoubleDouble {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		fmt::LowerExp::fmt(self, f)
+	}
+}
+
+impl Real for DoubleDouble {
+	fn from_f64(x: f64) -> DoubleDouble { DoubleDouble { hi: x, lo: 0.0 } }
+	fn to_f64(self) -> f64 { self.hi + self.lo }
+	fn zero() -> DoubleDouble { DoubleDouble::default() }
+	fn one() -> DoubleDouble { DoubleDouble::from_f64(1.0) }
+	fn infinity() -> DoubleDouble { DoubleDouble::from_f64(std::f64::INFINITY) }
+
+	fn sqrt(self) -> DoubleDouble {
+		if self.hi <= 0.0 {
+			return DoubleDouble::from_f64(self.hi.sqrt());
+		}
+		// One Newton step on top of the f64 root doubles the number of digits
+		let x = self.hi.sqrt();
+		let xx = DoubleDouble::from_f64(x);
+		xx + DoubleDouble::from_f64((self - xx*xx).hi*0.5/x)
+	}
+
+	fn cbrt(self) -> DoubleDouble {
+		if self.hi == 0.0 || !self.hi.is_finite() {
+			return DoubleDouble::from_f64(self.hi.cbrt());
+		}
+		let x = DoubleDouble::from_f64(self.hi.cbrt());
+		x - (x*x*x - self)/(DoubleDouble::from_f64(3.0)*x*x)
+	}
+
+	fn powi(self, n: i32) -> DoubleDouble {
+		let mut base = self;
+		let mut k = n.unsigned_abs();
+		let mut result = DoubleDouble::one();
+		while k > 0 {
+			if k & 1 == 1 {
+				result = result*base;
+			}
+			base = base*base;
+			k >>= 1;
+		}
+		if n < 0 { DoubleDouble::one()/result } else { result }
+	}
+
+	fn abs(self) -> DoubleDouble {
+		if self.hi < 0.0 { -self } else { self }
+	}
+
+	fn min(self, other: DoubleDouble) -> DoubleDouble {
+		if other < self { other } else { self }
+	}
+
+	fn max(self, other: DoubleDouble) -> DoubleDouble {
+		if other > self { other } else { self }
+	}
+
+	fn is_finite(self) -> bool { self.hi.is_finite() }
+	// 2^-104
+	fn epsilon() -> f64 { std::f64::EPSILON*std::f64::EPSILON/4.0 }
+	fn name() -> &'static str { "dd" }
+}
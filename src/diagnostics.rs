@@ -0,0 +1,390 @@
+/*
+ Cluster diagnostics that go beyond the energy budget.
+ */
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::thread;
+
+use neighbors::KdTree;
+use pairs;
+use real::c;
+use units::Units;
+use {Params, Real, Star};
+
+pub fn center_of_mass<R: Real>(s: &Vec<Star<R>>) -> Vec<R> {
+	let mut mtot: R = R::zero();
+	let mut com: Vec<R> = vec![R::zero(); 3];
+	for star in s {
+		mtot += star.m;
+		for i in 0..3 {
+			com[i] += star.m*star.r[i];
+		}
+	}
+	for i in 0..3 {
+		com[i] /= mtot;
+	}
+	com
+}
+
+// Neighbour count used for local density estimates
+pub static DENSITY_NEIGHBORS: usize = 6;
+
+/*
+ Local density around every star from its j nearest neighbours, following
+ Casertano & Hut (1985): the mass of the j-1 closest neighbours spread over the
+ sphere that reaches out to the j-th one. The neighbours come from the k-d
+ tree in neighbors.rs.
+ */
+pub fn local_densities<R: Real>(s: &Vec<Star<R>>, j: usize) -> Vec<R> {
+	let j = j.min(s.len().saturating_sub(1));
+	let mut rho: Vec<R> = vec![R::zero(); s.len()];
+	if j < 2 {
+		return rho;
+	}
+	let tree = KdTree::build(s);
+	for si in 0..s.len() {
+		let near = tree.nearest(si, j);
+		let rj = R::from_f64(near[j - 1].1).sqrt();
+		let mut m: R = R::zero();
+		for x in &near[..j - 1] {
+			m += s[x.0].m;
+		}
+		rho[si] = m/(c::<R>(4.0/3.0*std::f64::consts::PI)*rj.powi(3));
+	}
+	rho
+}
+
+pub struct DensityCenter<R = f64> {
+	pub center: Vec<R>,
+	pub core_radius: R,
+	pub core_density: R,
+}
+
+/*
+ Casertano & Hut (1985) density center and core radius. The center is the
+ density-weighted mean position, which follows the core instead of escapers
+ and the halo; the core radius is the rho^2-weighted rms distance from it.
+ */
+pub fn casertano_hut<R: Real>(s: &Vec<Star<R>>) -> DensityCenter<R> {
+	let rho = local_densities(s, DENSITY_NEIGHBORS);
+	let mut wsum: R = R::zero();
+	let mut center: Vec<R> = vec![R::zero(); 3];
+	for si in 0..s.len() {
+		wsum += rho[si];
+		for i in 0..3 {
+			center[i] += rho[si]*s[si].r[i];
+		}
+	}
+	if wsum == R::zero() {
+		return DensityCenter { center: center_of_mass(s), core_radius: R::zero(), core_density: R::zero() };
+	}
+	for i in 0..3 {
+		center[i] /= wsum;
+	}
+
+	let mut w2sum: R = R::zero();
+	let mut r2sum: R = R::zero();
+	for si in 0..s.len() {
+		let mut d: R = R::zero();
+		for i in 0..3 {
+			d += (s[si].r[i] - center[i]).powi(2);
+		}
+		w2sum += rho[si]*rho[si];
+		r2sum += rho[si]*rho[si]*d;
+	}
+	DensityCenter { center: center, core_radius: (r2sum/w2sum).sqrt(), core_density: w2sum/wsum }
+}
+
+pub fn density_center<R: Real>(s: &Vec<Star<R>>) -> Vec<R> {
+	casertano_hut(s).center
+}
+
+/*
+ Radii around center that enclose the given mass fractions.
+ Fractions should be sorted ascending.
+ */
+pub fn lagrangian_radii<R: Real>(s: &Vec<Star<R>>, center: &Vec<R>, fractions: &[f64]) -> Vec<R> {
+	let mut mtot: R = R::zero();
+	let mut shells: Vec<(R, R)> = Vec::with_capacity(s.len());
+	for star in s {
+		let mut d: R = R::zero();
+		for i in 0..3 {
+			d += (star.r[i] - center[i]).powi(2);
+		}
+		mtot += star.m;
+		shells.push((d.sqrt(), star.m));
+	}
+	shells.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("NaN radius"));
+
+	let mut radii: Vec<R> = Vec::with_capacity(fractions.len());
+	let mut menc: R = R::zero();
+	let mut f = 0;
+	for &(d, m) in &shells {
+		menc += m;
+		while f < fractions.len() && menc >= c::<R>(fractions[f])*mtot {
+			radii.push(d);
+			f += 1;
+		}
+	}
+	while radii.len() < fractions.len() {
+		radii.push(shells.last().map_or(R::zero(), |x| x.0));
+	}
+	radii
+}
+
+/*
+ Number of stars with negative total energy (kinetic plus their share of the
+ potential of all other stars).
+ */
+pub fn bound_count<R: Real>(s: &Vec<Star<R>>) -> usize {
+	let mut phi: Vec<R> = vec![R::zero(); s.len()];
+	pairs::for_each_pair(s, |si, sj, _, r2| {
+		let rij = r2.sqrt();
+		phi[si] -= s[sj].m/rij;
+		phi[sj] -= s[si].m/rij;
+	});
+
+	let mut n = 0;
+	for si in 0..s.len() {
+		let v2: R = s[si].v[0].powi(2) + s[si].v[1].powi(2) + s[si].v[2].powi(2);
+		if c::<R>(0.5)*v2 + phi[si] < R::zero() {
+			n += 1;
+		}
+	}
+	n
+}
+
+/*
+ Softened gravitational potential of all other stars at every star.
+ */
+pub fn potentials<R: Real>(s: &Vec<Star<R>>, p: &Params<R>) -> Vec<R> {
+	let eps = p.star_eps(s);
+	let mut phi: Vec<R> = vec![R::zero(); s.len()];
+	pairs::for_each_pair(s, |si, sj, _, r2| {
+		let rij = (r2 + p.pair_eps2(eps[si], eps[sj])).sqrt();
+		phi[si] -= s[sj].m/rij;
+		phi[sj] -= s[si].m/rij;
+	});
+	phi
+}
+
+/*
+ Jerk (the time derivative of the acceleration) of every star from the
+ softened pair forces, what Hermite integrators start from. External
+ potentials and other non-pair terms are left out.
+ */
+pub fn jerks<R: Real>(s: &Vec<Star<R>>, p: &Params<R>) -> Vec<[R; 3]> {
+	let eps = p.star_eps(s);
+	let mut jerk = vec![[R::zero(); 3]; s.len()];
+	pairs::for_each_pair(s, |si, sj, rij, r2| {
+		let d2 = r2 + p.pair_eps2(eps[si], eps[sj]);
+		let inv3 = R::one()/(d2*d2.sqrt());
+		let vij = [s[si].v[0] - s[sj].v[0], s[si].v[1] - s[sj].v[1], s[si].v[2] - s[sj].v[2]];
+		let rv = c::<R>(3.0)*(rij[0]*vij[0] + rij[1]*vij[1] + rij[2]*vij[2])/d2;
+		for k in 0..3 {
+			let x = (vij[k] - rv*rij[k])*inv3;
+			jerk[si][k] -= s[sj].m*x;
+			jerk[sj][k] += s[si].m*x;
+		}
+	});
+	jerk
+}
+
+/*
+ Energy per unit mass of every star: v^2/2 plus its potential. Negative means
+ bound to the cluster as it is now.
+ */
+pub fn specific_energies<R: Real>(s: &Vec<Star<R>>, p: &Params<R>) -> Vec<R> {
+	let phi = potentials(s, p);
+	s.iter().zip(phi.iter()).map(|(x, &phi)| c::<R>(0.5)*(x.v[0].powi(2) + x.v[1].powi(2) + x.v[2].powi(2)) + phi).collect()
+}
+
+/*
+ Smallest distance between any two stars.
+ */
+pub fn min_separation<R: Real>(s: &Vec<Star<R>>) -> R {
+	let mut rmin2 = R::infinity();
+	pairs::for_each_pair(s, |_, _, _, r2| rmin2 = rmin2.min(r2));
+	rmin2.sqrt()
+}
+
+/*
+ Virial ratio Q = -2T/W from an energies() triple. Q = 1 is virial equilibrium.
+ */
+pub fn virial_ratio<R: Real>(e: &Vec<R>) -> R {
+	c::<R>(-2.0)*e[1]/e[2]
+}
+
+/*
+ Rescales all velocities so the virial ratio becomes q. Since T scales with the
+ square of the velocities, every velocity gets multiplied by sqrt(q/Q). Fails
+ for q < 0, for a system without binding energy (W >= 0: a single star, only
+ massless tracers) and for a cold one (T = 0) unless q = 0.
+ */
+pub fn virialize<R: Real>(s: &mut Vec<Star<R>>, e: &Vec<R>, q: R) -> Result<(), String> {
+	if q < R::zero() {
+		return Err(format!("Virial ratio must be non-negative, got {}", q));
+	}
+	if !(e[2] < R::zero()) {
+		return Err(format!("Cannot virialize a system with no binding energy (W = {})", e[2]));
+	}
+	if e[1] == R::zero() && q != R::zero() {
+		return Err(format!("Cannot virialize a cold system (T = 0) to Q = {}", q));
+	}
+	let scale = if q == R::zero() { R::zero() } else { (q/virial_ratio(e)).sqrt() };
+	for star in s {
+		for x in star.v.iter_mut() {
+			*x *= scale;
+		}
+	}
+	Ok(())
+}
+
+pub static FRACTIONS: [f64; 5] = [0.1, 0.25, 0.5, 0.75, 0.9];
+
+/*
+ One row of the diagnostics time series. This is the standardized schema that
+ gets written to diagnostics.csv (energies) and lagrangian.csv (mass radii
+ about the density center), and that the report subcommand reads back.
+ */
+pub struct Sample {
+	pub t: f64,
+	pub e: Vec<f64>,
+	pub de: f64,
+	pub q: f64,
+	pub center: Vec<f64>,
+	pub core_radius: f64,
+	pub core_density: f64,
+	pub radii: Vec<f64>,
+	pub n_bound: usize,
+}
+
+impl Sample {
+	pub fn measure<R: Real>(t: R, e: &Vec<R>, e0: &Vec<R>, s: &Vec<Star<R>>, fractions: &[f64]) -> Sample {
+		let core = casertano_hut(s);
+		let f = |x: &Vec<R>| -> Vec<f64> { x.iter().map(|y| y.to_f64()).collect() };
+		Sample {
+			t: t.to_f64(),
+			e: f(e),
+			de: ((e[0] - e0[0])/e0[0]).to_f64(),
+			q: virial_ratio(e).to_f64(),
+			radii: f(&lagrangian_radii(s, &core.center, fractions)),
+			center: f(&core.center),
+			core_radius: core.core_radius.to_f64(),
+			core_density: core.core_density.to_f64(),
+			n_bound: bound_count(s),
+		}
+	}
+}
+
+pub static CSV_HEADER: &'static str = "t,E,T,W,dE,Q,n_bound";
+
+pub fn csv_row(x: &Sample) -> String {
+	format!("{},{},{},{},{},{},{}", x.t, x.e[0], x.e[1], x.e[2], x.de, x.q, x.n_bound)
+}
+
+pub fn lagrangian_header(fractions: &[f64]) -> String {
+	let mut header = String::from("t,xc,yc,zc,rc,rho_c");
+	for f in fractions {
+		header += &format!(",r{}", (f*100.0).round());
+	}
+	header
+}
+
+pub fn lagrangian_row(x: &Sample) -> String {
+	let mut row = format!("{},{},{},{},{},{}", x.t, x.center[0], x.center[1], x.center[2], x.core_radius, x.core_density);
+	for r in &x.radii {
+		row += &format!(",{}", r);
+	}
+	row
+}
+
+/*
+ Time series collected at every diagnostic step. Rows are streamed to the
+ output files as they come in and kept around for end-of-run output.
+
+ With overlap set, diagnostics run in the background: a sample is measured on
+ a copy of the stars in one plain thread while the integration carries on.
+ The copy is the snapshot the measurement sees, so the result is identical.
+ That thread has no priority and no core of its own, so it only pays off
+ with a core the force threads leave free; tree builds are not overlapped.
+ The sample is collected when the next one is recorded or at finish(), so
+ until then samples (and the files) are one sample behind: readers during
+ the run, such as the sqlite store, get the latest one a diagnostic later,
+ and everything after finish().
+
+ With units, diagnostics.csv gets t_Myr and E_erg columns as well.
+ */
+pub struct History {
+	pub fractions: Vec<f64>,
+	pub samples: Vec<Sample>,
+	pub overlap: bool,
+	pub units: Option<Units>,
+	files: Option<(BufWriter<File>, BufWriter<File>)>,
+	pending: Option<thread::JoinHandle<Sample>>,
+}
+
+impl History {
+	pub fn new(fractions: &[f64], out_dir: Option<&Path>) -> io::Result<History> {
+		History::with_units(fractions, out_dir, None)
+	}
+
+	pub fn with_units(fractions: &[f64], out_dir: Option<&Path>, units: Option<&Units>) -> io::Result<History> {
+		let files = match out_dir {
+			Some(dir) => {
+				let mut f = BufWriter::new(File::create(dir.join("diagnostics.csv"))?);
+				writeln!(f, "{}{}", CSV_HEADER, if units.is_some() { ",t_Myr,E_erg" } else { "" })?;
+				let mut l = BufWriter::new(File::create(dir.join("lagrangian.csv"))?);
+				writeln!(l, "{}", lagrangian_header(fractions))?;
+				Some((f, l))
+			},
+			None => None,
+		};
+		Ok(History { fractions: fractions.to_vec(), samples: vec![], overlap: false, units: units.cloned(), files: files, pending: None })
+	}
+
+	pub fn record<R: Real>(&mut self, t: R, e: &Vec<R>, e0: &Vec<R>, s: &Vec<Star<R>>) -> io::Result<()> {
+		self.collect()?;
+		if self.overlap {
+			let (s, e, e0, fractions) = (s.clone(), e.clone(), e0.clone(), self.fractions.clone());
+			self.pending = Some(thread::spawn(move || Sample::measure(t, &e, &e0, &s, &fractions)));
+			return Ok(());
+		}
+		let x = Sample::measure(t, e, e0, s, &self.fractions);
+		self.push(x)
+	}
+
+	// Waits for the sample measured in the background, if any
+	fn collect(&mut self) -> io::Result<()> {
+		match self.pending.take() {
+			Some(handle) => {
+				let x = handle.join().expect("Diagnostics thread panicked");
+				self.push(x)
+			},
+			None => Ok(()),
+		}
+	}
+
+	fn push(&mut self, x: Sample) -> io::Result<()> {
+		if let Some((ref mut f, ref mut l)) = self.files {
+			match self.units {
+				Some(ref u) => writeln!(f, "{},{},{}", csv_row(&x), u.myr(x.t), u.erg(x.e[0]))?,
+				None => writeln!(f, "{}", csv_row(&x))?,
+			}
+			writeln!(l, "{}", lagrangian_row(&x))?;
+		}
+		self.samples.push(x);
+		Ok(())
+	}
+
+	pub fn finish(&mut self) -> io::Result<()> {
+		self.collect()?;
+		if let Some((ref mut f, ref mut l)) = self.files {
+			f.flush()?;
+			l.flush()?;
+		}
+		Ok(())
+	}
+}
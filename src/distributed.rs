@@ -0,0 +1,143 @@
+/*
+ Direct summation spread over MPI ranks, for runs that want more cores than
+ one machine has. Built with the "mpi" feature and used with --backend mpi,
+ started as e.g. `mpirun -n 64 nbabel --backend mpi < input`.
+
+ Every rank keeps the whole system and integrates it the same way; what is
+ split is the O(N^2) work. For the forces rank r takes an equal block of
+ rows and sums them over all other stars, on p.threads threads, and the
+ blocks are exchanged with an allgather (which MPI libraries implement as a
+ ring or recursive doubling, whichever suits the message size). Every rank
+ then has all accelerations and takes the same step. The potential energy
+ of the diagnostics is a sum over pairs split the same way and added up
+ with an allreduce.
+
+ Only rank 0 reads the input (it is broadcast to the others), logs and
+ writes files. Memory is not distributed: each rank holds all N stars,
+ which for direct summation runs out of time long before it runs out of
+ memory.
+ */
+use std::cell::RefCell;
+
+use mpi::collective::SystemOperation;
+use mpi::datatype::PartitionMut;
+use mpi::environment::Universe;
+use mpi::topology::SimpleCommunicator;
+use mpi::traits::*;
+use mpi::Count;
+
+use pairs;
+use real::c;
+use solver::ForceSolver;
+use {Params, Real, Star};
+
+thread_local! {
+	static UNIVERSE: RefCell<Option<Universe>> = RefCell::new(None);
+}
+
+// Starts MPI; call once, before anything else talks to the other ranks
+pub fn init() -> Result<(), String> {
+	let universe = mpi::initialize().ok_or(String::from("MPI was already initialized"))?;
+	UNIVERSE.with(|x| *x.borrow_mut() = Some(universe));
+	Ok(())
+}
+
+// Shuts MPI down; the process must not exit without it
+pub fn finalize() {
+	UNIVERSE.with(|x| x.borrow_mut().take());
+}
+
+fn world() -> SimpleCommunicator {
+	SimpleCommunicator::world()
+}
+
+pub fn rank() -> usize {
+	world().rank() as usize
+}
+
+pub fn size() -> usize {
+	world().size() as usize
+}
+
+// The text of rank 0 on every rank
+pub fn broadcast_text(text: &mut String) {
+	let world = world();
+	let root = world.process_at_rank(0);
+	let mut len = text.len() as u64;
+	root.broadcast_into(&mut len);
+	let mut bytes = std::mem::take(text).into_bytes();
+	bytes.resize(len as usize, 0);
+	root.broadcast_into(&mut bytes[..]);
+	*text = String::from_utf8(bytes).expect("Input broadcast is not UTF-8");
+}
+
+// The flags of rank 0 on every rank
+pub fn broadcast_flags(flags: &[bool]) -> Vec<bool> {
+	let mut bytes: Vec<u8> = flags.iter().map(|&x| x as u8).collect();
+	world().process_at_rank(0).broadcast_into(&mut bytes[..]);
+	bytes.iter().map(|&x| x != 0).collect()
+}
+
+// Rows of rank r out of size for n stars, equal blocks
+fn block(n: usize, r: usize, size: usize) -> (usize, usize) {
+	(n*r/size, n*(r + 1)/size)
+}
+
+/*
+ The direct solver over all ranks. Accelerations travel as f64, which is
+ exact for f32 and f64 runs; double-double runs are not supported.
+ */
+pub struct Direct;
+
+impl<R: Real> ForceSolver<R> for Direct {
+	fn name(&self) -> &'static str {
+		"direct (MPI)"
+	}
+
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		let world = world();
+		let size = world.size() as usize;
+		let n = s.len();
+		let (lo, hi) = block(n, world.rank() as usize, size);
+		let eps = p.star_eps(s);
+
+		// This rank's rows, split again over its threads
+		let (rows, busy) = pairs::par_rows(s, &eps, p, lo, hi, p.threads);
+		let local: Vec<f64> = rows.iter().flat_map(|x| x.iter().map(|y| y.to_f64())).collect();
+
+		let counts: Vec<Count> = (0..size).map(|r| { let (a, b) = block(n, r, size); (3*(b - a)) as Count }).collect();
+		let displs: Vec<Count> = (0..size).map(|r| (3*block(n, r, size).0) as Count).collect();
+		let mut all = vec![0.0; 3*n];
+		{
+			let mut partition = PartitionMut::new(&mut all[..], counts, displs);
+			world.all_gather_varcount_into(&local[..], &mut partition);
+		}
+		for (i, star) in s.iter_mut().enumerate() {
+			for k in 0..3 {
+				star.a[k] = R::from_f64(all[3*i + k]);
+			}
+		}
+		busy
+	}
+
+	fn energies(&self, s: &Vec<Star<R>>, p: &Params<R>) -> Option<Vec<R>> {
+		let world = world();
+		let bounds = pairs::partition(s.len(), world.size() as usize);
+		let rank = world.rank() as usize;
+		let eps = p.star_eps(s);
+		let mut w = 0.0;
+		for i in bounds[rank]..bounds[rank + 1] {
+			for j in i + 1..s.len() {
+				let d = [s[j].r[0] - s[i].r[0], s[j].r[1] - s[i].r[1], s[j].r[2] - s[i].r[2]];
+				let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p.pair_eps2(eps[i], eps[j]);
+				w -= (s[i].m*s[j].m/r2.sqrt()).to_f64();
+			}
+		}
+		let mut total = 0.0;
+		world.all_reduce_into(&w, &mut total, SystemOperation::sum());
+
+		let t = s.iter().fold(R::zero(), |x, star| x + c::<R>(0.5)*star.m*(star.v[0].powi(2) + star.v[1].powi(2) + star.v[2].powi(2)));
+		let w = R::from_f64(total);
+		Some(vec![t + w, t, w])
+	}
+}
@@ -0,0 +1,96 @@
+/*
+ Velocity-dependent drag on every star, for gas drag and sinking-satellite
+ experiments:
+
+   linear:   a = -k v
+   friction: Chandrasekhar dynamical friction against a uniform background
+             of density rho with a Maxwellian velocity dispersion sigma,
+               a = -4 pi m rho lnL [erf(X) - 2X/sqrt(pi) exp(-X^2)] v/|v|^3
+             with X = |v|/(sqrt(2) sigma) and m the star's own mass
+
+ Like the Coriolis term the drag only changes velocities, so Simulation
+ applies it over half a step before and after each leapfrog step, which
+ keeps the scheme time-symmetric; linear drag is applied exactly as
+ exp(-k dt/2). Drag takes energy out of the system and dE shows it.
+
+ "--drag K" or "--friction rho=RHO,sigma=SIGMA[,lnL=L]" (lnL 3 by default).
+ */
+use std::f64::consts::PI;
+
+use {Real, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Drag {
+	Linear(f64),
+	Friction { rho: f64, sigma: f64, ln_lambda: f64 },
+}
+
+// Error function, Abramowitz and Stegun 7.1.26 (absolute error below 1.5e-7)
+pub fn erf(x: f64) -> f64 {
+	let t = 1.0/(1.0 + 0.3275911*x.abs());
+	let y = 1.0 - t*(0.254829592 + t*(-0.284496736 + t*(1.421413741 + t*(-1.453152027 + t*1.061405429))))*(-x*x).exp();
+	if x < 0.0 { -y } else { y }
+}
+
+impl Drag {
+	// Parses "rho=RHO,sigma=SIGMA[,lnL=L]" for --friction
+	pub fn friction(spec: &str) -> Result<Drag, String> {
+		let (mut rho, mut sigma, mut ln_lambda) = (None, None, 3.0);
+		for item in spec.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+			let mut kv = item.splitn(2, '=');
+			let name = kv.next().unwrap_or("").trim();
+			let value = kv.next().and_then(|x| x.trim().parse::<f64>().ok()).ok_or(format!("Bad parameter '{}' in --friction {}", item, spec))?;
+			if !(value > 0.0) {
+				return Err(format!("{} must be positive, got {}", name, value));
+			}
+			match name {
+				"rho" => rho = Some(value),
+				"sigma" => sigma = Some(value),
+				"lnL" => ln_lambda = value,
+				_ => return Err(format!("Unknown parameter '{}' for --friction, use rho, sigma and lnL", name)),
+			}
+		}
+		match (rho, sigma) {
+			(Some(rho), Some(sigma)) => Ok(Drag::Friction { rho: rho, sigma: sigma, ln_lambda: ln_lambda }),
+			_ => Err(format!("--friction needs rho= and sigma=, got '{}'", spec)),
+		}
+	}
+
+	// f in a = f v for a star of mass m moving at v
+	fn rate(&self, m: f64, v: [f64; 3]) -> f64 {
+		match *self {
+			Drag::Linear(k) => -k,
+			Drag::Friction { rho, sigma, ln_lambda } => {
+				let speed = (v[0]*v[0] + v[1]*v[1] + v[2]*v[2]).sqrt();
+				if speed == 0.0 {
+					return 0.0;
+				}
+				let x = speed/(2f64.sqrt()*sigma);
+				-4.0*PI*m*rho*ln_lambda*(erf(x) - 2.0*x/PI.sqrt()*(-x*x).exp())/speed.powi(3)
+			},
+		}
+	}
+
+	// Drag acceleration of a star of mass m moving at v
+	pub fn acceleration(&self, m: f64, v: [f64; 3]) -> [f64; 3] {
+		let f = self.rate(m, v);
+		[f*v[0], f*v[1], f*v[2]]
+	}
+
+	/*
+	 Changes the velocities as the drag would over a time dt: exactly for
+	 linear drag, by one Euler step for friction (never past standstill).
+	 */
+	pub fn apply<R: Real>(&self, s: &mut Vec<Star<R>>, dt: f64) {
+		for star in s.iter_mut() {
+			let v = [star.v[0].to_f64(), star.v[1].to_f64(), star.v[2].to_f64()];
+			let f = match *self {
+				Drag::Linear(k) => (-k*dt).exp(),
+				Drag::Friction { .. } => (1.0 + dt*self.rate(star.m.to_f64(), v)).max(0.0),
+			};
+			for k in 0..3 {
+				star.v[k] *= R::from_f64(f);
+			}
+		}
+	}
+}
@@ -0,0 +1,72 @@
+/*
+ What can go wrong outside the integration itself, with messages meant for
+ the person running the program: reading and writing files, bad input
+ (with the line and column, counting from 1), bad settings, and a solver
+ that cannot go on. Every kind has its own exit code, see status.rs.
+ */
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use status;
+
+#[derive(Debug)]
+pub enum NBodyError {
+	// What was being done, and the error
+	Io(String, io::Error),
+	Parse { line: usize, column: usize, message: String },
+	Config(String),
+	Solver(String),
+}
+
+impl NBodyError {
+	pub fn io(what: &str, error: io::Error) -> NBodyError {
+		NBodyError::Io(String::from(what), error)
+	}
+
+	pub fn parse(line: usize, column: usize, message: String) -> NBodyError {
+		NBodyError::Parse { line: line, column: column, message: message }
+	}
+
+	pub fn exit_code(&self) -> i32 {
+		match *self {
+			NBodyError::Io(..) => status::EXIT_IO,
+			NBodyError::Parse { .. } => status::EXIT_INPUT,
+			NBodyError::Config(_) => status::EXIT_CONFIG,
+			NBodyError::Solver(_) => status::EXIT_NUMERICAL,
+		}
+	}
+}
+
+impl fmt::Display for NBodyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			NBodyError::Io(ref what, ref error) => write!(f, "{}: {}", what, error),
+			NBodyError::Parse { line, column, ref message } => write!(f, "line {}, column {}: {}", line, column, message),
+			NBodyError::Config(ref x) => write!(f, "{}", x),
+			NBodyError::Solver(ref x) => write!(f, "solver: {}", x),
+		}
+	}
+}
+
+impl Error for NBodyError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match *self {
+			NBodyError::Io(_, ref error) => Some(error),
+			_ => None,
+		}
+	}
+}
+
+impl From<io::Error> for NBodyError {
+	fn from(error: io::Error) -> NBodyError {
+		NBodyError::Io(String::from("I/O error"), error)
+	}
+}
+
+// For the many functions that still report errors as strings
+impl From<NBodyError> for String {
+	fn from(error: NBodyError) -> String {
+		error.to_string()
+	}
+}
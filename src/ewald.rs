@@ -0,0 +1,278 @@
+/*
+ Periodic boundary conditions. With --periodic L the stars live in a cube of
+ side L centred on the origin, every star stands for an infinite lattice of
+ copies L apart, and a uniform negative background cancels the mean density,
+ so only density contrasts pull (the setting of cosmological simulations).
+ A star at separation d from another, d wrapped into [-L/2, L/2) per
+ component (the minimum image), then feels per unit mass of the other
+
+   a(d) = -d/|d|^3 + a_c(d),
+
+ the Newtonian pull of the nearest copy, softened as usual, plus a smooth
+ correction a_c for all other copies and the background. The Ewald sums
+ (alpha = 2/L, h over integer vectors)
+
+   phi(x) = -sum_n erfc(alpha |x - nL|)/|x - nL|
+            - sum_{h != 0} exp(-(pi |h|/(alpha L))^2) cos(2 pi h.x/L)/(pi L |h|^2)
+            + pi/(alpha^2 L^3)
+
+ converge after a few terms; like Hernquist, Bouchet & Suto (1991) they are
+ evaluated once for a_c = -grad phi - x/|x|^3 and phi_c = phi + 1/|x| on a
+ grid over the octant [0, L/2]^3 and interpolated trilinearly, the other
+ octants following from the symmetry of the lattice. The force loop stays
+ O(N^2) and costs about twice the isolated one.
+
+ W pairs the stars through phi and adds the interaction of every star with
+ its own copies, m^2 phi_c(0)/2, so the energy is conserved like that of an
+ isolated system. Positions are wrapped back into the box before every force
+ evaluation.
+ */
+use std::f64::consts::PI;
+
+use drag::erf;
+use pairs;
+use solver::ForceSolver;
+use {Params, Real, Star};
+
+// Grid intervals per axis of the correction table
+pub static TABLE: usize = 32;
+
+/*
+ Potential and acceleration at x of a unit mass at the origin, its lattice of
+ copies and the background, summed directly. Only for tabulating: a few
+ hundred terms per call.
+ */
+pub fn ewald(x: [f64; 3], l: f64) -> (f64, [f64; 3]) {
+	let alpha = 2.0/l;
+	let mut phi = PI/(alpha*alpha*l*l*l);
+	let mut a = [0.0; 3];
+	for n in lattice(3) {
+		let d = [x[0] - n[0]*l, x[1] - n[1]*l, x[2] - n[2]*l];
+		let r = (d[0]*d[0] + d[1]*d[1] + d[2]*d[2]).sqrt();
+		if r > 3.6*l || r == 0.0 {
+			continue;
+		}
+		let erfc = 1.0 - erf(alpha*r);
+		phi -= erfc/r;
+		let f = (erfc + 2.0*alpha*r/PI.sqrt()*(-alpha*alpha*r*r).exp())/(r*r*r);
+		for k in 0..3 {
+			a[k] -= f*d[k];
+		}
+	}
+	for h in lattice(3) {
+		let h2 = h[0]*h[0] + h[1]*h[1] + h[2]*h[2];
+		if h2 == 0.0 || h2 > 10.0 {
+			continue;
+		}
+		let g = (-PI*PI*h2/(alpha*alpha*l*l)).exp()/(PI*l*h2);
+		let kx = 2.0*PI*(h[0]*x[0] + h[1]*x[1] + h[2]*x[2])/l;
+		phi -= g*kx.cos();
+		for k in 0..3 {
+			a[k] -= g*2.0*PI*h[k]/l*kx.sin();
+		}
+	}
+	(phi, a)
+}
+
+// The minimum image of the separation d in a box of side l
+pub fn nearest<R: Real>(d: &[R; 3], l: f64) -> [R; 3] {
+	let (half, l) = (R::from_f64(0.5*l), R::from_f64(l));
+	let mut x = *d;
+	for k in 0..3 {
+		// Wrapped stars are less than a box apart; further only between wraps
+		while x[k] >= half {
+			x[k] -= l;
+		}
+		while x[k] < -half {
+			x[k] += l;
+		}
+	}
+	x
+}
+
+// Moves the stars back into the box [-l/2, l/2)^3
+pub fn wrap<R: Real>(s: &mut Vec<Star<R>>, l: f64) {
+	for star in s.iter_mut() {
+		for k in 0..3 {
+			let shift = R::from_f64(l*(star.r[k].to_f64()/l + 0.5).floor());
+			star.r[k] -= shift;
+		}
+	}
+}
+
+// Integer vectors with components in [-k, k]
+fn lattice(k: i64) -> Vec<[f64; 3]> {
+	let mut x = vec![];
+	for i in -k..=k {
+		for j in -k..=k {
+			for m in -k..=k {
+				x.push([i as f64, j as f64, m as f64]);
+			}
+		}
+	}
+	x
+}
+
+// The tabulated corrections for one box size
+#[derive(Clone, Debug)]
+pub struct Ewald {
+	pub box_size: f64,
+	// phi_c, then a_c, at the grid points of the octant
+	table: Vec<[f64; 4]>,
+}
+
+impl Ewald {
+	pub fn new(box_size: f64) -> Ewald {
+		let m = TABLE + 1;
+		let step = 0.5*box_size/TABLE as f64;
+		let mut table = Vec::with_capacity(m*m*m);
+		for i in 0..m {
+			for j in 0..m {
+				for k in 0..m {
+					let x = [i as f64*step, j as f64*step, k as f64*step];
+					let (mut phi, mut a) = ewald(x, box_size);
+					let r = (x[0]*x[0] + x[1]*x[1] + x[2]*x[2]).sqrt();
+					if r > 0.0 {
+						phi += 1.0/r;
+						for k in 0..3 {
+							a[k] += x[k]/(r*r*r);
+						}
+					} else {
+						// The limit of -erfc(alpha r)/r + 1/r, the nearest copy's term that ewald() skips
+						phi += 4.0/(box_size*PI.sqrt());
+					}
+					table.push([phi, a[0], a[1], a[2]]);
+				}
+			}
+		}
+		Ewald { box_size: box_size, table: table }
+	}
+
+	// Index of the grid cell holding the minimum image d, clamped to the table, and the position in it
+	fn cell(&self, d: &[f64; 3]) -> (usize, [f64; 3]) {
+		let scale = TABLE as f64/(0.5*self.box_size);
+		let m = TABLE + 1;
+		let (mut index, mut f) = (0, [0.0; 3]);
+		for k in 0..3 {
+			let u = (d[k].abs()*scale).min(TABLE as f64);
+			let i = (u as usize).min(TABLE - 1);
+			index = index*m + i;
+			f[k] = u - i as f64;
+		}
+		(index, f)
+	}
+
+	// Trilinear interpolation of the table at d
+	fn interpolate(&self, d: &[f64; 3]) -> [f64; 4] {
+		let (index, f) = self.cell(d);
+		let m = TABLE + 1;
+		let mut x = [0.0; 4];
+		for corner in 0..8 {
+			let (i, j, k) = (corner >> 2, (corner >> 1) & 1, corner & 1);
+			let w = (if i == 1 { f[0] } else { 1.0 - f[0] })*(if j == 1 { f[1] } else { 1.0 - f[1] })*(if k == 1 { f[2] } else { 1.0 - f[2] });
+			let y = &self.table[index + (i*m + j)*m + k];
+			for c in 0..4 {
+				x[c] += w*y[c];
+			}
+		}
+		x
+	}
+
+	// phi_c at the minimum image d
+	pub fn potential_correction(&self, d: [f64; 3]) -> f64 {
+		self.interpolate(&d)[0]
+	}
+
+	// a_c at the minimum image d, odd in each of its own component and even in the others
+	pub fn acceleration_correction(&self, d: [f64; 3]) -> [f64; 3] {
+		let x = self.interpolate(&d);
+		let sign = |k: usize| if d[k] < 0.0 { -1.0 } else { 1.0 };
+		[sign(0)*x[1], sign(1)*x[2], sign(2)*x[3]]
+	}
+
+	/*
+	 Periodic accelerations. Thread sums are added in thread order, so like
+	 --deterministic the result only depends on the thread count.
+	 */
+	pub fn acceleration<R: Real>(&self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		let n = s.len();
+		let eps = p.star_eps(s);
+		let threads = p.threads.max(1);
+		let mut busy = vec![0.0; threads];
+		let mut parts: Vec<Vec<[R; 3]>> = vec![vec![]; threads];
+		{
+			let sr: &Vec<Star<R>> = s;
+			pairs::par_for_each_pair(sr, threads, vec![[R::zero(); 3]; n], |a: &mut Vec<[R; 3]>, i, j, rij, _| {
+				let d = nearest(rij, self.box_size);
+				let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p.pair_eps2(eps[i], eps[j]);
+				let f = R::one()/(r2*r2.sqrt());
+				let ac = self.acceleration_correction([d[0].to_f64(), d[1].to_f64(), d[2].to_f64()]);
+				for k in 0..3 {
+					let x = R::from_f64(ac[k]) - f*d[k];
+					a[i][k] += sr[j].m*x;
+					a[j][k] -= sr[i].m*x;
+				}
+			}, |thread_index, a, seconds| {
+				busy[thread_index] = seconds;
+				parts[thread_index] = a;
+			});
+		}
+		for star in s.iter_mut() {
+			star.a = vec![R::zero(); 3];
+		}
+		for part in parts {
+			for (star, a) in s.iter_mut().zip(part.iter()) {
+				for k in 0..3 {
+					star.a[k] += a[k];
+				}
+			}
+		}
+		busy
+	}
+
+	// [E, T, W] with the periodic potential
+	pub fn energies<R: Real>(&self, s: &Vec<Star<R>>, p: &Params<R>) -> Vec<R> {
+		let eps = p.star_eps(s);
+		let half = R::from_f64(0.5);
+		let kinetic = s.iter().fold(R::zero(), |e, x| e + half*x.m*(x.v[0]*x.v[0] + x.v[1]*x.v[1] + x.v[2]*x.v[2]));
+		let self_energy = R::from_f64(self.potential_correction([0.0; 3]));
+		let mut potential = s.iter().fold(R::zero(), |w, x| w + half*x.m*x.m*self_energy);
+		let mut parts = vec![R::zero(); p.threads.max(1)];
+		pairs::par_for_each_pair(s, p.threads.max(1), R::zero(), |w: &mut R, i, j, rij, _| {
+			let d = nearest(rij, self.box_size);
+			let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p.pair_eps2(eps[i], eps[j]);
+			let phi = self.potential_correction([d[0].to_f64(), d[1].to_f64(), d[2].to_f64()]);
+			*w += s[i].m*s[j].m*(R::from_f64(phi) - R::one()/r2.sqrt());
+		}, |thread_index, w, _| parts[thread_index] = w);
+		for w in parts {
+			potential += w;
+		}
+		vec![kinetic + potential, kinetic, potential]
+	}
+}
+
+// Direct summation in a periodic box, see the top of this file
+pub struct Periodic {
+	pub ewald: Ewald,
+}
+
+impl Periodic {
+	pub fn new(box_size: f64) -> Periodic {
+		Periodic { ewald: Ewald::new(box_size) }
+	}
+}
+
+impl<R: Real> ForceSolver<R> for Periodic {
+	fn name(&self) -> &'static str {
+		"periodic"
+	}
+
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		wrap(s, self.ewald.box_size);
+		self.ewald.acceleration(s, p)
+	}
+
+	fn energies(&self, s: &Vec<Star<R>>, p: &Params<R>) -> Option<Vec<R>> {
+		Some(self.ewald.energies(s, p))
+	}
+}
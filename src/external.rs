@@ -0,0 +1,203 @@
+/*
+ Analytic external potentials, e.g. the host galaxy of a cluster. Their
+ accelerations are added to the N-body forces on every step and their
+ potential energy sum m phi(r) to W, so dE stays a conservation check. All
+ are centred on the origin and use G = 1 and the internal units of the run.
+
+ "--external KIND:NAME=VALUE,..." on the command line, repeatable:
+   point:m=M                  point mass, phi = -M/r
+   harmonic:omega=W           harmonic trap, phi = W^2 r^2/2
+   log:v0=V,rc=R[,q=Q]        logarithmic halo, phi = V^2/2 ln(R^2 + x^2 + y^2 + z^2/Q^2)
+   nfw:rs=R,rho0=RHO          NFW profile, phi = -4 pi RHO R^3 ln(1 + r/R)/r
+ */
+use std::f64::consts::PI;
+
+use {Real, Star};
+
+pub trait ExternalPotential {
+	fn name(&self) -> &'static str;
+
+	fn potential(&self, r: [f64; 3]) -> f64;
+
+	fn acceleration(&self, r: [f64; 3]) -> [f64; 3];
+}
+
+fn norm(r: [f64; 3]) -> f64 {
+	(r[0]*r[0] + r[1]*r[1] + r[2]*r[2]).sqrt()
+}
+
+pub struct PointMass {
+	pub m: f64,
+}
+
+impl ExternalPotential for PointMass {
+	fn name(&self) -> &'static str {
+		"point"
+	}
+
+	fn potential(&self, r: [f64; 3]) -> f64 {
+		-self.m/norm(r)
+	}
+
+	fn acceleration(&self, r: [f64; 3]) -> [f64; 3] {
+		let d = norm(r);
+		let f = -self.m/(d*d*d);
+		[f*r[0], f*r[1], f*r[2]]
+	}
+}
+
+pub struct Harmonic {
+	pub omega: f64,
+}
+
+impl ExternalPotential for Harmonic {
+	fn name(&self) -> &'static str {
+		"harmonic"
+	}
+
+	fn potential(&self, r: [f64; 3]) -> f64 {
+		0.5*self.omega*self.omega*(r[0]*r[0] + r[1]*r[1] + r[2]*r[2])
+	}
+
+	fn acceleration(&self, r: [f64; 3]) -> [f64; 3] {
+		let w2 = self.omega*self.omega;
+		[-w2*r[0], -w2*r[1], -w2*r[2]]
+	}
+}
+
+// Flattened along z for q < 1; flat rotation curve v0 well outside rc
+pub struct Logarithmic {
+	pub v0: f64,
+	pub rc: f64,
+	pub q: f64,
+}
+
+impl ExternalPotential for Logarithmic {
+	fn name(&self) -> &'static str {
+		"log"
+	}
+
+	fn potential(&self, r: [f64; 3]) -> f64 {
+		0.5*self.v0*self.v0*(self.rc*self.rc + r[0]*r[0] + r[1]*r[1] + r[2]*r[2]/(self.q*self.q)).ln()
+	}
+
+	fn acceleration(&self, r: [f64; 3]) -> [f64; 3] {
+		let q2 = self.q*self.q;
+		let f = -self.v0*self.v0/(self.rc*self.rc + r[0]*r[0] + r[1]*r[1] + r[2]*r[2]/q2);
+		[f*r[0], f*r[1], f*r[2]/q2]
+	}
+}
+
+pub struct Nfw {
+	pub rs: f64,
+	pub rho0: f64,
+}
+
+impl ExternalPotential for Nfw {
+	fn name(&self) -> &'static str {
+		"nfw"
+	}
+
+	fn potential(&self, r: [f64; 3]) -> f64 {
+		let scale = 4.0*PI*self.rho0*self.rs*self.rs;
+		let x = norm(r)/self.rs;
+		// ln(1 + x)/x -> 1 at the centre
+		if x < 1e-8 {
+			-scale
+		} else {
+			-scale*x.ln_1p()/x
+		}
+	}
+
+	// -M(<r)/r^2 towards the centre, M(<r) = 4 pi rho0 rs^3 (ln(1 + x) - x/(1 + x))
+	fn acceleration(&self, r: [f64; 3]) -> [f64; 3] {
+		let d = norm(r);
+		if d == 0.0 {
+			return [0.0; 3];
+		}
+		let x = d/self.rs;
+		let enclosed = 4.0*PI*self.rho0*self.rs.powi(3)*(x.ln_1p() - x/(1.0 + x));
+		let f = -enclosed/(d*d*d);
+		[f*r[0], f*r[1], f*r[2]]
+	}
+}
+
+/*
+ Parses "KIND:NAME=VALUE,...", see the top of this file. Every parameter
+ without a default must be given and be positive.
+ */
+pub fn parse(spec: &str) -> Result<Box<dyn ExternalPotential>, String> {
+	let (kind, rest) = match spec.find(':') {
+		Some(i) => (&spec[..i], &spec[i + 1..]),
+		None => (spec, ""),
+	};
+	let mut values: Vec<(String, f64)> = vec![];
+	for item in rest.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+		let mut kv = item.splitn(2, '=');
+		let name = kv.next().unwrap_or("").trim();
+		let value = kv.next().and_then(|x| x.trim().parse::<f64>().ok()).ok_or(format!("Bad parameter '{}' in --external {}", item, spec))?;
+		values.push((name.to_string(), value));
+	}
+	let known: &[&str] = match kind {
+		"point" => &["m"],
+		"harmonic" => &["omega"],
+		"log" => &["v0", "rc", "q"],
+		"nfw" => &["rs", "rho0"],
+		_ => return Err(format!("Unknown external potential '{}', use point, harmonic, log or nfw", kind)),
+	};
+	if let Some((name, _)) = values.iter().find(|x| !known.contains(&x.0.as_str())) {
+		return Err(format!("Unknown parameter '{}' for {}, use {}", name, kind, known.join(", ")));
+	}
+	let get = |name: &str, default: Option<f64>| -> Result<f64, String> {
+		let x = values.iter().rev().find(|x| x.0 == name).map(|x| x.1).or(default)
+			.ok_or(format!("--external {} needs {}=", kind, name))?;
+		if x > 0.0 {
+			Ok(x)
+		} else {
+			Err(format!("{} must be positive, got {}", name, x))
+		}
+	};
+	Ok(match kind {
+		"point" => Box::new(PointMass { m: get("m", None)? }),
+		"harmonic" => Box::new(Harmonic { omega: get("omega", None)? }),
+		"log" => Box::new(Logarithmic { v0: get("v0", None)?, rc: get("rc", None)?, q: get("q", Some(1.0))? }),
+		_ => Box::new(Nfw { rs: get("rs", None)?, rho0: get("rho0", None)? }),
+	})
+}
+
+fn position<R: Real>(star: &Star<R>) -> [f64; 3] {
+	[star.r[0].to_f64(), star.r[1].to_f64(), star.r[2].to_f64()]
+}
+
+// Adds the external accelerations to the ones already in s
+pub fn add_accelerations<R: Real>(s: &mut Vec<Star<R>>, external: &[Box<dyn ExternalPotential>]) {
+	for x in external {
+		for star in s.iter_mut() {
+			let a = x.acceleration(position(star));
+			for k in 0..3 {
+				star.a[k] += R::from_f64(a[k]);
+			}
+		}
+	}
+}
+
+// Potential energy of the stars in the external field
+pub fn energy<R: Real>(s: &Vec<Star<R>>, external: &[Box<dyn ExternalPotential>]) -> f64 {
+	let mut w = 0.0;
+	for x in external {
+		for star in s {
+			w += star.m.to_f64()*x.potential(position(star));
+		}
+	}
+	w
+}
+
+// [E, T, W] from energies() with the external potential energy added to E and W
+pub fn with_energy<R: Real>(mut e: Vec<R>, s: &Vec<Star<R>>, external: &[Box<dyn ExternalPotential>]) -> Vec<R> {
+	if !external.is_empty() {
+		let w = R::from_f64(energy(s, external));
+		e[0] += w;
+		e[2] += w;
+	}
+	e
+}
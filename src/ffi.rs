@@ -0,0 +1,202 @@
+/*
+ C interface, for C, C++ and Fortran codes that want the integrator or just
+ the gravity kernel. Built into the cdylib (libnbabel.so / .dylib / .dll);
+ include/nbabel.h declares it. Everything is f64 and N-body units (G = 1);
+ positions, velocities and accelerations are arrays of 3n doubles, x y z of
+ each star in turn.
+
+ Functions returning int give 0 on success and a negative NBABEL_E* code
+ otherwise: -1 a null pointer, -2 a bad count or parameter, -3 a panic
+ inside the library (caught, so it never unwinds into the caller). A
+ simulation is only ever used from one thread at a time.
+ */
+// The safety contract is the C one above: valid pointers to arrays of the stated length
+#![allow(clippy::missing_safety_doc)]
+
+use std::panic;
+use std::slice;
+
+use solver::Direct;
+use {acceleration, Params, Simulation, Star};
+
+pub const NBABEL_OK: i32 = 0;
+pub const NBABEL_ENULL: i32 = -1;
+pub const NBABEL_EINVAL: i32 = -2;
+pub const NBABEL_EPANIC: i32 = -3;
+
+// Opaque to C
+pub struct NBabelSimulation {
+	sim: Simulation<f64>,
+}
+
+unsafe fn stars(n: usize, m: *const f64, r: *const f64, v: *const f64) -> Vec<Star> {
+	let m = slice::from_raw_parts(m, n);
+	let r = slice::from_raw_parts(r, 3*n);
+	let v = if v.is_null() { None } else { Some(slice::from_raw_parts(v, 3*n)) };
+	(0..n).map(|i| Star {
+		m: m[i],
+		r: r[3*i..3*i + 3].to_vec(),
+		v: v.map_or(vec![0.0; 3], |v| v[3*i..3*i + 3].to_vec()),
+		a: vec![0.0; 3],
+		a0: vec![0.0; 3],
+	}).collect()
+}
+
+fn params(dt: f64, eps: f64) -> Option<Params> {
+	if !(dt > 0.0) || !(eps >= 0.0) {
+		return None;
+	}
+	let mut p = Params::default();
+	p.dt = dt;
+	p.eps = eps;
+	Some(p)
+}
+
+fn guard<F: FnOnce() -> i32 + panic::UnwindSafe>(f: F) -> i32 {
+	panic::catch_unwind(f).unwrap_or(NBABEL_EPANIC)
+}
+
+/*
+ A simulation of n stars with masses m, positions r and velocities v (v may
+ be null for stars at rest), step dt and softening eps. Returns null on bad
+ arguments. Free it with nbabel_destroy().
+ */
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_create(n: usize, m: *const f64, r: *const f64, v: *const f64, dt: f64, eps: f64) -> *mut NBabelSimulation {
+	if m.is_null() || r.is_null() {
+		return std::ptr::null_mut();
+	}
+	let p = match params(dt, eps) {
+		Some(x) => x,
+		None => return std::ptr::null_mut(),
+	};
+	let s = stars(n, m, r, v);
+	match panic::catch_unwind(move || Simulation::with_solver(s, p, Box::new(Direct))) {
+		Ok(sim) => Box::into_raw(Box::new(NBabelSimulation { sim: sim })),
+		Err(_) => std::ptr::null_mut(),
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_destroy(sim: *mut NBabelSimulation) {
+	if !sim.is_null() {
+		drop(Box::from_raw(sim));
+	}
+}
+
+// Replaces the stars, keeping the time and settings
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_set_particles(sim: *mut NBabelSimulation, n: usize, m: *const f64, r: *const f64, v: *const f64) -> i32 {
+	if sim.is_null() || m.is_null() || r.is_null() {
+		return NBABEL_ENULL;
+	}
+	let sim = &mut (*sim).sim;
+	let s = stars(n, m, r, v);
+	guard(panic::AssertUnwindSafe(move || {
+		let t = sim.t;
+		let steps = sim.steps;
+		let p = sim.p.clone();
+		*sim = Simulation::with_solver(s, p, Box::new(Direct));
+		sim.t = t;
+		sim.steps = steps;
+		NBABEL_OK
+	}))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_step(sim: *mut NBabelSimulation, steps: usize) -> i32 {
+	if sim.is_null() {
+		return NBABEL_ENULL;
+	}
+	let sim = &mut (*sim).sim;
+	guard(panic::AssertUnwindSafe(move || {
+		for _ in 0..steps {
+			sim.step();
+		}
+		NBABEL_OK
+	}))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_count(sim: *const NBabelSimulation) -> usize {
+	if sim.is_null() { 0 } else { (*sim).sim.s.len() }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_time(sim: *const NBabelSimulation) -> f64 {
+	if sim.is_null() { 0.0 } else { (*sim).sim.t }
+}
+
+/*
+ Copies the state out: any of m (n doubles), r, v and a (3n doubles each)
+ may be null to skip it. n must be nbabel_count().
+ */
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_get_state(sim: *const NBabelSimulation, n: usize, m: *mut f64, r: *mut f64, v: *mut f64, a: *mut f64) -> i32 {
+	if sim.is_null() {
+		return NBABEL_ENULL;
+	}
+	let s = &(*sim).sim.s;
+	if n != s.len() {
+		return NBABEL_EINVAL;
+	}
+	if !m.is_null() {
+		let m = slice::from_raw_parts_mut(m, n);
+		for i in 0..n {
+			m[i] = s[i].m;
+		}
+	}
+	for &(out, which) in [(r, 0), (v, 1), (a, 2)].iter() {
+		if out.is_null() {
+			continue;
+		}
+		let out = slice::from_raw_parts_mut(out, 3*n);
+		for i in 0..n {
+			let x = match which {
+				0 => &s[i].r,
+				1 => &s[i].v,
+				_ => &s[i].a,
+			};
+			out[3*i..3*i + 3].copy_from_slice(&x[..3]);
+		}
+	}
+	NBABEL_OK
+}
+
+// Total, kinetic and potential energy into e[0], e[1] and e[2]
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_energy(sim: *const NBabelSimulation, e: *mut f64) -> i32 {
+	if sim.is_null() || e.is_null() {
+		return NBABEL_ENULL;
+	}
+	let sim = &(*sim).sim;
+	guard(panic::AssertUnwindSafe(move || {
+		let x = sim.energies();
+		slice::from_raw_parts_mut(e, 3).copy_from_slice(&x[..3]);
+		NBABEL_OK
+	}))
+}
+
+/*
+ The gravity kernel alone: accelerations a (3n doubles) of n stars with
+ masses m and positions r, softened with eps, on all cores.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn nbabel_accelerations(n: usize, m: *const f64, r: *const f64, eps: f64, a: *mut f64) -> i32 {
+	if m.is_null() || r.is_null() || a.is_null() {
+		return NBABEL_ENULL;
+	}
+	let p = match params(1.0, eps) {
+		Some(x) => x,
+		None => return NBABEL_EINVAL,
+	};
+	let mut s = stars(n, m, r, std::ptr::null());
+	let out = slice::from_raw_parts_mut(a, 3*n);
+	guard(panic::AssertUnwindSafe(move || {
+		acceleration(&mut s, &p);
+		for i in 0..n {
+			out[3*i..3*i + 3].copy_from_slice(&s[i].a[..3]);
+		}
+		NBABEL_OK
+	}))
+}
@@ -0,0 +1,143 @@
+/*
+ "nbabel fit TARGET < input" tunes the parameters of an initial model so that,
+ after a short relaxation, its Lagrangian radii match a target profile. The
+ search is simulated annealing: every trial generates a model, runs it for a
+ while and scores it, so the cost is many short simulations.
+
+ The target file has one "fraction radius" pair per line, e.g. "0.5 0.8" for
+ a half-mass radius of 0.8. Lines starting with # are comments.
+ */
+use std::fs;
+use std::io;
+use std::io::Read;
+
+use rng::Rng;
+use {diagnostics, energies, masses, read_stars, Params, Simulation, Star};
+
+pub struct Anneal {
+	pub iterations: usize,
+	// Starting temperature, relative to the cost of the initial guess
+	pub temperature: f64,
+	pub seed: u64,
+}
+
+impl Default for Anneal {
+	fn default() -> Anneal {
+		Anneal { iterations: 200, temperature: 0.1, seed: 1 }
+	}
+}
+
+/*
+ Minimizes cost over the box lo..hi starting from x0. Trial steps shrink and
+ the temperature cools linearly to zero over the iterations, so early on
+ uphill moves are accepted and at the end it is a plain local search.
+ Returns the best parameters seen and their cost.
+ */
+pub fn anneal<F>(x0: &[f64], lo: &[f64], hi: &[f64], opts: &Anneal, mut cost: F) -> (Vec<f64>, f64)
+	where F: FnMut(&[f64]) -> f64
+{
+	let mut rng = Rng::new(opts.seed);
+	let mut x = x0.to_vec();
+	let mut fx = cost(&x);
+	let mut best = (x.clone(), fx);
+	let t0 = opts.temperature*fx.abs().max(1e-12);
+
+	for it in 0..opts.iterations {
+		let cool = 1.0 - it as f64/opts.iterations as f64;
+		let mut y = x.clone();
+		for k in 0..y.len() {
+			let width = 0.25*(hi[k] - lo[k])*cool.max(0.05);
+			y[k] = (y[k] + width*rng.normal()).max(lo[k]).min(hi[k]);
+		}
+		let fy = cost(&y);
+		let temperature = t0*cool;
+		if fy < fx || (temperature > 0.0 && rng.uniform() < (-(fy - fx)/temperature).exp()) {
+			x = y;
+			fx = fy;
+			if fx < best.1 {
+				best = (x.clone(), fx);
+			}
+		}
+	}
+	best
+}
+
+pub fn read_target(text: &str) -> Result<Vec<(f64, f64)>, String> {
+	let mut target = vec![];
+	for line in text.lines() {
+		let line = line.trim();
+		if line == "" || line.starts_with('#') {
+			continue;
+		}
+		let words: Vec<&str> = line.split_whitespace().collect();
+		if words.len() != 2 {
+			return Err(format!("Expected 'fraction radius', got '{}'", line));
+		}
+		let f: f64 = words[0].parse().map_err(|_| format!("Bad mass fraction '{}'", words[0]))?;
+		let r: f64 = words[1].parse().map_err(|_| format!("Bad radius '{}'", words[1]))?;
+		target.push((f, r));
+	}
+	target.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("NaN fraction"));
+	Ok(target)
+}
+
+// Sum of squared log ratios of the Lagrangian radii to the target radii
+pub fn profile_cost(s: &Vec<Star>, target: &[(f64, f64)]) -> f64 {
+	let fractions: Vec<f64> = target.iter().map(|x| x.0).collect();
+	let center = diagnostics::density_center(s);
+	let radii = diagnostics::lagrangian_radii(s, &center, &fractions);
+	radii.iter().zip(target.iter()).map(|(r, t)| (r/t.1).ln().powi(2)).sum()
+}
+
+/*
+ The model fitted by the subcommand: the input system scaled to radius a
+ (velocities scaled by 1/sqrt(a) so it stays in the same dynamical state)
+ and then virialized to Q.
+ */
+pub fn scaled_model(base: &Vec<Star>, a: f64, q: f64) -> Vec<Star> {
+	let mut s = base.clone();
+	for star in s.iter_mut() {
+		for i in 0..3 {
+			star.r[i] *= a;
+			star.v[i] /= a.sqrt();
+		}
+	}
+	let e = energies(&s, &Params::default());
+	diagnostics::virialize(&mut s, &e, q).unwrap_or_else(|x| panic!("{}", x));
+	s
+}
+
+pub fn main(args: &[String]) {
+	let usage = "Usage: nbabel fit TARGET [--relax T] [--iterations N] [--seed S] < input";
+	let mut target_path = None;
+	let mut relax = 0.1;
+	let mut opts = Anneal::default();
+	let mut it = args.iter();
+	while let Some(arg) = it.next() {
+		match arg.as_str() {
+			"--relax" => relax = it.next().and_then(|x| x.parse().ok()).expect("--relax needs a time"),
+			"--iterations" => opts.iterations = it.next().and_then(|x| x.parse().ok()).expect("--iterations needs a count"),
+			"--seed" => opts.seed = it.next().and_then(|x| x.parse().ok()).expect("--seed needs a number"),
+			_ => target_path = Some(arg.clone()),
+		}
+	}
+	let target_path = target_path.expect(usage);
+	let target = read_target(&fs::read_to_string(&target_path).expect("Could not read target file")).unwrap_or_else(|x| panic!("{}", x));
+
+	let mut text = String::new();
+	io::stdin().read_to_string(&mut text).expect("Could not read input");
+	let base: Vec<Star> = read_stars(&text);
+
+	let mut p: Params = Params::default();
+	p.dt = p.dt.min(masses::suggest_dt(&base, masses::ETA));
+	let (best, cost) = anneal(&[1.0, 1.0], &[0.05, 0.1], &[20.0, 2.0], &opts, |x| {
+		let mut sim = Simulation::new(scaled_model(&base, x[0], x[1]), p.clone());
+		while sim.t < relax {
+			sim.step();
+		}
+		let c = profile_cost(&sim.s, &target);
+		verbose!("a = {:.4}, Q = {:.4}: cost {:.6}", x[0], x[1], c);
+		c
+	});
+	println!("Best fit: a = {}, Q = {} (cost {})", best[0], best[1], cost);
+}
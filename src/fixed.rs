@@ -0,0 +1,49 @@
+/*
+ Stars held in place: a static perturber, or anchors marking out a
+ potential. Stars with a non-zero "fixed" column pull on the others as
+ usual, but their accelerations and velocities are zeroed after every force
+ evaluation, so the drift and kicks of update_positions and
+ update_velocities (and of every other step) leave them where they are.
+ They do no work, so E = T + W is still conserved, with T only counting the
+ stars that move.
+ */
+use columns::Columns;
+use {Real, Star};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fixed {
+	// Indices of the fixed stars
+	pub stars: Vec<usize>,
+}
+
+impl Fixed {
+	// The stars with a non-zero "fixed" column
+	pub fn new(n: usize, columns: &Columns) -> Fixed {
+		Fixed { stars: (0..n).filter(|&i| columns.value(i, "fixed").map_or(false, |x| x != 0.0)).collect() }
+	}
+
+	// Stops the fixed stars
+	pub fn hold<R: Real>(&self, s: &mut Vec<Star<R>>) {
+		for &i in &self.stars {
+			let star = &mut s[i];
+			for x in star.a.iter_mut().chain(star.v.iter_mut()) {
+				*x = R::zero();
+			}
+		}
+	}
+
+	// j was merged into i and removed; the merger is fixed if either was
+	pub fn merged(&mut self, i: usize, j: usize) {
+		let either = self.stars.contains(&i) || self.stars.contains(&j);
+		self.stars.retain(|&x| x != i && x != j);
+		for x in self.stars.iter_mut() {
+			if *x > j {
+				*x -= 1;
+			}
+		}
+		if either {
+			self.stars.push(i);
+			self.stars.sort();
+		}
+	}
+}
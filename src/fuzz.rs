@@ -0,0 +1,97 @@
+/*
+ Structured fuzzing: turns arbitrary bytes into a random but valid system
+ (star count, masses including massless stars, clustered or coincident
+ positions, softening rule, precision tricks, thread count) and checks the
+ invariants that must hold for any such system over a few steps. Driven by
+ the cargo-fuzz target in fuzz/ and, with fixed seeds, by tests/fuzz.rs.
+ */
+use rng::Rng;
+use {momentum_residual, Params, Simulation, Softening, Star};
+
+// Seed from the fuzzer's bytes, so every input maps to one system
+fn seed(data: &[u8]) -> u64 {
+	data.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+pub fn random_system(data: &[u8]) -> (Vec<Star>, Params) {
+	let mut rng = Rng::new(seed(data));
+	let n = 1 + (rng.next_u64() % 48) as usize;
+	let mut p: Params = Params::default();
+	p.threads = 1 + (rng.next_u64() % 4) as usize;
+	p.compensated = rng.uniform() < 0.3;
+	p.deterministic = rng.uniform() < 0.3;
+	p.mixed = rng.uniform() < 0.2;
+	p.softening = match rng.next_u64() % 3 {
+		0 => Softening::Fixed,
+		1 => Softening::Mean,
+		_ => Softening::Min,
+	};
+	p.dt = 10f64.powf(rng.range(-5.0, -2.0));
+	// Stars may only share a position when the pair is softened
+	let coincident = rng.uniform() < 0.2;
+	p.eps = if coincident || rng.uniform() < 0.5 { 10f64.powf(rng.range(-3.0, -1.0)) } else { 0.0 };
+	if coincident {
+		p.softening = Softening::Fixed;
+	}
+
+	let spread = 10f64.powf(rng.range(-3.0, 1.0));
+	let heavy = 10f64.powf(rng.range(0.0, 6.0));
+	let mut s: Vec<Star> = Vec::with_capacity(n);
+	for i in 0..n {
+		let m = match rng.next_u64() % 8 {
+			0 => 0.0,
+			1 => heavy,
+			_ => rng.range(0.1, 1.0),
+		};
+		let r = if coincident && i > 0 && rng.uniform() < 0.3 {
+			s[0].r.clone()
+		} else {
+			vec![spread*rng.normal(), spread*rng.normal(), spread*rng.normal()]
+		};
+		let v = vec![rng.normal(), rng.normal(), rng.normal()];
+		s.push(Star { m: m, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] });
+	}
+	// Normalize the total mass so the time step stays meaningful
+	let mtot: f64 = s.iter().map(|x| x.m).sum();
+	if mtot > 0.0 {
+		for star in s.iter_mut() {
+			star.m /= mtot;
+		}
+	}
+	(s, p)
+}
+
+/*
+ Steps the system and checks after every step that the star count and mass
+ are unchanged, positions and velocities are finite and the forces conserve
+ momentum (Newton's third law) to rounding.
+ */
+pub fn check(s: Vec<Star>, p: Params, steps: usize) -> Result<(), String> {
+	let n = s.len();
+	let mass: f64 = s.iter().map(|x| x.m).sum();
+	let tolerance = if p.mixed { 1e-4 } else { 1e-9 };
+	let mut sim = Simulation::new(s, p);
+	for step in 0..=steps {
+		if step > 0 {
+			sim.step();
+		}
+		if sim.s.len() != n {
+			return Err(format!("step {}: {} stars, started with {}", step, sim.s.len(), n));
+		}
+		let m: f64 = sim.s.iter().map(|x| x.m).sum();
+		if m != mass {
+			return Err(format!("step {}: mass {} changed from {}", step, m, mass));
+		}
+		for (i, x) in sim.s.iter().enumerate() {
+			if x.r.iter().chain(x.v.iter()).chain(x.a.iter()).any(|v| !v.is_finite()) {
+				return Err(format!("step {}: star {} is not finite: r {:?} v {:?} a {:?}", step, i, x.r, x.v, x.a));
+			}
+		}
+		let a: Vec<[f64; 3]> = sim.s.iter().map(|x| [x.a[0], x.a[1], x.a[2]]).collect();
+		let residual = momentum_residual(&sim.s, &a);
+		if residual > tolerance {
+			return Err(format!("step {}: momentum residual {:e} above {:e}", step, residual, tolerance));
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,506 @@
+/*
+ "nbabel generate MODEL [options] > input" writes initial conditions in the
+ input format, in N-body units (G = 1, total mass 1, E = -1/4, virial
+ equilibrium) unless the model says otherwise.
+
+ Models:
+   king --w0 W0    King (1966) model with dimensionless central potential W0
+   uniform [--q Q] homogeneous sphere with virial ratio Q (default 0, cold)
+   plummer         Plummer (1911) sphere, isotropic
+   cube [--box L]  cold, equal-mass stars spread uniformly over the cube
+                   [-L/2, L/2)^3 (default L = 1), for runs with --periodic L
+   binary --a A --e E [--mass-ratio Q] [--a3 A3 --e3 E3 --m3 M3 --i3 DEG]
+                   Keplerian binary of mass 1, optionally with a tertiary on
+                   an outer orbit around it
+   solarsystem     Sun and the eight planets at J2000.0, in au, solar masses
+                   and G = 1 (one year is about 2 pi)
+ Common options: -n N (default 1024), --seed S
+ king, uniform and plummer also take --imf salpeter|kroupa [--mmin M] [--mmax M] to
+ draw the masses from an initial mass function instead of making them equal.
+ */
+use std::io;
+use std::io::Write;
+
+use imf::Imf;
+use orbit;
+use orbit::Elements;
+use rng::Rng;
+use {diagnostics, energies, write_stars, Params, Star};
+
+/*
+ Error function, Abramowitz & Stegun 7.1.26 (absolute error below 1.5e-7),
+ which is plenty for sampling.
+ */
+fn erf(x: f64) -> f64 {
+	let t = 1.0/(1.0 + 0.3275911*x.abs());
+	let y = 1.0 - t*(0.254829592 + t*(-0.284496736 + t*(1.421413741 + t*(-1.453152027 + t*1.061405429))))*(-x*x).exp();
+	if x < 0.0 { -y } else { y }
+}
+
+// King density as a function of the dimensionless potential, up to a constant
+fn king_density(w: f64) -> f64 {
+	if w <= 0.0 {
+		return 0.0;
+	}
+	w.exp()*erf(w.sqrt()) - (4.0*w/std::f64::consts::PI).sqrt()*(1.0 + 2.0*w/3.0)
+}
+
+/*
+ Radial profile of a King model: radii in units of the King radius, the
+ potential W and the enclosed mass (arbitrary units), out to the tidal
+ radius where W = 0. Solves Poisson's equation
+ W'' + 2W'/r = -9 rho(W)/rho(W0) with RK4 from the centre.
+ */
+pub struct KingProfile {
+	pub r: Vec<f64>,
+	pub w: Vec<f64>,
+	pub mass: Vec<f64>,
+}
+
+pub fn king_profile(w0: f64) -> KingProfile {
+	let rho0 = king_density(w0);
+	let deriv = |r: f64, w: f64, dw: f64| -> (f64, f64) {
+		(dw, -2.0*dw/r - 9.0*king_density(w)/rho0)
+	};
+	// Series solution W = W0 - 3/2 r^2 near the centre
+	let mut r = 1e-4;
+	let mut w = w0 - 1.5*r*r;
+	let mut dw = -3.0*r;
+	let h = 1e-3;
+	let mut profile = KingProfile { r: vec![0.0], w: vec![w0], mass: vec![0.0] };
+	while w > 0.0 && r < 1e4 {
+		let (k1w, k1d) = deriv(r, w, dw);
+		let (k2w, k2d) = deriv(r + 0.5*h, w + 0.5*h*k1w, dw + 0.5*h*k1d);
+		let (k3w, k3d) = deriv(r + 0.5*h, w + 0.5*h*k2w, dw + 0.5*h*k2d);
+		let (k4w, k4d) = deriv(r + h, w + h*k3w, dw + h*k3d);
+		let w_next = w + h/6.0*(k1w + 2.0*k2w + 2.0*k3w + k4w);
+		let dw_next = dw + h/6.0*(k1d + 2.0*k2d + 2.0*k3d + k4d);
+		if w_next <= 0.0 {
+			// Interpolate the tidal radius
+			let f = w/(w - w_next);
+			r += f*h;
+			dw += f*(dw_next - dw);
+			w = 0.0;
+		} else {
+			r += h;
+			w = w_next;
+			dw = dw_next;
+		}
+		profile.r.push(r);
+		profile.w.push(w);
+		profile.mass.push(-r*r*dw);
+	}
+	profile
+}
+
+// Linear interpolation in a table with ascending xs
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+	let k = match xs.iter().position(|&v| v >= x) {
+		Some(0) => return ys[0],
+		Some(k) => k,
+		None => return ys[ys.len() - 1],
+	};
+	let f = (x - xs[k - 1])/(xs[k] - xs[k - 1]);
+	ys[k - 1] + f*(ys[k] - ys[k - 1])
+}
+
+fn isotropic(rng: &mut Rng, length: f64) -> Vec<f64> {
+	let z = rng.range(-1.0, 1.0);
+	let phi = rng.range(0.0, 2.0*std::f64::consts::PI);
+	let rho = (1.0 - z*z).sqrt();
+	vec![length*rho*phi.cos(), length*rho*phi.sin(), length*z]
+}
+
+// A vector of the given length in a random direction in the z = 0 plane
+fn planar(rng: &mut Rng, length: f64) -> Vec<f64> {
+	let phi = rng.range(0.0, 2.0*std::f64::consts::PI);
+	vec![length*phi.cos(), length*phi.sin(), 0.0]
+}
+
+// Moves the system to its centre-of-mass frame
+fn center(s: &mut Vec<Star>) {
+	let mtot: f64 = s.iter().map(|x| x.m).sum();
+	for i in 0..3 {
+		let rc: f64 = s.iter().map(|x| x.m*x.r[i]).sum::<f64>()/mtot;
+		let vc: f64 = s.iter().map(|x| x.m*x.v[i]).sum::<f64>()/mtot;
+		for star in s.iter_mut() {
+			star.r[i] -= rc;
+			star.v[i] -= vc;
+		}
+	}
+}
+
+/*
+ Rescales positions and velocities so the system is in N-body units: total
+ mass 1 (the masses are expected to be), potential energy -1/2 and kinetic
+ energy q/4, i.e. virial ratio q and E = -1/4 in virial equilibrium. Also
+ moves it to the centre-of-mass frame.
+ */
+fn scale_virial(s: &mut Vec<Star>, q: f64) {
+	let n = s.len();
+	center(s);
+	if n < 2 {
+		return;
+	}
+	let e = energies(s, &Params::default());
+	let rscale = e[2]/-0.5;
+	let vscale = if e[1] > 0.0 { (0.25*q/e[1]).sqrt() } else { 0.0 };
+	for star in s.iter_mut() {
+		for i in 0..3 {
+			star.r[i] *= rscale;
+			star.v[i] *= vscale;
+		}
+	}
+}
+
+/*
+ Equal-mass King model. Radii are drawn from the enclosed mass profile and
+ speeds from the lowered Maxwellian f(E) ~ exp(W - v^2/2) - 1 at the local
+ potential, by rejection.
+ */
+pub fn king(n: usize, w0: f64, rng: &mut Rng) -> Vec<Star> {
+	let profile = king_profile(w0);
+	let mtot = *profile.mass.last().expect("Empty King profile");
+	let mut s = Vec::with_capacity(n);
+	for _ in 0..n {
+		let r = interpolate(&profile.mass, &profile.r, rng.uniform()*mtot);
+		let w = interpolate(&profile.r, &profile.w, r).max(0.0);
+
+		let vmax = (2.0*w).sqrt();
+		let density = |v: f64| v*v*((w - 0.5*v*v).exp() - 1.0);
+		let peak = (0..=64).map(|k| density(vmax*k as f64/64.0)).fold(0.0, f64::max)*1.1;
+		let mut v = 0.0;
+		if peak > 0.0 {
+			loop {
+				v = rng.range(0.0, vmax);
+				if rng.uniform()*peak <= density(v) {
+					break;
+				}
+			}
+		}
+		s.push(Star { m: 1.0/n as f64, r: isotropic(rng, r), v: isotropic(rng, v), a: vec![0.0; 3], a0: vec![0.0; 3] });
+	}
+	scale_virial(&mut s, 1.0);
+	s
+}
+
+/*
+ Equal-mass homogeneous sphere with Gaussian velocities scaled to virial
+ ratio q. In N-body units W = -1/2 puts the edge at radius 6/5. q = 0 is the
+ classic cold collapse: all stars at rest, E = -1/2, and the sphere falls in
+ on itself after the free-fall time pi/2 sqrt(R^3/2) ~ 1.46.
+ */
+pub fn uniform(n: usize, q: f64, rng: &mut Rng) -> Vec<Star> {
+	let mut s = Vec::with_capacity(n);
+	for _ in 0..n {
+		let radius = rng.uniform().cbrt();
+		let r = isotropic(rng, radius);
+		let v = if q > 0.0 { vec![rng.normal(), rng.normal(), rng.normal()] } else { vec![0.0; 3] };
+		s.push(Star { m: 1.0/n as f64, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] });
+	}
+	scale_virial(&mut s, q);
+	s
+}
+
+// The flat counterpart of uniform(): a homogeneous disc in the z = 0 plane
+pub fn uniform_disc(n: usize, q: f64, rng: &mut Rng) -> Vec<Star> {
+	let mut s = Vec::with_capacity(n);
+	for _ in 0..n {
+		let radius = rng.uniform().sqrt();
+		let r = planar(rng, radius);
+		let v = if q > 0.0 { vec![rng.normal(), rng.normal(), 0.0] } else { vec![0.0; 3] };
+		s.push(Star { m: 1.0/n as f64, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] });
+	}
+	scale_virial(&mut s, q);
+	s
+}
+
+// Equal-mass stars at rest, uniform in the cube of side l around the origin
+pub fn cube(n: usize, l: f64, rng: &mut Rng) -> Vec<Star> {
+	(0..n).map(|_| {
+		let r = (0..3).map(|_| l*(rng.uniform() - 0.5)).collect();
+		Star { m: 1.0/n as f64, r: r, v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] }
+	}).collect()
+}
+
+// Equal-mass stars at rest, uniform in the square of side l in the z = 0 plane
+pub fn square(n: usize, l: f64, rng: &mut Rng) -> Vec<Star> {
+	(0..n).map(|_| {
+		let r = vec![l*(rng.uniform() - 0.5), l*(rng.uniform() - 0.5), 0.0];
+		Star { m: 1.0/n as f64, r: r, v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] }
+	}).collect()
+}
+
+/*
+ Cold, rotating, equal-mass Kuzmin disc in the z = 0 plane: surface density
+ M a/(2 pi (R^2 + a^2)^(3/2)) with M = a = 1, radii from the inverted mass
+ profile cut at 95% of the mass, and every star on the circular orbit of the
+ full disc, v^2 = R^2/(R^2 + 1)^(3/2), counter-clockwise about z. With no
+ velocity dispersion it is violently unstable and grows spiral arms and a
+ bar within a few rotations, which is the point of a demo.
+ */
+pub fn kuzmin(n: usize, rng: &mut Rng) -> Vec<Star> {
+	let mut s = Vec::with_capacity(n);
+	for _ in 0..n {
+		let m = rng.range(0.0, 0.95);
+		let radius = (1.0/((1.0 - m)*(1.0 - m)) - 1.0).sqrt();
+		let r = planar(rng, radius);
+		let v = radius/(radius*radius + 1.0).powf(0.75);
+		let v = vec![-v*r[1]/radius.max(1e-300), v*r[0]/radius.max(1e-300), 0.0];
+		s.push(Star { m: 1.0/n as f64, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] });
+	}
+	center(&mut s);
+	s
+}
+
+/*
+ Equal-mass Plummer sphere, sampled as in Aarseth, Henon & Wielen (1974):
+ radii from the inverted mass profile, cut at 99.9% of the mass so no star
+ starts hundreds of scale radii out, and speeds in units of the local escape
+ speed from g(q) = q^2 (1 - q^2)^(7/2) by rejection.
+ */
+pub fn plummer(n: usize, rng: &mut Rng) -> Vec<Star> {
+	let mut s = Vec::with_capacity(n);
+	for _ in 0..n {
+		let m = rng.range(0.0, 0.999);
+		let r = 1.0/(m.powf(-2.0/3.0) - 1.0).sqrt();
+		let q = loop {
+			let q = rng.uniform();
+			// g peaks below 0.1
+			if rng.uniform()*0.1 < q*q*(1.0 - q*q).powf(3.5) {
+				break q;
+			}
+		};
+		let v = q*2f64.sqrt()*(1.0 + r*r).powf(-0.25);
+		s.push(Star { m: 1.0/n as f64, r: isotropic(rng, r), v: isotropic(rng, v), a: vec![0.0; 3], a0: vec![0.0; 3] });
+	}
+	scale_virial(&mut s, 1.0);
+	s
+}
+
+/*
+ Replaces the masses by draws from imf, scaled to total mass 1, and rescales
+ back to N-body units at the virial ratio the system had. Masses are not
+ correlated with position, i.e. there is no primordial mass segregation.
+ */
+pub fn apply_imf(s: &mut Vec<Star>, imf: &Imf, rng: &mut Rng) {
+	let q = if s.len() < 2 { 0.0 } else { diagnostics::virial_ratio(&energies(s, &Params::default())) };
+	let masses: Vec<f64> = s.iter().map(|_| imf.sample(rng)).collect();
+	let total: f64 = masses.iter().sum();
+	for (star, m) in s.iter_mut().zip(masses) {
+		star.m = m/total;
+	}
+	scale_virial(s, q);
+}
+
+/*
+ Relative position and velocity of a Kepler orbit with total mass m,
+ semi-major axis a and eccentricity e at true anomaly f, in the x-y plane with
+ the pericentre on the x axis, tilted about the x axis by the inclination i
+ (radians).
+ */
+pub fn kepler(m: f64, a: f64, e: f64, f: f64, i: f64) -> (Vec<f64>, Vec<f64>) {
+	let semi_latus = a*(1.0 - e*e);
+	let r = semi_latus/(1.0 + e*f.cos());
+	let speed = (m/semi_latus).sqrt();
+	let (x, y) = (r*f.cos(), r*f.sin());
+	let (vx, vy) = (-speed*f.sin(), speed*(e + f.cos()));
+	(vec![x, y*i.cos(), y*i.sin()], vec![vx, vy*i.cos(), vy*i.sin()])
+}
+
+// Orbital period of a Kepler orbit, G = 1
+pub fn period(m: f64, a: f64) -> f64 {
+	2.0*std::f64::consts::PI*(a.powi(3)/m).sqrt()
+}
+
+/*
+ Binary of total mass 1 with masses in the ratio m2/m1 = mass_ratio, started
+ at apocentre in its centre-of-mass frame. The period is 2 pi a^(3/2).
+ */
+pub fn binary(a: f64, e: f64, mass_ratio: f64) -> Vec<Star> {
+	let m1 = 1.0/(1.0 + mass_ratio);
+	let m2 = 1.0 - m1;
+	let (r, v) = kepler(1.0, a, e, std::f64::consts::PI, 0.0);
+	let mut s = vec![
+		Star { m: m1, r: r.iter().map(|x| -m2*x).collect(), v: v.iter().map(|x| -m2*x).collect(), a: vec![0.0; 3], a0: vec![0.0; 3] },
+		Star { m: m2, r: r.iter().map(|x| m1*x).collect(), v: v.iter().map(|x| m1*x).collect(), a: vec![0.0; 3], a0: vec![0.0; 3] },
+	];
+	center(&mut s);
+	s
+}
+
+/*
+ Adds a tertiary of mass m3 on an outer orbit (a3, e3, inclination i3 in
+ radians) around the centre of mass of s, starting at apocentre, and moves
+ the whole to its centre-of-mass frame.
+ */
+pub fn add_tertiary(mut s: Vec<Star>, m3: f64, a3: f64, e3: f64, i3: f64) -> Vec<Star> {
+	let inner: f64 = s.iter().map(|x| x.m).sum();
+	center(&mut s);
+	let (r, v) = kepler(inner + m3, a3, e3, std::f64::consts::PI, i3);
+	s.push(Star { m: m3, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] });
+	center(&mut s);
+	s
+}
+
+/*
+ Position and velocity relative to the central body for orbital elements:
+ gravitational parameter mu, semi-major axis a, eccentricity e, inclination
+ i, longitude of the ascending node, argument of pericentre and mean anomaly
+ (angles in radians). The reference plane is x-y.
+ */
+pub fn from_elements(mu: f64, a: f64, e: f64, i: f64, node: f64, peri: f64, mean_anomaly: f64) -> (Vec<f64>, Vec<f64>) {
+	let (r, v) = orbit::from_elements(mu, &Elements { a: a, e: e, i: i, node: node, peri: peri, mean_anomaly: mean_anomaly });
+	(r.to_vec(), v.to_vec())
+}
+
+/*
+ J2000.0 mean elements of the planets from Standish, "Keplerian Elements for
+ Approximate Positions of the Major Planets" (JPL, table 1): a (au), e,
+ I, mean longitude L, longitude of perihelion and of the ascending node
+ (degrees), ecliptic and equinox of J2000. The masses are Sun/planet ratios
+ (IAU 2009). Earth stands for the Earth-Moon barycentre.
+ */
+static PLANETS: [(&'static str, f64, f64, f64, f64, f64, f64, f64); 8] = [
+	("Mercury", 6023600.0, 0.38709927, 0.20563593, 7.00497902, 252.25032350, 77.45779628, 48.33076593),
+	("Venus", 408523.71, 0.72333566, 0.00677672, 3.39467605, 181.97909950, 131.60246718, 76.67984255),
+	("Earth", 328900.56, 1.00000261, 0.01671123, -0.00001531, 100.46457166, 102.93768193, 0.0),
+	("Mars", 3098708.0, 1.52371034, 0.09339410, 1.84969142, -4.55343205, -23.94362959, 49.55953891),
+	("Jupiter", 1047.3486, 5.20288700, 0.04838624, 1.30439695, 34.39644051, 14.72847983, 100.47390909),
+	("Saturn", 3497.898, 9.53667594, 0.05386179, 2.48599187, 49.95424423, 92.59887831, 113.66242448),
+	("Uranus", 22902.98, 19.18916464, 0.04725744, 0.77263783, 313.23810451, 170.95427630, 74.01692503),
+	("Neptune", 19412.24, 30.06992276, 0.00859048, 1.77004347, -55.12002969, 44.96476227, 131.78422574),
+];
+
+/*
+ The Sun (star 0) and the eight planets in order, at J2000.0 in the
+ barycentric ecliptic frame. Units: au, solar masses and G = 1, so the time
+ unit is a year/2 pi (58.13 days) and the Earth's period about 2 pi. The
+ mean elements put the planets within about a degree of their true J2000
+ places, plenty to check periods but not an ephemeris.
+ */
+pub fn solar_system() -> Vec<Star> {
+	let mut s = vec![Star { m: 1.0, r: vec![0.0; 3], v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] }];
+	for &(_, ratio, a, e, i, l, perihelion, node) in PLANETS.iter() {
+		let m = 1.0/ratio;
+		let (r, v) = from_elements(1.0 + m, a, e, i.to_radians(), node.to_radians(),
+			(perihelion - node).to_radians(), (l - perihelion).to_radians());
+		s.push(Star { m: m, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] });
+	}
+	center(&mut s);
+	s
+}
+
+// Names of the bodies in solar_system(), in order
+pub fn solar_system_names() -> Vec<&'static str> {
+	let mut names = vec!["Sun"];
+	names.extend(PLANETS.iter().map(|x| x.0));
+	names
+}
+
+pub fn main(args: &[String]) {
+	let usage = "Usage: nbabel generate king --w0 W0 | uniform [--q Q] | plummer | cube [--box L] | binary --a A --e E [--mass-ratio Q] [--a3 A3 --e3 E3 --m3 M3 --i3 DEG] | disc | solarsystem [-n N] [--seed S] [--dim 2] [--imf salpeter|kroupa --mmin M --mmax M] > input";
+	let model = args.first().expect(usage).clone();
+	let mut n: usize = 1024;
+	let mut seed: u64 = 1;
+	let mut w0: f64 = 6.0;
+	let mut q: f64 = 0.0;
+	let mut box_size: f64 = 1.0;
+	let (mut a, mut e, mut mass_ratio): (f64, f64, f64) = (1.0, 0.0, 1.0);
+	let mut a3: Option<f64> = None;
+	let (mut e3, mut m3, mut i3): (f64, f64, f64) = (0.0, 0.5, 0.0);
+	let mut imf: Option<String> = None;
+	let (mut mmin, mut mmax): (f64, f64) = (0.08, 100.0);
+	let mut dim: usize = 3;
+	let mut it = args[1..].iter();
+	while let Some(arg) = it.next() {
+		match arg.as_str() {
+			"-n" => n = it.next().and_then(|x| x.parse().ok()).expect("-n needs a star count"),
+			"--seed" => seed = it.next().and_then(|x| x.parse().ok()).expect("--seed needs a number"),
+			"--w0" => w0 = it.next().and_then(|x| x.parse().ok()).expect("--w0 needs a central potential"),
+			"--q" => q = it.next().and_then(|x| x.parse().ok()).expect("--q needs a virial ratio"),
+			"--box" => box_size = it.next().and_then(|x| x.parse().ok()).expect("--box needs a side length"),
+			"--a" => a = it.next().and_then(|x| x.parse().ok()).expect("--a needs a semi-major axis"),
+			"--e" => e = it.next().and_then(|x| x.parse().ok()).expect("--e needs an eccentricity"),
+			"--mass-ratio" => mass_ratio = it.next().and_then(|x| x.parse().ok()).expect("--mass-ratio needs m2/m1"),
+			"--a3" => a3 = Some(it.next().and_then(|x| x.parse().ok()).expect("--a3 needs a semi-major axis")),
+			"--e3" => e3 = it.next().and_then(|x| x.parse().ok()).expect("--e3 needs an eccentricity"),
+			"--m3" => m3 = it.next().and_then(|x| x.parse().ok()).expect("--m3 needs a mass"),
+			"--i3" => i3 = it.next().and_then(|x| x.parse().ok()).expect("--i3 needs an inclination in degrees"),
+			"--imf" => imf = Some(it.next().expect("--imf needs salpeter or kroupa").clone()),
+			"--mmin" => mmin = it.next().and_then(|x| x.parse().ok()).expect("--mmin needs a mass"),
+			"--mmax" => mmax = it.next().and_then(|x| x.parse().ok()).expect("--mmax needs a mass"),
+			"--dim" => dim = it.next().and_then(|x| x.parse().ok()).expect("--dim needs 2 or 3"),
+			_ => panic!("Unknown argument: {}\n{}", arg, usage),
+		}
+	}
+
+	if dim != 2 && dim != 3 {
+		panic!("--dim must be 2 or 3, got {}", dim);
+	}
+	// Flat models only, or models that are flat anyway
+	if dim == 2 && model != "uniform" && model != "cube" && model != "disc" && !(model == "binary" && i3 == 0.0) {
+		panic!("--dim 2 applies to the uniform, cube, disc and (coplanar) binary models");
+	}
+	let mut rng = Rng::new(seed);
+	let (mut header, mut s) = match model.as_str() {
+		"king" => {
+			if w0 <= 0.0 || w0 > 16.0 {
+				panic!("--w0 must be in (0, 16], got {}", w0);
+			}
+			(format!("King model W0 = {}", w0), king(n, w0, &mut rng))
+		},
+		"uniform" => {
+			if !(q >= 0.0) {
+				panic!("--q must be non-negative, got {}", q);
+			}
+			if dim == 2 {
+				(format!("Uniform disc Q = {}", q), uniform_disc(n, q, &mut rng))
+			} else {
+				(format!("Uniform sphere Q = {}", q), uniform(n, q, &mut rng))
+			}
+		},
+		"plummer" => (String::from("Plummer sphere"), plummer(n, &mut rng)),
+		"disc" => (String::from("Cold Kuzmin disc a = 1, in the z = 0 plane"), kuzmin(n, &mut rng)),
+		"cube" => {
+			if !(box_size > 0.0) {
+				panic!("--box must be positive, got {}", box_size);
+			}
+			if dim == 2 {
+				(format!("Uniform square L = {}, cold", box_size), square(n, box_size, &mut rng))
+			} else {
+				(format!("Uniform cube L = {}, cold", box_size), cube(n, box_size, &mut rng))
+			}
+		},
+		"binary" => {
+			if !(a > 0.0) || !(e >= 0.0 && e < 1.0) || !(mass_ratio > 0.0) {
+				panic!("Need --a > 0, 0 <= --e < 1 and --mass-ratio > 0");
+			}
+			let mut header = format!("Binary a = {}, e = {}, m2/m1 = {}, period {}", a, e, mass_ratio, period(1.0, a));
+			let mut s = binary(a, e, mass_ratio);
+			if let Some(a3) = a3 {
+				if !(a3 > a) || !(e3 >= 0.0 && e3 < 1.0) || !(m3 > 0.0) {
+					panic!("Need --a3 > --a, 0 <= --e3 < 1 and --m3 > 0");
+				}
+				header += &format!("; tertiary m3 = {}, a3 = {}, e3 = {}, i3 = {} deg, period {}", m3, a3, e3, i3, period(1.0 + m3, a3));
+				s = add_tertiary(s, m3, a3, e3, i3.to_radians());
+			}
+			(header, s)
+		},
+		"solarsystem" => (format!("Sun and planets at J2000.0 ({}), au, Msun, G = 1", solar_system_names().join(" ")), solar_system()),
+		_ => panic!("Unknown model '{}'\n{}", model, usage),
+	};
+	if let Some(name) = imf {
+		if model != "king" && model != "uniform" && model != "plummer" {
+			panic!("--imf only applies to the king, uniform and plummer models");
+		}
+		let x = Imf::by_name(&name, mmin, mmax).unwrap_or_else(|msg| panic!("{}", msg));
+		apply_imf(&mut s, &x, &mut rng);
+		header += &format!(", {} IMF {}-{} Msun", name, mmin, mmax);
+	}
+
+	let stdout = io::stdout();
+	let mut out = io::BufWriter::new(stdout.lock());
+	writeln!(out, "# {}, N = {}, seed {}", header, s.len(), seed).expect("Could not write output");
+	write_stars(&mut out, &s).expect("Could not write output");
+}
@@ -0,0 +1,193 @@
+/*
+ Force computation on the GPU through wgpu (Vulkan, Metal, DX12). The shader
+ in gpu.wgsl does the all-pairs sum with workgroup-memory tiling. Positions,
+ masses and softening lengths are uploaded every step and the accelerations
+ read back, all in f32: WGSL has no portable f64, so this trades accuracy for
+ the ability to run 1e5 stars by direct summation.
+
+ With p.hybrid the GPU only takes a share of the stars and the CPU threads
+ sum the rest at the same time (pairs::par_rows). After every step the share
+ moves toward the split that would have made both finish together, given
+ the stars per second each just managed, so on mid-sized problems neither
+ sits idle waiting for the other.
+ */
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Instant;
+
+use wgpu::util::DeviceExt;
+
+use pairs;
+use {Params, Real, Softening, Star};
+
+// Must match the workgroup size in gpu.wgsl
+static TILE: usize = 64;
+
+struct Context {
+	device: wgpu::Device,
+	queue: wgpu::Queue,
+	pipeline: wgpu::ComputePipeline,
+	// Fraction of the stars the GPU gets in hybrid mode
+	share: f64,
+}
+
+impl Context {
+	fn new() -> Option<Context> {
+		let instance = wgpu::Instance::default();
+		let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+			power_preference: wgpu::PowerPreference::HighPerformance,
+			..Default::default()
+		}))?;
+		let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+			label: Some("nbabel"),
+			required_features: wgpu::Features::empty(),
+			required_limits: adapter.limits(),
+		}, None)).ok()?;
+		let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("forces"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+		});
+		let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+			label: Some("forces"),
+			layout: None,
+			module: &module,
+			entry_point: "main",
+		});
+		Some(Context { device: device, queue: queue, pipeline: pipeline, share: 0.5 })
+	}
+}
+
+// The device is set up once, on first use
+fn context() -> Option<&'static Mutex<Context>> {
+	static CONTEXT: OnceLock<Option<Mutex<Context>>> = OnceLock::new();
+	CONTEXT.get_or_init(|| Context::new().map(Mutex::new)).as_ref()
+}
+
+// Whether a GPU adapter could be opened
+pub fn available() -> bool {
+	context().is_some()
+}
+
+// Accelerations of stars first..first+count as x, y, z, 0 quadruples
+fn rows<R: Real>(ctx: &Context, s: &[Star<R>], eps: &[R], p: &Params<R>, first: usize, count: usize) -> Vec<f32> {
+	let n = s.len();
+	let body: Vec<f32> = s.iter().flat_map(|x| vec![x.r[0].to_f64() as f32, x.r[1].to_f64() as f32, x.r[2].to_f64() as f32, x.m.to_f64() as f32]).collect();
+	// Storage buffers may not be empty
+	let mut soft: Vec<f32> = eps.iter().map(|x| x.to_f64() as f32).collect();
+	soft.push(0.0);
+	let settings: [u32; 4] = [n as u32, (p.softening == Softening::Mean) as u32, first as u32, count as u32];
+	let bytes = (4*(count.max(1))*std::mem::size_of::<f32>()) as u64;
+
+	let device = &ctx.device;
+	let body_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some("body"), contents: bytemuck::cast_slice(&body), usage: wgpu::BufferUsages::STORAGE,
+	});
+	let soft_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some("soft"), contents: bytemuck::cast_slice(&soft), usage: wgpu::BufferUsages::STORAGE,
+	});
+	let settings_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some("settings"), contents: bytemuck::cast_slice(&settings), usage: wgpu::BufferUsages::UNIFORM,
+	});
+	let acc_buf = device.create_buffer(&wgpu::BufferDescriptor {
+		label: Some("acc"), size: bytes, usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC, mapped_at_creation: false,
+	});
+	let readback = device.create_buffer(&wgpu::BufferDescriptor {
+		label: Some("readback"), size: bytes, usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST, mapped_at_creation: false,
+	});
+	let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+		label: None,
+		layout: &ctx.pipeline.get_bind_group_layout(0),
+		entries: &[
+			wgpu::BindGroupEntry { binding: 0, resource: body_buf.as_entire_binding() },
+			wgpu::BindGroupEntry { binding: 1, resource: soft_buf.as_entire_binding() },
+			wgpu::BindGroupEntry { binding: 2, resource: acc_buf.as_entire_binding() },
+			wgpu::BindGroupEntry { binding: 3, resource: settings_buf.as_entire_binding() },
+		],
+	});
+
+	let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+	{
+		let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None, timestamp_writes: None });
+		pass.set_pipeline(&ctx.pipeline);
+		pass.set_bind_group(0, &bind_group, &[]);
+		pass.dispatch_workgroups(((count + TILE - 1)/TILE) as u32, 1, 1);
+	}
+	encoder.copy_buffer_to_buffer(&acc_buf, 0, &readback, 0, bytes);
+	ctx.queue.submit(Some(encoder.finish()));
+
+	let slice = readback.slice(..);
+	slice.map_async(wgpu::MapMode::Read, |x| x.expect("Could not read back accelerations"));
+	device.poll(wgpu::Maintain::Wait);
+	let acc = {
+		let data = slice.get_mapped_range();
+		let acc: &[f32] = bytemuck::cast_slice(&data);
+		acc[..4*count].to_vec()
+	};
+	readback.unmap();
+	acc
+}
+
+/*
+ Computes all accelerations on the GPU. Returns the wall time of the round
+ trip as the busy time of a single "thread", like ::acceleration.
+ */
+pub fn acceleration<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+	let clock = Instant::now();
+	let ctx = context().expect("No GPU adapter available").lock().expect("GPU context poisoned");
+	let acc = rows(&ctx, s, &p.star_eps(s), p, 0, s.len());
+	for (si, star) in s.iter_mut().enumerate() {
+		for i in 0..3 {
+			star.a[i] = R::from_f64(acc[4*si + i] as f64);
+		}
+	}
+	vec![clock.elapsed().as_secs_f64()]
+}
+
+/*
+ Computes the accelerations of the first stars on the GPU and of the others
+ on p.threads CPU threads, concurrently, then rebalances the share for the
+ next call. Returns the GPU round trip followed by the CPU threads' busy
+ times.
+ */
+pub fn hybrid_acceleration<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+	let mut ctx = context().expect("No GPU adapter available").lock().expect("GPU context poisoned");
+	let n = s.len();
+	if n < 2 {
+		drop(ctx);
+		return acceleration(s, p);
+	}
+	let g = ((ctx.share*n as f64).round() as usize).max(1).min(n - 1);
+	let eps = p.star_eps(s);
+
+	let ((gpu_acc, gpu_seconds), (cpu_acc, mut busy), cpu_seconds) = {
+		let (ctx, sr, eps) = (&*ctx, &s[..], &eps[..]);
+		thread::scope(|scope| {
+			let gpu = scope.spawn(move || {
+				let clock = Instant::now();
+				(rows(ctx, sr, eps, p, 0, g), clock.elapsed().as_secs_f64())
+			});
+			let clock = Instant::now();
+			let cpu = pairs::par_rows(sr, eps, p, g, n, p.threads);
+			let cpu_seconds = clock.elapsed().as_secs_f64();
+			(gpu.join().expect("GPU thread panicked"), cpu, cpu_seconds)
+		})
+	};
+	for si in 0..n {
+		for i in 0..3 {
+			s[si].a[i] = if si < g { R::from_f64(gpu_acc[4*si + i] as f64) } else { cpu_acc[si - g][i] };
+		}
+	}
+
+	// Stars per second of each side; the share that would have evened them out, smoothed
+	let gpu_rate = g as f64/gpu_seconds.max(1e-9);
+	let cpu_rate = (n - g) as f64/cpu_seconds.max(1e-9);
+	ctx.share = (0.5*ctx.share + 0.5*gpu_rate/(gpu_rate + cpu_rate)).max(0.01).min(0.99);
+
+	busy.insert(0, gpu_seconds);
+	busy
+}
+
+// The GPU's current share of the stars in hybrid mode, if the GPU is in use
+pub fn share() -> Option<f64> {
+	context().map(|x| x.lock().expect("GPU context poisoned").share)
+}
@@ -0,0 +1,66 @@
+// All-pairs accelerations, one invocation per star. Each workgroup stages
+// TILE stars at a time in workgroup memory, so every position is read from
+// global memory once per workgroup instead of once per invocation.
+
+struct Settings {
+	n: u32,
+	// 1 for the mean softening rule, 0 for min (fixed has all lengths equal)
+	mean: u32,
+	// The invocations compute stars first..first+count
+	first: u32,
+	count: u32,
+}
+
+const TILE: u32 = 64u;
+
+// x, y, z, m
+@group(0) @binding(0) var<storage, read> body: array<vec4<f32>>;
+// Softening length per star
+@group(0) @binding(1) var<storage, read> soft: array<f32>;
+@group(0) @binding(2) var<storage, read_write> acc: array<vec4<f32>>;
+@group(0) @binding(3) var<uniform> settings: Settings;
+
+var<workgroup> tile: array<vec4<f32>, 64>;
+var<workgroup> tile_soft: array<f32, 64>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) gid: vec3<u32>, @builtin(local_invocation_id) lid: vec3<u32>) {
+	let i = settings.first + gid.x;
+	var pi = vec4<f32>(0.0);
+	var ei = 0.0;
+	if (gid.x < settings.count) {
+		pi = body[i];
+		ei = soft[i];
+	}
+	var a = vec3<f32>(0.0);
+	for (var start = 0u; start < settings.n; start = start + TILE) {
+		let j = start + lid.x;
+		// Padding stars have zero mass and contribute nothing
+		if (j < settings.n) {
+			tile[lid.x] = body[j];
+			tile_soft[lid.x] = soft[j];
+		} else {
+			tile[lid.x] = vec4<f32>(0.0);
+			tile_soft[lid.x] = 0.0;
+		}
+		workgroupBarrier();
+		for (var k = 0u; k < TILE; k = k + 1u) {
+			let pj = tile[k];
+			let ej = tile_soft[k];
+			var eps2 = min(ei, ej)*min(ei, ej);
+			if (settings.mean == 1u) {
+				eps2 = 0.5*(ei*ei + ej*ej);
+			}
+			let d = pj.xyz - pi.xyz;
+			let r2 = dot(d, d) + eps2;
+			// Skips the star itself when there is no softening
+			if (r2 > 0.0) {
+				a = a + pj.w*d/(r2*sqrt(r2));
+			}
+		}
+		workgroupBarrier();
+	}
+	if (gid.x < settings.count) {
+		acc[gid.x] = vec4<f32>(a, 0.0);
+	}
+}
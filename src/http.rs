@@ -0,0 +1,196 @@
+/*
+ A small HTTP API for watching and steering a running simulation, built with
+ the "http" feature and switched on with --http HOST:PORT:
+
+   GET  /status              t, steps, energies, dE, cadence and timings as JSON
+   GET  /snapshot            the current stars in the input format
+   POST /pause               stop integrating until /resume
+   POST /resume
+   POST /cadence?steps=N     diagnostics every N steps from now on
+
+ Connections are taken one at a time on a background thread. The requests
+ themselves are answered by the main loop between steps (Api::serve), so a
+ snapshot is always a consistent state; a request can wait a step for its
+ answer. While paused the main loop only answers requests, and a Ctrl-C
+ still stops the run.
+ */
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use interrupt;
+use status::{json_number, json_string};
+use timing::{Times, PHASES};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Request {
+	Status,
+	Snapshot,
+	Pause,
+	Resume,
+	Cadence(usize),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Response {
+	pub status: u16,
+	pub content_type: &'static str,
+	pub body: String,
+}
+
+impl Response {
+	pub fn json(body: String) -> Response {
+		Response { status: 200, content_type: "application/json", body: body }
+	}
+
+	pub fn text(body: String) -> Response {
+		Response { status: 200, content_type: "text/plain", body: body }
+	}
+
+	pub fn error(status: u16, message: &str) -> Response {
+		Response { status: status, content_type: "application/json", body: format!("{{\"error\": {}}}", json_string(message)) }
+	}
+}
+
+fn reason(status: u16) -> &'static str {
+	match status {
+		200 => "OK",
+		400 => "Bad Request",
+		404 => "Not Found",
+		405 => "Method Not Allowed",
+		503 => "Service Unavailable",
+		_ => "",
+	}
+}
+
+// The request for a method and path, or the error to answer with
+pub fn route(method: &str, path: &str) -> Result<Request, Response> {
+	let (path, query) = match path.find('?') {
+		Some(i) => (&path[..i], &path[i + 1..]),
+		None => (path, ""),
+	};
+	let (request, wanted) = match path.trim_end_matches('/') {
+		"/status" => (Request::Status, "GET"),
+		"/snapshot" => (Request::Snapshot, "GET"),
+		"/pause" => (Request::Pause, "POST"),
+		"/resume" => (Request::Resume, "POST"),
+		"/cadence" => {
+			let steps = query.split('&').filter_map(|x| x.strip_prefix("steps=")).next();
+			match steps.and_then(|x| x.parse::<usize>().ok()) {
+				Some(n) if n > 0 => (Request::Cadence(n), "POST"),
+				_ => return Err(Response::error(400, "/cadence needs ?steps=N with N > 0")),
+			}
+		},
+		_ => return Err(Response::error(404, &format!("No such endpoint: {}", path))),
+	};
+	if method != wanted {
+		return Err(Response::error(405, &format!("{} needs {}", path, wanted)));
+	}
+	Ok(request)
+}
+
+/*
+ The JSON for GET /status. e and de are from the last diagnostic; the step
+ rate counts the time spent in the phases only, so pauses do not lower it.
+ */
+pub fn status_json(t: f64, steps: usize, e: &[f64], de: f64, interval: usize, paused: bool, times: &Times) -> String {
+	let phases: Vec<String> = PHASES.iter().map(|&x| format!("{}: {}", json_string(x.name()), json_number(times.get(x)))).collect();
+	let busy = times.total();
+	let rate = if busy > 0.0 { times.steps as f64/busy } else { 0.0 };
+	format!("{{\"t\": {}, \"steps\": {}, \"E\": {}, \"T\": {}, \"W\": {}, \"dE\": {}, \"paused\": {}, \"diagnostic_interval\": {}, \"steps_per_second\": {}, \"seconds\": {{{}}}}}",
+		json_number(t), steps, json_number(e[0]), json_number(e[1]), json_number(e[2]), json_number(de), paused, interval, json_number(rate), phases.join(", "))
+}
+
+type Ask = (Request, mpsc::Sender<Response>);
+
+fn connection(stream: &mut TcpStream, requests: &mpsc::Sender<Ask>) -> io::Result<()> {
+	let mut reader = BufReader::new(stream.try_clone()?);
+	let mut first = String::new();
+	reader.read_line(&mut first)?;
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+			break;
+		}
+	}
+	let mut words = first.split_whitespace();
+	let method = words.next().unwrap_or("");
+	let path = words.next().unwrap_or("");
+	let response = match route(method, path) {
+		Ok(request) => {
+			let (tx, rx) = mpsc::channel();
+			match requests.send((request, tx)) {
+				Ok(()) => rx.recv().unwrap_or_else(|_| Response::error(503, "The run has finished")),
+				Err(_) => Response::error(503, "The run has finished"),
+			}
+		},
+		Err(x) => x,
+	};
+	write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		response.status, reason(response.status), response.content_type, response.body.len(), response.body)?;
+	stream.flush()
+}
+
+pub struct Api {
+	addr: SocketAddr,
+	requests: mpsc::Receiver<Ask>,
+	pub paused: bool,
+}
+
+impl Api {
+	// Listens on address, HOST:PORT (port 0 picks a free one)
+	pub fn bind(address: &str) -> Result<Api, String> {
+		let listener = TcpListener::bind(address).map_err(|x| format!("Could not listen on {}: {}", address, x))?;
+		let addr = listener.local_addr().map_err(|x| x.to_string())?;
+		let (tx, rx) = mpsc::channel();
+		thread::spawn(move || {
+			for mut stream in listener.incoming().flatten() {
+				let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+				if let Err(x) = connection(&mut stream, &tx) {
+					verbose!("HTTP request failed: {}", x);
+				}
+			}
+		});
+		Ok(Api { addr: addr, requests: rx, paused: false })
+	}
+
+	pub fn local_addr(&self) -> SocketAddr {
+		self.addr
+	}
+
+	/*
+	 Answers the waiting requests, /pause and /resume here and the others with
+	 answer. Returns at once when running; while paused it keeps answering
+	 until resumed or interrupted.
+	 */
+	pub fn serve<F: FnMut(Request, bool) -> Response>(&mut self, mut answer: F) {
+		loop {
+			let next = if self.paused {
+				self.requests.recv_timeout(Duration::from_millis(100)).map_err(|_| ())
+			} else {
+				self.requests.try_recv().map_err(|_| ())
+			};
+			match next {
+				Ok((request, reply)) => {
+					let response = match request {
+						Request::Pause | Request::Resume => {
+							let was = self.paused;
+							self.paused = request == Request::Pause;
+							if was != self.paused {
+								info!("{} through the HTTP API", if self.paused { "Paused" } else { "Resumed" });
+							}
+							Response::json(format!("{{\"paused\": {}}}", self.paused))
+						},
+						_ => answer(request, self.paused),
+					};
+					let _ = reply.send(response);
+				},
+				Err(()) if !self.paused || interrupt::requested() => return,
+				Err(()) => (),
+			}
+		}
+	}
+}
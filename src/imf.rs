@@ -0,0 +1,94 @@
+/*
+ Initial mass functions as piecewise power laws dN/dm ~ m^-alpha, truncated
+ to [mmin, mmax] (solar masses; generated clusters are renormalized to total
+ mass 1 afterwards, so only the shape matters).
+
+ salpeter: alpha = 2.35 everywhere (Salpeter 1955)
+ kroupa:   alpha = 0.3 below 0.08, 1.3 up to 0.5, 2.3 above (Kroupa 2001)
+ */
+use rng::Rng;
+
+pub struct Imf {
+	// (lo, hi, alpha, number of stars in the segment, relative)
+	segments: Vec<(f64, f64, f64, f64)>,
+}
+
+// Integral of m^-alpha from lo to hi
+fn integral(lo: f64, hi: f64, alpha: f64) -> f64 {
+	if (alpha - 1.0).abs() < 1e-12 {
+		(hi/lo).ln()
+	} else {
+		(hi.powf(1.0 - alpha) - lo.powf(1.0 - alpha))/(1.0 - alpha)
+	}
+}
+
+impl Imf {
+	/*
+	 Power law with slopes alphas between the given break masses, continuous
+	 at the breaks, cut to [mmin, mmax].
+	 */
+	pub fn broken(breaks: &[f64], alphas: &[f64], mmin: f64, mmax: f64) -> Imf {
+		let mut segments = vec![];
+		let mut scale = 1.0;
+		for k in 0..alphas.len() {
+			let lo = if k == 0 { 0.0 } else { breaks[k - 1] };
+			let hi = if k < breaks.len() { breaks[k] } else { std::f64::INFINITY };
+			if k > 0 {
+				// Continuity at lo
+				scale *= lo.powf(alphas[k] - alphas[k - 1]);
+			}
+			let (lo, hi) = (lo.max(mmin), hi.min(mmax));
+			if lo < hi {
+				segments.push((lo, hi, alphas[k], scale*integral(lo, hi, alphas[k])));
+			}
+		}
+		Imf { segments: segments }
+	}
+
+	pub fn salpeter(mmin: f64, mmax: f64) -> Imf {
+		Imf::broken(&[], &[2.35], mmin, mmax)
+	}
+
+	pub fn kroupa(mmin: f64, mmax: f64) -> Imf {
+		Imf::broken(&[0.08, 0.5], &[0.3, 1.3, 2.3], mmin, mmax)
+	}
+
+	pub fn by_name(name: &str, mmin: f64, mmax: f64) -> Result<Imf, String> {
+		if !(mmin > 0.0 && mmax > mmin) {
+			return Err(format!("Need 0 < mmin < mmax, got {} and {}", mmin, mmax));
+		}
+		match name {
+			"salpeter" => Ok(Imf::salpeter(mmin, mmax)),
+			"kroupa" => Ok(Imf::kroupa(mmin, mmax)),
+			_ => Err(format!("Unknown IMF '{}', use salpeter or kroupa", name)),
+		}
+	}
+
+	// One mass: pick a segment by its share of the stars, then invert its CDF
+	pub fn sample(&self, rng: &mut Rng) -> f64 {
+		let total: f64 = self.segments.iter().map(|x| x.3).sum();
+		let mut u = rng.uniform()*total;
+		let mut segment = self.segments[self.segments.len() - 1];
+		for x in &self.segments {
+			if u < x.3 {
+				segment = *x;
+				break;
+			}
+			u -= x.3;
+		}
+		let (lo, hi, alpha, _) = segment;
+		let f = rng.uniform();
+		if (alpha - 1.0).abs() < 1e-12 {
+			lo*(hi/lo).powf(f)
+		} else {
+			let (a, b) = (lo.powf(1.0 - alpha), hi.powf(1.0 - alpha));
+			(a + f*(b - a)).powf(1.0/(1.0 - alpha))
+		}
+	}
+
+	// Mean mass, for checks
+	pub fn mean(&self) -> f64 {
+		let total: f64 = self.segments.iter().map(|x| x.3).sum();
+		self.segments.iter().map(|&(lo, hi, alpha, w)| w*integral(lo, hi, alpha - 1.0)/integral(lo, hi, alpha)).sum::<f64>()/total
+	}
+}
@@ -0,0 +1,55 @@
+/*
+ Stopping a run cleanly on Ctrl-C (SIGINT) or SIGTERM. The handler only sets
+ a flag; the main loop checks it after every step, writes a checkpoint,
+ closes the diagnostics and logs the energy budget before exiting. The
+ handler also puts the default action back, so a second Ctrl-C kills the
+ program at once if the first one takes too long.
+
+ Only Unix signals are trapped; elsewhere install() does nothing.
+ */
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+mod sys {
+	pub const SIGINT: i32 = 2;
+	pub const SIGTERM: i32 = 15;
+	// SIG_DFL
+	pub const DEFAULT: usize = 0;
+
+	extern "C" {
+		pub fn signal(signum: i32, handler: usize) -> usize;
+	}
+}
+
+#[cfg(unix)]
+extern "C" fn handle(signum: i32) {
+	REQUESTED.store(true, Ordering::SeqCst);
+	unsafe {
+		sys::signal(signum, sys::DEFAULT);
+	}
+}
+
+// Traps SIGINT and SIGTERM
+pub fn install() {
+	#[cfg(unix)]
+	unsafe {
+		sys::signal(sys::SIGINT, handle as extern "C" fn(i32) as usize);
+		sys::signal(sys::SIGTERM, handle as extern "C" fn(i32) as usize);
+	}
+}
+
+// Whether the run was asked to stop
+pub fn requested() -> bool {
+	REQUESTED.load(Ordering::SeqCst)
+}
+
+// Asks the run to stop as a signal would
+pub fn request() {
+	REQUESTED.store(true, Ordering::SeqCst);
+}
+
+pub fn clear() {
+	REQUESTED.store(false, Ordering::SeqCst);
+}
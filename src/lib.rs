@@ -0,0 +1,629 @@
+/*
+ Written by Joris Dalderup <joris@jorisdalderup>
+ The physics and analysis code lives here so it can be used as a library,
+ main.rs only deals with the command line.
+ */
+#[cfg(feature = "plots")]
+extern crate plotters;
+#[cfg(feature = "gpu")]
+extern crate bytemuck;
+#[cfg(feature = "gpu")]
+extern crate pollster;
+#[cfg(feature = "gpu")]
+extern crate wgpu;
+#[cfg(feature = "viz")]
+extern crate kiss3d;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "mpi")]
+extern crate mpi;
+#[cfg(feature = "hdf5")]
+extern crate hdf5;
+#[cfg(feature = "parquet")]
+extern crate arrow;
+#[cfg(feature = "parquet")]
+extern crate parquet;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+
+use std::io;
+use std::io::Write;
+
+// First, so the logging macros are visible in every module
+#[macro_use]
+pub mod log;
+pub mod analyze;
+pub mod autotune;
+pub mod bench;
+pub mod binaries;
+pub mod cadence;
+pub mod central;
+pub mod collisions;
+pub mod checkpoint;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod columns;
+pub mod compose;
+pub mod constants;
+pub mod convert;
+pub mod cosmology;
+#[cfg(feature = "sqlite")]
+pub mod database;
+pub mod dd;
+pub mod diagnostics;
+#[cfg(feature = "mpi")]
+pub mod distributed;
+pub mod drag;
+pub mod error;
+pub mod ewald;
+pub mod external;
+pub mod ffi;
+pub mod fit;
+pub mod fixed;
+pub mod fuzz;
+pub mod generate;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod imf;
+pub mod interrupt;
+pub mod masses;
+pub mod massloss;
+pub mod metadata;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod neighbors;
+pub mod normalize;
+pub mod observer;
+pub mod orbit;
+pub mod pairs;
+pub mod planar;
+pub mod pm;
+pub mod pn;
+pub mod progress;
+pub mod real;
+pub mod regularize;
+#[cfg(feature = "plots")]
+pub mod plots;
+pub mod repl;
+pub mod report;
+pub mod rng;
+pub mod serve;
+pub mod simd;
+pub mod simulation;
+pub mod solver;
+pub mod status;
+pub mod sum;
+pub mod tidal;
+pub mod timestep;
+pub mod timing;
+pub mod track;
+pub mod tracers;
+pub mod transform;
+pub mod tree;
+pub mod units;
+pub mod validate;
+pub mod verlet;
+pub mod vtk;
+#[cfg(feature = "viz")]
+pub mod viz;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error::NBodyError;
+pub use real::Real;
+pub use simulation::Simulation;
+
+use real::c;
+use timing::Clock;
+
+// The precision the binary runs in
+#[cfg(feature = "f32")]
+pub type Float = f32;
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+
+pub static DT: f64 = 1e-3;
+/*
+ How to choose a good thread count you ask? By default it is the number of
+ cores the OS lets this process use. NBABEL_THREADS or --threads override it;
+ on a hyperthreading CPU it can pay to try both the core and the hardware
+ thread count. Also see what works best for your situation.
+ Fair warning: having your processor at high use for long periods of time can
+ damage it.
+
+ Any number of stars works with any thread count; threads beyond the number of
+ rows simply get no work.
+ */
+pub fn thread_count() -> usize {
+	if let Ok(x) = std::env::var("NBABEL_THREADS") {
+		match x.trim().parse::<usize>() {
+			Ok(n) if n > 0 => return n,
+			_ => warn!("ignoring NBABEL_THREADS={}, expected a positive number", x),
+		}
+	}
+	std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/*
+ How close pairs are softened. Under Mean and Min every star gets its own
+ softening length eps*(m/m_max)^(1/3), so light bodies are softened less than
+ heavy ones, and a pair uses the rms or the smaller of the two lengths.
+ Min keeps a planet-star pair essentially Newtonian.
+ */
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Softening {
+	Fixed,
+	Mean,
+	Min,
+}
+
+impl Softening {
+	pub fn parse(name: &str) -> Option<Softening> {
+		match name {
+			"fixed" => Some(Softening::Fixed),
+			"mean" => Some(Softening::Mean),
+			"min" => Some(Softening::Min),
+			_ => None,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Softening::Fixed => "fixed",
+			Softening::Mean => "mean",
+			Softening::Min => "min",
+		}
+	}
+}
+
+/*
+ Run-time physics settings shared by the force and integration code.
+ */
+#[derive(Clone)]
+pub struct Params<R = f64> {
+	pub dt: R,
+	pub eps: R,
+	pub softening: Softening,
+	// Compensated summation in energies() and the force reduction
+	pub compensated: bool,
+	// Add the per-thread force buffers in thread order instead of arrival order
+	pub deterministic: bool,
+	// Force threads, thread_count() by default; 1 runs the pair loop serially on the calling thread
+	pub threads: usize,
+	// Pairwise terms in f32, accumulation in R
+	pub mixed: bool,
+	// Use the vectorized f64 kernel with this instruction set
+	pub simd: Option<simd::Isa>,
+	// Compute forces on the GPU (needs the gpu feature)
+	pub gpu: bool,
+	// With gpu, give part of the stars to the CPU threads, balanced by measured throughput
+	pub hybrid: bool,
+	// Components of r, v and a the kernels use, see by_dim!
+	pub dim: usize,
+}
+
+impl<R: Real> Default for Params<R> {
+	fn default() -> Params<R> {
+		Params { dt: c(DT), eps: R::zero(), softening: Softening::Fixed, compensated: false, deterministic: false, threads: thread_count(), mixed: false, simd: None, gpu: false, hybrid: false, dim: 3 }
+	}
+}
+
+impl<R: Real> Params<R> {
+	// Same settings in another precision
+	pub fn convert<S: Real>(&self) -> Params<S> {
+		Params {
+			dt: S::from_f64(self.dt.to_f64()),
+			eps: S::from_f64(self.eps.to_f64()),
+			softening: self.softening,
+			compensated: self.compensated,
+			deterministic: self.deterministic,
+			threads: self.threads,
+			mixed: self.mixed,
+			simd: self.simd,
+			gpu: self.gpu,
+			hybrid: self.hybrid,
+			dim: self.dim,
+		}
+	}
+
+	// Per-star softening lengths
+	pub fn star_eps(&self, s: &Vec<Star<R>>) -> Vec<R> {
+		if self.softening == Softening::Fixed || self.eps == R::zero() {
+			return vec![self.eps; s.len()];
+		}
+		let mmax = s.iter().fold(R::zero(), |x, star| x.max(star.m));
+		if mmax == R::zero() {
+			return vec![self.eps; s.len()];
+		}
+		s.iter().map(|star| self.eps*(star.m/mmax).cbrt()).collect()
+	}
+
+	// Squared softening length of a pair
+	pub fn pair_eps2(&self, ei: R, ej: R) -> R {
+		match self.softening {
+			Softening::Fixed => self.eps*self.eps,
+			Softening::Mean => c::<R>(0.5)*(ei*ei + ej*ej),
+			Softening::Min => ei.min(ej).powi(2),
+		}
+	}
+}
+
+/*
+ Calls the copy of a kernel monomorphized for p.dim components, so the hot
+ loops see the dimension as a constant instead of branching on it. Star
+ vectors always have at least 3 components: a 2D run keeps z at zero (see
+ planar.rs), and 4D experiments give every star 4, which check_dim()
+ enforces. The force law is 1/r^2 in any dimension. Only the kernels in this
+ file run in more than 3 dimensions: acceleration() hands tracers, p.simd and
+ p.gpu to the plain pair loop there, and Simulation::try_with_solver()
+ rejects the tree, Verlet and periodic solvers, which stay 3D (harmless in
+ 2D, where z is zero). Fixed stars and the incremental energy tracker
+ handle p.dim components; external potentials and the other optional
+ physics of simulation.rs act on x, y and z only.
+ */
+macro_rules! by_dim {
+	($dim:expr, $f:ident($($arg:expr),*)) => {
+		match $dim {
+			2 => $f::<R, 2>($($arg),*),
+			3 => $f::<R, 3>($($arg),*),
+			4 => $f::<R, 4>($($arg),*),
+			d => panic!("No kernels for {} dimensions, only 2, 3 and 4", d),
+		}
+	};
+}
+
+// Whether p.dim has kernels and every star has the components they index
+pub fn check_dim<R: Real>(s: &Vec<Star<R>>, p: &Params<R>) -> Result<(), NBodyError> {
+	if p.dim < 2 || p.dim > 4 {
+		return Err(NBodyError::Config(format!("No kernels for {} dimensions, only 2, 3 and 4", p.dim)));
+	}
+	let need = p.dim.max(3);
+	for (i, x) in s.iter().enumerate() {
+		let have = x.r.len().min(x.v.len()).min(x.a.len()).min(x.a0.len());
+		if have < need {
+			return Err(NBodyError::Config(format!("{} dimensions need {} components per star, star {} has {}", p.dim, need, i, have)));
+		}
+	}
+	Ok(())
+}
+
+pub struct Star<R = f64> {
+	pub m: R,
+	pub r: Vec<R>,
+	pub v: Vec<R>,
+	pub a: Vec<R>,
+	pub a0: Vec<R>,
+}
+
+//Black magic
+impl<R: Real> Clone for Star<R> {
+    fn clone(&self) -> Self {
+        Star {
+            m: self.m.clone(),
+			r: self.r.clone(),
+			v: self.v.clone(),
+			a: self.a.clone(),
+			a0: self.a0.clone(),
+        }
+    }
+}
+
+/*
+ Newton's third law check for a set of accelerations from pair forces: the
+ mass-weighted sum must vanish. Returns |sum m a|/sum |m a| per component,
+ the largest of the three.
+ */
+pub fn momentum_residual<R: Real, const D: usize>(s: &Vec<Star<R>>, a: &Vec<[R; D]>) -> f64 {
+	let mut worst: f64 = 0.0;
+	for i in 0..D {
+		let mut total = R::zero();
+		let mut scale = R::zero();
+		for si in 0..s.len() {
+			total += s[si].m*a[si][i];
+			scale += (s[si].m*a[si][i]).abs();
+		}
+		if scale > R::zero() {
+			worst = worst.max((total.abs()/scale).to_f64());
+		}
+	}
+	worst
+}
+
+// Audit every force evaluation, see audit()
+const AUDIT: bool = cfg!(any(debug_assertions, feature = "paranoid"));
+
+/*
+ In debug builds and with the "paranoid" feature every force evaluation is
+ audited: each thread's partial sum has to conserve momentum on its own, so a
+ failure points at the thread and row range that broke it. eps is the
+ precision the pair terms were computed in.
+ */
+fn audit<R: Real, const D: usize>(s: &Vec<Star<R>>, a: &Vec<[R; D]>, eps: f64, what: &str) {
+	let residual = momentum_residual(s, a);
+	let tolerance = 64.0*(s.len() as f64)*eps;
+	if !(residual <= tolerance) {
+		panic!("Momentum audit failed in {}: |sum m a|/sum |m a| = {:e} (tolerance {:e})", what, residual, tolerance);
+	}
+}
+
+fn audit_stars<R: Real>(s: &Vec<Star<R>>, eps: f64, what: &str) {
+	if AUDIT {
+		let a: Vec<[R; 3]> = s.iter().map(|x| [x.a[0], x.a[1], x.a[2]]).collect();
+		audit(s, &a, eps, what);
+	}
+}
+
+/*
+ Computes all accelerations. Returns how long each thread was busy, in seconds.
+ With p.threads == 1 the pair loop runs serially on the calling thread.
+ With p.simd set the work is handed to the vectorized kernel in simd.rs,
+ with p.gpu to the compute shader in gpu.rs.
+
+ Every thread owns a fixed block of rows with about the same number of pairs
+ (see pairs::partition). Normally the partial results are
+ added up in whatever order the threads finish, so the last bits depend on
+ scheduling; with p.deterministic they are added in thread order, which makes
+ runs bitwise reproducible for a given thread count. Massless stars are
+ only summed over the massive ones (see tracers.rs). More than 3
+ dimensions always run the plain pair loop, see by_dim!.
+ */
+pub fn acceleration<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+	if p.dim > 3 {
+		return by_dim!(p.dim, pair_accelerations(s, p));
+	}
+	// Massless tracers pull on nothing, see tracers.rs
+	if s.iter().any(|x| x.m == R::zero()) && s.iter().any(|x| x.m != R::zero()) {
+		return tracers::accelerations(s, p);
+	}
+	#[cfg(feature = "gpu")]
+	{
+		if p.gpu {
+			let busy = if p.hybrid { gpu::hybrid_acceleration(s, p) } else { gpu::acceleration(s, p) };
+			audit_stars(s, std::f32::EPSILON as f64, "the GPU kernel");
+			return busy;
+		}
+	}
+	if let Some(isa) = p.simd {
+		let busy = simd::acceleration(s, p, isa);
+		audit_stars(s, std::f64::EPSILON, "the vector kernel");
+		return busy;
+	}
+	by_dim!(p.dim, pair_accelerations(s, p))
+}
+
+// The pair loop of acceleration() for D components
+fn pair_accelerations<R: Real, const D: usize>(s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+	// Pair terms are f32 in mixed mode
+	let precision = if p.mixed { R::epsilon().max(std::f32::EPSILON as f64) } else { R::epsilon() };
+	let n = s.len();
+	let eps = p.star_eps(s);
+	let mut total: Vec<[R; D]> = vec![[R::zero(); D]; n];
+	let mut carry: Vec<[R; D]> = if p.compensated { vec![[R::zero(); D]; n] } else { vec![] };
+	let threads = p.threads.max(1);
+	let mut busy: Vec<f64> = vec![0.0; threads];
+	let mut parked: Vec<Option<Vec<[R; D]>>> = vec![None; threads];
+
+	{
+		let sr: &Vec<Star<R>> = s;
+		let mut add = |ax: &Vec<[R; D]>| {
+			for si in 0..n {
+				for i in 0..D {
+					if p.compensated {
+						sum::neumaier_add(&mut total[si][i], &mut carry[si][i], ax[si][i]);
+					} else {
+						total[si][i] += ax[si][i];
+					}
+				}
+			}
+		};
+		let kernel = |adiff: &mut Vec<[R; D]>, si: usize, sj: usize, rij: &[R; D], r2: R| {
+			if p.mixed {
+				// Pair term in f32, accumulated in R
+				let mut x = [0f32; D];
+				let mut r2 = p.pair_eps2(eps[si], eps[sj]).to_f64() as f32;
+				for i in 0..D {
+					x[i] = rij[i].to_f64() as f32;
+					r2 += x[i]*x[i];
+				}
+				let apre: f32 = 1.0/(r2*r2.sqrt());
+				for i in 0..D {
+					let f = R::from_f64((apre*x[i]) as f64);
+					adiff[si][i] -= sr[sj].m*f;
+					adiff[sj][i] += sr[si].m*f;
+				}
+				return;
+			}
+			let r_dot_r: R = (r2 + p.pair_eps2(eps[si], eps[sj])).sqrt();
+			let apre: R = R::one()/(r_dot_r.powi(3));
+			for i in 0..D {
+				adiff[si][i] -= sr[sj].m*apre*rij[i];
+				adiff[sj][i] += sr[si].m*apre*rij[i];
+			}
+		};
+		if threads == 1 {
+			let clock = Clock::start();
+			let mut adiff = vec![[R::zero(); D]; n];
+			pairs::for_each_pair_dim(sr, |si, sj, rij, r2| kernel(&mut adiff, si, sj, rij, r2));
+			if AUDIT {
+				audit(sr, &adiff, precision, "the serial pair loop");
+			}
+			add(&adiff);
+			busy[0] = clock.seconds();
+		} else {
+			let bounds = pairs::partition(n, threads);
+			pairs::par_for_each_pair_dim(sr, threads, vec![[R::zero(); D]; n], kernel, |thread_index, ax, seconds| {
+				if AUDIT {
+					audit(sr, &ax, precision, &format!("thread {} (rows {}..{})", thread_index, bounds[thread_index], bounds[thread_index + 1]));
+				}
+				busy[thread_index] = seconds;
+				if p.deterministic {
+					parked[thread_index] = Some(ax);
+				} else {
+					add(&ax);
+				}
+			});
+			for ax in parked.iter().flatten() {
+				add(ax);
+			}
+		}
+	}
+
+	for si in 0..n {
+		for i in 0..D {
+			s[si].a[i] = total[si][i];
+			if p.compensated {
+				s[si].a[i] += carry[si][i];
+			}
+		}
+	}
+	busy
+}
+
+pub fn update_positions<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>) {
+	by_dim!(p.dim, drift(s, p))
+}
+
+fn drift<R: Real, const D: usize>(s: &mut Vec<Star<R>>, p: &Params<R>) {
+	let half: R = c(0.5);
+	for star in s {
+		for i in 0..D {
+			star.a0[i] = star.a[i];
+			star.r[i] += p.dt*star.v[i] + half*p.dt*p.dt*star.a0[i];
+		}
+	}
+}
+
+pub fn update_velocities<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>) {
+	by_dim!(p.dim, kick(s, p))
+}
+
+fn kick<R: Real, const D: usize>(s: &mut Vec<Star<R>>, p: &Params<R>) {
+	let half: R = c(0.5);
+	for star in s {
+		for i in 0..D {
+			star.v[i] += half*p.dt*(star.a0[i] + star.a[i]);
+			star.a0[i] = star.a[i];
+		}
+	}
+}
+
+/*
+ [E, T, W]. The pair sum for W runs on p.threads threads with the row split
+ of the force loop; the partial sums are added in thread order, so the result
+ only depends on the thread count, not on scheduling.
+ */
+pub fn energies<R: Real>(tos: &Vec<Star<R>>, p: &Params<R>) -> Vec<R> {
+	by_dim!(p.dim, pair_energies(tos, p))
+}
+
+fn pair_energies<R: Real, const D: usize>(tos: &Vec<Star<R>>, p: &Params<R>) -> Vec<R> {
+	let ref s = *tos;
+	let mut e: Vec<R> = vec![R::zero(); 3];
+	let eps = p.star_eps(s);
+	let mut kinetic = sum::Neumaier::default();
+	let mut potential = sum::Neumaier::default();
+	let threads = p.threads.max(1);
+
+	//Kinetic energy
+	for star in s {
+		let ek = c::<R>(0.5)*star.m*star.v[..D].iter().fold(R::zero(), |x, &v| x + v.powi(2));
+		if p.compensated {
+			kinetic.add(ek);
+		} else {
+			e[1] += ek;
+		}
+	}
+
+	// Plain and compensated partial sums of every thread
+	let mut parts: Vec<(R, sum::Neumaier<R>)> = vec![(R::zero(), sum::Neumaier::default()); threads];
+	let visit = |part: &mut (R, sum::Neumaier<R>), si: usize, sj: usize, _: &[R; D], r2: R| {
+		let rij = (r2 + p.pair_eps2(eps[si], eps[sj])).sqrt();
+		if p.compensated {
+			part.1.add(-s[si].m*s[sj].m/rij);
+		} else {
+			part.0 -= s[si].m*s[sj].m/rij;
+		}
+	};
+	pairs::par_for_each_pair_dim(s, threads, (R::zero(), sum::Neumaier::default()), visit, |thread_index, part, _| parts[thread_index] = part);
+	for part in parts {
+		e[2] += part.0;
+		potential.add(part.1.value());
+	}
+	if p.compensated {
+		e[1] = kinetic.value();
+		e[2] = potential.value();
+	}
+	e[0] = e[1] + e[2];
+	return e;
+}
+
+/*
+ Parses the plain text input format: one star per line, "id m x y z vx vy vz".
+ Lines starting with # are comments. Panics on bad input, see parse_stars().
+ */
+pub fn read_stars<R: Real>(text: &str) -> Vec<Star<R>> {
+	parse_stars(text).unwrap_or_else(|x| panic!("Invalid input: {}", x))
+}
+
+/*
+ Same as read_stars() but reports the first bad line, and where in it,
+ instead of panicking: too few columns, something that is not a number, a
+ non-finite value or a negative mass. Columns after the eighth are left to
+ columns::Columns.
+ */
+pub fn parse_stars<R: Real>(text: &str) -> Result<Vec<Star<R>>, NBodyError> {
+	let mut s: Vec<Star<R>> = vec![];
+
+	for (number, line) in text.split("\n").enumerate() {
+		if line.trim() == "" || line.starts_with('#') {
+			continue;
+		}
+		let mut arr: Vec<R> = Vec::with_capacity(8);
+		let mut columns: Vec<usize> = Vec::with_capacity(8);
+		for num in line.split_whitespace().take(8) {
+			// Where the word starts, counting characters from 1
+			let column = line[..num.as_ptr() as usize - line.as_ptr() as usize].chars().count() + 1;
+			let x: R = num.parse().map_err(|_| NBodyError::parse(number + 1, column, format!("'{}' is not a number", num)))?;
+			if !x.is_finite() {
+				return Err(NBodyError::parse(number + 1, column, format!("'{}' is not finite", num)));
+			}
+			arr.push(x);
+			columns.push(column);
+		}
+		if arr.len() < 8 {
+			let end = line.trim_end().chars().count() + 1;
+			return Err(NBodyError::parse(number + 1, end, format!("expected 8 columns (id m x y z vx vy vz), found {}", arr.len())));
+		}
+		if arr[1] < R::zero() {
+			return Err(NBodyError::parse(number + 1, columns[1], format!("negative mass {}", arr[1])));
+		}
+		s.push(Star { m: arr[1], r: arr[2..5].to_vec(), v: arr[5..8].to_vec(), a: vec![R::zero(); 3], a0: vec![R::zero(); 3] });
+	}
+
+	Ok(s)
+}
+
+/*
+ Writes stars in the input format, numbering them from 0 in the id column.
+ */
+pub fn write_stars<R: Real, W: Write>(w: &mut W, s: &Vec<Star<R>>) -> io::Result<()> {
+	write_stars_with(w, s, &columns::Columns::default())
+}
+
+// Same, with the ids and extra columns of extra, and their header line first
+pub fn write_stars_with<R: Real, W: Write>(w: &mut W, s: &Vec<Star<R>>, extra: &columns::Columns) -> io::Result<()> {
+	if !extra.is_empty() {
+		writeln!(w, "{}", extra.header())?;
+	}
+	for (idx, star) in s.iter().enumerate() {
+		write!(w, "{} {:e} {:e} {:e} {:e} {:e} {:e} {:e}", extra.id(idx), star.m,
+			star.r[0], star.r[1], star.r[2], star.v[0], star.v[1], star.v[2])?;
+		for x in extra.row(idx) {
+			write!(w, " {}", x)?;
+		}
+		writeln!(w)?;
+	}
+	Ok(())
+}
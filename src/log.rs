@@ -0,0 +1,157 @@
+/*
+ Run log. Every message has a level and goes to the console when the level
+ is within the verbosity (-q: errors and warnings, default: info, -v:
+ verbose, -vv: debug) and, once open() was called, to a log file as well.
+ Errors and warnings are written to stderr, the rest to stdout.
+
+ Progress lines (the periodic energy report) are info messages that are
+ left off the console when stdout is not a terminal, so piping a run into a
+ file or another program does not fill it with them; the log file still has
+ them.
+
+While a progress bar (progress.rs) is on the terminal, console messages
+clear its line first.
+
+ Use the macros: error!, warn!, info!, verbose!, debug! and progress!, all
+ with format! arguments.
+ */
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum Level {
+	Error,
+	Warn,
+	Info,
+	Verbose,
+	Debug,
+}
+
+static VERBOSITY: AtomicUsize = AtomicUsize::new(Level::Info as usize);
+// Progress bar: 0 none, 1 on the terminal, 2 cleared by a message
+static BAR: AtomicUsize = AtomicUsize::new(0);
+static FILE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+impl Level {
+	fn from_usize(x: usize) -> Level {
+		match x {
+			0 => Level::Error,
+			1 => Level::Warn,
+			2 => Level::Info,
+			3 => Level::Verbose,
+			_ => Level::Debug,
+		}
+	}
+
+	fn prefix(self) -> &'static str {
+		match self {
+			Level::Error => "Error: ",
+			Level::Warn => "Warning: ",
+			_ => "",
+		}
+	}
+}
+
+pub fn set_verbosity(level: Level) {
+	VERBOSITY.store(level as usize, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Level {
+	Level::from_usize(VERBOSITY.load(Ordering::Relaxed))
+}
+
+pub fn enabled(level: Level) -> bool {
+	level <= verbosity()
+}
+
+// Also writes the log to path from now on, replacing any earlier file
+pub fn open(path: &Path) -> io::Result<()> {
+	let f = BufWriter::new(File::create(path)?);
+	*FILE.lock().expect("Log file lock poisoned") = Some(f);
+	Ok(())
+}
+
+// Flushes and closes the log file
+pub fn close() {
+	if let Some(mut f) = FILE.lock().expect("Log file lock poisoned").take() {
+		let _ = f.flush();
+	}
+}
+
+// Whether a progress bar is on the terminal, see progress.rs
+pub fn set_bar(shown: bool) {
+	BAR.store(shown as usize, Ordering::Relaxed);
+}
+
+// Whether a message cleared the progress bar since it was last drawn
+pub fn bar_cleared() -> bool {
+	BAR.load(Ordering::Relaxed) == 2
+}
+
+// What a console line starts with to clear the progress bar first
+fn clear_bar() -> &'static str {
+	if BAR.compare_exchange(1, 2, Ordering::Relaxed, Ordering::Relaxed).is_ok() { "\r\x1b[K" } else { "" }
+}
+
+fn to_file(msg: &str) {
+	if let Some(ref mut f) = *FILE.lock().expect("Log file lock poisoned") {
+		let _ = writeln!(f, "{}", msg);
+	}
+}
+
+pub fn log(level: Level, msg: &str) {
+	if !enabled(level) {
+		return;
+	}
+	let line = format!("{}{}", level.prefix(), msg);
+	if level <= Level::Warn {
+		eprintln!("{}{}", clear_bar(), line);
+	} else {
+		println!("{}{}", clear_bar(), line);
+	}
+	to_file(&line);
+}
+
+pub fn progress(msg: &str) {
+	if !enabled(Level::Info) {
+		return;
+	}
+	if io::stdout().is_terminal() {
+		println!("{}{}", clear_bar(), msg);
+	}
+	to_file(msg);
+}
+
+#[macro_export]
+macro_rules! error {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Error, &format!($($arg)*)))
+}
+
+#[macro_export]
+macro_rules! warn {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Warn, &format!($($arg)*)))
+}
+
+#[macro_export]
+macro_rules! info {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Info, &format!($($arg)*)))
+}
+
+#[macro_export]
+macro_rules! verbose {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Verbose, &format!($($arg)*)))
+}
+
+#[macro_export]
+macro_rules! debug {
+	($($arg:tt)*) => ($crate::log::log($crate::log::Level::Debug, &format!($($arg)*)))
+}
+
+#[macro_export]
+macro_rules! progress {
+	($($arg:tt)*) => ($crate::log::progress(&format!($($arg)*)))
+}
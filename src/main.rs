@@ -2,184 +2,1492 @@
  Written by Joris Dalderup <joris@jorisdalderup>
  Compile with "cargo build --release"
  */
+extern crate nbabel;
+
+use std::env;
+use std::fs;
 use std::io;
 use std::io::Read;
-use std::thread;
-use std::sync::mpsc;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+use std::time::Instant;
+
+use nbabel::*;
+use nbabel::dd::DoubleDouble;
+use nbabel::status::{Outcome, Status};
+use nbabel::timing::Phase;
 
-static DT: f64 = 1e-3;
 /*
- How to choose a good thread count you ask? Well, how many virtual cores do(es)
- you CPU(s) have? Multiply it by 1 to 2, and you have it. If your CPU hyperthreads
- I would stay on the low end of that, if it doesn't you can go up to two.
- Also see what works best for your situation.
- Fair warning: having your processor at high use for long periods of time can
- damage it.
-
- Make sure that your  input file line count is devisable by THREAD_COUNT.
+ Everything the command line can set. Numbers are kept in f64 here and
+ converted once the precision of the run is known.
  */
-static THREAD_COUNT: usize = 8;
-
-struct Star {
-	m: f64,
-	r: Vec<f64>,
-	v: Vec<f64>,
-	a: Vec<f64>,
-	a0: Vec<f64>,
+struct Options {
+	// The arguments as given, for the metadata of the outputs
+	argv: Vec<String>,
+	tend: f64,
+	out_dir: Option<String>,
+	virialize: Option<f64>,
+	cadence: cadence::Cadence,
+	p: Params<f64>,
+	dt_given: bool,
+	rule_given: bool,
+	// Wall-clock seconds after which the run checkpoints and stops
+	walltime: Option<f64>,
+	// |dE/E0| above which the run checkpoints and fails
+	max_de: Option<f64>,
+	// Measure diagnostics in the background while integrating, one sample behind
+	overlap: bool,
+	// Show a progress bar on a terminal
+	progress: bool,
+	// Report the timing per phase at every diagnostic
+	timing: bool,
+	// Show the stars live in a window
+	viz: bool,
+	// svg or png for the end-of-run plots, if given
+	plot_format: Option<String>,
+	// Stream the run over WebSocket at this ws:// address, at most serve_rate messages a second
+	serve: Option<String>,
+	serve_rate: f64,
+	// HOST:PORT of the HTTP control API
+	http: Option<String>,
+	// Format of the input on stdin, detected when None; and the input as given when it was converted
+	input_format: Option<convert::Format>,
+	given_input: Option<String>,
+	// HOST:PORT of the Prometheus metrics endpoint
+	metrics: Option<String>,
+	// Stop on any problem validate finds in the input
+	strict: bool,
+	// Keep a binary catalog in the output directory
+	binaries: bool,
+	// Write ParaView snapshots at every diagnostic
+	vtk: bool,
+	// Write the snapshots and diagnostics as Parquet too
+	parquet: bool,
+	// SQLite database collecting the diagnostics of runs, and this run's key in it
+	sqlite: Option<String>,
+	run_id: Option<String>,
+	// Neighbour count for a density array in the VTK snapshots
+	density: Option<usize>,
+	// Specific kinetic and total energy of every star in the VTK snapshots
+	star_energies: bool,
+	// Acceleration and jerk of every star in the VTK and Parquet snapshots
+	accelerations: bool,
+	// Star indices "0,5,17" whose trajectories are written every step
+	track: Option<String>,
+	// Track the energy incrementally, resyncing every this many steps
+	incremental_energy: Option<usize>,
+	// Tune the thread count and tile size even when cached, or never (given by hand)
+	autotune: bool,
+	no_autotune: bool,
+	// direct, tree or auto, and the tree opening angle, opening criterion and multipole order
+	solver: String,
+	theta: f64,
+	opening: tree::Opening,
+	quadrupole: bool,
+	// Refresh the tree instead of rebuilding it until a star drifts this many leaf sides
+	tree_reuse: Option<f64>,
+	// Interaction cutoff and list skin of --solver verlet, see verlet.rs
+	cutoff: Option<f64>,
+	skin: Option<f64>,
+	// Side of the periodic box, see ewald.rs, and the mesh of --solver pm, see pm.rs
+	periodic: Option<f64>,
+	grid: Option<usize>,
+	pp: bool,
+	// Comoving coordinates in an expanding periodic box, see cosmology.rs
+	comoving: Option<cosmology::Cosmology>,
+	// 3, or 2 to keep the stars in the z = 0 plane, see planar.rs
+	dim: usize,
+	// Every this many steps, check the forces of this many random stars against direct summation
+	verify_forces: Option<(usize, usize)>,
+	// local, or mpi to spread the direct solver over MPI ranks, and the rank of this process
+	backend: String,
+	rank: usize,
+	// Rescale the input to N-body units first
+	normalize: bool,
+	// Physical units of the input
+	units: Option<units::Units>,
+	// Analytic potentials added to the N-body forces
+	external: Vec<Box<dyn external::ExternalPotential>>,
+	// Galactic tidal field, in the frame rotating with the orbit
+	tidal: Option<tidal::Tidal>,
+	// Star index or "heaviest" for an unsoftened central object, and its substeps
+	central: Option<String>,
+	central_substeps: usize,
+	// Separation below which the closest bound pair is regularized
+	regularize: Option<f64>,
+	// ETA of the adaptive step size, time-symmetrized or not; --dt becomes the longest step
+	adaptive_dt: Option<f64>,
+	time_symmetric: bool,
+	// What touching stars do, the restitution of bounces and the default radius
+	collisions: collisions::Mode,
+	restitution: f64,
+	radius: f64,
+	// Post-Newtonian order (1 or 2.5) and the speed of light in internal units
+	pn: Option<f64>,
+	pn_c: Option<f64>,
+	// Linear drag or dynamical friction
+	drag: Option<drag::Drag>,
+	// Stellar mass loss model
+	mass_loss: Option<Box<dyn massloss::MassLoss>>,
 }
 
-//Black magic
-impl Clone for Star {
-    fn clone(&self) -> Self {
-        Star {
-            m: self.m.clone(),
-			r: self.r.clone(),
-			v: self.v.clone(),
-			a: self.a.clone(),
-			a0: self.a0.clone(),
-        }
-    }
+// Parses the value following a flag
+fn value<T: FromStr>(args: &mut std::vec::IntoIter<String>, flag: &str, what: &str) -> Result<T, String> {
+	args.next().and_then(|x| x.parse().ok()).ok_or(format!("{} needs {}", flag, what))
 }
 
-fn acceleration(s: &mut Vec<Star>) {
-	for si in 0..s.len() {
-		s[si].a = vec![0.0; 3];
-	}
+// Sets the verbosity from -q, -v and -vv and returns the other arguments
+fn take_verbosity(argv: Vec<String>) -> Vec<String> {
+	argv.into_iter().filter(|x| {
+		match x.as_str() {
+			"-q" => log::set_verbosity(log::Level::Warn),
+			"-v" => log::set_verbosity(if log::verbosity() >= log::Level::Verbose { log::Level::Debug } else { log::Level::Verbose }),
+			"-vv" => log::set_verbosity(log::Level::Debug),
+			_ => return true,
+		}
+		false
+	}).collect()
+}
 
-	let mut handles = vec![];
-    let (tx, rx): (mpsc::Sender<Vec<Vec<f64>>>, mpsc::Receiver<Vec<Vec<f64>>>) = mpsc::channel();
+/*
+ The subcommands besides run, each with its own flags. Their results go to
+ stdout and the log around them, so they chain in pipes.
+ */
+static SUBCOMMANDS: [(&'static str, fn(&[String]), &'static str); 11] = [
+	("generate", generate::main, "write initial conditions (King, uniform, binaries, the Solar System)"),
+	("analyze", analyze::main, "summarize snapshot files as CSV"),
+	("bench", bench::main, "time the solvers over sizes and thread counts and recommend settings"),
+	("convert", convert::main, "translate snapshots between text, CSV, JSON, Gadget, Tipsy and HDF5"),
+	("compose", compose::main, "merge particle files into one input"),
+	("normalize", normalize::main, "rescale an input to N-body units"),
+	("transform", transform::main, "rotate, shift, boost or rescale an input"),
+	("fit", fit::main, "tune an initial model to match target Lagrangian radii"),
+	("report", report::main, "compare the diagnostics of runs in an HTML report"),
+	("repl", repl::main, "load a system and poke at it interactively"),
+	("help", help, "list the subcommands"),
+];
 
-	for thread_index in 0..THREAD_COUNT {
-		let tx = tx.clone();
-		let sc = s.clone();
-		handles.push(thread::spawn(move || {
-			let thread_start = sc.len() / THREAD_COUNT * thread_index;
-			let thread_end = sc.len() / THREAD_COUNT * (thread_index + 1);
-			let mut adiff: Vec<Vec<f64>> = vec![vec![0.0; 3]; sc.len()];
-			for si in thread_start..thread_end {
-				let mut rij: Vec<f64> = vec![0.0; 3];
-				for sj in (si + 1)..sc.len() {
-					for i in 0..3 {
-						rij[i] = sc[si].r[i] - sc[sj].r[i];
-					}
+fn help(_: &[String]) {
+	println!("Usage: nbabel [run] [FLAGS] < input, or nbabel SUBCOMMAND [ARGS]\n");
+	println!("  {:<10} integrate the stars on stdin (the default; see README.md for the flags)", "run");
+	for &(name, _, what) in SUBCOMMANDS.iter() {
+		println!("  {:<10} {}", name, what);
+	}
+}
 
-					let r_dot_r: f64 = (rij[0]*rij[0] + rij[1]*rij[1] + rij[2]*rij[2]).sqrt();
-					let apre: f64 = 1.0/(r_dot_r.powi(3));
-					for i in 1..3 {
-						adiff[si][i] -= sc[sj].m*apre*rij[i];
-						adiff[sj][i] += sc[si].m*apre*rij[i];
-					}
-				}
+fn main() {
+	let mut argv: Vec<String> = env::args().skip(1).collect();
+	match argv.first().map(|x| x.as_str()) {
+		Some("run") => {
+			argv.remove(0);
+		},
+		Some("--help") | Some("-h") => {
+			help(&[]);
+			return;
+		},
+		Some(name) => {
+			if let Some(&(_, subcommand, _)) = SUBCOMMANDS.iter().find(|x| x.0 == name) {
+				// Subcommands take the verbosity flags anywhere
+				let argv = take_verbosity(argv);
+				subcommand(&argv[1..]);
+				return;
 			}
-			tx.send(adiff.clone()).expect("Thread failure, RIP");
-		}));
+		},
+		None => {},
 	}
 
-	for _ in 0..THREAD_COUNT {
-        let ax = rx.recv().expect("RIP");
-		for si in 0..s.len() {
-			for i in 0..3 {
-				s[si].a[i] += ax[si][i];
+	let clock = Instant::now();
+	interrupt::install();
+	let (opts, precision) = match parse(argv) {
+		Ok(x) => x,
+		Err(msg) => {
+			error!("{}", msg);
+			process::exit(status::EXIT_CONFIG);
+		},
+	};
+	#[cfg(feature = "mpi")]
+	let mut opts = opts;
+	#[cfg(feature = "mpi")]
+	{
+		if opts.backend == "mpi" {
+			if let Err(x) = distributed::init() {
+				error!("{}", x);
+				process::exit(status::EXIT_CONFIG);
+			}
+			// Rank 0 speaks for the run; the others only compute
+			opts.rank = distributed::rank();
+			if opts.rank > 0 {
+				log::set_verbosity(log::Level::Error);
+				opts.out_dir = None;
+				opts.serve = None;
+				opts.http = None;
+				opts.metrics = None;
+				opts.viz = false;
+				opts.progress = false;
 			}
 		}
-    }
-}
+	}
+	let out_dir = opts.out_dir.clone();
 
-fn update_positions(s: &mut Vec<Star>) {
-	for star in s {
-		for i in 1..3 {
-			star.a0[i] = star.a[i];
-			star.r[i] += DT*star.v[i] + 0.5*DT*DT*star.a0[i];
+	let mut status = start(opts, &precision);
+	status.wall_seconds = clock.elapsed().as_secs_f64();
+	match status.outcome {
+		Outcome::ConfigError(ref msg) | Outcome::InputError(ref msg) | Outcome::IoError(ref msg) => error!("{}", msg),
+		_ => {},
+	}
+	if let Some(dir) = out_dir {
+		if let Err(x) = status.write(&Path::new(&dir).join("status.json")) {
+			error!("Could not write status.json: {}", x);
 		}
 	}
+	log::close();
+	#[cfg(feature = "mpi")]
+	distributed::finalize();
+	process::exit(status.outcome.exit_code());
 }
 
-fn update_velocities(s: &mut Vec<Star>) {
-	for star in s {
-		for i in 1..3 {
-			star.v[i] += 0.5*DT*(star.a0[i] + star.a[i]);
-			star.a0[i] = star.a[i];
+fn parse(argv: Vec<String>) -> Result<(Options, String), String> {
+	let mut precision = String::from(Float::name());
+	let mut opts = Options {
+		argv: argv.clone(),
+		tend: 1.0,
+		out_dir: None,
+		virialize: None,
+		cadence: cadence::Cadence::fixed(10),
+		p: Params::default(),
+		dt_given: false,
+		rule_given: false,
+		walltime: None,
+		max_de: None,
+		overlap: false,
+		progress: true,
+		timing: false,
+		viz: false,
+		plot_format: None,
+		serve: None,
+		serve_rate: 10.0,
+		http: None,
+		metrics: None,
+		input_format: None,
+		given_input: None,
+		strict: false,
+		binaries: false,
+		vtk: false,
+		parquet: false,
+		sqlite: None,
+		run_id: None,
+		density: None,
+		star_energies: false,
+		accelerations: false,
+		track: None,
+		incremental_energy: None,
+		autotune: false,
+		no_autotune: false,
+		solver: String::from("direct"),
+		backend: String::from("local"),
+		rank: 0,
+		theta: solver::THETA,
+		opening: tree::Opening::Geometric,
+		quadrupole: false,
+		tree_reuse: None,
+		cutoff: None,
+		skin: None,
+		periodic: None,
+		grid: None,
+		comoving: None,
+		dim: 3,
+		pp: false,
+		verify_forces: None,
+		normalize: false,
+		units: None,
+		external: vec![],
+		tidal: None,
+		central: None,
+		central_substeps: 1,
+		regularize: None,
+		adaptive_dt: None,
+		time_symmetric: false,
+		collisions: collisions::Mode::Off,
+		restitution: 1.0,
+		radius: 0.0,
+		pn: None,
+		pn_c: None,
+		drag: None,
+		mass_loss: None,
+	};
+	let mut units_spec: Option<String> = None;
+	let mut interval: Option<usize> = None;
+	let mut log_every: Option<f64> = None;
+	let mut constants = constants::Constants::default();
+
+	let mut args = argv.into_iter();
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--out" => opts.out_dir = Some(value(&mut args, "--out", "a directory")?),
+			"--diagnostic-interval" => {
+				let n: usize = value(&mut args, "--diagnostic-interval", "a number of steps")?;
+				if n == 0 {
+					return Err(String::from("--diagnostic-interval needs at least 1 step"));
+				}
+				interval = Some(n);
+			},
+			"--log-every" => {
+				let x: f64 = value(&mut args, "--log-every", "a simulation time")?;
+				if !(x > 0.0) {
+					return Err(format!("--log-every needs a positive time, got {}", x));
+				}
+				log_every = Some(x);
+			},
+			"--adaptive" => {
+				let spec: String = value(&mut args, "--adaptive", "a trigger list")?;
+				opts.cadence = cadence::Cadence::parse(&spec)?;
+			},
+			"--dt" => {
+				opts.p.dt = value(&mut args, "--dt", "a step size")?;
+				opts.dt_given = true;
+			},
+			"--adaptive-dt" => {
+				let eta: f64 = value(&mut args, "--adaptive-dt", "a fraction of the shortest timescale")?;
+				if !(eta > 0.0) {
+					return Err(format!("--adaptive-dt needs a positive fraction, got {}", eta));
+				}
+				opts.adaptive_dt = Some(eta);
+			},
+			"--time-symmetric" => opts.time_symmetric = true,
+			"--softening" => opts.p.eps = value(&mut args, "--softening", "a length")?,
+			"--softening-rule" => {
+				let rule: String = value(&mut args, "--softening-rule", "fixed, mean or min")?;
+				opts.p.softening = Softening::parse(&rule).ok_or(format!("Unknown softening rule '{}', use fixed, mean or min", rule))?;
+				opts.rule_given = true;
+			},
+			"--compensated" => opts.p.compensated = true,
+			"--deterministic" => opts.p.deterministic = true,
+			"--serial" => {
+				opts.p.threads = 1;
+				opts.no_autotune = true;
+			},
+			"--threads" => {
+				opts.p.threads = value(&mut args, "--threads", "a thread count")?;
+				opts.no_autotune = true;
+				if opts.p.threads == 0 {
+					return Err(String::from("--threads needs at least 1 thread"));
+				}
+			},
+			"--overlap" => opts.overlap = true,
+			"--simd" => opts.p.simd = Some(simd::Isa::detect()),
+			"--gpu" => opts.p.gpu = true,
+			"--hybrid" => {
+				opts.p.gpu = true;
+				opts.p.hybrid = true;
+			},
+			"--binaries" => opts.binaries = true,
+			"--vtk" => opts.vtk = true,
+			"--parquet" => opts.parquet = true,
+			"--sqlite" => opts.sqlite = Some(value(&mut args, "--sqlite", "a database file")?),
+			"--run-id" => opts.run_id = Some(value(&mut args, "--run-id", "a run name")?),
+			"--density" => {
+				let k: usize = value(&mut args, "--density", "a neighbour count")?;
+				if k < 2 {
+					return Err(format!("--density needs at least 2 neighbours, got {}", k));
+				}
+				opts.density = Some(k);
+			},
+			"--star-energies" => opts.star_energies = true,
+			"--accelerations" => opts.accelerations = true,
+			"--track" => opts.track = Some(value(&mut args, "--track", "star indices I,J,...")?),
+			"--solver" => {
+				opts.solver = value(&mut args, "--solver", "direct, tree, auto, pm or verlet")?;
+				if opts.solver != "pm" && opts.solver != "verlet" && solver::by_name::<f64>(&opts.solver, opts.theta).is_none() {
+					return Err(format!("Unknown solver '{}', use direct, tree, auto, pm or verlet", opts.solver));
+				}
+			},
+			"--backend" => {
+				opts.backend = value(&mut args, "--backend", "local or mpi")?;
+				if opts.backend != "local" && opts.backend != "mpi" {
+					return Err(format!("Unknown backend '{}', use local or mpi", opts.backend));
+				}
+			},
+			"--incremental-energy" => opts.incremental_energy = Some(value(&mut args, "--incremental-energy", "a resync interval in steps")?),
+			"--theta" => opts.theta = value(&mut args, "--theta", "an opening angle")?,
+			"--opening" => {
+				let name: String = value(&mut args, "--opening", "bh or bmax")?;
+				opts.opening = tree::Opening::parse(&name).ok_or(format!("Unknown opening criterion '{}', use bh or bmax", name))?;
+			},
+			"--cutoff" => {
+				let rc: f64 = value(&mut args, "--cutoff", "an interaction cutoff")?;
+				if !(rc > 0.0) {
+					return Err(format!("--cutoff needs a positive length, got {}", rc));
+				}
+				opts.cutoff = Some(rc);
+			},
+			"--skin" => {
+				let skin: f64 = value(&mut args, "--skin", "a neighbour list skin")?;
+				if !(skin > 0.0) {
+					return Err(format!("--skin needs a positive length, got {}", skin));
+				}
+				opts.skin = Some(skin);
+			},
+			"--tree-reuse" => {
+				let drift: f64 = value(&mut args, "--tree-reuse", "a drift in leaf sides")?;
+				if !(drift > 0.0) {
+					return Err(format!("--tree-reuse needs a positive drift, got {}", drift));
+				}
+				opts.tree_reuse = Some(drift);
+			},
+			"--multipole" => {
+				let order: String = value(&mut args, "--multipole", "monopole or quadrupole")?;
+				opts.quadrupole = match order.as_str() {
+					"monopole" => false,
+					"quadrupole" => true,
+					_ => return Err(format!("Unknown multipole order '{}', use monopole or quadrupole", order)),
+				};
+			},
+			"--periodic" => {
+				let l: f64 = value(&mut args, "--periodic", "a box size")?;
+				if !(l > 0.0) {
+					return Err(format!("--periodic needs a positive box size, got {}", l));
+				}
+				opts.periodic = Some(l);
+			},
+			"--grid" => {
+				let g: usize = value(&mut args, "--grid", "a number of cells")?;
+				if !g.is_power_of_two() || g < 8 {
+					return Err(format!("--grid needs a power of two of at least 8 cells, got {}", g));
+				}
+				opts.grid = Some(g);
+			},
+			"--pp" => opts.pp = true,
+			"--comoving" => {
+				let spec: String = value(&mut args, "--comoving", "a cosmology omega_m=OM,omega_lambda=OL[,h0=H][,z=Z]")?;
+				opts.comoving = Some(cosmology::Cosmology::parse(&spec)?);
+			},
+			"--dim" => {
+				opts.dim = value(&mut args, "--dim", "2 or 3")?;
+				if opts.dim != 2 && opts.dim != 3 {
+					return Err(format!("--dim must be 2 or 3, got {}", opts.dim));
+				}
+			},
+			"--verify-forces" => {
+				let spec: String = value(&mut args, "--verify-forces", "a step interval M or M,K")?;
+				let mut parts = spec.splitn(2, ',').map(|x| x.trim().parse::<usize>());
+				let every = parts.next().and_then(|x| x.ok()).filter(|&x| x > 0);
+				let sample = parts.next().map_or(Some(solver::VERIFY_SAMPLE), |x| x.ok().filter(|&x| x > 0));
+				opts.verify_forces = match (every, sample) {
+					(Some(m), Some(k)) => Some((m, k)),
+					_ => return Err(format!("--verify-forces needs a step interval M or M,K with K stars, got '{}'", spec)),
+				};
+			},
+			"--tile" => {
+				pairs::set_tile_size(value(&mut args, "--tile", "a number of stars")?);
+				opts.no_autotune = true;
+			},
+			"--autotune" => opts.autotune = true,
+			"--no-autotune" => opts.no_autotune = true,
+			"--precision" => precision = value(&mut args, "--precision", "f32, f64, mixed or dd")?,
+			"--normalize" => opts.normalize = true,
+			"--external" => {
+				let spec: String = value(&mut args, "--external", "KIND:NAME=VALUE,...")?;
+				opts.external.push(external::parse(&spec)?);
+			},
+			"--tidal" => {
+				let spec: String = value(&mut args, "--tidal", "omega=W[,kappa=K][,nu=N]")?;
+				opts.tidal = Some(tidal::Tidal::parse(&spec)?);
+			},
+			"--central" => opts.central = Some(value(&mut args, "--central", "a star index or heaviest")?),
+			"--central-substeps" => {
+				opts.central_substeps = value(&mut args, "--central-substeps", "a number of substeps")?;
+				if opts.central_substeps == 0 {
+					return Err(String::from("--central-substeps needs at least 1 substep"));
+				}
+			},
+			"--regularize" => {
+				let radius: f64 = value(&mut args, "--regularize", "a separation")?;
+				if !(radius > 0.0) {
+					return Err(format!("--regularize needs a positive separation, got {}", radius));
+				}
+				opts.regularize = Some(radius);
+			},
+			"--collisions" => {
+				let mode: String = value(&mut args, "--collisions", "merge, bounce or off")?;
+				opts.collisions = collisions::Mode::parse(&mode).ok_or(format!("Unknown collision mode '{}', use merge, bounce or off", mode))?;
+			},
+			"--restitution" => {
+				opts.restitution = value(&mut args, "--restitution", "a coefficient between 0 and 1")?;
+				if !(opts.restitution >= 0.0 && opts.restitution <= 1.0) {
+					return Err(format!("--restitution must be between 0 and 1, got {}", opts.restitution));
+				}
+			},
+			"--radius" => opts.radius = value(&mut args, "--radius", "a stellar radius")?,
+			"--pn" => {
+				let order: f64 = value(&mut args, "--pn", "an order, 1 or 2.5")?;
+				if order != 1.0 && order != 2.5 {
+					return Err(format!("--pn needs order 1 or 2.5, got {}", order));
+				}
+				opts.pn = Some(order);
+			},
+			"--pn-c" => opts.pn_c = Some(value(&mut args, "--pn-c", "the speed of light")?),
+			"--drag" => {
+				let k: f64 = value(&mut args, "--drag", "a drag coefficient")?;
+				if !(k > 0.0) {
+					return Err(format!("--drag needs a positive coefficient, got {}", k));
+				}
+				opts.drag = Some(drag::Drag::Linear(k));
+			},
+			"--friction" => {
+				let spec: String = value(&mut args, "--friction", "rho=RHO,sigma=SIGMA[,lnL=L]")?;
+				opts.drag = Some(drag::Drag::friction(&spec)?);
+			},
+			"--mass-loss" => {
+				let spec: String = value(&mut args, "--mass-loss", "wind:tau=T[,floor=F] or table:PATH")?;
+				opts.mass_loss = Some(massloss::parse(&spec)?);
+			},
+			"--units" => units_spec = Some(value(&mut args, "--units", "MASS,LENGTH,VELOCITY")?),
+			"--constants" => {
+				let spec: String = value(&mut args, "--constants", "a preset or NAME=VALUE list")?;
+				constants = constants::Constants::parse(&spec)?;
+			},
+			"--virialize" => {
+				let q: f64 = value(&mut args, "--virialize", "a ratio Q")?;
+				if !(q >= 0.0) {
+					return Err(format!("--virialize needs a non-negative ratio, got {}", q));
+				}
+				opts.virialize = Some(q);
+			},
+			"--no-progress" => opts.progress = false,
+			"--strict" => opts.strict = true,
+			"--timing" => opts.timing = true,
+			"--viz" => opts.viz = true,
+			"--serve" => opts.serve = Some(value(&mut args, "--serve", "an address ws://HOST:PORT")?),
+			"--serve-rate" => opts.serve_rate = value(&mut args, "--serve-rate", "messages per second")?,
+			"--http" => opts.http = Some(value(&mut args, "--http", "an address HOST:PORT")?),
+			"--input-format" => {
+				let name: String = value(&mut args, "--input-format", "text, csv, json, nbody or starlab")?;
+				opts.input_format = match convert::Format::parse(&name) {
+					Some(x) if x.is_text() => Some(x),
+					_ => return Err(format!("Unknown input format '{}', use text, csv, json, nbody or starlab", name)),
+				};
+			},
+			"--metrics" => opts.metrics = Some(value(&mut args, "--metrics", "an address HOST:PORT")?),
+			"--plot-format" => {
+				let format: String = value(&mut args, "--plot-format", "svg or png")?;
+				if format != "svg" && format != "png" {
+					return Err(format!("--plot-format needs svg or png, got {}", format));
+				}
+				opts.plot_format = Some(format);
+			},
+			"-q" => log::set_verbosity(log::Level::Warn),
+			"-v" => log::set_verbosity(if log::verbosity() >= log::Level::Verbose { log::Level::Debug } else { log::Level::Verbose }),
+			"-vv" => log::set_verbosity(log::Level::Debug),
+			"--walltime" => opts.walltime = Some(value(&mut args, "--walltime", "a number of seconds")?),
+			"--max-de" => {
+				let x: f64 = value(&mut args, "--max-de", "a relative energy error")?;
+				if !(x > 0.0) {
+					return Err(format!("--max-de needs a positive error, got {}", x));
+				}
+				opts.max_de = Some(x);
+			},
+			_ => return Err(format!("Unknown argument: {}", arg)),
 		}
 	}
+	// Also the starting interval of an adaptive cadence, whichever flag came first
+	if let Some(n) = interval {
+		opts.cadence.set_interval(n);
+	}
+	if log_every.is_some() && (interval.is_some() || opts.cadence.triggers.is_some()) {
+		return Err(String::from("--log-every cannot be combined with --diagnostic-interval or --adaptive"));
+	}
+	opts.cadence.every = log_every;
+	if opts.time_symmetric && opts.adaptive_dt.is_none() {
+		return Err(String::from("--time-symmetric needs --adaptive-dt"));
+	}
+	if opts.autotune && opts.no_autotune {
+		return Err(String::from("--autotune picks the threads and tile size itself and cannot be combined with --threads, --serial, --tile or --no-autotune"));
+	}
+	if let Some(spec) = units_spec {
+		opts.units = Some(units::Units::parse(&spec, constants)?);
+	}
+	Ok((opts, precision))
 }
 
-fn energies(tos: &Vec<Star>) -> Vec<f64> {
-	let ref s = *tos;
-	let mut e: Vec<f64> = vec![0.0; 3];
-	let mut rij: f64;
+// A run that ended before it started
+fn failed(error: NBodyError) -> Status {
+	Status { outcome: Outcome::from(error), t: 0.0, steps: 0, de: 0.0, wall_seconds: 0.0, outputs: vec![] }
+}
 
-	//Kinetic energy
-	for star in s {
-		e[1] += 0.5*star.m*((star.v[0].powi(2) + star.v[1].powi(2) + star.v[2].powi(2)).sqrt());
+fn io_failed(what: &str, error: io::Error) -> Outcome {
+	Outcome::from(NBodyError::io(what, error))
+}
+
+fn start(mut opts: Options, precision: &str) -> Status {
+	let config = |msg: String| failed(NBodyError::Config(msg));
+	if let Some(ref dir) = opts.out_dir {
+		if let Err(x) = fs::create_dir_all(dir) {
+			return failed(NBodyError::io(&format!("Could not create output directory {}", dir), x));
+		}
+		if let Err(x) = log::open(&Path::new(dir).join("run.log")) {
+			return failed(NBodyError::io("Could not create run.log", x));
+		}
 	}
 
-	for si in 0..s.len() {
-		for sj in (si + 1)..s.len() {
-			rij = 0.0;
-			for i in 0..3 {
-				rij += (s[si].r[i] - s[sj].r[i]).powi(2);
-			}
-			e[2] -= s[si].m*s[sj].m/(rij.sqrt());
+	if opts.backend == "mpi" && !cfg!(feature = "mpi") {
+		return config(String::from("--backend mpi needs a build with --features mpi"));
+	}
+	// With MPI only rank 0 reads stdin and passes the input on
+	let mut line_buffer = String::new();
+	if opts.rank == 0 {
+		if let Err(x) = io::stdin().read_to_string(&mut line_buffer) {
+			return failed(NBodyError::io("Could not read the input", x));
 		}
 	}
-	e[0] = e[1] + e[2];
-	return e;
+	#[cfg(feature = "mpi")]
+	{
+		if opts.backend == "mpi" {
+			distributed::broadcast_text(&mut line_buffer);
+		}
+	}
+	// NBODY, Starlab and the other text formats are run from the text they convert to
+	let format = opts.input_format.unwrap_or_else(|| convert::detect(&line_buffer));
+	if format != convert::Format::Text {
+		let mut x = match convert::read_str(&line_buffer, format) {
+			Ok(x) => x,
+			Err(x) => return failed(x),
+		};
+		if x.ids.iter().any(|id| id.parse::<f64>().is_err()) {
+			x.ids = (0..x.s.len()).map(|i| i.to_string()).collect();
+		}
+		verbose!("Read {} stars at t = {} from {:?} input", x.s.len(), x.t, format);
+		let mut text = vec![];
+		convert::write_text(&mut text, &x).expect("Writing to memory");
+		opts.given_input = Some(std::mem::replace(&mut line_buffer, String::from_utf8(text).expect("Text is UTF-8")));
+	}
+	let issues = validate::validate(&line_buffer);
+	for x in issues.iter().take(20) {
+		warn!("Input {}", x);
+	}
+	if issues.len() > 20 {
+		warn!("... and {} more problems in the input", issues.len() - 20);
+	}
+	if let (true, Some(x)) = (opts.strict, issues.first()) {
+		return failed(x.to_error());
+	}
+
+	if opts.p.gpu && !cfg!(feature = "gpu") {
+		return config(String::from("--gpu needs a build with --features gpu"));
+	}
+	#[cfg(feature = "gpu")]
+	{
+		if opts.p.gpu && !gpu::available() {
+			return config(String::from("--gpu: no GPU adapter found"));
+		}
+	}
+	if opts.plot_format.is_some() && !cfg!(feature = "plots") {
+		return config(String::from("--plot-format needs a build with --features plots"));
+	}
+	if opts.http.is_some() && !cfg!(feature = "http") {
+		return config(String::from("--http needs a build with --features http"));
+	}
+	if opts.metrics.is_some() && !cfg!(feature = "metrics") {
+		return config(String::from("--metrics needs a build with --features metrics"));
+	}
+	if opts.viz && !cfg!(feature = "viz") {
+		return config(String::from("--viz needs a build with --features viz"));
+	}
+	if opts.parquet && !cfg!(feature = "parquet") {
+		return config(String::from("--parquet needs a build with --features parquet"));
+	}
+	if opts.sqlite.is_some() && !cfg!(feature = "sqlite") {
+		return config(String::from("--sqlite needs a build with --features sqlite"));
+	}
+	if opts.run_id.is_some() && opts.sqlite.is_none() {
+		return config(String::from("--run-id names the run in the --sqlite database and needs --sqlite"));
+	}
+	if opts.central_substeps > 1 && opts.central.is_none() {
+		return config(String::from("--central-substeps needs --central"));
+	}
+	if opts.central_substeps > 1 && opts.regularize.is_some() {
+		return config(String::from("--regularize cannot be combined with --central-substeps"));
+	}
+	if opts.binaries && opts.out_dir.is_none() {
+		return config(String::from("--binaries needs --out"));
+	}
+	if opts.vtk && opts.out_dir.is_none() {
+		return config(String::from("--vtk needs --out"));
+	}
+	if opts.density.is_some() && !opts.vtk {
+		return config(String::from("--density needs --vtk"));
+	}
+	if opts.star_energies && !opts.vtk {
+		return config(String::from("--star-energies needs --vtk"));
+	}
+	if opts.accelerations && !opts.vtk && !opts.parquet {
+		return config(String::from("--accelerations needs --vtk or --parquet"));
+	}
+	if opts.track.is_some() && opts.out_dir.is_none() {
+		return config(String::from("--track needs --out"));
+	}
+	if opts.solver == "pm" && opts.periodic.is_none() {
+		return config(String::from("--solver pm needs a periodic box, --periodic L"));
+	}
+	if (opts.grid.is_some() || opts.pp) && opts.solver != "pm" {
+		return config(String::from("--grid and --pp set up --solver pm"));
+	}
+	if opts.solver == "verlet" && opts.cutoff.is_none() {
+		return config(String::from("--solver verlet needs an interaction cutoff, --cutoff RC"));
+	}
+	if (opts.cutoff.is_some() || opts.skin.is_some()) && opts.solver != "verlet" {
+		return config(String::from("--cutoff and --skin set up --solver verlet"));
+	}
+	if opts.solver == "verlet" && (opts.p.gpu || opts.p.simd.is_some()) {
+		return config(String::from("--solver verlet runs its own pair loop: no --gpu or --simd"));
+	}
+	if opts.tree_reuse.is_some() && opts.solver != "tree" && opts.solver != "auto" {
+		return config(String::from("--tree-reuse sets up the tree of --solver tree or auto"));
+	}
+	if opts.periodic.is_some() && ((opts.solver != "direct" && opts.solver != "pm") || opts.backend == "mpi" || opts.p.gpu || opts.p.simd.is_some() || opts.verify_forces.is_some()) {
+		return config(String::from("--periodic runs its own solvers, direct or pm: no --solver tree/auto, --backend mpi, --gpu, --simd or --verify-forces"));
+	}
+	if opts.periodic.is_some() && (opts.central.is_some() || opts.regularize.is_some() || opts.collisions != collisions::Mode::Off) {
+		return config(String::from("--central, --regularize and --collisions measure separations without the periodic images and cannot be combined with --periodic"));
+	}
+	if opts.comoving.is_some() && opts.periodic.is_none() {
+		return config(String::from("--comoving needs a periodic box, --periodic L"));
+	}
+	if opts.comoving.is_some() && (opts.adaptive_dt.is_some() || opts.drag.is_some() || opts.tidal.is_some() || opts.pn.is_some()) {
+		return config(String::from("--comoving steps the stars in its own way: no --adaptive-dt, --drag, --tidal or --pn"));
+	}
+	if opts.dim == 2 && opts.periodic.is_some() {
+		return config(String::from("--dim 2 keeps the stars in a plane and cannot be combined with the periodic box of --periodic"));
+	}
+	if opts.backend == "mpi" && opts.verify_forces.is_some() {
+		return config(String::from("--verify-forces needs the stars on one process, not --backend mpi"));
+	}
+	if opts.backend == "mpi" && (opts.solver != "direct" || opts.p.gpu || opts.p.simd.is_some() || opts.overlap || precision == "dd") {
+		return config(String::from("--backend mpi only runs the plain direct solver: no --solver tree/auto, --gpu, --simd, --overlap or --precision dd"));
+	}
+	if opts.p.simd.is_some() && precision != "f64" {
+		return config(format!("--simd computes in f64 and cannot be combined with --precision {}", precision));
+	}
+
+	// Each precision is its own monomorphized copy of the run
+	match precision {
+		"mixed" => {
+			opts.p.mixed = true;
+			run::<f64>(opts, &line_buffer)
+		},
+		"f32" => run::<f32>(opts, &line_buffer),
+		"f64" => run::<f64>(opts, &line_buffer),
+		"dd" => run::<DoubleDouble>(opts, &line_buffer),
+		x => config(format!("Unknown precision '{}', use f32, f64, mixed or dd", x)),
+	}
 }
 
-fn main() {
+/*
+ The settings the run ends up with, after defaults, masses::apply_defaults()
+ and tuning, as the flags that select them. Everything else is in the
+ command line as given.
+ */
+fn resolved<R: Real>(opts: &Options, p: &Params<R>) -> Vec<(String, String)> {
+	let flag = |name: &str, value: String| (String::from(name), value);
+	let mut x = vec![
+		flag("--precision", String::from(if p.mixed { "mixed" } else { R::name() })),
+		flag("--dt", p.dt.to_string()),
+		flag("--softening", p.eps.to_string()),
+		flag("--softening-rule", String::from(p.softening.name())),
+		flag("--solver", opts.solver.clone()),
+		flag("--theta", opts.theta.to_string()),
+		flag("--opening", String::from(opts.opening.name())),
+		flag("--multipole", String::from(if opts.quadrupole { "quadrupole" } else { "monopole" })),
+		flag("--backend", opts.backend.clone()),
+		flag("--threads", p.threads.to_string()),
+		flag("--tile", pairs::tile_size().to_string()),
+		flag("--diagnostic-interval", opts.cadence.interval.to_string()),
+	];
+	if let Some(eta) = opts.adaptive_dt {
+		x.push(flag("--adaptive-dt", eta.to_string()));
+	}
+	if let Some(drift) = opts.tree_reuse {
+		x.push(flag("--tree-reuse", drift.to_string()));
+	}
+	if let Some(rc) = opts.cutoff {
+		x.push(flag("--cutoff", rc.to_string()));
+		x.push(flag("--skin", opts.skin.unwrap_or(verlet::SKIN*rc).to_string()));
+	}
+	if let Some(l) = opts.periodic {
+		x.push(flag("--periodic", l.to_string()));
+	}
+	if opts.solver == "pm" {
+		x.push(flag("--grid", opts.grid.unwrap_or(pm::GRID).to_string()));
+	}
+	if opts.pp {
+		x.push(flag("--pp", String::new()));
+	}
+	if opts.dim == 2 {
+		x.push(flag("--dim", String::from("2")));
+	}
+	if let Some(c) = opts.comoving {
+		let h0 = c.h0.map_or(String::new(), |h| format!(",h0={}", h));
+		x.push(flag("--comoving", format!("omega_m={},omega_lambda={}{},z={}", c.omega_m, c.omega_lambda, h0, c.z)));
+	}
+	for &(on, name) in [(p.compensated, "--compensated"), (p.deterministic, "--deterministic"), (p.simd.is_some(), "--simd"), (p.gpu && !p.hybrid, "--gpu"), (p.hybrid, "--hybrid"), (opts.normalize, "--normalize"), (opts.time_symmetric, "--time-symmetric")].iter() {
+		if on {
+			x.push(flag(name, String::new()));
+		}
+	}
+	x
+}
 
-	let mut s: Vec<Star> = vec![];
-	let mut line_buffer = String::new();
-	let mut t: f64 = 0.0;
-	let tend: f64 = 1.0;
-	let mut k = 0;
+/*
+ Threads and tile size from the per-machine cache, measured on the first run
+ of this size or with --autotune. Runs that are small, set the threads or
+ tile themselves, or do not use the CPU pair loop (GPU, MPI, periodic boxes)
+ are left alone.
+ */
+fn autotune<R: Real>(s: &Vec<Star<R>>, p: &mut Params<R>, opts: &Options) {
+	if opts.no_autotune || p.gpu || opts.backend != "local" || opts.periodic.is_some() || opts.solver == "verlet" {
+		return;
+	}
+	if !opts.autotune && (s.len() < autotune::MIN_N || env::var("NBABEL_THREADS").is_ok()) {
+		return;
+	}
+	let key = autotune::Key::new(&opts.solver, if p.mixed { "mixed" } else { R::name() }, s.len());
+	let path = autotune::cache_path();
+	let cached = if opts.autotune { None } else { path.as_ref().and_then(|x| autotune::load(x, &key)) };
+	let x = match cached {
+		Some(x) => {
+			verbose!("Cached tuning for N ~ {}: {} threads, tile {}", key.n, x.threads, x.tile);
+			x
+		},
+		None => {
+			info!("Autotuning threads and tile size for N = {} with the {} solver", s.len(), opts.solver);
+			let x = autotune::tune(s, p, &opts.solver, opts.theta);
+			info!("Autotune: {} threads, tile {}", x.threads, x.tile);
+			match path {
+				Some(path) => if let Err(e) = autotune::save(&path, &key, x) {
+					warn!("Could not cache the tuning in {}: {}", path.display(), e);
+				},
+				None => warn!("Nowhere to cache the tuning; set NBABEL_TUNE_FILE"),
+			}
+			x
+		},
+	};
+	p.threads = x.threads;
+	pairs::set_tile_size(x.tile);
+}
 
-	io::stdin().read_to_string(&mut line_buffer).expect("Something went wrong");
+/*
+ Integrates up to tend and reports how it ended. A checkpoint on stdin resumes
+ at its time; dE is then measured against the energy at the checkpoint.
+ */
+fn run<R: Real>(mut opts: Options, line_buffer: &str) -> Status {
+	let clock = Instant::now();
+	let tend: R = R::from_f64(opts.tend);
+	let mut p: Params<R> = opts.p.convert();
 
-	let lines = line_buffer.split("\n");
+	let (mut s, mut t0, steps0) = match checkpoint::read::<R>(line_buffer) {
+		Ok(x) => x,
+		Err(x) => return failed(x),
+	};
+	let columns = match columns::Columns::read(line_buffer) {
+		Ok(x) => x,
+		Err(msg) => return Status { outcome: Outcome::InputError(msg), t: 0.0, steps: 0, de: 0.0, wall_seconds: 0.0, outputs: vec![] },
+	};
+	if !columns.is_empty() {
+		verbose!("Carrying extra columns: {}", columns.names.join(" "));
+	}
+	let mut units = opts.units.clone();
+	if let Some(ref u) = units {
+		info!("Input units {}: {}", u.names.join(","), u);
+		u.to_internal(&mut s);
+	}
+	if opts.normalize {
+		// Unsoftened, so that the settings can all be given in N-body units
+		let mut raw = p.clone();
+		raw.eps = R::zero();
+		match normalize::normalize(&mut s, &raw) {
+			Ok(scale) => {
+				info!("Normalized to N-body units: {}", scale);
+				t0 = t0/R::from_f64(scale.time);
+				units = units.map(|u| u.rescaled(scale.mass, scale.length));
+				if let Some(ref u) = units {
+					info!("Internal units: {}", u);
+				}
+			},
+			Err(msg) => return failed(NBodyError::Config(msg)),
+		}
+	}
+	let mut next_diagnostic = steps0 + opts.cadence.interval;
+	for warning in masses::apply_defaults(&s, &mut p, opts.dt_given, opts.rule_given) {
+		warn!("{}", warning);
+	}
 
-	for line in lines {
-		let mut r: Vec<f64> = Vec::with_capacity(3);
-		let mut v: Vec<f64> = Vec::with_capacity(3);
-		let m: f64;
-		if line == "" {
-			continue;
+	let mut e: Vec<R>;
+	if let Some(q) = opts.virialize {
+		let e = energies(&s, &p);
+		info!("Virializing: Q = {} -> {}", diagnostics::virial_ratio(&e), q);
+		if let Err(msg) = diagnostics::virialize(&mut s, &e, R::from_f64(q)) {
+			return failed(NBodyError::Config(msg));
 		}
-		let var = line.split(" ");
-		let mut arr: Vec<f64> = Vec::with_capacity(8);
-		for num in var {
-			if num == "" {
-				continue;
-			}
-			arr.push(num.parse().expect("Invalid input"));
+	}
+	if let Some(isa) = p.simd {
+		verbose!("Vectorized force kernel: {}", isa.name());
+	}
+	for x in &opts.external {
+		info!("External potential: {}", x.name());
+	}
+	if let Some(x) = opts.tidal {
+		let mass: f64 = s.iter().map(|x| x.m.to_f64()).sum();
+		info!("Tidal field: omega = {}, kappa = {}, nu = {}, tidal radius {}", x.omega, x.kappa, x.nu, x.tidal_radius(mass));
+		opts.external.push(Box::new(x));
+	}
+	let central = match opts.central {
+		Some(ref spec) => match central::Central::pick(spec, &s) {
+			Ok(mut x) => {
+				x.substeps = opts.central_substeps;
+				info!("Central object: star {} of mass {}, {} substeps", x.index, s[x.index].m, x.substeps);
+				Some(x)
+			},
+			Err(msg) => return failed(NBodyError::Config(msg)),
+		},
+		None => None,
+	};
+	// A planar run starts in the plane, from the first energies on
+	if opts.dim == 2 {
+		let moved = planar::flatten(&mut s);
+		if moved > 0 {
+			warn!("--dim 2: projected {} stars onto the z = 0 plane", moved);
+		}
+	}
+	// Stars with a non-zero "fixed" column stay put, from the first energies on
+	let fixed = fixed::Fixed::new(s.len(), &columns);
+	if !fixed.stars.is_empty() {
+		if central.is_some() || opts.regularize.is_some() {
+			return failed(NBodyError::Config(String::from("Fixed stars cannot be combined with --central or --regularize, which move stars by their own rules")));
+		}
+		info!("Stars held in place by the fixed column: {}", fixed.stars.len());
+		fixed.hold(&mut s);
+	}
+	// Periodic and short-range solvers compute the energies themselves, so they come first
+	let solver: Box<dyn solver::ForceSolver<R>> = match opts.periodic {
+		Some(l) if opts.solver == "pm" => Box::new(pm::Pm::new(l, opts.grid.unwrap_or(pm::GRID), opts.pp)),
+		Some(l) => Box::new(ewald::Periodic::new(l)),
+		None if opts.solver == "verlet" => {
+			let rc = opts.cutoff.expect("--solver verlet was checked to have --cutoff");
+			Box::new(verlet::Verlet::new(R::from_f64(rc), R::from_f64(opts.skin.unwrap_or(verlet::SKIN*rc))))
+		},
+		None => solver::by_name_with::<R>(&opts.solver, opts.theta, opts.opening, opts.quadrupole, opts.tree_reuse).expect("Solver name was checked when parsing"),
+	};
+	let w0 = solver.energies(&s, &p).unwrap_or_else(|| energies(&s, &p));
+	let mut e0: Vec<R> = external::with_energy(w0, &s, &opts.external);
+	if let Some(x) = central {
+		let w = x.energy_correction(&s, &p);
+		e0[0] += w;
+		e0[2] += w;
+	}
+	info!("Energies: {} {} {}, Q = {}", e0[0], e0[1], e0[2], diagnostics::virial_ratio(&e0));
+	autotune(&s, &mut p, &opts);
+	let metadata = metadata::Metadata::new(opts.given_input.as_ref().map_or(line_buffer, |x| x.as_str()).as_bytes(), &opts.argv, &resolved(&opts, &p));
+	verbose!("Input sha256 {}, nbabel {} (git {})", metadata.input_sha256, metadata.version, metadata.git);
+
+	// Diagnostics are only collected when something is going to consume them
+	let out_dir = opts.out_dir;
+	let mut history = if out_dir.is_some() || opts.parquet || opts.sqlite.is_some() || cfg!(feature = "plots") {
+		let mut h = match diagnostics::History::with_units(&diagnostics::FRACTIONS, out_dir.as_ref().map(Path::new), units.as_ref()) {
+			Ok(x) => x,
+			Err(x) => return failed(NBodyError::io("Could not create the diagnostics files", x)),
+		};
+		h.overlap = opts.overlap;
+		if let Err(x) = h.record(t0, &e0, &e0, &s) {
+			return failed(NBodyError::io("Could not write diagnostics", x));
+		}
+		Some(h)
+	} else {
+		None
+	};
+
+	let dir = Path::new(out_dir.as_ref().map_or(".", |x| x.as_str()));
+	let mut outputs: Vec<String> = vec![];
+	if out_dir.is_some() {
+		outputs.push(String::from("run.log"));
+		outputs.push(String::from("diagnostics.csv"));
+		outputs.push(String::from("lagrangian.csv"));
+	}
+	let mut catalog = if opts.binaries {
+		let mut c = match binaries::Catalog::new(Some(dir)) {
+			Ok(x) => x,
+			Err(x) => return failed(NBodyError::io("Could not create binaries.csv", x)),
+		};
+		c.columns = columns.clone();
+		if let Err(x) = c.update(t0.to_f64(), &s) {
+			return failed(NBodyError::io("Could not write binaries.csv", x));
+		}
+		outputs.push(String::from("binaries.csv"));
+		outputs.push(String::from("binary_catalog.csv"));
+		Some(c)
+	} else {
+		None
+	};
+
+	#[cfg(feature = "mpi")]
+	let solver: Box<dyn solver::ForceSolver<R>> = if opts.backend == "mpi" { Box::new(distributed::Direct) } else { solver };
+	let mut sim = Simulation::with_solver(s, p, solver);
+	sim.t = t0;
+	sim.steps = steps0;
+	sim.columns = columns;
+	sim.metadata = Some(metadata);
+	if !opts.external.is_empty() {
+		sim.set_external(opts.external);
+	}
+	sim.rotating = opts.tidal.map(|x| x.omega);
+	if let Some(x) = central {
+		sim.set_central(x);
+	}
+	if opts.collisions != collisions::Mode::Off {
+		let x = collisions::Collisions::new(opts.collisions, opts.restitution, sim.s.len(), &sim.columns, opts.radius);
+		if x.radii.iter().all(|&r| r == 0.0) {
+			warn!("--collisions is on but every star has radius 0; give a radius column or --radius");
+		}
+		sim.collisions = Some(x);
+	}
+	if let Some(order) = opts.pn {
+		let c = match (opts.pn_c, units.as_ref()) {
+			(Some(c), _) => c,
+			(None, Some(u)) => u.constants.c/u.velocity(),
+			(None, None) => return failed(NBodyError::Config(String::from("--pn needs --pn-c or --units for the speed of light"))),
+		};
+		let x = pn::PostNewtonian::new(c, order > 1.0, sim.s.len(), &sim.columns);
+		if x.flagged.len() < 2 {
+			warn!("--pn is on but fewer than two stars are flagged in a pn column");
+		}
+		info!("Post-Newtonian order {} for {} compact stars, c = {}", order, x.flagged.len(), c);
+		sim.set_post_newtonian(x);
+	}
+	if !fixed.stars.is_empty() {
+		sim.set_fixed(fixed);
+	}
+	if opts.dim == 2 {
+		sim.set_planar();
+	}
+	if let Some(x) = opts.drag {
+		info!("Drag: {:?}", x);
+		sim.drag = Some(x);
+	}
+	if let Some(mut x) = opts.comoving {
+		let l = opts.periodic.expect("--comoving was checked to have --periodic");
+		let mass: f64 = sim.s.iter().map(|x| x.m.to_f64()).sum();
+		let h0 = x.matching_h0(mass, l);
+		match x.h0 {
+			Some(given) if (given - h0).abs() > 0.01*h0 => warn!("--comoving h0 = {} does not match the mean density of the box, which needs h0 = {}", given, h0),
+			Some(_) => (),
+			None => x.h0 = Some(h0),
 		}
-		m = arr[1];
-		for i in 2..5 {
-			r.push(arr[i]);
+		x.a = x.scale_at(sim.t.to_f64());
+		info!("Comoving coordinates: omega_m = {}, omega_lambda = {}, h0 = {}, a = {} (z = {})", x.omega_m, x.omega_lambda, x.h0.unwrap_or(h0), x.a, x.redshift());
+		sim.cosmology = Some(x);
+	}
+	if let Some(x) = opts.mass_loss {
+		info!("Mass loss: {}", x.name());
+		sim.set_mass_loss(x);
+	}
+	if let Some(radius) = opts.regularize {
+		verbose!("Regularizing the closest bound pair below separation {}", radius);
+		sim.regularize(radius);
+	}
+	if let Some(every) = opts.incremental_energy {
+		sim.track_energy_every(every);
+	}
+	if let Some(eta) = opts.adaptive_dt {
+		let x = timestep::Timestep { eta: eta, max: sim.p.dt.to_f64(), symmetric: opts.time_symmetric };
+		verbose!("Adaptive steps of {} times the shortest pair timescale, at most {}{}", eta, x.max, if x.symmetric { ", time-symmetrized" } else { "" });
+		sim.timestep = Some(x);
+	}
+	let mut series = if opts.vtk {
+		let mut x = match vtk::Series::new(dir) {
+			Ok(x) => x,
+			Err(x) => return failed(NBodyError::io("Could not create the vtk directory", x)),
+		};
+		x.fields = vtk::Fields { density: opts.density, energies: opts.star_energies, accelerations: opts.accelerations };
+		x.metadata = sim.metadata.clone();
+		if let Err(x) = x.write_with(sim.t.to_f64(), &sim.s, &sim.p, &sim.columns) {
+			return failed(NBodyError::io("Could not write a VTK snapshot", x));
 		}
-		for i in 5..8 {
-			v.push(arr[i]);
+		outputs.push(String::from("vtk/snapshots.pvd"));
+		Some(x)
+	} else {
+		None
+	};
+	#[cfg(feature = "sqlite")]
+	let mut store = match opts.sqlite {
+		Some(ref path) => {
+			let metadata = sim.metadata.as_ref().expect("Metadata is set above");
+			let run_id = opts.run_id.clone().unwrap_or_else(|| database::default_run_id(metadata));
+			let mut x = match database::Database::open(Path::new(path), &run_id, metadata, sim.s.len()) {
+				Ok(x) => x,
+				Err(msg) => return Status { outcome: Outcome::IoError(msg), t: 0.0, steps: 0, de: 0.0, wall_seconds: 0.0, outputs: vec![] },
+			};
+			if let Some(Err(msg)) = history.as_ref().map(|h| x.samples(&h.samples, &h.fractions)) {
+				return Status { outcome: Outcome::IoError(msg), t: 0.0, steps: 0, de: 0.0, wall_seconds: 0.0, outputs: vec![] };
+			}
+			info!("Recording diagnostics in {} as run {}", path, run_id);
+			Some(x)
+		},
+		None => None,
+	};
+	#[cfg(feature = "parquet")]
+	let mut parquet = if opts.parquet {
+		let mut x = match columnar::Parquet::new(dir, &sim.columns, sim.metadata.as_ref(), opts.accelerations) {
+			Ok(x) => x,
+			Err(x) => return failed(NBodyError::io("Could not create snapshots.parquet", x)),
+		};
+		if let Err(x) = x.write(sim.t.to_f64(), sim.steps, &sim.s, &sim.p, &sim.columns) {
+			return failed(NBodyError::io("Could not write snapshots.parquet", x));
+		}
+		outputs.push(String::from("snapshots.parquet"));
+		outputs.push(String::from("diagnostics.parquet"));
+		Some(x)
+	} else {
+		None
+	};
+	if let Some(ref spec) = opts.track {
+		let indices = match track::parse(spec, sim.s.len()) {
+			Ok(x) => x,
+			Err(x) => return failed(NBodyError::Config(x)),
+		};
+		let mut x = match track::Tracks::create(dir, &indices) {
+			Ok(x) => x,
+			Err(x) => return failed(NBodyError::io("Could not create the track files", x)),
+		};
+		if let Err(x) = x.write(sim.t.to_f64(), &sim.s) {
+			return failed(NBodyError::io("Could not write a track file", x));
 		}
-		s.push(Star { m: m, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] });
+		outputs.extend(indices.iter().map(|&i| track::name(i)));
+		sim.tracks = Some(x);
+	}
+	let mut server = match opts.serve {
+		Some(ref url) => match serve::Server::bind(url, opts.serve_rate) {
+			Ok(x) => {
+				info!("Streaming to ws://{}", x.local_addr());
+				Some(x)
+			},
+			Err(x) => return failed(NBodyError::Config(x)),
+		},
+		None => None,
+	};
+	#[cfg(feature = "http")]
+	let mut api = match opts.http {
+		Some(ref address) => match http::Api::bind(address) {
+			Ok(x) => {
+				info!("HTTP API on http://{}", x.local_addr());
+				Some(x)
+			},
+			Err(x) => return failed(NBodyError::Config(x)),
+		},
+		None => None,
+	};
+	#[cfg(feature = "metrics")]
+	let gauges = match opts.metrics {
+		Some(ref address) => {
+			let meta = sim.metadata.as_ref().expect("Metadata is set above");
+			match metrics::Metrics::bind(address, &meta.version, &meta.git) {
+				Ok(x) => {
+					info!("Prometheus metrics on http://{}/metrics", x.local_addr());
+					Some(x)
+				},
+				Err(x) => return failed(NBodyError::Config(x)),
+			}
+		},
+		None => None,
+	};
+	// The energies of the last diagnostic, for the stream and the HTTP API
+	let mut streamed: Vec<f64> = e0.iter().map(|x| x.to_f64()).collect();
+	let cadence = &mut opts.cadence;
+	// With --log-every the step into each log time (or tend) is cut short to end on it exactly
+	let mut next_log = cadence.next_time(sim.t.to_f64());
+	let mut outcome = Outcome::Success;
+	let mut de = 0.0;
+	// Picks the stars of --verify-forces, the same ones in every run
+	let mut verify_rng = rng::Rng::new(1);
+
+	let mut bar = progress::Bar::new(sim.t.to_f64(), tend.to_f64(), sim.steps);
+	if !opts.progress {
+		bar.disable();
 	}
+	#[cfg(feature = "viz")]
+	let mut viewer = if opts.viz { Some(viz::Viewer::open()) } else { None };
+	while sim.t < tend {
+		#[cfg(feature = "viz")]
+		{
+			if viewer.as_mut().map_or(false, |x| !x.show(&sim.s, &sim.p, sim.t.to_f64())) {
+				info!("Viewer closed, the run goes on");
+				viewer = None;
+			}
+		}
+		#[cfg(feature = "http")]
+		{
+			if let Some(ref mut x) = api {
+				x.serve(|request, paused| match request {
+					http::Request::Status => http::Response::json(http::status_json(sim.t.to_f64(), sim.steps, &streamed, de, cadence.interval, paused, &sim.timers.run)),
+					http::Request::Snapshot => {
+						let mut text = vec![];
+						match write_stars_with(&mut text, &sim.s, &sim.columns) {
+							Ok(()) => http::Response::text(String::from_utf8_lossy(&text).into_owned()),
+							Err(x) => http::Response::error(503, &x.to_string()),
+						}
+					},
+					http::Request::Cadence(n) => {
+						info!("Diagnostics every {} steps, through the HTTP API", n);
+						cadence.set_interval(n);
+						next_diagnostic = sim.steps + n;
+						next_log = None;
+						http::Response::json(format!("{{\"diagnostic_interval\": {}}}", n))
+					},
+					http::Request::Pause | http::Request::Resume => unreachable!("Api::serve answers these itself"),
+				});
+			}
+		}
+		sim.adapt();
+		let target = next_log.map(|x| R::from_f64(x).min(tend)).filter(|&x| x - sim.t <= sim.p.dt*R::from_f64(1.0 + 1e-9));
+		if let Some(x) = target {
+			let dt = sim.p.dt;
+			sim.p.dt = x - sim.t;
+			sim.step();
+			sim.p.dt = dt;
+			sim.t = x;
+		} else {
+			sim.step();
+		}
+		bar.update(sim.t.to_f64(), sim.steps);
+		if let Some(i) = sim.first_non_finite() {
+			error!("Star {} has a non-finite position or velocity at t = {} (step {})", i, sim.t, sim.steps);
+			outcome = Outcome::NumericalFailure(format!("non-finite position or velocity of star {} at t = {}", i, sim.t));
+			break;
+		}
+		if let Some(ref mut x) = sim.tracks {
+			let phase = Instant::now();
+			if let Err(x) = x.write(sim.t.to_f64(), &sim.s) {
+				outcome = io_failed("Could not write a track file", x);
+				break;
+			}
+			sim.timers.add(Phase::Io, phase.elapsed().as_secs_f64());
+		}
+		if let Some(ref mut x) = server {
+			if x.due() {
+				let phase = Instant::now();
+				x.broadcast(&serve::message(sim.t.to_f64(), sim.steps, &streamed, de, &sim.s));
+				sim.timers.add(Phase::Io, phase.elapsed().as_secs_f64());
+			}
+		}
+		for (t, event) in sim.events.drain(..) {
+			info!("Event at t = {}: {}", t, event);
+		}
+		if let Some((every, k)) = opts.verify_forces {
+			if sim.steps % every == 0 {
+				let errors = sim.verify_forces(k, &mut verify_rng);
+				info!("Force check at step {} ({}): {}", sim.steps, sim.solver.name(), solver::ErrorSummary::new(&errors));
+			}
+		}
+
+		let stop = [interrupt::requested(), opts.walltime.map_or(false, |x| clock.elapsed().as_secs_f64() > x)];
+		// All ranks must stop at the same step, so rank 0 decides for them
+		#[cfg(feature = "mpi")]
+		let stop = if opts.backend == "mpi" { distributed::broadcast_flags(&stop) } else { stop.to_vec() };
+		let interrupted = stop[0];
+		if interrupted || stop[1] {
+			let path = dir.join("checkpoint.txt");
+			let phase = Instant::now();
+			let written = if opts.rank == 0 { checkpoint::write_units(&path, &sim, units.as_ref()) } else { Ok(()) };
+			sim.timers.add(Phase::Io, phase.elapsed().as_secs_f64());
+			match written {
+				Ok(()) if interrupted => {
+					bar.finish();
+					warn!("Interrupted at t = {}, checkpoint written to {}", sim.t, path.display());
+					outputs.push(String::from("checkpoint.txt"));
+					outcome = Outcome::Interrupted;
+				},
+				Ok(()) => {
+					warn!("Wall-clock limit reached at t = {}, checkpoint written to {}", sim.t, path.display());
+					outputs.push(String::from("checkpoint.txt"));
+					outcome = Outcome::Walltime;
+				},
+				Err(x) => outcome = io_failed("Could not write checkpoint", x),
+			}
+			break;
+		}
 
-	let mut e: Vec<f64>;
-	let e0: Vec<f64> = energies(&s);
-	println!("Energies: {} {} {}", e0[0], e0[1], e0[2]);
+		let due = match next_log {
+			Some(_) => target.is_some(),
+			None => sim.steps >= next_diagnostic,
+		};
+		if due {
+			let phase = Instant::now();
+			e = sim.tracked_energies();
+			// dE leaves out the energy carried off by mass loss
+			let mut e0 = e0.clone();
+			e0[0] += R::from_f64(sim.mass_loss_adjustment());
+			de = ((e[0]-e0[0])/e0[0]).to_f64();
+			streamed = e.iter().map(|x| x.to_f64()).collect();
+			if !e[0].is_finite() {
+				error!("Energy is no longer finite at t = {}", sim.t);
+				outcome = Outcome::NumericalFailure(format!("non-finite energy at t = {}", sim.t));
+				break;
+			}
+			let physical = units.as_ref().map_or(String::new(), |u| format!(" (t = {:.6e} Myr, E = {:.6e} erg)", u.myr(sim.t.to_f64()), u.erg(e[0].to_f64())));
+			progress!("t = {}, E = {} {} {}, dE = {}, Q = {}{}", sim.t, e[0], e[1], e[2], (e[0]-e0[0])/e0[0], diagnostics::virial_ratio(&e), physical);
+			if sim.mass_loss.is_some() {
+				verbose!("Energy change from mass loss so far: {}", sim.mass_loss_adjustment());
+			}
+			if log::enabled(log::Level::Debug) {
+				debug!("{}", sim.stats());
+			}
+			if let Some(Err(x)) = history.as_mut().map(|h| h.record(sim.t, &e, &e0, &sim.s)) {
+				outcome = io_failed("Could not write diagnostics", x);
+				break;
+			}
+			if let Some(Err(x)) = catalog.as_mut().map(|c| c.update(sim.t.to_f64(), &sim.s)) {
+				outcome = io_failed("Could not write binaries.csv", x);
+				break;
+			}
+			#[cfg(feature = "sqlite")]
+			{
+				let stored = match (store.as_mut(), history.as_ref()) {
+					(Some(db), Some(h)) => db.samples(&h.samples, &h.fractions).and_then(|_| db.timings(sim.steps, sim.t.to_f64(), clock.elapsed().as_secs_f64(), &sim.timers.run)),
+					_ => Ok(()),
+				};
+				if let Err(msg) = stored {
+					outcome = Outcome::IoError(msg);
+					break;
+				}
+			}
+			if let Some(limit) = opts.max_de.filter(|&x| !(de.abs() <= x)) {
+				let path = dir.join("checkpoint.txt");
+				error!("|dE| = {:e} exceeds --max-de {:e} at t = {}", de.abs(), limit, sim.t);
+				let written = if opts.rank == 0 { checkpoint::write_units(&path, &sim, units.as_ref()) } else { Ok(()) };
+				outcome = match written {
+					Ok(()) => {
+						outputs.push(String::from("checkpoint.txt"));
+						Outcome::NumericalFailure(format!("|dE| = {:e} above --max-de {:e} at t = {}, checkpoint written to {}", de.abs(), limit, sim.t, path.display()))
+					},
+					Err(x) => io_failed("Could not write checkpoint", x),
+				};
+				break;
+			}
+			sim.snapshot(&e);
+
+			let old = cadence.interval;
+			let fired = cadence.update(sim.t.to_f64(), ((e[0]-e0[0])/e0[0]).to_f64(), &sim.s);
+			if cadence.interval != old {
+				verbose!("Diagnostic interval {} -> {} steps {:?}", old, cadence.interval, fired);
+			}
+			next_diagnostic = sim.steps + cadence.interval;
+			next_log = cadence.next_time(sim.t.to_f64());
+			sim.timers.add(Phase::Diagnostics, phase.elapsed().as_secs_f64());
+			let phase = Instant::now();
+			if let Some(Err(x)) = series.as_mut().map(|x| x.write_with(sim.t.to_f64(), &sim.s, &sim.p, &sim.columns)) {
+				outcome = io_failed("Could not write a VTK snapshot", x);
+				break;
+			}
+			#[cfg(feature = "parquet")]
+			{
+				if let Some(Err(x)) = parquet.as_mut().map(|x| x.write(sim.t.to_f64(), sim.steps, &sim.s, &sim.p, &sim.columns)) {
+					outcome = io_failed("Could not write snapshots.parquet", x);
+					break;
+				}
+			}
+			sim.timers.add(Phase::Io, phase.elapsed().as_secs_f64());
+			if opts.timing {
+				let x = sim.timers.take_interval();
+				info!("Timing over the last {} steps: {}", x.steps, x);
+			}
+		}
+		#[cfg(feature = "metrics")]
+		{
+			if let Some(ref x) = gauges {
+				x.update(metrics::Gauges { t: sim.t.to_f64(), steps: sim.steps, stars: sim.s.len(), energy: streamed[0], de: de, wall_seconds: clock.elapsed().as_secs_f64(), times: sim.timers.run });
+			}
+		}
+	}
 
-	acceleration(&mut s);
+	bar.finish();
+	#[cfg(feature = "gpu")]
+	{
+		if opts.p.hybrid {
+			verbose!("The GPU ended with {:.1}% of the stars", 100.0*gpu::share().unwrap_or(0.0));
+		}
+	}
+	// The final state always goes out
+	if let Some(ref mut x) = server {
+		x.broadcast(&serve::message(sim.t.to_f64(), sim.steps, &streamed, de, &sim.s));
+	}
 
-	while t < tend {
-		update_positions(&mut s);
-		acceleration(&mut s);
-		update_velocities(&mut s);
+	// The state at the signal goes into the diagnostics too, with its energy budget
+	let phase = Instant::now();
+	if let Outcome::Interrupted = outcome {
+		let e = sim.energies();
+		let mut e0 = e0.clone();
+		e0[0] += R::from_f64(sim.mass_loss_adjustment());
+		de = ((e[0]-e0[0])/e0[0]).to_f64();
+		info!("Energies at t = {}: {} {} {}, dE = {}, Q = {}", sim.t, e[0], e[1], e[2], de, diagnostics::virial_ratio(&e));
+		if sim.mass_loss.is_some() {
+			info!("Energy change from mass loss: {}", sim.mass_loss_adjustment());
+		}
+		if let Some(Err(x)) = history.as_mut().map(|h| h.record(sim.t, &e, &e0, &sim.s)) {
+			outcome = io_failed("Could not write diagnostics", x);
+		}
+	}
 
-		t += DT;
-		k += 1; //Ugh, Rust doesn't support k++;
+	sim.timers.add(Phase::Diagnostics, phase.elapsed().as_secs_f64());
 
-		if k % 10 == 0 {
-			e = energies(&s);
-			println!("t = {}, E = {} {} {}, dE = {}", t, e[0], e[1], e[2], (e[0]-e0[0])/e0[0]);
+	let phase = Instant::now();
+	if let Some(ref mut h) = history {
+		if let Err(x) = h.finish() {
+			outcome = io_failed("Could not write diagnostics", x);
 		}
+		#[cfg(feature = "plots")]
+		{
+			let format = plots::Format::parse(opts.plot_format.as_ref().map_or("svg", |x| x.as_str())).expect("Plot format was checked when parsing");
+			match plots::write_all(dir, h, format) {
+				Ok(()) => outputs.extend(plots::names(format)),
+				Err(x) => outcome = Outcome::IoError(format!("Could not write plots: {}", x)),
+			}
+		}
+	}
+	if let Some(Err(x)) = sim.tracks.as_mut().map(|x| x.finish()) {
+		outcome = io_failed("Could not write a track file", x);
+	}
+	if let Some(Err(x)) = catalog.as_mut().map(|c| c.finish()) {
+		outcome = io_failed("Could not write binary_catalog.csv", x);
+	}
+	if let Some(ref c) = catalog {
+		info!("Binaries: {}", c.statistics());
+	}
+	// The final state, unless the last diagnostic already wrote it
+	if let Some(Err(x)) = series.as_mut().map(|x| x.write_with(sim.t.to_f64(), &sim.s, &sim.p, &sim.columns)) {
+		outcome = io_failed("Could not write a VTK snapshot", x);
+	}
+	#[cfg(feature = "parquet")]
+	{
+		if let Some(mut x) = parquet.take() {
+			let written = x.write(sim.t.to_f64(), sim.steps, &sim.s, &sim.p, &sim.columns).and_then(|_| x.finish(history.as_ref()));
+			if let Err(x) = written {
+				outcome = io_failed("Could not write the Parquet files", x);
+			}
+		}
+	}
+	sim.timers.add(Phase::Io, phase.elapsed().as_secs_f64());
+	#[cfg(feature = "sqlite")]
+	{
+		if let Some(mut x) = store.take() {
+			let stored = history.as_ref().map_or(Ok(()), |h| x.samples(&h.samples, &h.fractions))
+				.and_then(|_| x.timings(sim.steps, sim.t.to_f64(), clock.elapsed().as_secs_f64(), &sim.timers.run))
+				.and_then(|_| x.finish(sim.steps, sim.t.to_f64(), outcome.name(), clock.elapsed().as_secs_f64()));
+			if let Err(msg) = stored {
+				outcome = Outcome::IoError(msg);
+			}
+		}
+	}
+	if let Some(ref x) = sim.cosmology {
+		info!("Scale factor at t = {}: a = {} (z = {})", sim.t, x.a, x.redshift());
 	}
+	info!("Timing over {} steps: {}", sim.timers.run.steps, sim.timers.run);
+	Status { outcome: outcome, t: sim.t.to_f64(), steps: sim.steps, de: de, wall_seconds: 0.0, outputs: outputs }
 }
@@ -0,0 +1,73 @@
+/*
+ Helpers for systems with very unequal masses (planets around stars, a black
+ hole among stars). The defaults that work for an equal-mass cluster either
+ over-soften the light bodies or take far too long a step for their orbits.
+ */
+use real::c;
+use {pairs, Params, Real, Softening, Star};
+
+// Above this max/min mass ratio the run gets mass-ratio-aware defaults
+pub static EXTREME_RATIO: f64 = 1e3;
+// Fraction of the shortest per-star timescale used as step size
+pub static ETA: f64 = 0.01;
+
+pub fn mass_ratio<R: Real>(s: &Vec<Star<R>>) -> f64 {
+	let mut mmin = std::f64::INFINITY;
+	let mut mmax: f64 = 0.0;
+	for star in s {
+		let m = star.m.to_f64();
+		if m > 0.0 {
+			mmin = mmin.min(m);
+			mmax = mmax.max(m);
+		}
+	}
+	if mmax == 0.0 { 1.0 } else { mmax/mmin }
+}
+
+/*
+ Per-star dynamical timescale: the shortest sqrt(r^3/(m_i + m_j)) to any other
+ star, i.e. roughly the orbital time of its tightest pairing.
+ */
+pub fn timescales<R: Real>(s: &Vec<Star<R>>) -> Vec<R> {
+	let mut ts: Vec<R> = vec![R::infinity(); s.len()];
+	pairs::for_each_pair(s, |si, sj, _, r2| {
+		let mu = s[si].m + s[sj].m;
+		if mu > R::zero() {
+			let tij = (r2*r2.sqrt()/mu).sqrt();
+			ts[si] = ts[si].min(tij);
+			ts[sj] = ts[sj].min(tij);
+		}
+	});
+	ts
+}
+
+// Step size that resolves the fastest star with eta steps per timescale
+pub fn suggest_dt<R: Real>(s: &Vec<Star<R>>, eta: f64) -> R {
+	c::<R>(eta)*timescales(s).iter().fold(R::infinity(), |x, &t| x.min(t))
+}
+
+/*
+ Adjusts the settings the user did not set explicitly when the mass ratio is
+ extreme. Returns warnings describing what was changed.
+ */
+pub fn apply_defaults<R: Real>(s: &Vec<Star<R>>, p: &mut Params<R>, dt_given: bool, rule_given: bool) -> Vec<String> {
+	let mut warnings = vec![];
+	let ratio = mass_ratio(s);
+	if ratio < EXTREME_RATIO {
+		return warnings;
+	}
+	warnings.push(format!("Mass ratio {:e} exceeds {:e}, fixed-dt and fixed-softening defaults are unreliable", ratio, EXTREME_RATIO));
+
+	if !dt_given {
+		let dt = suggest_dt(s, ETA);
+		if dt < p.dt {
+			warnings.push(format!("Using dt = {:e} from the shortest per-star timescale (pass --dt to override)", dt));
+			p.dt = dt;
+		}
+	}
+	if !rule_given && p.eps > R::zero() && p.softening == Softening::Fixed {
+		warnings.push("Using the 'min' softening rule so light bodies are not over-softened (pass --softening-rule to override)".to_string());
+		p.softening = Softening::Min;
+	}
+	warnings
+}
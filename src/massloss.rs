@@ -0,0 +1,163 @@
+/*
+ Stellar mass loss. A model gives the fraction of its initial mass a star
+ keeps at time t; after every step Simulation sets the masses to match and
+ recomputes the forces. The mass is assumed to leave isotropically, so
+ positions and velocities stay as they are and the energy changes by the
+ kinetic and potential energy the lost mass carried. That change is not an
+ integration error: it is added up in MassEvolution::adjustment, which dE
+ is measured against. Measuring it costs two energy sums per step.
+
+   wind:  exponential wind, fraction = floor + (1 - floor) exp(-t/tau)
+   table: linear interpolation in a file of "t fraction" lines, constant
+          before the first and after the last
+
+ Times are internal times. Other models implement MassLoss and are passed to
+ Simulation::set_mass_loss().
+
+ "--mass-loss wind:tau=T[,floor=F]" or "--mass-loss table:PATH".
+ */
+use std::fs;
+
+use {Real, Star};
+
+pub trait MassLoss {
+	fn name(&self) -> &'static str;
+	// Fraction of its initial mass a star keeps at time t
+	fn fraction(&self, t: f64) -> f64;
+}
+
+pub struct Wind {
+	pub tau: f64,
+	pub floor: f64,
+}
+
+impl MassLoss for Wind {
+	fn name(&self) -> &'static str {
+		"wind"
+	}
+
+	fn fraction(&self, t: f64) -> f64 {
+		self.floor + (1.0 - self.floor)*(-t.max(0.0)/self.tau).exp()
+	}
+}
+
+pub struct Table {
+	// (t, fraction), sorted by t
+	pub points: Vec<(f64, f64)>,
+}
+
+impl Table {
+	// Reads "t fraction" lines, skipping blank lines and # comments
+	pub fn parse(text: &str) -> Result<Table, String> {
+		let mut points = vec![];
+		for (n, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let x: Vec<f64> = line.split_whitespace().map(|x| x.parse::<f64>()).collect::<Result<_, _>>()
+				.map_err(|_| format!("Bad mass-loss table line {}: '{}'", n + 1, line))?;
+			if x.len() != 2 || !x[0].is_finite() || !(x[1] > 0.0) {
+				return Err(format!("Mass-loss table line {} needs a time and a positive fraction, got '{}'", n + 1, line));
+			}
+			points.push((x[0], x[1]));
+		}
+		if points.is_empty() {
+			return Err(String::from("Mass-loss table is empty"));
+		}
+		points.sort_by(|a, b| a.0.partial_cmp(&b.0).expect("NaN time"));
+		Ok(Table { points: points })
+	}
+}
+
+impl MassLoss for Table {
+	fn name(&self) -> &'static str {
+		"table"
+	}
+
+	fn fraction(&self, t: f64) -> f64 {
+		let after = self.points.iter().position(|x| x.0 > t).unwrap_or(self.points.len());
+		if after == 0 {
+			return self.points[0].1;
+		}
+		if after == self.points.len() {
+			return self.points[after - 1].1;
+		}
+		let (a, b) = (self.points[after - 1], self.points[after]);
+		a.1 + (b.1 - a.1)*(t - a.0)/(b.0 - a.0)
+	}
+}
+
+// Parses a --mass-loss spec
+pub fn parse(spec: &str) -> Result<Box<dyn MassLoss>, String> {
+	let (kind, rest) = match spec.find(':') {
+		Some(i) => (&spec[..i], &spec[i + 1..]),
+		None => (spec, ""),
+	};
+	match kind {
+		"wind" => {
+			let (mut tau, mut floor) = (None, 0.0);
+			for item in rest.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+				let mut kv = item.splitn(2, '=');
+				let name = kv.next().unwrap_or("").trim();
+				let value = kv.next().and_then(|x| x.trim().parse::<f64>().ok()).ok_or(format!("Bad parameter '{}' in --mass-loss {}", item, spec))?;
+				match name {
+					"tau" if value > 0.0 => tau = Some(value),
+					"tau" => return Err(format!("tau must be positive, got {}", value)),
+					"floor" if (0.0..1.0).contains(&value) => floor = value,
+					"floor" => return Err(format!("floor must be in [0, 1), got {}", value)),
+					_ => return Err(format!("Unknown parameter '{}' for wind, use tau and floor", name)),
+				}
+			}
+			let tau = tau.ok_or(format!("--mass-loss wind needs tau=, got '{}'", spec))?;
+			Ok(Box::new(Wind { tau: tau, floor: floor }))
+		},
+		"table" => {
+			let text = fs::read_to_string(rest).map_err(|x| format!("Could not read mass-loss table '{}': {}", rest, x))?;
+			Ok(Box::new(Table::parse(&text)?))
+		},
+		_ => Err(format!("Unknown mass-loss model '{}', use wind or table", kind)),
+	}
+}
+
+pub struct MassEvolution {
+	pub model: Box<dyn MassLoss>,
+	// Initial mass of every star
+	pub initial: Vec<f64>,
+	// Fraction applied last
+	pub last: f64,
+	// Total energy change due to mass loss so far
+	pub adjustment: f64,
+}
+
+impl MassEvolution {
+	/*
+	 Starts from the masses at time t, so a run restarted from a checkpoint
+	 carries on with the same initial masses.
+	 */
+	pub fn new<R: Real>(model: Box<dyn MassLoss>, s: &Vec<Star<R>>, t: f64) -> MassEvolution {
+		let f = model.fraction(t);
+		let initial = s.iter().map(|x| x.m.to_f64()/f).collect();
+		MassEvolution { model: model, initial: initial, last: f, adjustment: 0.0 }
+	}
+
+	// Whether the masses at time t differ from the ones applied last
+	pub fn due(&self, t: f64) -> bool {
+		self.model.fraction(t) != self.last
+	}
+
+	// Sets the masses for time t
+	pub fn apply<R: Real>(&mut self, s: &mut Vec<Star<R>>, t: f64) {
+		let f = self.model.fraction(t);
+		for (star, &m0) in s.iter_mut().zip(self.initial.iter()) {
+			star.m = R::from_f64(m0*f);
+		}
+		self.last = f;
+	}
+
+	// Renumbers after star j was merged into star i, see collisions.rs
+	pub fn merged(&mut self, i: usize, j: usize) {
+		self.initial[i] += self.initial[j];
+		self.initial.remove(j);
+	}
+}
@@ -0,0 +1,181 @@
+/*
+ Where a result came from: the nbabel version and git commit, the resolved
+ configuration, a checksum of the input, the host and the start time. Runs
+ write it into every checkpoint and snapshot, so a file found on its own
+ still says how to reproduce it.
+
+ In checkpoints it follows the header as comment lines that readers skip,
+
+ # meta version = 0.1.0
+ # meta git = 3c5fb59a1e2d
+ # meta host = node17
+ # meta start = 2026-10-16T09:30:00Z
+ # meta input_sha256 = 9f86d081...
+ # meta command = --dt 1e-3 --out run1
+ # meta config = --dt 0.001 --softening 0 ...
+
+ and VTK snapshots carry the same lines in an XML comment. The git commit is
+ taken at build time (build.rs) and is "unknown" outside a checkout, with
+ "-dirty" appended when the tree had uncommitted changes.
+ */
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+	pub version: String,
+	pub git: String,
+	pub host: String,
+	pub start: String,
+	pub input_sha256: String,
+	// The arguments as given, and every setting after defaults and tuning, as flags
+	pub command: String,
+	pub config: String,
+}
+
+impl Metadata {
+	// For a run starting now on input
+	pub fn new(input: &[u8], command: &[String], config: &[(String, String)]) -> Metadata {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+		Metadata {
+			version: String::from(env!("CARGO_PKG_VERSION")),
+			git: String::from(option_env!("NBABEL_GIT").unwrap_or("unknown")),
+			host: hostname(),
+			start: utc(now),
+			input_sha256: sha256(input),
+			command: command.join(" "),
+			config: config.iter().map(|x| if x.1.is_empty() { x.0.clone() } else { format!("{} {}", x.0, x.1) }).collect::<Vec<_>>().join(" "),
+		}
+	}
+
+	pub fn pairs(&self) -> Vec<(&'static str, &str)> {
+		vec![
+			("version", &self.version),
+			("git", &self.git),
+			("host", &self.host),
+			("start", &self.start),
+			("input_sha256", &self.input_sha256),
+			("command", &self.command),
+			("config", &self.config),
+		]
+	}
+
+	// As "# meta key = value" lines
+	pub fn write_comments<W: Write>(&self, w: &mut W) -> io::Result<()> {
+		for (key, value) in self.pairs() {
+			writeln!(w, "# meta {} = {}", key, value)?;
+		}
+		Ok(())
+	}
+
+	// The "# meta" lines of a file, if it has them
+	pub fn read(text: &str) -> Option<Metadata> {
+		let mut x = Metadata::default();
+		let mut found = false;
+		for line in text.lines().take_while(|x| x.starts_with('#')) {
+			let rest = match line.strip_prefix("# meta ") {
+				Some(rest) => rest,
+				None => continue,
+			};
+			let (key, value) = match rest.find(" = ") {
+				Some(at) => (&rest[..at], rest[at + 3..].to_string()),
+				None => (rest.trim_end_matches(" ="), String::new()),
+			};
+			let field = match key {
+				"version" => &mut x.version,
+				"git" => &mut x.git,
+				"host" => &mut x.host,
+				"start" => &mut x.start,
+				"input_sha256" => &mut x.input_sha256,
+				"command" => &mut x.command,
+				"config" => &mut x.config,
+				_ => continue,
+			};
+			*field = value;
+			found = true;
+		}
+		if found { Some(x) } else { None }
+	}
+}
+
+// The machine's name, from the kernel or the environment
+pub fn hostname() -> String {
+	let name = fs::read_to_string("/proc/sys/kernel/hostname")
+		.or_else(|_| fs::read_to_string("/etc/hostname"))
+		.ok()
+		.or_else(|| env::var("HOSTNAME").ok())
+		.or_else(|| env::var("COMPUTERNAME").ok())
+		.map(|x| x.trim().to_string())
+		.unwrap_or_default();
+	if name.is_empty() { String::from("unknown") } else { name }
+}
+
+// Seconds since 1970 as ISO 8601 UTC, 2026-10-16T09:30:00Z
+pub fn utc(seconds: u64) -> String {
+	let days = (seconds/86400) as i64;
+	let rem = seconds % 86400;
+	// Civil date from days since the epoch (Hinnant)
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z - era*146097;
+	let yoe = (doe - doe/1460 + doe/36524 - doe/146096)/365;
+	let doy = doe - (365*yoe + yoe/4 - yoe/100);
+	let mp = (5*doy + 2)/153;
+	let day = doy - (153*mp + 2)/5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era*400 + if month <= 2 { 1 } else { 0 };
+	format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem/3600, rem/60 % 60, rem % 60)
+}
+
+static K: [u32; 64] = [
+	0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+	0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+	0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+	0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+	0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+	0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+	0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+	0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/*
+ SHA-256 (FIPS 180-4) as lowercase hex, the same as sha256sum prints for the
+ input file, so a published input can be matched to the run.
+ */
+pub fn sha256(data: &[u8]) -> String {
+	let mut h: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+	let mut message = data.to_vec();
+	message.push(0x80);
+	while message.len() % 64 != 56 {
+		message.push(0);
+	}
+	message.extend_from_slice(&((data.len() as u64)*8).to_be_bytes());
+	for block in message.chunks(64) {
+		let mut w = [0u32; 64];
+		for t in 0..16 {
+			w[t] = u32::from_be_bytes([block[4*t], block[4*t + 1], block[4*t + 2], block[4*t + 3]]);
+		}
+		for t in 16..64 {
+			let s0 = w[t - 15].rotate_right(7) ^ w[t - 15].rotate_right(18) ^ (w[t - 15] >> 3);
+			let s1 = w[t - 2].rotate_right(17) ^ w[t - 2].rotate_right(19) ^ (w[t - 2] >> 10);
+			w[t] = w[t - 16].wrapping_add(s0).wrapping_add(w[t - 7]).wrapping_add(s1);
+		}
+		let mut x = h;
+		for t in 0..64 {
+			let s1 = x[4].rotate_right(6) ^ x[4].rotate_right(11) ^ x[4].rotate_right(25);
+			let ch = (x[4] & x[5]) ^ (!x[4] & x[6]);
+			let t1 = x[7].wrapping_add(s1).wrapping_add(ch).wrapping_add(K[t]).wrapping_add(w[t]);
+			let s0 = x[0].rotate_right(2) ^ x[0].rotate_right(13) ^ x[0].rotate_right(22);
+			let maj = (x[0] & x[1]) ^ (x[0] & x[2]) ^ (x[1] & x[2]);
+			let t2 = s0.wrapping_add(maj);
+			x = [t1.wrapping_add(t2), x[0], x[1], x[2], x[3].wrapping_add(t1), x[4], x[5], x[6]];
+		}
+		for k in 0..8 {
+			h[k] = h[k].wrapping_add(x[k]);
+		}
+	}
+	h.iter().map(|x| format!("{:08x}", x)).collect()
+}
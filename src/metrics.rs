@@ -0,0 +1,147 @@
+/*
+ Prometheus metrics for watching long runs, built with the "metrics" feature
+ and switched on with --metrics HOST:PORT. GET /metrics answers in the text
+ exposition format:
+
+   nbabel_time                      simulated time
+   nbabel_steps_total               steps taken
+   nbabel_steps_per_second          steps per second spent in the phases
+   nbabel_stars                     stars left
+   nbabel_energy                    total energy at the last diagnostic
+   nbabel_energy_error              relative energy error dE at the last diagnostic
+   nbabel_phase_seconds_total       seconds per phase, labelled phase=forces|...
+   nbabel_wall_seconds              wall-clock time since the run started
+   process_resident_memory_bytes    resident memory (Linux only)
+   nbabel_build_info                1, labelled with the version and git commit
+
+ Unlike the HTTP API the answers do not wait for the main loop: it leaves
+ the latest values behind after every step (update), and a background thread
+ serves them, so a scrape never has to wait for a slow step.
+ */
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use timing::{Times, PHASES};
+
+// What a scrape reports
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Gauges {
+	pub t: f64,
+	pub steps: usize,
+	pub stars: usize,
+	pub energy: f64,
+	pub de: f64,
+	pub wall_seconds: f64,
+	pub times: Times,
+}
+
+// Resident set size from /proc, where there is one
+pub fn resident_memory() -> Option<u64> {
+	let status = fs::read_to_string("/proc/self/status").ok()?;
+	let line = status.lines().find(|x| x.starts_with("VmRSS:"))?;
+	let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+	Some(kb*1024)
+}
+
+fn escape(x: &str) -> String {
+	x.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// One metric with its help and type lines
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, f64)]) {
+	out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+	for &(ref labels, x) in samples {
+		out.push_str(&format!("{}{} {}\n", name, labels, x));
+	}
+}
+
+/*
+ The text exposition of g, with the build labels version and git. Phase
+ names become labels in lower case, I/O as io.
+ */
+pub fn exposition(g: &Gauges, version: &str, git: &str, memory: Option<u64>) -> String {
+	let mut out = String::new();
+	let one = |x: f64| vec![(String::new(), x)];
+	let busy = g.times.total();
+	let rate = if busy > 0.0 { g.times.steps as f64/busy } else { 0.0 };
+	metric(&mut out, "nbabel_time", "gauge", "Simulated time in N-body units.", &one(g.t));
+	metric(&mut out, "nbabel_steps_total", "counter", "Integration steps taken.", &one(g.steps as f64));
+	metric(&mut out, "nbabel_steps_per_second", "gauge", "Steps per second of time spent in the phases.", &one(rate));
+	metric(&mut out, "nbabel_stars", "gauge", "Stars in the system.", &one(g.stars as f64));
+	metric(&mut out, "nbabel_energy", "gauge", "Total energy at the last diagnostic.", &one(g.energy));
+	metric(&mut out, "nbabel_energy_error", "gauge", "Relative energy error dE at the last diagnostic.", &one(g.de));
+	let phases: Vec<(String, f64)> = PHASES.iter().map(|&x| (format!("{{phase=\"{}\"}}", if x.name() == "I/O" { "io" } else { x.name() }), g.times.get(x))).collect();
+	metric(&mut out, "nbabel_phase_seconds_total", "counter", "Wall-clock seconds spent per phase.", &phases);
+	metric(&mut out, "nbabel_wall_seconds", "gauge", "Wall-clock seconds since the run started.", &one(g.wall_seconds));
+	if let Some(bytes) = memory {
+		metric(&mut out, "process_resident_memory_bytes", "gauge", "Resident memory size in bytes.", &one(bytes as f64));
+	}
+	metric(&mut out, "nbabel_build_info", "gauge", "Version and git commit of nbabel.", &[(format!("{{version=\"{}\",git=\"{}\"}}", escape(version), escape(git)), 1.0)]);
+	out
+}
+
+fn connection(stream: &mut TcpStream, body: &dyn Fn() -> String) -> io::Result<()> {
+	let mut reader = BufReader::new(stream.try_clone()?);
+	let mut first = String::new();
+	reader.read_line(&mut first)?;
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+			break;
+		}
+	}
+	let mut words = first.split_whitespace();
+	let (method, path) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+	let (status, content_type, body) = if method == "GET" && path.split('?').next() == Some("/metrics") {
+		("200 OK", "text/plain; version=0.0.4", body())
+	} else {
+		("404 Not Found", "text/plain", String::from("Metrics are at GET /metrics\n"))
+	};
+	write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, content_type, body.len(), body)?;
+	stream.flush()
+}
+
+pub struct Metrics {
+	addr: SocketAddr,
+	gauges: Arc<Mutex<Gauges>>,
+}
+
+impl Metrics {
+	// Serves /metrics on address, HOST:PORT (port 0 picks a free one)
+	pub fn bind(address: &str, version: &str, git: &str) -> Result<Metrics, String> {
+		let listener = TcpListener::bind(address).map_err(|x| format!("Could not listen on {}: {}", address, x))?;
+		let addr = listener.local_addr().map_err(|x| x.to_string())?;
+		let gauges = Arc::new(Mutex::new(Gauges::default()));
+		let shared = gauges.clone();
+		let (version, git) = (String::from(version), String::from(git));
+		thread::spawn(move || {
+			let body = || {
+				let g = shared.lock().map(|x| x.clone()).unwrap_or_default();
+				exposition(&g, &version, &git, resident_memory())
+			};
+			for mut stream in listener.incoming().flatten() {
+				let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+				if let Err(x) = connection(&mut stream, &body) {
+					verbose!("Metrics request failed: {}", x);
+				}
+			}
+		});
+		Ok(Metrics { addr: addr, gauges: gauges })
+	}
+
+	pub fn local_addr(&self) -> SocketAddr {
+		self.addr
+	}
+
+	// The values the next scrapes see
+	pub fn update(&self, g: Gauges) {
+		if let Ok(mut x) = self.gauges.lock() {
+			*x = g;
+		}
+	}
+}
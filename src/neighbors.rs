@@ -0,0 +1,121 @@
+/*
+ Nearest neighbours through a k-d tree, for the local densities in
+ diagnostics.rs and anyone else who needs them. The tree splits the stars
+ at the median of the axis with the largest spread until at most LEAF are
+ left, so a query visits O(log N) leaves for small k.
+ */
+use {Real, Star};
+
+static LEAF: usize = 8;
+
+struct Node {
+	// Split axis and value, or the stars of a leaf in order[lo..hi]
+	axis: usize,
+	split: f64,
+	lo: usize,
+	hi: usize,
+	children: Option<(usize, usize)>,
+}
+
+pub struct KdTree {
+	nodes: Vec<Node>,
+	order: Vec<usize>,
+	pos: Vec<[f64; 3]>,
+}
+
+fn distance2(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+	(a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+impl KdTree {
+	pub fn build<R: Real>(s: &Vec<Star<R>>) -> KdTree {
+		let mut tree = KdTree {
+			nodes: vec![],
+			order: (0..s.len()).collect(),
+			pos: s.iter().map(|x| [x.r[0].to_f64(), x.r[1].to_f64(), x.r[2].to_f64()]).collect(),
+		};
+		if !s.is_empty() {
+			tree.add(0, s.len());
+		}
+		tree
+	}
+
+	fn add(&mut self, lo: usize, hi: usize) -> usize {
+		let idx = self.nodes.len();
+		self.nodes.push(Node { axis: 0, split: 0.0, lo: lo, hi: hi, children: None });
+		if hi - lo <= LEAF {
+			return idx;
+		}
+		let mut min = [std::f64::INFINITY; 3];
+		let mut max = [-std::f64::INFINITY; 3];
+		for &i in &self.order[lo..hi] {
+			for k in 0..3 {
+				min[k] = min[k].min(self.pos[i][k]);
+				max[k] = max[k].max(self.pos[i][k]);
+			}
+		}
+		let axis = (0..3).fold(0, |a, k| if max[k] - min[k] > max[a] - min[a] { k } else { a });
+		if max[axis] == min[axis] {
+			// All coincident, no split can separate them
+			return idx;
+		}
+		let mid = (lo + hi)/2;
+		let pos = &self.pos;
+		self.order[lo..hi].select_nth_unstable_by(mid - lo, |&a, &b| pos[a][axis].partial_cmp(&pos[b][axis]).unwrap());
+		let split = self.pos[self.order[mid]][axis];
+		let left = self.add(lo, mid);
+		let right = self.add(mid, hi);
+		self.nodes[idx].axis = axis;
+		self.nodes[idx].split = split;
+		self.nodes[idx].children = Some((left, right));
+		idx
+	}
+
+	/*
+	 The k stars closest to star i, not counting i, nearest first, with their
+	 squared distances. Fewer when there are not k other stars.
+	 */
+	pub fn nearest(&self, i: usize, k: usize) -> Vec<(usize, f64)> {
+		let mut best: Vec<(usize, f64)> = Vec::with_capacity(k + 1);
+		if k == 0 || self.nodes.is_empty() {
+			return best;
+		}
+		let x = self.pos[i];
+		// Nodes still to visit with a lower bound on their squared distance
+		let mut stack: Vec<(usize, f64)> = vec![(0, 0.0)];
+		while let Some((idx, bound)) = stack.pop() {
+			if best.len() == k && bound >= best[k - 1].1 {
+				continue;
+			}
+			let node = &self.nodes[idx];
+			match node.children {
+				None => {
+					for &j in &self.order[node.lo..node.hi] {
+						if j == i {
+							continue;
+						}
+						let d2 = distance2(&x, &self.pos[j]);
+						if best.len() < k || d2 < best[k - 1].1 {
+							let at = best.iter().position(|&(_, d)| d > d2).unwrap_or(best.len());
+							best.insert(at, (j, d2));
+							best.truncate(k);
+						}
+					}
+				},
+				Some((left, right)) => {
+					let gap = x[node.axis] - node.split;
+					let (near, far) = if gap < 0.0 { (left, right) } else { (right, left) };
+					// Far side last on the stack, so it is visited after the near side has tightened the bound
+					stack.push((far, bound.max(gap*gap)));
+					stack.push((near, bound));
+				},
+			}
+		}
+		best
+	}
+
+	// Indices of the k nearest neighbours of star i, nearest first
+	pub fn neighbors(&self, i: usize, k: usize) -> Vec<usize> {
+		self.nearest(i, k).into_iter().map(|(j, _)| j).collect()
+	}
+}
@@ -0,0 +1,99 @@
+/*
+ Standard N-body (Hénon) units: G = 1, total mass M = 1 and total energy
+ E = -1/4. normalize() moves a bound system to its centre-of-mass frame and
+ rescales masses, lengths and velocities to those units without changing its
+ dynamical state (the virial ratio stays the same). The input is taken to
+ use G = 1 already, e.g. an N-body model in some other scaling.
+
+ The returned Scale holds the size of one N-body unit in the input units, so
+ multiplying N-body results by it converts them back.
+
+ "nbabel normalize < input > output" does this to a file and reports the
+ factors in the output header; the driver's --normalize does it on the fly.
+ */
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::process;
+
+use columns::Columns;
+use {energies, parse_stars, write_stars_with, Params, Real, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Scale {
+	pub mass: f64,
+	pub length: f64,
+	pub velocity: f64,
+	pub time: f64,
+	pub energy: f64,
+	// Centre of mass position and velocity that were subtracted
+	pub com: [f64; 3],
+	pub vcom: [f64; 3],
+}
+
+impl fmt::Display for Scale {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "one N-body unit is mass {:e}, length {:e}, velocity {:e}, time {:e}, energy {:e} in input units; \
+			centre of mass at {:?} moving at {:?} removed", self.mass, self.length, self.velocity, self.time, self.energy, self.com, self.vcom)
+	}
+}
+
+/*
+ Rescales s in place to N-body units. p supplies the softening used for the
+ potential energy, in input units. Fails for massless or unbound systems.
+ */
+pub fn normalize<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>) -> Result<Scale, String> {
+	let mass: R = s.iter().map(|x| x.m).sum();
+	if !(mass > R::zero()) {
+		return Err(format!("Cannot normalize a system with total mass {}", mass));
+	}
+	let mut com = [0.0; 3];
+	let mut vcom = [0.0; 3];
+	for k in 0..3 {
+		let rc = s.iter().map(|x| x.m*x.r[k]).sum::<R>()/mass;
+		let vc = s.iter().map(|x| x.m*x.v[k]).sum::<R>()/mass;
+		for star in s.iter_mut() {
+			star.r[k] -= rc;
+			star.v[k] -= vc;
+		}
+		com[k] = rc.to_f64();
+		vcom[k] = vc.to_f64();
+	}
+	let e = energies(s, p)[0].to_f64();
+	if !(e < 0.0) {
+		return Err(format!("Cannot normalize an unbound system, E = {}", e));
+	}
+
+	// E in units of G M^2/L must come out as -1/4
+	let m = mass.to_f64();
+	let length = -m*m/(4.0*e);
+	let velocity = (m/length).sqrt();
+	let (mr, lr, vr) = (R::from_f64(m), R::from_f64(length), R::from_f64(velocity));
+	for star in s.iter_mut() {
+		star.m /= mr;
+		for k in 0..3 {
+			star.r[k] /= lr;
+			star.v[k] /= vr;
+		}
+	}
+	Ok(Scale { mass: m, length: length, velocity: velocity, time: length/velocity, energy: m*m/length, com: com, vcom: vcom })
+}
+
+pub fn main(args: &[String]) {
+	if !args.is_empty() {
+		panic!("Usage: nbabel normalize < input > output");
+	}
+	let mut text = String::new();
+	io::stdin().read_to_string(&mut text).expect("Could not read input");
+	let mut s: Vec<Star> = parse_stars(&text).unwrap_or_else(|x| {
+		error!("Invalid input, {}", x);
+		process::exit(x.exit_code());
+	});
+	let columns = Columns::read(&text).unwrap_or_else(|msg| panic!("{}", msg));
+	let scale = normalize(&mut s, &Params::default()).unwrap_or_else(|msg| panic!("{}", msg));
+
+	let stdout = io::stdout();
+	let mut out = io::BufWriter::new(stdout.lock());
+	writeln!(out, "# nbabel normalize: {}", scale).expect("Could not write output");
+	write_stars_with(&mut out, &s, &columns).expect("Could not write output");
+}
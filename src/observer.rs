@@ -0,0 +1,58 @@
+/*
+ Hooks for library users who want to look at a run while it goes, e.g. to
+ follow one star or collect their own statistics, without copying the main
+ loop. Register an Observer with Simulation::observe(); on_step is called at
+ the end of every step and on_snapshot whenever the driver takes a
+ diagnostic (Simulation::snapshot(), which the command line calls on every
+ diagnostic line). Both get the state read-only. Time spent in observers is
+ not counted in any phase of the timers.
+
+ The simulation owns what it is given, so to read the results afterwards
+ register an Rc<RefCell<_>> of the observer and keep a clone of it.
+ */
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use {Real, Star};
+
+pub trait Observer<R: Real> {
+	// After every step, with the time reached
+	fn on_step(&mut self, _t: R, _s: &Vec<Star<R>>) {}
+
+	// At every diagnostic, with the energies [E, T, W]
+	fn on_snapshot(&mut self, _t: R, _s: &Vec<Star<R>>, _e: &[R]) {}
+}
+
+impl<R: Real, O: Observer<R>> Observer<R> for Rc<RefCell<O>> {
+	fn on_step(&mut self, t: R, s: &Vec<Star<R>>) {
+		self.borrow_mut().on_step(t, s);
+	}
+
+	fn on_snapshot(&mut self, t: R, s: &Vec<Star<R>>, e: &[R]) {
+		self.borrow_mut().on_snapshot(t, s, e);
+	}
+}
+
+/*
+ Records the position of one star after every step, the simplest useful
+ observer and an example of how to write one.
+ */
+pub struct Track<R = f64> {
+	pub index: usize,
+	// t, x, y, z
+	pub path: Vec<[R; 4]>,
+}
+
+impl<R: Real> Track<R> {
+	pub fn new(index: usize) -> Track<R> {
+		Track { index: index, path: vec![] }
+	}
+}
+
+impl<R: Real> Observer<R> for Track<R> {
+	fn on_step(&mut self, t: R, s: &Vec<Star<R>>) {
+		if let Some(star) = s.get(self.index) {
+			self.path.push([t, star.r[0], star.r[1], star.r[2]]);
+		}
+	}
+}
@@ -0,0 +1,153 @@
+/*
+ Keplerian elements of a two-body relative orbit and back (G = 1, total mass
+ mu). Used by the binary detection to describe pairs and by the generator to
+ place bodies on given orbits.
+
+ Bound orbits have a > 0 and e < 1, unbound ones a < 0 and e > 1, with the
+ mean anomaly taken from the hyperbolic anomaly, M = e sinh H - H. The exact
+ parabola (e = 1, a infinite) is not covered. Angles are in radians with x-y
+ as the reference plane; where the node or the pericentre is undefined
+ (equatorial or circular orbits) it is measured from the x axis, or from
+ the node, and the other angle set to zero.
+ */
+use std::f64::consts::PI;
+
+use {Real, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Elements {
+	pub a: f64,
+	pub e: f64,
+	// Inclination, longitude of the ascending node and argument of pericentre
+	pub i: f64,
+	pub node: f64,
+	pub peri: f64,
+	// In [0, 2 pi) for bound orbits, negative before pericentre for unbound ones
+	pub mean_anomaly: f64,
+}
+
+impl Elements {
+	// Radians per unit time: sqrt(mu/|a|^3)
+	pub fn mean_motion(&self, mu: f64) -> f64 {
+		(mu/self.a.abs().powi(3)).sqrt()
+	}
+
+	// Orbital period; infinite for unbound orbits
+	pub fn period(&self, mu: f64) -> f64 {
+		if self.e < 1.0 { 2.0*PI/self.mean_motion(mu) } else { std::f64::INFINITY }
+	}
+
+	/*
+	 Time since the last pericentre passage; for an unbound orbit, negative
+	 while the pericentre is still ahead.
+	 */
+	pub fn time_since_periapsis(&self, mu: f64) -> f64 {
+		self.mean_anomaly/self.mean_motion(mu)
+	}
+
+	// Pericentre distance
+	pub fn periapsis(&self) -> f64 {
+		self.a*(1.0 - self.e)
+	}
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+	a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+	[a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]]
+}
+
+fn unit(a: [f64; 3]) -> [f64; 3] {
+	let n = dot(a, a).sqrt();
+	[a[0]/n, a[1]/n, a[2]/n]
+}
+
+// Below this e counts as circular and sin(i) as equatorial
+static TINY: f64 = 1e-12;
+
+// The elements of relative position r and velocity v about total mass mu
+pub fn to_elements(mu: f64, r: [f64; 3], v: [f64; 3]) -> Elements {
+	let d = dot(r, r).sqrt();
+	let h = cross(r, v);
+	let hn = dot(h, h).sqrt();
+	let energy = 0.5*dot(v, v) - mu/d;
+	let a = -mu/(2.0*energy);
+	let vh = cross(v, h);
+	let ev = [vh[0]/mu - r[0]/d, vh[1]/mu - r[1]/d, vh[2]/mu - r[2]/d];
+	let e = dot(ev, ev).sqrt();
+	let i = (h[2]/hn).max(-1.0).min(1.0).acos();
+
+	let z = unit(h);
+	let n = [-h[1], h[0], 0.0];
+	let equatorial = dot(n, n).sqrt() <= TINY*hn;
+	let node_dir = if equatorial { [1.0, 0.0, 0.0] } else { unit(n) };
+	let node = if equatorial { 0.0 } else { n[1].atan2(n[0]).rem_euclid(2.0*PI) };
+	let p = if e > TINY { [ev[0]/e, ev[1]/e, ev[2]/e] } else { node_dir };
+	let q = cross(z, p);
+	let peri = dot(cross(node_dir, p), z).atan2(dot(node_dir, p)).rem_euclid(2.0*PI);
+	let f = dot(r, q).atan2(dot(r, p));
+
+	let mean_anomaly = if e < 1.0 {
+		let ecc = 2.0*(((1.0 - e)/(1.0 + e)).sqrt()*(0.5*f).tan()).atan();
+		(ecc - e*ecc.sin()).rem_euclid(2.0*PI)
+	} else {
+		let hyp = 2.0*(((e - 1.0)/(e + 1.0)).sqrt()*(0.5*f).tan()).atanh();
+		e*hyp.sinh() - hyp
+	};
+	Elements { a: a, e: e, i: i, node: node, peri: peri, mean_anomaly: mean_anomaly }
+}
+
+// Relative position and velocity for the elements x about total mass mu
+pub fn from_elements(mu: f64, x: &Elements) -> ([f64; 3], [f64; 3]) {
+	let (e, m) = (x.e, x.mean_anomaly);
+	let (orbit, speed) = if e < 1.0 {
+		// Kepler's equation by Newton iteration
+		let mut ecc = if e < 0.8 { m } else { PI };
+		for _ in 0..50 {
+			let step = (ecc - e*ecc.sin() - m)/(1.0 - e*ecc.cos());
+			ecc -= step;
+			if step.abs() < 1e-15 {
+				break;
+			}
+		}
+		let b = (1.0 - e*e).sqrt();
+		let rate = x.mean_motion(mu)/(1.0 - e*ecc.cos());
+		([x.a*(ecc.cos() - e), x.a*b*ecc.sin()], [-x.a*ecc.sin()*rate, x.a*b*ecc.cos()*rate])
+	} else {
+		let a = x.a.abs();
+		let mut hyp = (m/e).asinh();
+		for _ in 0..100 {
+			let step = (e*hyp.sinh() - hyp - m)/(e*hyp.cosh() - 1.0);
+			hyp -= step;
+			if step.abs() < 1e-15*hyp.abs().max(1.0) {
+				break;
+			}
+		}
+		let b = (e*e - 1.0).sqrt();
+		let rate = x.mean_motion(mu)/(e*hyp.cosh() - 1.0);
+		([a*(e - hyp.cosh()), a*b*hyp.sinh()], [-a*hyp.sinh()*rate, a*b*hyp.cosh()*rate])
+	};
+
+	// Rotate by the argument of pericentre, the inclination and the node
+	let (cw, sw, ci, si, cn, sn) = (x.peri.cos(), x.peri.sin(), x.i.cos(), x.i.sin(), x.node.cos(), x.node.sin());
+	let rotate = |x: f64, y: f64| [
+		(cn*cw - sn*sw*ci)*x - (cn*sw + sn*cw*ci)*y,
+		(sn*cw + cn*sw*ci)*x - (sn*sw - cn*cw*ci)*y,
+		sw*si*x + cw*si*y,
+	];
+	(rotate(orbit[0], orbit[1]), rotate(speed[0], speed[1]))
+}
+
+// The elements of star j about star i, and their total mass
+pub fn of_pair<R: Real>(s: &Vec<Star<R>>, i: usize, j: usize) -> (Elements, f64) {
+	let mu = (s[i].m + s[j].m).to_f64();
+	let mut r = [0.0; 3];
+	let mut v = [0.0; 3];
+	for k in 0..3 {
+		r[k] = (s[j].r[k] - s[i].r[k]).to_f64();
+		v[k] = (s[j].v[k] - s[i].v[k]).to_f64();
+	}
+	(to_elements(mu, r, v), mu)
+}
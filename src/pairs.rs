@@ -0,0 +1,215 @@
+/*
+ The triangular i < j pair loop that forces, energies and the diagnostics all
+ need. Pairs are visited in tile x tile blocks so both blocks of stars stay
+ in cache, and the visitor gets the separation vector r_i - r_j together with
+ its squared length. The loops are generic over the number of components D
+ of that vector (see Params::dim); the plain names are the 3D loops almost
+ everything uses, the _dim ones take D from the visitor.
+ */
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use timing::Clock;
+use {Params, Real, Star};
+
+// Default stars per tile side: two tiles of 64 stars fit in a 32 KiB L1
+pub static BLOCK: usize = 64;
+
+static TILE: AtomicUsize = AtomicUsize::new(BLOCK);
+
+/*
+ Sets the tile side for all pair loops in the process. The best value depends
+ on the cache sizes of the machine; a tile at least as large as N turns the
+ tiling off, which is how the untiled loop is benchmarked.
+ */
+pub fn set_tile_size(n: usize) {
+	TILE.store(n.max(1), Ordering::Relaxed);
+}
+
+pub fn tile_size() -> usize {
+	TILE.load(Ordering::Relaxed)
+}
+
+fn separation<R: Real, const D: usize>(s: &[Star<R>], i: usize, j: usize) -> ([R; D], R) {
+	let mut rij = [R::zero(); D];
+	let mut r2 = R::zero();
+	for k in 0..D {
+		rij[k] = s[i].r[k] - s[j].r[k];
+		r2 += rij[k]*rij[k];
+	}
+	(rij, r2)
+}
+
+/*
+ Visits every pair with i in lo..hi and j > i, skipping pairs further apart
+ than sqrt(cutoff2).
+ */
+fn visit_rows<R: Real, F, const D: usize>(s: &[Star<R>], lo: usize, hi: usize, cutoff2: R, f: &mut F)
+	where F: FnMut(usize, usize, &[R; D], R)
+{
+	let n = s.len();
+	let block = tile_size();
+	let mut ib = lo;
+	while ib < hi {
+		let ie = (ib + block).min(hi);
+		let mut jb = ib;
+		while jb < n {
+			let je = (jb + block).min(n);
+			for i in ib..ie {
+				for j in (i + 1).max(jb)..je {
+					let (rij, r2) = separation(s, i, j);
+					if r2 <= cutoff2 {
+						f(i, j, &rij, r2);
+					}
+				}
+			}
+			jb = je;
+		}
+		ib = ie;
+	}
+}
+
+pub fn for_each_pair<R: Real, F>(s: &[Star<R>], f: F)
+	where F: FnMut(usize, usize, &[R; 3], R)
+{
+	for_each_pair_dim(s, f);
+}
+
+pub fn for_each_pair_dim<R: Real, F, const D: usize>(s: &[Star<R>], mut f: F)
+	where F: FnMut(usize, usize, &[R; D], R)
+{
+	visit_rows(s, 0, s.len(), R::infinity(), &mut f);
+}
+
+// Only pairs closer than cutoff
+pub fn for_each_pair_within<R: Real, F>(s: &[Star<R>], cutoff: R, mut f: F)
+	where F: FnMut(usize, usize, &[R; 3], R)
+{
+	visit_rows(s, 0, s.len(), cutoff*cutoff, &mut f);
+}
+
+/*
+ Parallel pair loop. Every thread gets its own copy of init to accumulate
+ into, and reduce(thread_index, accumulator, busy_seconds) is called on the
+ calling thread as soon as each thread is done.
+ */
+pub fn par_for_each_pair<R: Real, T, V, F>(s: &[Star<R>], threads: usize, init: T, visit: V, reduce: F)
+	where T: Clone + Send, V: Fn(&mut T, usize, usize, &[R; 3], R) + Sync, F: FnMut(usize, T, f64)
+{
+	par_for_each_pair_dim(s, threads, init, visit, reduce);
+}
+
+pub fn par_for_each_pair_dim<R: Real, T, V, F, const D: usize>(s: &[Star<R>], threads: usize, init: T, visit: V, reduce: F)
+	where T: Clone + Send, V: Fn(&mut T, usize, usize, &[R; D], R) + Sync, F: FnMut(usize, T, f64)
+{
+	par_visit(s, threads, R::infinity(), init, visit, reduce);
+}
+
+pub fn par_for_each_pair_within<R: Real, T, V, F>(s: &[Star<R>], threads: usize, cutoff: R, init: T, visit: V, reduce: F)
+	where T: Clone + Send, V: Fn(&mut T, usize, usize, &[R; 3], R) + Sync, F: FnMut(usize, T, f64)
+{
+	par_visit(s, threads, cutoff*cutoff, init, visit, reduce);
+}
+
+/*
+ Row boundaries that give every thread about the same number of pairs. Row i
+ has n-1-i partners, so equal row counts would leave the first thread with
+ almost twice the average work and the last with almost none. Thread t gets
+ rows bounds[t]..bounds[t+1].
+ */
+pub fn partition(n: usize, threads: usize) -> Vec<usize> {
+	let total = n*n.saturating_sub(1)/2;
+	let mut bounds = vec![0];
+	let mut row = 0;
+	let mut done = 0;
+	for t in 1..threads {
+		let target = total*t/threads;
+		while row < n && done + (n - 1 - row) <= target {
+			done += n - 1 - row;
+			row += 1;
+		}
+		bounds.push(row);
+	}
+	bounds.push(n);
+	bounds
+}
+
+fn par_visit<R: Real, T, V, F, const D: usize>(s: &[Star<R>], threads: usize, cutoff2: R, init: T, visit: V, mut reduce: F)
+	where T: Clone + Send, V: Fn(&mut T, usize, usize, &[R; D], R) + Sync, F: FnMut(usize, T, f64)
+{
+	if threads <= 1 {
+		// On the calling thread, also where there are no threads (wasm32)
+		let clock = Clock::start();
+		let mut acc = init;
+		visit_rows(s, 0, s.len(), cutoff2, &mut |i, j, rij: &[R; D], r2| visit(&mut acc, i, j, rij, r2));
+		reduce(0, acc, clock.seconds());
+		return;
+	}
+	let bounds = &partition(s.len(), threads);
+	let visit = &visit;
+	let (tx, rx) = mpsc::channel();
+	thread::scope(|scope| {
+		for thread_index in 0..threads {
+			let tx = tx.clone();
+			let mut acc = init.clone();
+			scope.spawn(move || {
+				let clock = Clock::start();
+				visit_rows(s, bounds[thread_index], bounds[thread_index + 1], cutoff2, &mut |i, j, rij: &[R; D], r2| visit(&mut acc, i, j, rij, r2));
+				tx.send((thread_index, acc, clock.seconds())).expect("Thread failure, RIP");
+			});
+		}
+		for _ in 0..threads {
+			let (thread_index, acc, busy) = rx.recv().expect("RIP");
+			reduce(thread_index, acc, busy);
+		}
+	});
+}
+
+/*
+ Accelerations of the stars in rows lo..hi, each summed over all other stars.
+ That does every pair twice, but rows are independent, so the work can be
+ split by star between processes or devices that each fill in their own
+ stars. Runs on threads threads (equal row counts, as every row costs the
+ same) and returns the accelerations and the busy seconds per thread.
+ */
+pub fn par_rows<R: Real>(s: &[Star<R>], eps: &[R], p: &Params<R>, lo: usize, hi: usize, threads: usize) -> (Vec<[R; 3]>, Vec<f64>) {
+	let rows = |lo: usize, hi: usize| {
+		let clock = Clock::start();
+		let mut a = vec![[R::zero(); 3]; hi - lo];
+		for i in lo..hi {
+			for j in 0..s.len() {
+				if j == i {
+					continue;
+				}
+				let (rji, r2): ([R; 3], R) = separation(s, j, i);
+				let r2 = r2 + p.pair_eps2(eps[i], eps[j]);
+				let f = s[j].m/(r2*r2.sqrt());
+				for k in 0..3 {
+					a[i - lo][k] += f*rji[k];
+				}
+			}
+		}
+		(a, clock.seconds())
+	};
+	let threads = threads.max(1).min((hi - lo).max(1));
+	if threads == 1 {
+		let (a, busy) = rows(lo, hi);
+		return (a, vec![busy]);
+	}
+	let rows = &rows;
+	thread::scope(|scope| {
+		let handles: Vec<_> = (0..threads).map(|t| {
+			let (a, b) = (lo + (hi - lo)*t/threads, lo + (hi - lo)*(t + 1)/threads);
+			scope.spawn(move || rows(a, b))
+		}).collect();
+		let mut a = Vec::with_capacity(hi - lo);
+		let mut busy = vec![];
+		for handle in handles {
+			let (x, seconds) = handle.join().expect("Thread failure, RIP");
+			a.extend(x);
+			busy.push(seconds);
+		}
+		(a, busy)
+	})
+}
@@ -0,0 +1,32 @@
+/*
+ Two-dimensional runs (--dim 2): every star stays in the z = 0 plane. The
+ force law is still the 3D 1/r^2 between point masses, so a planar run is a
+ razor-thin disc (the usual toy model for disc dynamics, and what a
+ classroom demo on a screen shows) rather than the logarithmic potential of
+ true 2D gravity between infinite rods. Stars in the plane with no vertical
+ velocity feel no vertical pull from each other, so the direct kernels run
+ their 2D copies (see by_dim! in lib.rs) and the 3D solvers and outputs work
+ as they are; confine() only removes what external potentials or rounding
+ add out of the plane.
+ */
+use {Real, Star};
+
+// Projects the stars onto the plane; returns how many were out of it
+pub fn flatten<R: Real>(s: &mut Vec<Star<R>>) -> usize {
+	let mut moved = 0;
+	for star in s.iter_mut() {
+		if star.r[2] != R::zero() || star.v[2] != R::zero() {
+			moved += 1;
+		}
+		star.r[2] = R::zero();
+		star.v[2] = R::zero();
+	}
+	moved
+}
+
+// Drops the vertical accelerations
+pub fn confine<R: Real>(s: &mut Vec<Star<R>>) {
+	for star in s.iter_mut() {
+		star.a[2] = R::zero();
+	}
+}
@@ -0,0 +1,147 @@
+/*
+ End-of-run plots of the recorded diagnostics, as SVG or PNG. Only built with
+ the "plots" feature, so the default build stays dependency free.
+ */
+use std::error::Error;
+use std::path::Path;
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use diagnostics::History;
+
+type PlotResult = Result<(), Box<dyn Error>>;
+
+const SIZE: (u32, u32) = (800, 600);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+	Svg,
+	Png,
+}
+
+impl Format {
+	pub fn parse(name: &str) -> Option<Format> {
+		match name {
+			"svg" => Some(Format::Svg),
+			"png" => Some(Format::Png),
+			_ => None,
+		}
+	}
+
+	pub fn extension(self) -> &'static str {
+		match self {
+			Format::Svg => "svg",
+			Format::Png => "png",
+		}
+	}
+}
+
+fn bounds(values: &[f64]) -> (f64, f64) {
+	let mut lo = std::f64::INFINITY;
+	let mut hi = std::f64::NEG_INFINITY;
+	for &v in values {
+		if v.is_finite() {
+			lo = lo.min(v);
+			hi = hi.max(v);
+		}
+	}
+	if !(lo < hi) {
+		// Flat or empty series, give the axis some room
+		let c = if lo.is_finite() { lo } else { 0.0 };
+		return (c - 1.0, c + 1.0);
+	}
+	let pad = 0.05*(hi - lo);
+	(lo - pad, hi + pad)
+}
+
+fn line_plot<DB: DrawingBackend>(area: &DrawingArea<DB, Shift>, title: &str, ylabel: &str, t: &[f64], series: &[(String, Vec<f64>)]) -> PlotResult
+	where DB::ErrorType: 'static
+{
+	let all: Vec<f64> = series.iter().flat_map(|x| x.1.iter().cloned()).collect();
+	let (y0, y1) = bounds(&all);
+	let (t0, t1) = bounds(t);
+
+	let mut chart = ChartBuilder::on(area)
+		.caption(title, ("sans-serif", 24))
+		.margin(15)
+		.x_label_area_size(40)
+		.y_label_area_size(80)
+		.build_cartesian_2d(t0..t1, y0..y1)?;
+	chart.configure_mesh().x_desc("t").y_desc(ylabel).draw()?;
+
+	for (idx, &(ref name, ref ys)) in series.iter().enumerate() {
+		let color = Palette99::pick(idx).to_rgba();
+		chart.draw_series(LineSeries::new(t.iter().cloned().zip(ys.iter().cloned()), &color))?
+			.label(name.clone())
+			.legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], &color));
+	}
+	if series.len() > 1 {
+		chart.configure_series_labels().background_style(&WHITE).border_style(&BLACK).draw()?;
+	}
+	Ok(())
+}
+
+// E, T and W above, the relative error dE below
+fn energy_plot<DB: DrawingBackend>(root: &DrawingArea<DB, Shift>, t: &[f64], h: &History) -> PlotResult
+	where DB::ErrorType: 'static
+{
+	let (upper, lower) = root.split_vertically((SIZE.1*3/5) as i32);
+	let names = ["E", "T", "W"];
+	let energies: Vec<(String, Vec<f64>)> = (0..3).map(|k| (names[k].to_string(), h.samples.iter().map(|x| x.e[k]).collect())).collect();
+	line_plot(&upper, "Energy", "E", t, &energies)?;
+	line_plot(&lower, "Relative energy error", "dE", t, &[("dE".to_string(), h.samples.iter().map(|x| x.de).collect())])
+}
+
+enum Figure<'a> {
+	Energy(&'a History),
+	// Title, y label and named series
+	Lines(&'a str, &'a str, Vec<(String, Vec<f64>)>),
+}
+
+impl<'a> Figure<'a> {
+	fn draw<DB: DrawingBackend>(&self, root: &DrawingArea<DB, Shift>, t: &[f64]) -> PlotResult
+		where DB::ErrorType: 'static
+	{
+		root.fill(&WHITE)?;
+		match *self {
+			Figure::Energy(h) => energy_plot(root, t, h)?,
+			Figure::Lines(title, ylabel, ref series) => line_plot(root, title, ylabel, t, series)?,
+		}
+		root.present()?;
+		Ok(())
+	}
+
+	fn save(&self, path: &Path, format: Format, t: &[f64]) -> PlotResult {
+		match format {
+			Format::Svg => self.draw(&SVGBackend::new(path, SIZE).into_drawing_area(), t),
+			Format::Png => self.draw(&BitMapBackend::new(path, SIZE).into_drawing_area(), t),
+		}
+	}
+}
+
+// The files write_all() produces
+pub fn names(format: Format) -> Vec<String> {
+	["energy", "dE", "lagrangian_radii", "n_bound"].iter().map(|x| format!("{}.{}", x, format.extension())).collect()
+}
+
+/*
+ Writes the energy (E, T and W, and dE, over time), dE, lagrangian_radii and
+ n_bound plots into dir, named as names() says.
+ */
+pub fn write_all(dir: &Path, h: &History, format: Format) -> PlotResult {
+	let t: Vec<f64> = h.samples.iter().map(|x| x.t).collect();
+	let mut radii: Vec<(String, Vec<f64>)> = vec![];
+	for (f, frac) in h.fractions.iter().enumerate() {
+		radii.push((format!("{}%", frac*100.0), h.samples.iter().map(|x| x.radii[f]).collect()));
+	}
+	let figures = [
+		Figure::Energy(h),
+		Figure::Lines("Relative energy error", "dE", vec![("dE".to_string(), h.samples.iter().map(|x| x.de).collect())]),
+		Figure::Lines("Lagrangian radii", "r", radii),
+		Figure::Lines("Bound stars", "N_bound", vec![("N_bound".to_string(), h.samples.iter().map(|x| x.n_bound as f64).collect())]),
+	];
+	for (figure, name) in figures.iter().zip(names(format)) {
+		figure.save(&dir.join(name), format, &t)?;
+	}
+	Ok(())
+}
@@ -0,0 +1,351 @@
+/*
+ Particle-mesh forces for periodic boxes, --solver pm with --periodic L.
+ The mass is spread over a G^3 grid by cloud-in-cell (CIC) assignment,
+ Poisson's equation is solved with FFTs, and the forces are interpolated
+ back to the stars with the same weights. A step costs O(N + G^3 log G)
+ instead of the O(N^2) of the Ewald sums in ewald.rs, which makes large
+ uniform boxes affordable.
+
+ As in GADGET-2 (Springel 2005) the potential is split with a Gaussian of
+ width r_s = 1.25 cells. The mesh solves for the long-range part,
+
+   phi_k = -4 pi rho_k exp(-k^2 r_s^2)/k^2,
+
+ with the k = 0 mode left out (the neutralizing background of ewald.rs) and
+ the CIC window divided out for both assignment and interpolation. Plain
+ PM stops there, so forces are smoothed over a few cells. With --pp (P3M)
+ the short-range rest
+
+   phi_short(r) = -m erfc(r/(2 r_s))/r
+
+ is added for every minimum-image pair closer than 4.5 r_s. Those pairs are
+ found through a chaining mesh of cells at least that wide. Softening
+ applies to the short-range part only, with r replaced by sqrt(r^2 + eps^2).
+
+ The FFT is a plain radix-2 one, so G has to be a power of two. Gradients
+ of the mesh potential are four-point finite differences.
+ */
+use std::f64::consts::PI;
+use std::thread;
+
+use drag::erf;
+use ewald::{nearest, wrap};
+use solver::ForceSolver;
+use timing::Clock;
+use {Params, Real, Star};
+
+// Default grid cells per axis
+pub static GRID: usize = 64;
+// Width r_s of the force split, in cells
+pub static SPLIT: f64 = 1.25;
+// Range of the short-range forces, in r_s
+pub static CUTOFF: f64 = 4.5;
+
+type Complex = (f64, f64);
+
+// In-place radix-2 FFT of a power-of-two length, unnormalized
+fn fft(x: &mut [Complex], inverse: bool) {
+	let n = x.len();
+	let mut j = 0;
+	for i in 1..n {
+		let mut bit = n >> 1;
+		while j & bit != 0 {
+			j ^= bit;
+			bit >>= 1;
+		}
+		j |= bit;
+		if i < j {
+			x.swap(i, j);
+		}
+	}
+	let sign = if inverse { 1.0 } else { -1.0 };
+	let mut len = 2;
+	while len <= n {
+		let twiddles: Vec<Complex> = (0..len/2).map(|k| {
+			let angle = sign*2.0*PI*k as f64/len as f64;
+			(angle.cos(), angle.sin())
+		}).collect();
+		for start in (0..n).step_by(len) {
+			for (k, w) in twiddles.iter().enumerate() {
+				let (a, b) = (x[start + k], x[start + k + len/2]);
+				let b = (b.0*w.0 - b.1*w.1, b.0*w.1 + b.1*w.0);
+				x[start + k] = (a.0 + b.0, a.1 + b.1);
+				x[start + k + len/2] = (a.0 - b.0, a.1 - b.1);
+			}
+		}
+		len <<= 1;
+	}
+}
+
+// FFT of a g^3 grid along all three axes
+fn fft3(x: &mut [Complex], g: usize, inverse: bool) {
+	let mut line = vec![(0.0, 0.0); g];
+	for axis in 0..3 {
+		let stride = [1, g, g*g][axis];
+		for base in 0..g*g {
+			// The g*g lines along this axis start where its own coordinate is 0
+			let start = match axis {
+				0 => base*g,
+				1 => (base/g)*g*g + base%g,
+				_ => base,
+			};
+			for i in 0..g {
+				line[i] = x[start + i*stride];
+			}
+			fft(&mut line, inverse);
+			for i in 0..g {
+				x[start + i*stride] = line[i];
+			}
+		}
+	}
+}
+
+pub struct Pm {
+	pub box_size: f64,
+	pub grid: usize,
+	// Short-range pair forces on top of the mesh (P3M)
+	pub pp: bool,
+	// r_s, in length units
+	pub split: f64,
+	// The Green's function with the split and the CIC deconvolution, per mode
+	green: Vec<f64>,
+}
+
+impl Pm {
+	// grid has to be a power of two
+	pub fn new(box_size: f64, grid: usize, pp: bool) -> Pm {
+		assert!(grid.is_power_of_two(), "the PM grid needs a power of two, got {}", grid);
+		let g = grid;
+		let h = box_size/g as f64;
+		let split = SPLIT*h;
+		let wave = |i: usize| 2.0*PI*(if i < g/2 { i as f64 } else { i as f64 - g as f64 })/box_size;
+		let sinc = |k: f64| if k == 0.0 { 1.0 } else { (0.5*k*h).sin()/(0.5*k*h) };
+		let mut green = vec![0.0; g*g*g];
+		for i in 0..g {
+			for j in 0..g {
+				for l in 0..g {
+					let k = [wave(i), wave(j), wave(l)];
+					let k2 = k[0]*k[0] + k[1]*k[1] + k[2]*k[2];
+					if k2 > 0.0 {
+						let window = sinc(k[0])*sinc(k[1])*sinc(k[2]);
+						green[(i*g + j)*g + l] = -4.0*PI*(-k2*split*split).exp()/(k2*window.powi(4));
+					}
+				}
+			}
+		}
+		Pm { box_size: box_size, grid: grid, pp: pp, split: split, green: green }
+	}
+
+	// Grid cell and CIC weights of a position: the 8 cells from base on and the fraction towards the upper one per axis
+	fn cic(&self, r: [f64; 3]) -> ([usize; 3], [f64; 3]) {
+		let g = self.grid;
+		let h = self.box_size/g as f64;
+		let mut base = [0; 3];
+		let mut f = [0.0; 3];
+		for k in 0..3 {
+			let u = (r[k] + 0.5*self.box_size)/h - 0.5;
+			let lower = u.floor();
+			f[k] = u - lower;
+			base[k] = (lower as i64).rem_euclid(g as i64) as usize;
+		}
+		(base, f)
+	}
+
+	// The cells and weights of a CIC cloud
+	fn cloud(&self, r: [f64; 3]) -> [(usize, f64); 8] {
+		let g = self.grid;
+		let (base, f) = self.cic(r);
+		let mut x = [(0, 0.0); 8];
+		for corner in 0..8 {
+			let offset = [corner >> 2, (corner >> 1) & 1, corner & 1];
+			let mut w = 1.0;
+			let mut index = 0;
+			for k in 0..3 {
+				w *= if offset[k] == 1 { f[k] } else { 1.0 - f[k] };
+				index = index*g + (base[k] + offset[k])%g;
+			}
+			x[corner] = (index, w);
+		}
+		x
+	}
+
+	// The long-range potential on the grid
+	pub fn potential<R: Real>(&self, s: &Vec<Star<R>>) -> Vec<f64> {
+		let g = self.grid;
+		let h = self.box_size/g as f64;
+		let mut rho = vec![(0.0, 0.0); g*g*g];
+		for star in s {
+			let m = star.m.to_f64()/(h*h*h);
+			for &(index, w) in self.cloud(position(star)).iter() {
+				rho[index].0 += m*w;
+			}
+		}
+		fft3(&mut rho, g, false);
+		for (x, green) in rho.iter_mut().zip(self.green.iter()) {
+			*x = (x.0*green, x.1*green);
+		}
+		fft3(&mut rho, g, true);
+		let norm = 1.0/(g*g*g) as f64;
+		rho.iter().map(|x| x.0*norm).collect()
+	}
+
+	// -grad phi at a grid point, from four-point differences
+	fn gradient(&self, phi: &[f64], index: usize) -> [f64; 3] {
+		let g = self.grid;
+		let h = self.box_size/g as f64;
+		let cell = [index/(g*g), (index/g)%g, index%g];
+		let mut a = [0.0; 3];
+		for k in 0..3 {
+			let at = |offset: i64| {
+				let mut c = cell;
+				c[k] = (cell[k] as i64 + offset).rem_euclid(g as i64) as usize;
+				phi[(c[0]*g + c[1])*g + c[2]]
+			};
+			a[k] = -(2.0/3.0*(at(1) - at(-1)) - 1.0/12.0*(at(2) - at(-2)))/h;
+		}
+		a
+	}
+
+	/*
+	 Short-range accelerations and potentials of every star (P3M), on
+	 threads. Every pair is visited from both ends, so no thread writes to
+	 another's stars.
+	 */
+	fn short_range<R: Real>(&self, s: &Vec<Star<R>>, p: &Params<R>) -> (Vec<([f64; 3], f64)>, Vec<f64>) {
+		let n = s.len();
+		let p64: Params<f64> = p.convert();
+		let eps: Vec<f64> = p.star_eps(s).iter().map(|x| x.to_f64()).collect();
+		let pos: Vec<[f64; 3]> = s.iter().map(position).collect();
+		let mass: Vec<f64> = s.iter().map(|x| x.m.to_f64()).collect();
+		let cutoff = CUTOFF*self.split;
+		// Chaining mesh; with fewer than 3 cells a side every pair is a candidate
+		let m = ((self.box_size/cutoff).floor() as usize).max(1);
+		let m = if m < 3 { 1 } else { m };
+		let width = self.box_size/m as f64;
+		let cell_of = |r: [f64; 3]| {
+			let c: Vec<usize> = (0..3).map(|k| (((r[k] + 0.5*self.box_size)/width).floor() as i64).rem_euclid(m as i64) as usize).collect();
+			[c[0], c[1], c[2]]
+		};
+		let mut cells: Vec<Vec<usize>> = vec![vec![]; m*m*m];
+		for (i, r) in pos.iter().enumerate() {
+			let c = cell_of(*r);
+			cells[(c[0]*m + c[1])*m + c[2]].push(i);
+		}
+		let threads = p.threads.max(1).min(n.max(1));
+		let mut out = vec![([0.0; 3], 0.0); n];
+		let mut busy = vec![0.0; threads];
+		let visit = |lo: usize, hi: usize| {
+			let clock = Clock::start();
+			let mut x = vec![([0.0; 3], 0.0); hi - lo];
+			let reach: Vec<i64> = if m == 1 { vec![0] } else { vec![-1, 0, 1] };
+			for i in lo..hi {
+				let c = cell_of(pos[i]);
+				for &dx in &reach {
+					for &dy in &reach {
+						for &dz in &reach {
+							let neighbor = [(c[0] as i64 + dx).rem_euclid(m as i64) as usize, (c[1] as i64 + dy).rem_euclid(m as i64) as usize, (c[2] as i64 + dz).rem_euclid(m as i64) as usize];
+							for &j in &cells[(neighbor[0]*m + neighbor[1])*m + neighbor[2]] {
+								if j == i {
+									continue;
+								}
+								let d = nearest(&[pos[i][0] - pos[j][0], pos[i][1] - pos[j][1], pos[i][2] - pos[j][2]], self.box_size);
+								let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+								if r2 >= cutoff*cutoff {
+									continue;
+								}
+								let r = (r2 + p64.pair_eps2(eps[i], eps[j])).sqrt();
+								let u = r/(2.0*self.split);
+								let erfc = 1.0 - erf(u);
+								let mj = mass[j];
+								let f = mj*(erfc + 2.0*u/PI.sqrt()*(-u*u).exp())/(r*r*r);
+								let y = &mut x[i - lo];
+								for k in 0..3 {
+									y.0[k] -= f*d[k];
+								}
+								y.1 -= mj*erfc/r;
+							}
+						}
+					}
+				}
+			}
+			(x, clock.seconds())
+		};
+		thread::scope(|scope| {
+			let visit = &visit;
+			let handles: Vec<_> = (0..threads).map(|t| scope.spawn(move || visit(n*t/threads, n*(t + 1)/threads))).collect();
+			for (t, handle) in handles.into_iter().enumerate() {
+				let (x, seconds) = handle.join().expect("Thread failure, RIP");
+				out[n*t/threads..n*(t + 1)/threads].copy_from_slice(&x);
+				busy[t] = seconds;
+			}
+		});
+		(out, busy)
+	}
+
+	// Mesh (and with pp short-range) accelerations; busy seconds per thread
+	pub fn acceleration<R: Real>(&self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		let clock = Clock::start();
+		let phi = self.potential(s);
+		let mesh: Vec<[f64; 3]> = s.iter().map(|star| {
+			let mut a = [0.0; 3];
+			for &(index, w) in self.cloud(position(star)).iter() {
+				let x = self.gradient(&phi, index);
+				for k in 0..3 {
+					a[k] += w*x[k];
+				}
+			}
+			a
+		}).collect();
+		let mesh_seconds = clock.seconds();
+		let (short, mut busy) = if self.pp { self.short_range(s, p) } else { (vec![], vec![0.0]) };
+		busy[0] += mesh_seconds;
+		for (i, star) in s.iter_mut().enumerate() {
+			for k in 0..3 {
+				star.a[k] = R::from_f64(mesh[i][k] + short.get(i).map_or(0.0, |x| x.0[k]));
+			}
+		}
+		busy
+	}
+
+	/*
+	 [E, T, W]. W is half the sum of m phi over the stars, the mesh potential
+	 interpolated like the forces, without the smoothed interaction of every
+	 star with itself at zero distance (-m^2/(sqrt(pi) r_s) each). The mesh
+	 has no k = 0 mode, where the Ewald sums keep the mean of the short-range
+	 part, 2 pi M^2 r_s^2/L^3 for total mass M. Both are constants; with them
+	 W matches the Ewald energy of ewald.rs as closely as the forces do.
+	 */
+	pub fn energies<R: Real>(&self, s: &Vec<Star<R>>, p: &Params<R>) -> Vec<R> {
+		let phi = self.potential(s);
+		let short = if self.pp { self.short_range(s, p).0 } else { vec![] };
+		let mass: f64 = s.iter().map(|x| x.m.to_f64()).sum();
+		let mut kinetic = 0.0;
+		let mut potential = 2.0*PI*mass*mass*self.split*self.split/self.box_size.powi(3);
+		for (i, star) in s.iter().enumerate() {
+			let m = star.m.to_f64();
+			kinetic += 0.5*m*(0..3).map(|k| star.v[k].to_f64().powi(2)).sum::<f64>();
+			let mesh: f64 = self.cloud(position(star)).iter().map(|&(index, w)| w*phi[index]).sum();
+			potential += 0.5*m*(mesh + short.get(i).map_or(0.0, |x| x.1)) + 0.5*m*m/(PI.sqrt()*self.split);
+		}
+		vec![R::from_f64(kinetic + potential), R::from_f64(kinetic), R::from_f64(potential)]
+	}
+}
+
+fn position<R: Real>(star: &Star<R>) -> [f64; 3] {
+	[star.r[0].to_f64(), star.r[1].to_f64(), star.r[2].to_f64()]
+}
+
+impl<R: Real> ForceSolver<R> for Pm {
+	fn name(&self) -> &'static str {
+		if self.pp { "p3m" } else { "pm" }
+	}
+
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		wrap(s, self.box_size);
+		self.acceleration(s, p)
+	}
+
+	fn energies(&self, s: &Vec<Star<R>>, p: &Params<R>) -> Option<Vec<R>> {
+		Some(Pm::energies(self, s, p))
+	}
+}
@@ -0,0 +1,94 @@
+/*
+ Post-Newtonian corrections for compact pairs. Stars flagged with a non-zero
+ "pn" column are compact objects; every pair of them gets the 1PN correction
+ to its relative acceleration and, optionally, the 2.5PN radiation reaction
+ that drives a gravitational-wave inspiral. In harmonic coordinates, with
+ m = m_i + m_j, eta = m_i m_j/m^2, n the unit vector from j to i, v the
+ relative velocity and rdot = n.v (Kidder 1995, eq. 2.2):
+
+   a_1PN   = -m/(c^2 r^2) [ ((1 + 3 eta) v^2 - 3/2 eta rdot^2 - 2 (2 + eta) m/r) n
+                            - 2 (2 - eta) rdot v ]
+   a_2.5PN = 8/5 eta m^2/(c^5 r^3) [ rdot (18 v^2 + 2/3 m/r - 25 rdot^2) n
+                                    - (6 v^2 - 2 m/r - 15 rdot^2) v ]
+
+ The relative correction is shared between the two stars by mass, which
+ keeps the momentum. The terms depend on the velocities, which are taken at
+ the start of each step. energies() stays Newtonian, so with radiation
+ reaction dE measures the energy carried off by gravitational waves.
+
+ c is the speed of light in the internal units: "--pn-c C", or from the
+ constants when --units are given. "--pn 1" or "--pn 2.5" picks the order.
+ */
+use columns::Columns;
+use {Real, Star};
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PostNewtonian {
+	pub c: f64,
+	// Also apply the 2.5PN radiation reaction
+	pub radiation: bool,
+	// Indices of the compact stars
+	pub flagged: Vec<usize>,
+}
+
+impl PostNewtonian {
+	// Flags the stars with a non-zero "pn" column
+	pub fn new(c: f64, radiation: bool, n: usize, columns: &Columns) -> PostNewtonian {
+		let flagged = (0..n).filter(|&i| columns.value(i, "pn").map_or(false, |x| x != 0.0)).collect();
+		PostNewtonian { c: c, radiation: radiation, flagged: flagged }
+	}
+
+	// Corrections to the acceleration of i relative to j, in f64
+	pub fn relative(&self, m: f64, eta: f64, r: [f64; 3], v: [f64; 3]) -> [f64; 3] {
+		let d = (r[0]*r[0] + r[1]*r[1] + r[2]*r[2]).sqrt();
+		let n = [r[0]/d, r[1]/d, r[2]/d];
+		let v2 = v[0]*v[0] + v[1]*v[1] + v[2]*v[2];
+		let rdot = n[0]*v[0] + n[1]*v[1] + n[2]*v[2];
+		let (c2, mr) = (self.c*self.c, m/d);
+
+		let pre = -m/(c2*d*d);
+		let mut an = pre*((1.0 + 3.0*eta)*v2 - 1.5*eta*rdot*rdot - 2.0*(2.0 + eta)*mr);
+		let mut av = -pre*2.0*(2.0 - eta)*rdot;
+		if self.radiation {
+			let pre = 1.6*eta*m*m/(c2*c2*self.c*d*d*d);
+			an += pre*rdot*(18.0*v2 + 2.0/3.0*mr - 25.0*rdot*rdot);
+			av -= pre*(6.0*v2 - 2.0*mr - 15.0*rdot*rdot);
+		}
+		[an*n[0] + av*v[0], an*n[1] + av*v[1], an*n[2] + av*v[2]]
+	}
+
+	// Adds the corrections for every pair of flagged stars to their accelerations
+	pub fn add_accelerations<R: Real>(&self, s: &mut Vec<Star<R>>) {
+		for (x, &i) in self.flagged.iter().enumerate() {
+			for &j in &self.flagged[x + 1..] {
+				let (mi, mj) = (s[i].m.to_f64(), s[j].m.to_f64());
+				let m = mi + mj;
+				if !(mi > 0.0 && mj > 0.0) {
+					continue;
+				}
+				let r = [(s[i].r[0] - s[j].r[0]).to_f64(), (s[i].r[1] - s[j].r[1]).to_f64(), (s[i].r[2] - s[j].r[2]).to_f64()];
+				let v = [(s[i].v[0] - s[j].v[0]).to_f64(), (s[i].v[1] - s[j].v[1]).to_f64(), (s[i].v[2] - s[j].v[2]).to_f64()];
+				let a = self.relative(m, mi*mj/(m*m), r, v);
+				for k in 0..3 {
+					s[i].a[k] += R::from_f64(mj/m*a[k]);
+					s[j].a[k] -= R::from_f64(mi/m*a[k]);
+				}
+			}
+		}
+	}
+
+	// Renumbers after star j was merged into star i, see collisions.rs
+	pub fn merged(&mut self, i: usize, j: usize) {
+		let either = self.flagged.contains(&i) || self.flagged.contains(&j);
+		self.flagged.retain(|&x| x != i && x != j);
+		for x in self.flagged.iter_mut() {
+			if *x > j {
+				*x -= 1;
+			}
+		}
+		if either {
+			self.flagged.push(i);
+			self.flagged.sort();
+		}
+	}
+}
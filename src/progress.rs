@@ -0,0 +1,94 @@
+/*
+ Progress bar for long runs: the fraction of the simulated time done, steps
+ per second and the estimated wall-clock time left, redrawn in place on
+ stdout a few times a second. It is only shown when stdout is a terminal and
+ messages of level info are, so pipes, files and -q see nothing of it. Log
+ lines printed meanwhile clear it first and it is drawn again on the next
+ update.
+
+ The estimate assumes the rest of the run goes at the pace of the run so
+ far. "--no-progress" switches it off.
+ */
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::time::Instant;
+
+use log;
+
+const WIDTH: usize = 30;
+// Seconds between redraws
+const INTERVAL: f64 = 0.2;
+
+pub struct Bar {
+	t0: f64,
+	tend: f64,
+	steps0: usize,
+	start: Instant,
+	// Seconds since start at the last redraw
+	drawn: Option<f64>,
+	enabled: bool,
+}
+
+// "2h 05m", "3m 12s" or "12s"
+pub fn duration(secs: f64) -> String {
+	if !secs.is_finite() {
+		return String::from("?");
+	}
+	let s = secs.max(0.0).round() as u64;
+	if s >= 3600 {
+		format!("{}h {:02}m", s/3600, s%3600/60)
+	} else if s >= 60 {
+		format!("{}m {:02}s", s/60, s%60)
+	} else {
+		format!("{}s", s)
+	}
+}
+
+impl Bar {
+	// A bar for a run from t0 to tend that starts at steps0 steps
+	pub fn new(t0: f64, tend: f64, steps0: usize) -> Bar {
+		let enabled = io::stdout().is_terminal() && log::enabled(log::Level::Info);
+		Bar { t0: t0, tend: tend, steps0: steps0, start: Instant::now(), drawn: None, enabled: enabled }
+	}
+
+	pub fn disable(&mut self) {
+		self.enabled = false;
+	}
+
+	// The bar at time t after steps steps, elapsed seconds into the run
+	pub fn line(&self, t: f64, steps: usize, elapsed: f64) -> String {
+		let done = if self.tend > self.t0 { ((t - self.t0)/(self.tend - self.t0)).clamp(0.0, 1.0) } else { 1.0 };
+		let filled = (done*WIDTH as f64) as usize;
+		let rate = if elapsed > 0.0 { steps.saturating_sub(self.steps0) as f64/elapsed } else { 0.0 };
+		let eta = if done > 0.0 { elapsed*(1.0 - done)/done } else { f64::INFINITY };
+		format!("[{}{}] {:5.1}% t = {:.4}/{}, {:.1} steps/s, ETA {}", "#".repeat(filled), ".".repeat(WIDTH - filled), 100.0*done, t, self.tend, rate, duration(eta))
+	}
+
+	// Redraws the bar if it is due
+	pub fn update(&mut self, t: f64, steps: usize) {
+		if !self.enabled {
+			return;
+		}
+		let elapsed = self.start.elapsed().as_secs_f64();
+		if self.drawn.map_or(false, |x| elapsed - x < INTERVAL) && !log::bar_cleared() {
+			return;
+		}
+		let line = self.line(t, steps, elapsed);
+		let mut out = io::stdout();
+		let _ = write!(out, "\r\x1b[K{}", line);
+		let _ = out.flush();
+		log::set_bar(true);
+		self.drawn = Some(elapsed);
+	}
+
+	// Removes the bar from the terminal
+	pub fn finish(&mut self) {
+		if self.enabled && self.drawn.is_some() {
+			let mut out = io::stdout();
+			let _ = write!(out, "\r\x1b[K");
+			let _ = out.flush();
+			log::set_bar(false);
+		}
+		self.enabled = false;
+	}
+}
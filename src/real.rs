@@ -0,0 +1,59 @@
+/*
+ The floating point type everything is computed in. The whole crate is
+ generic over Real so precision studies and memory-constrained runs share one
+ code path; the binary picks f64, or f32 with the "f32" feature.
+ */
+use std::fmt::{Debug, Display, LowerExp};
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+pub trait Real: Copy + Default + PartialOrd + Debug + Display + LowerExp + FromStr + Sum + Send + Sync + 'static
+	+ Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self>
+	+ AddAssign + SubAssign + MulAssign + DivAssign
+{
+	fn from_f64(x: f64) -> Self;
+	fn to_f64(self) -> f64;
+	fn zero() -> Self;
+	fn one() -> Self;
+	fn infinity() -> Self;
+	fn sqrt(self) -> Self;
+	fn cbrt(self) -> Self;
+	fn powi(self, n: i32) -> Self;
+	fn abs(self) -> Self;
+	fn min(self, other: Self) -> Self;
+	fn max(self, other: Self) -> Self;
+	fn is_finite(self) -> bool;
+	// Machine epsilon, as f64
+	fn epsilon() -> f64;
+	fn name() -> &'static str;
+}
+
+macro_rules! impl_real {
+	($t:ident) => {
+		impl Real for $t {
+			fn from_f64(x: f64) -> $t { x as $t }
+			fn to_f64(self) -> f64 { self as f64 }
+			fn zero() -> $t { 0.0 }
+			fn one() -> $t { 1.0 }
+			fn infinity() -> $t { std::$t::INFINITY }
+			fn sqrt(self) -> $t { $t::sqrt(self) }
+			fn cbrt(self) -> $t { $t::cbrt(self) }
+			fn powi(self, n: i32) -> $t { $t::powi(self, n) }
+			fn abs(self) -> $t { $t::abs(self) }
+			fn min(self, other: $t) -> $t { $t::min(self, other) }
+			fn max(self, other: $t) -> $t { $t::max(self, other) }
+			fn is_finite(self) -> bool { $t::is_finite(self) }
+			fn epsilon() -> f64 { std::$t::EPSILON as f64 }
+			fn name() -> &'static str { stringify!($t) }
+		}
+	}
+}
+
+impl_real!(f32);
+impl_real!(f64);
+
+// Shorthand for constants in generic code
+pub fn c<R: Real>(x: f64) -> R {
+	R::from_f64(x)
+}
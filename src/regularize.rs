@@ -0,0 +1,205 @@
+/*
+ Regularized treatment of the closest pair. A hard binary at a fixed dt
+ loses energy at every pericentre; here its relative motion is instead
+ advanced along the exact Kepler orbit, so the pair's own interaction adds no
+ integration error however eccentric or tight it gets. The step splits as
+
+   half kick with all forces except the pair's mutual one
+   drift: other stars in a straight line, the pair's centre of mass in a
+     straight line and its relative position along the two-body orbit
+   forces, half kick as before
+
+ which is a symplectic splitting with the pair's Kepler problem solved
+ exactly (universal variables, so close hyperbolic passages work too); the
+ other stars still perturb the pair through the kicks. This is a Kepler
+ drift rather than Kustaanheimo-Stiefel coordinates, but it removes the
+ same singularity for the pair. The pair is unsoftened while regularized,
+ in the forces and in the energies.
+
+ A pair is taken in when it is the closest pair, closer than the radius and
+ bound, and released again when it is unbound or wider than twice the
+ radius. The Kepler drift is done in f64 whatever the precision of the run.
+ */
+use std::f64::consts::PI;
+
+use pairs;
+use {Params, Real, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Regularization {
+	pub radius: f64,
+	// The pair being regularized, if any
+	pub pair: Option<(usize, usize)>,
+}
+
+// Stumpff functions C(z) and S(z)
+fn stumpff(z: f64) -> (f64, f64) {
+	if z.abs() < 1e-3 {
+		(0.5 - z/24.0 + z*z/720.0, 1.0/6.0 - z/120.0 + z*z/5040.0)
+	} else if z > 0.0 {
+		let x = z.sqrt();
+		((1.0 - x.cos())/z, (x - x.sin())/(x*z))
+	} else {
+		let x = (-z).sqrt();
+		((x.cosh() - 1.0)/(-z), (x.sinh() - x)/(x*(-z)))
+	}
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+	a[0]*b[0] + a[1]*b[1] + a[2]*b[2]
+}
+
+/*
+ Relative position and velocity after a time dt on the two-body orbit with
+ gravitational parameter mu, by universal variables with Laguerre-Conway
+ iteration. Bound orbits are first wound back by whole periods.
+ */
+pub fn kepler_drift(mu: f64, r0: [f64; 3], v0: [f64; 3], dt: f64) -> ([f64; 3], [f64; 3]) {
+	let rn = dot(r0, r0).sqrt();
+	let vr = dot(r0, v0)/rn;
+	let alpha = 2.0/rn - dot(v0, v0)/mu;
+	let sqmu = mu.sqrt();
+	let mut t = dt;
+	if alpha > 0.0 {
+		let period = 2.0*PI/(alpha.powf(1.5)*sqmu);
+		t -= period*(t/period).trunc();
+	}
+
+	let mut chi = if alpha > 0.0 { sqmu*alpha*t } else { sqmu*t/rn };
+	for _ in 0..50 {
+		let z = alpha*chi*chi;
+		let (c, s) = stumpff(z);
+		let f = rn*vr/sqmu*chi*chi*c + (1.0 - alpha*rn)*chi*chi*chi*s + rn*chi - sqmu*t;
+		let df = rn*vr/sqmu*chi*(1.0 - z*s) + (1.0 - alpha*rn)*chi*chi*c + rn;
+		let ddf = rn*vr/sqmu*(1.0 - z*c) + (1.0 - alpha*rn)*chi*(1.0 - z*s);
+		let n = 5.0;
+		let root = ((n - 1.0)*(n - 1.0)*df*df - n*(n - 1.0)*f*ddf).abs().sqrt();
+		let delta = n*f/(df + root*df.signum());
+		chi -= delta;
+		if delta.abs() <= 1e-15*chi.abs().max(1e-300) {
+			break;
+		}
+	}
+
+	let z = alpha*chi*chi;
+	let (c, s) = stumpff(z);
+	let f = 1.0 - chi*chi/rn*c;
+	let g = t - chi*chi*chi*s/sqmu;
+	let r: Vec<f64> = (0..3).map(|k| f*r0[k] + g*v0[k]).collect();
+	let r = [r[0], r[1], r[2]];
+	let r1 = dot(r, r).sqrt();
+	let df = sqmu/(r1*rn)*(z*chi*s - chi);
+	let dg = 1.0 - chi*chi/r1*c;
+	let v: Vec<f64> = (0..3).map(|k| df*r0[k] + dg*v0[k]).collect();
+	(r, [v[0], v[1], v[2]])
+}
+
+fn relative<R: Real>(s: &Vec<Star<R>>, i: usize, j: usize) -> ([f64; 3], [f64; 3]) {
+	let r = [(s[j].r[0] - s[i].r[0]).to_f64(), (s[j].r[1] - s[i].r[1]).to_f64(), (s[j].r[2] - s[i].r[2]).to_f64()];
+	let v = [(s[j].v[0] - s[i].v[0]).to_f64(), (s[j].v[1] - s[i].v[1]).to_f64(), (s[j].v[2] - s[i].v[2]).to_f64()];
+	(r, v)
+}
+
+// Relative specific energy of a pair, negative when bound
+fn pair_energy<R: Real>(s: &Vec<Star<R>>, i: usize, j: usize) -> f64 {
+	let (r, v) = relative(s, i, j);
+	0.5*dot(v, v) - (s[i].m + s[j].m).to_f64()/dot(r, r).sqrt()
+}
+
+impl Regularization {
+	pub fn new(radius: f64) -> Regularization {
+		Regularization { radius: radius, pair: None }
+	}
+
+	/*
+	 Takes in or releases a pair, see the top of this file. Returns a
+	 description of the switch for the event log, if there was one.
+	 */
+	pub fn update<R: Real>(&mut self, s: &Vec<Star<R>>) -> Option<String> {
+		if let Some((i, j)) = self.pair {
+			let (r, _) = relative(s, i, j);
+			let d = dot(r, r).sqrt();
+			if d > 2.0*self.radius || pair_energy(s, i, j) >= 0.0 {
+				self.pair = None;
+				return Some(format!("released pair ({}, {}) at separation {:e}", i, j, d));
+			}
+			return None;
+		}
+		let mut closest = (0, 0, R::infinity());
+		pairs::for_each_pair(s, |i, j, _, r2| {
+			if r2 < closest.2 {
+				closest = (i, j, r2);
+			}
+		});
+		let (i, j, r2) = closest;
+		let d = r2.to_f64().sqrt();
+		if d < self.radius && s[i].m + s[j].m > R::zero() && pair_energy(s, i, j) < 0.0 {
+			self.pair = Some((i, j));
+			return Some(format!("regularized pair ({}, {}) at separation {:e}", i, j, d));
+		}
+		None
+	}
+
+	// The pair's mutual accelerations as p softens them, or exact without p
+	pub fn pair_accelerations<R: Real>(s: &Vec<Star<R>>, i: usize, j: usize, p: Option<&Params<R>>) -> ([R; 3], [R; 3]) {
+		let d = [s[i].r[0] - s[j].r[0], s[i].r[1] - s[j].r[1], s[i].r[2] - s[j].r[2]];
+		let mut r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+		if let Some(p) = p {
+			let eps = p.star_eps(s);
+			r2 += p.pair_eps2(eps[i], eps[j]);
+		}
+		let apre = R::one()/(r2*r2.sqrt());
+		let mut ai = [R::zero(); 3];
+		let mut aj = [R::zero(); 3];
+		for k in 0..3 {
+			ai[k] = -s[j].m*apre*d[k];
+			aj[k] = s[i].m*apre*d[k];
+		}
+		(ai, aj)
+	}
+
+	// Exact minus softened potential energy of the regularized pair, to add to W
+	pub fn energy_correction<R: Real>(&self, s: &Vec<Star<R>>, p: &Params<R>) -> R {
+		match self.pair {
+			Some((i, j)) => {
+				let eps = p.star_eps(s);
+				let d = [s[i].r[0] - s[j].r[0], s[i].r[1] - s[j].r[1], s[i].r[2] - s[j].r[2]];
+				let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+				s[i].m*s[j].m*(R::one()/(r2 + p.pair_eps2(eps[i], eps[j])).sqrt() - R::one()/r2.sqrt())
+			},
+			None => R::zero(),
+		}
+	}
+
+	/*
+	 The drift of a regularized step: every star in a straight line except
+	 the pair, whose centre of mass moves in a straight line and whose
+	 relative motion follows the Kepler orbit.
+	 */
+	pub fn drift<R: Real>(&self, s: &mut Vec<Star<R>>, dt: R) {
+		for (n, star) in s.iter_mut().enumerate() {
+			if self.pair.map_or(false, |(i, j)| n == i || n == j) {
+				continue;
+			}
+			for k in 0..3 {
+				star.r[k] += dt*star.v[k];
+			}
+		}
+		let (i, j) = match self.pair {
+			Some(x) => x,
+			None => return,
+		};
+		let (mi, mj) = (s[i].m.to_f64(), s[j].m.to_f64());
+		let (r0, v0) = relative(s, i, j);
+		let (r, v) = kepler_drift(mi + mj, r0, v0, dt.to_f64());
+		let (fi, fj) = (mj/(mi + mj), mi/(mi + mj));
+		for k in 0..3 {
+			let rc = (s[i].m*s[i].r[k] + s[j].m*s[j].r[k])/(s[i].m + s[j].m) + dt*(s[i].m*s[i].v[k] + s[j].m*s[j].v[k])/(s[i].m + s[j].m);
+			let vc = (s[i].m*s[i].v[k] + s[j].m*s[j].v[k])/(s[i].m + s[j].m);
+			s[i].r[k] = rc - R::from_f64(fi*r[k]);
+			s[j].r[k] = rc + R::from_f64(fj*r[k]);
+			s[i].v[k] = vc - R::from_f64(fi*v[k]);
+			s[j].v[k] = vc + R::from_f64(fj*v[k]);
+		}
+	}
+}
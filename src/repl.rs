@@ -0,0 +1,51 @@
+/*
+ "nbabel repl FILE" loads a system and then reads commands from stdin, which
+ is handy for poking at a simulation while debugging.
+ */
+use std::fs;
+use std::io;
+use std::io::{BufRead, Write};
+
+use {diagnostics, read_stars, Float, Params, Simulation};
+
+static HELP: &'static str = "commands: step [N], run T, stats, energy, help, quit";
+
+pub fn main(args: &[String]) {
+	let path = args.first().expect("Usage: nbabel repl INPUT_FILE");
+	let text = fs::read_to_string(path).expect("Could not read input file");
+	let mut sim: Simulation<Float> = Simulation::new(read_stars(&text), Params::default());
+	let e0 = sim.energies();
+	println!("Loaded {} stars. {}", sim.s.len(), HELP);
+
+	let stdin = io::stdin();
+	loop {
+		print!("> ");
+		io::stdout().flush().expect("Could not write prompt");
+		let mut line = String::new();
+		if stdin.lock().read_line(&mut line).expect("Could not read command") == 0 {
+			break;
+		}
+		let words: Vec<&str> = line.split_whitespace().collect();
+		match words.as_slice() {
+			[] => {},
+			["step"] => sim.step(),
+			["step", n] => match n.parse::<usize>() {
+				Ok(n) => for _ in 0..n { sim.step() },
+				Err(_) => println!("step needs a number of steps"),
+			},
+			["run", tend] => match tend.parse::<Float>() {
+				Ok(tend) => while sim.t < tend { sim.step() },
+				Err(_) => println!("run needs an end time"),
+			},
+			["stats"] => println!("{}", sim.stats()),
+			["energy"] => {
+				let e = sim.energies();
+				println!("t = {}, E = {} {} {}, dE = {}, Q = {}", sim.t, e[0], e[1], e[2],
+					(e[0] - e0[0])/e0[0], diagnostics::virial_ratio(&e));
+			},
+			["help"] => println!("{}", HELP),
+			["quit"] | ["exit"] => break,
+			_ => println!("Unknown command. {}", HELP),
+		}
+	}
+}
@@ -0,0 +1,180 @@
+/*
+ "nbabel report run1/ run2/ ..." builds one self-contained HTML page that
+ overlays the diagnostics.csv time series of several runs.
+ */
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+
+static COLORS: [&'static str; 8] = ["#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b", "#e377c2", "#17becf"];
+
+pub struct Table {
+	pub names: Vec<String>,
+	pub rows: Vec<Vec<f64>>,
+}
+
+impl Table {
+	pub fn column(&self, name: &str) -> Option<Vec<f64>> {
+		let idx = self.names.iter().position(|x| x == name)?;
+		Some(self.rows.iter().map(|r| r[idx]).collect())
+	}
+}
+
+pub fn read_csv(path: &Path) -> io::Result<Table> {
+	let mut lines = BufReader::new(File::open(path)?).lines();
+	let header = match lines.next() {
+		Some(h) => h?,
+		None => return Err(io::Error::new(io::ErrorKind::InvalidData, "empty diagnostics file")),
+	};
+	let names: Vec<String> = header.trim().split(',').map(|x| x.to_string()).collect();
+
+	let mut rows = vec![];
+	for (n, line) in lines.enumerate() {
+		let line = line?;
+		if line.trim() == "" {
+			continue;
+		}
+		let row: Result<Vec<f64>, _> = line.trim().split(',').map(|x| x.parse::<f64>()).collect();
+		match row {
+			Ok(ref r) if r.len() == names.len() => rows.push(r.clone()),
+			_ => return Err(io::Error::new(io::ErrorKind::InvalidData,
+				format!("{}: bad row on line {}", path.display(), n + 2))),
+		}
+	}
+	Ok(Table { names: names, rows: rows })
+}
+
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/*
+ Inline SVG line chart, one polyline per run.
+ */
+fn chart(title: &str, series: &[(String, Vec<f64>, Vec<f64>)]) -> String {
+	let (w, h, pad) = (720.0, 360.0, 60.0);
+	let mut x0 = std::f64::INFINITY;
+	let mut x1 = std::f64::NEG_INFINITY;
+	let mut y0 = std::f64::INFINITY;
+	let mut y1 = std::f64::NEG_INFINITY;
+	for &(_, ref xs, ref ys) in series {
+		for (&x, &y) in xs.iter().zip(ys.iter()) {
+			if x.is_finite() && y.is_finite() {
+				x0 = x0.min(x);
+				x1 = x1.max(x);
+				y0 = y0.min(y);
+				y1 = y1.max(y);
+			}
+		}
+	}
+	if !(x0 < x1) {
+		x1 = x0 + 1.0;
+	}
+	if !(y0 < y1) {
+		y0 -= 1.0;
+		y1 += 1.0;
+	}
+	let px = |x: f64| pad + (x - x0)/(x1 - x0)*(w - 2.0*pad);
+	let py = |y: f64| h - pad - (y - y0)/(y1 - y0)*(h - 2.0*pad);
+
+	let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n", w, h);
+	svg += &format!("<text x=\"{}\" y=\"24\" text-anchor=\"middle\" font-size=\"16\">{}</text>\n", w/2.0, escape(title));
+	svg += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#888\"/>\n",
+		pad, pad, w - 2.0*pad, h - 2.0*pad);
+	svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"11\">{:.3e}</text>\n", 2.0, py(y1) + 4.0, y1);
+	svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"11\">{:.3e}</text>\n", 2.0, py(y0) + 4.0, y0);
+	svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"11\">{}</text>\n", pad, h - pad + 16.0, x0);
+	svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"11\" text-anchor=\"end\">t = {}</text>\n", w - pad, h - pad + 16.0, x1);
+
+	for (idx, &(ref name, ref xs, ref ys)) in series.iter().enumerate() {
+		let color = COLORS[idx % COLORS.len()];
+		let mut points = String::new();
+		for (&x, &y) in xs.iter().zip(ys.iter()) {
+			if x.is_finite() && y.is_finite() {
+				points += &format!("{:.2},{:.2} ", px(x), py(y));
+			}
+		}
+		svg += &format!("<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"1.5\" points=\"{}\"/>\n", color, points);
+		svg += &format!("<text x=\"{}\" y=\"{}\" font-size=\"12\" fill=\"{}\">{}</text>\n",
+			w - pad + 6.0, pad + 14.0*(idx as f64 + 1.0), color, escape(name));
+	}
+	svg += "</svg>\n";
+	svg
+}
+
+pub fn write_report(runs: &[String], out: &Path) -> io::Result<()> {
+	let mut tables: Vec<(String, Table)> = vec![];
+	for run in runs {
+		let mut table = read_csv(&Path::new(run).join("diagnostics.csv"))?;
+		// Lagrangian radii are sampled at the same times, so they line up row by row
+		let lagr = Path::new(run).join("lagrangian.csv");
+		if lagr.exists() {
+			let extra = read_csv(&lagr)?;
+			if extra.rows.len() == table.rows.len() {
+				for (idx, name) in extra.names.iter().enumerate() {
+					if !table.names.contains(name) {
+						table.names.push(name.clone());
+						for (row, x) in table.rows.iter_mut().zip(extra.rows.iter()) {
+							row.push(x[idx]);
+						}
+					}
+				}
+			}
+		}
+		tables.push((run.trim_end_matches('/').to_string(), table));
+	}
+
+	// Every column (except t) that all runs have in common gets a chart
+	let mut columns: Vec<String> = vec![];
+	if let Some(&(_, ref first)) = tables.first() {
+		for name in &first.names {
+			if name != "t" && tables.iter().all(|x| x.1.names.contains(name)) {
+				columns.push(name.clone());
+			}
+		}
+	}
+
+	let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>nbabel report</title>\n");
+	html += "<style>body { font-family: sans-serif; margin: 2em; } table { border-collapse: collapse; } td, th { border: 1px solid #ccc; padding: 4px 8px; }</style>\n";
+	html += "</head>\n<body>\n<h1>nbabel run comparison</h1>\n";
+
+	html += "<table>\n<tr><th>run</th><th>samples</th><th>t end</th><th>final dE</th></tr>\n";
+	for &(ref name, ref table) in &tables {
+		let t = table.column("t").unwrap_or_default();
+		let de = table.column("dE").unwrap_or_default();
+		html += &format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{:e}</td></tr>\n", escape(name), table.rows.len(),
+			t.last().cloned().unwrap_or(0.0), de.last().cloned().unwrap_or(0.0));
+	}
+	html += "</table>\n";
+
+	for column in &columns {
+		let mut series = vec![];
+		for &(ref name, ref table) in &tables {
+			series.push((name.clone(), table.column("t").unwrap_or_default(), table.column(column).unwrap_or_default()));
+		}
+		html += &format!("<h2>{}</h2>\n", escape(column));
+		html += &chart(&format!("{} vs t", column), &series);
+	}
+	html += "</body>\n</html>\n";
+
+	let mut f = File::create(out)?;
+	f.write_all(html.as_bytes())
+}
+
+pub fn main(args: &[String]) {
+	let mut runs: Vec<String> = vec![];
+	let mut out = String::from("report.html");
+	let mut it = args.iter();
+	while let Some(arg) = it.next() {
+		match arg.as_str() {
+			"-o" | "--out" => out = it.next().expect("-o needs a file name").clone(),
+			_ => runs.push(arg.clone()),
+		}
+	}
+	if runs.is_empty() {
+		panic!("Usage: nbabel report RUN_DIR... [-o report.html]");
+	}
+	write_report(&runs, Path::new(&out)).expect("Could not write report");
+	info!("Wrote {}", out);
+}
@@ -0,0 +1,37 @@
+/*
+ Small seeded random number generator (xorshift64*), so generated models and
+ fits are reproducible from a seed without pulling in a dependency.
+ */
+pub struct Rng {
+	state: u64,
+}
+
+impl Rng {
+	pub fn new(seed: u64) -> Rng {
+		// Zero is a fixed point of xorshift
+		Rng { state: seed ^ 0x9E37_79B9_7F4A_7C15 }
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+	}
+
+	// Uniform in [0, 1)
+	pub fn uniform(&mut self) -> f64 {
+		(self.next_u64() >> 11) as f64/(1u64 << 53) as f64
+	}
+
+	pub fn range(&mut self, lo: f64, hi: f64) -> f64 {
+		lo + (hi - lo)*self.uniform()
+	}
+
+	// Standard normal deviate (Box-Muller)
+	pub fn normal(&mut self) -> f64 {
+		let u = 1.0 - self.uniform();
+		let v = self.uniform();
+		(-2.0*u.ln()).sqrt()*(2.0*std::f64::consts::PI*v).cos()
+	}
+}
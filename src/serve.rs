@@ -0,0 +1,231 @@
+/*
+ Streaming a run over WebSocket, for web frontends that want to watch it
+ remotely. --serve ws://HOST:PORT listens there; any number of clients can
+ connect at any time and get a JSON text message at most --serve-rate times
+ per wall-clock second:
+
+   {"t": .., "steps": .., "n": .., "E": .., "T": .., "W": .., "dE": ..,
+    "positions": [x0, y0, z0, x1, ...]}
+
+ The energies are those of the last diagnostic step, so streaming adds no
+ force or potential evaluations. Positions are sent in single precision.
+
+ Only what the stream needs of RFC 6455 is here: the opening handshake and
+ unmasked server-to-client text frames. Clients are never read from after
+ the handshake; one that cannot keep up (a write blocking for more than a
+ second) or goes away is dropped. Connections are accepted on a background
+ thread, so a slow handshake never holds up the integration.
+ */
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use status::json_number;
+use {Real, Star};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+	let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+	let mut message = data.to_vec();
+	message.push(0x80);
+	while message.len() % 64 != 56 {
+		message.push(0);
+	}
+	message.extend_from_slice(&((data.len() as u64)*8).to_be_bytes());
+	for block in message.chunks(64) {
+		let mut w = [0u32; 80];
+		for i in 0..16 {
+			w[i] = u32::from_be_bytes([block[4*i], block[4*i + 1], block[4*i + 2], block[4*i + 3]]);
+		}
+		for i in 16..80 {
+			w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+		}
+		let (mut a, mut b, mut c, mut d, mut e) = (h[0], h[1], h[2], h[3], h[4]);
+		for i in 0..80 {
+			let (f, k) = match i {
+				0..=19 => ((b & c) | (!b & d), 0x5A827999),
+				20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+				40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+				_ => (b ^ c ^ d, 0xCA62C1D6),
+			};
+			let x = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(w[i]);
+			e = d;
+			d = c;
+			c = b.rotate_left(30);
+			b = a;
+			a = x;
+		}
+		h[0] = h[0].wrapping_add(a);
+		h[1] = h[1].wrapping_add(b);
+		h[2] = h[2].wrapping_add(c);
+		h[3] = h[3].wrapping_add(d);
+		h[4] = h[4].wrapping_add(e);
+	}
+	let mut out = [0u8; 20];
+	for i in 0..5 {
+		out[4*i..4*i + 4].copy_from_slice(&h[i].to_be_bytes());
+	}
+	out
+}
+
+fn base64(data: &[u8]) -> String {
+	const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+	let mut out = String::new();
+	for chunk in data.chunks(3) {
+		let x = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+		for k in 0..4 {
+			if k <= chunk.len() {
+				out.push(ALPHABET[(x >> (18 - 6*k) & 63) as usize] as char);
+			} else {
+				out.push('=');
+			}
+		}
+	}
+	out
+}
+
+// Sec-WebSocket-Accept for a client's Sec-WebSocket-Key
+pub fn accept_key(key: &str) -> String {
+	base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+// One unmasked text frame
+pub fn text_frame(payload: &str) -> Vec<u8> {
+	let n = payload.len();
+	let mut frame = vec![0x81];
+	if n < 126 {
+		frame.push(n as u8);
+	} else if n <= 0xFFFF {
+		frame.push(126);
+		frame.extend_from_slice(&(n as u16).to_be_bytes());
+	} else {
+		frame.push(127);
+		frame.extend_from_slice(&(n as u64).to_be_bytes());
+	}
+	frame.extend_from_slice(payload.as_bytes());
+	frame
+}
+
+// The message for the stars at time t after steps steps, with energies e and error de
+pub fn message<R: Real>(t: f64, steps: usize, e: &[f64], de: f64, s: &Vec<Star<R>>) -> String {
+	let mut positions = String::with_capacity(30*s.len());
+	for (i, x) in s.iter().enumerate() {
+		for k in 0..3 {
+			if i + k > 0 {
+				positions.push(',');
+			}
+			positions += &format!("{}", x.r[k].to_f64() as f32);
+		}
+	}
+	format!("{{\"t\": {}, \"steps\": {}, \"n\": {}, \"E\": {}, \"T\": {}, \"W\": {}, \"dE\": {}, \"positions\": [{}]}}",
+		json_number(t), steps, s.len(), json_number(e[0]), json_number(e[1]), json_number(e[2]), json_number(de), positions)
+}
+
+// Reads the HTTP upgrade request and answers it; Err for anything that is not a WebSocket handshake
+fn handshake(stream: &mut TcpStream) -> io::Result<()> {
+	let mut reader = BufReader::new(stream.try_clone()?);
+	let mut key = None;
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed during the handshake"));
+		}
+		let line = line.trim_end();
+		if line.is_empty() {
+			break;
+		}
+		if let Some(i) = line.find(':') {
+			if line[..i].trim().eq_ignore_ascii_case("sec-websocket-key") {
+				key = Some(line[i + 1..].trim().to_string());
+			}
+		}
+	}
+	match key {
+		Some(key) => write!(stream, "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept_key(&key)),
+		None => {
+			write!(stream, "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")?;
+			Err(io::Error::new(io::ErrorKind::InvalidData, "not a WebSocket handshake"))
+		},
+	}
+}
+
+pub struct Server {
+	addr: SocketAddr,
+	clients: Arc<Mutex<Vec<TcpStream>>>,
+	interval: f64,
+	sent: Option<Instant>,
+}
+
+impl Server {
+	/*
+	 Listens on url, ws://HOST:PORT (port 0 picks a free one), and sends at
+	 most rate messages per second.
+	 */
+	pub fn bind(url: &str, rate: f64) -> Result<Server, String> {
+		let address = url.trim_start_matches("ws://").trim_end_matches('/');
+		if address.contains('/') || !url.starts_with("ws://") {
+			return Err(format!("--serve needs ws://HOST:PORT, got {}", url));
+		}
+		if !(rate > 0.0) {
+			return Err(format!("--serve-rate needs a positive rate, got {}", rate));
+		}
+		let listener = TcpListener::bind(address).map_err(|x| format!("Could not listen on {}: {}", address, x))?;
+		let addr = listener.local_addr().map_err(|x| x.to_string())?;
+		let clients = Arc::new(Mutex::new(vec![]));
+		let accepted = clients.clone();
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				let mut stream = match stream {
+					Ok(x) => x,
+					Err(_) => continue,
+				};
+				let peer = stream.peer_addr().map(|x| x.to_string()).unwrap_or_default();
+				let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+				match handshake(&mut stream) {
+					Ok(()) => {
+						let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+						let _ = stream.set_nodelay(true);
+						verbose!("Stream client {} connected", peer);
+						accepted.lock().expect("Poisoned client list").push(stream);
+					},
+					Err(x) => verbose!("Stream client {} refused: {}", peer, x),
+				}
+			}
+		});
+		Ok(Server { addr: addr, clients: clients, interval: 1.0/rate, sent: None })
+	}
+
+	pub fn local_addr(&self) -> SocketAddr {
+		self.addr
+	}
+
+	pub fn clients(&self) -> usize {
+		self.clients.lock().expect("Poisoned client list").len()
+	}
+
+	// Whether the next message is due, with at least one client to get it
+	pub fn due(&self) -> bool {
+		self.sent.map_or(true, |x| x.elapsed().as_secs_f64() >= self.interval) && self.clients() > 0
+	}
+
+	// Sends text to every client, dropping those that fail
+	pub fn broadcast(&mut self, text: &str) {
+		let frame = text_frame(text);
+		let mut clients = self.clients.lock().expect("Poisoned client list");
+		clients.retain(|x| {
+			let mut stream = x;
+			match stream.write_all(&frame) {
+				Ok(()) => true,
+				Err(error) => {
+					verbose!("Stream client {} dropped: {}", x.peer_addr().map(|x| x.to_string()).unwrap_or_default(), error);
+					false
+				},
+			}
+		});
+		self.sent = Some(Instant::now());
+	}
+}
@@ -0,0 +1,271 @@
+/*
+ Explicitly vectorized force kernel. Positions, masses and softening lengths
+ are copied into f64 arrays (structure of arrays) and every i-star is
+ interacted with 8 (AVX-512), 4 (AVX2) or 2 (NEON) j-stars per iteration.
+
+ Unlike the pair loop this visits the full i != j square instead of the
+ i < j triangle: twice the arithmetic, but no scattered writes to a_j, and
+ every row is independent so threads need no reduction and the result does
+ not depend on the thread count.
+ */
+use std::thread;
+
+use timing::Clock;
+use {Params, Real, Softening, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Isa {
+	Avx512,
+	Avx2,
+	Neon,
+	Scalar,
+}
+
+impl Isa {
+	// Widest instruction set the CPU supports
+	pub fn detect() -> Isa {
+		for &isa in &[Isa::Avx512, Isa::Avx2, Isa::Neon] {
+			if isa.available() {
+				return isa;
+			}
+		}
+		Isa::Scalar
+	}
+
+	pub fn available(self) -> bool {
+		match self {
+			#[cfg(target_arch = "x86_64")]
+			Isa::Avx512 => is_x86_feature_detected!("avx512f"),
+			#[cfg(target_arch = "x86_64")]
+			Isa::Avx2 => is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma"),
+			#[cfg(target_arch = "aarch64")]
+			Isa::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+			Isa::Scalar => true,
+			#[allow(unreachable_patterns)]
+			_ => false,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Isa::Avx512 => "avx512",
+			Isa::Avx2 => "avx2",
+			Isa::Neon => "neon",
+			Isa::Scalar => "scalar",
+		}
+	}
+}
+
+struct Soa {
+	x: Vec<f64>,
+	y: Vec<f64>,
+	z: Vec<f64>,
+	m: Vec<f64>,
+	e: Vec<f64>,
+	// Mean softening rule; otherwise the pair uses min(e_i, e_j), which is also
+	// the fixed rule since all e are equal then
+	mean: bool,
+}
+
+// Acceleration of star i due to star j, added to acc
+fn pair(soa: &Soa, i: usize, j: usize, acc: &mut [f64; 3]) {
+	let d = [soa.x[j] - soa.x[i], soa.y[j] - soa.y[i], soa.z[j] - soa.z[i]];
+	let eps2 = if soa.mean {
+		0.5*(soa.e[i]*soa.e[i] + soa.e[j]*soa.e[j])
+	} else {
+		soa.e[i].min(soa.e[j]).powi(2)
+	};
+	let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + eps2;
+	if r2 > 0.0 {
+		let f = soa.m[j]/(r2*r2.sqrt());
+		for k in 0..3 {
+			acc[k] += f*d[k];
+		}
+	}
+}
+
+fn row_scalar(soa: &Soa, i: usize, from: usize) -> [f64; 3] {
+	let mut acc = [0.0; 3];
+	for j in from..soa.x.len() {
+		pair(soa, i, j, &mut acc);
+	}
+	acc
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2,fma")]
+unsafe fn row_avx2(soa: &Soa, i: usize) -> [f64; 3] {
+	use std::arch::x86_64::*;
+	let n = soa.x.len();
+	let (xi, yi, zi) = (_mm256_set1_pd(soa.x[i]), _mm256_set1_pd(soa.y[i]), _mm256_set1_pd(soa.z[i]));
+	let ei = _mm256_set1_pd(soa.e[i]);
+	let half = _mm256_set1_pd(0.5);
+	let zero = _mm256_setzero_pd();
+	let (mut ax, mut ay, mut az) = (zero, zero, zero);
+	let mut j = 0;
+	while j + 4 <= n {
+		let dx = _mm256_sub_pd(_mm256_loadu_pd(soa.x.as_ptr().add(j)), xi);
+		let dy = _mm256_sub_pd(_mm256_loadu_pd(soa.y.as_ptr().add(j)), yi);
+		let dz = _mm256_sub_pd(_mm256_loadu_pd(soa.z.as_ptr().add(j)), zi);
+		let ej = _mm256_loadu_pd(soa.e.as_ptr().add(j));
+		let eps2 = if soa.mean {
+			_mm256_mul_pd(half, _mm256_fmadd_pd(ei, ei, _mm256_mul_pd(ej, ej)))
+		} else {
+			let e = _mm256_min_pd(ei, ej);
+			_mm256_mul_pd(e, e)
+		};
+		let r2 = _mm256_fmadd_pd(dz, dz, _mm256_fmadd_pd(dy, dy, _mm256_fmadd_pd(dx, dx, eps2)));
+		let f = _mm256_div_pd(_mm256_loadu_pd(soa.m.as_ptr().add(j)), _mm256_mul_pd(r2, _mm256_sqrt_pd(r2)));
+		// Drops i == j without softening, where f is infinite
+		let f = _mm256_and_pd(f, _mm256_cmp_pd(r2, zero, _CMP_GT_OQ));
+		ax = _mm256_fmadd_pd(f, dx, ax);
+		ay = _mm256_fmadd_pd(f, dy, ay);
+		az = _mm256_fmadd_pd(f, dz, az);
+		j += 4;
+	}
+	let mut acc = row_scalar(soa, i, j);
+	let mut lanes = [0.0; 4];
+	for (k, v) in [ax, ay, az].iter().enumerate() {
+		_mm256_storeu_pd(lanes.as_mut_ptr(), *v);
+		acc[k] += lanes[0] + lanes[1] + lanes[2] + lanes[3];
+	}
+	acc
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn row_avx512(soa: &Soa, i: usize) -> [f64; 3] {
+	use std::arch::x86_64::*;
+	let n = soa.x.len();
+	let (xi, yi, zi) = (_mm512_set1_pd(soa.x[i]), _mm512_set1_pd(soa.y[i]), _mm512_set1_pd(soa.z[i]));
+	let ei = _mm512_set1_pd(soa.e[i]);
+	let half = _mm512_set1_pd(0.5);
+	let zero = _mm512_setzero_pd();
+	let (mut ax, mut ay, mut az) = (zero, zero, zero);
+	let mut j = 0;
+	while j + 8 <= n {
+		let dx = _mm512_sub_pd(_mm512_loadu_pd(soa.x.as_ptr().add(j)), xi);
+		let dy = _mm512_sub_pd(_mm512_loadu_pd(soa.y.as_ptr().add(j)), yi);
+		let dz = _mm512_sub_pd(_mm512_loadu_pd(soa.z.as_ptr().add(j)), zi);
+		let ej = _mm512_loadu_pd(soa.e.as_ptr().add(j));
+		let eps2 = if soa.mean {
+			_mm512_mul_pd(half, _mm512_fmadd_pd(ei, ei, _mm512_mul_pd(ej, ej)))
+		} else {
+			let e = _mm512_min_pd(ei, ej);
+			_mm512_mul_pd(e, e)
+		};
+		let r2 = _mm512_fmadd_pd(dz, dz, _mm512_fmadd_pd(dy, dy, _mm512_fmadd_pd(dx, dx, eps2)));
+		let f = _mm512_div_pd(_mm512_loadu_pd(soa.m.as_ptr().add(j)), _mm512_mul_pd(r2, _mm512_sqrt_pd(r2)));
+		let f = _mm512_maskz_mov_pd(_mm512_cmp_pd_mask(r2, zero, _CMP_GT_OQ), f);
+		ax = _mm512_fmadd_pd(f, dx, ax);
+		ay = _mm512_fmadd_pd(f, dy, ay);
+		az = _mm512_fmadd_pd(f, dz, az);
+		j += 8;
+	}
+	let mut acc = row_scalar(soa, i, j);
+	acc[0] += _mm512_reduce_add_pd(ax);
+	acc[1] += _mm512_reduce_add_pd(ay);
+	acc[2] += _mm512_reduce_add_pd(az);
+	acc
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn row_neon(soa: &Soa, i: usize) -> [f64; 3] {
+	use std::arch::aarch64::*;
+	let n = soa.x.len();
+	let (xi, yi, zi) = (vdupq_n_f64(soa.x[i]), vdupq_n_f64(soa.y[i]), vdupq_n_f64(soa.z[i]));
+	let ei = vdupq_n_f64(soa.e[i]);
+	let half = vdupq_n_f64(0.5);
+	let zero = vdupq_n_f64(0.0);
+	let (mut ax, mut ay, mut az) = (zero, zero, zero);
+	let mut j = 0;
+	while j + 2 <= n {
+		let dx = vsubq_f64(vld1q_f64(soa.x.as_ptr().add(j)), xi);
+		let dy = vsubq_f64(vld1q_f64(soa.y.as_ptr().add(j)), yi);
+		let dz = vsubq_f64(vld1q_f64(soa.z.as_ptr().add(j)), zi);
+		let ej = vld1q_f64(soa.e.as_ptr().add(j));
+		let eps2 = if soa.mean {
+			vmulq_f64(half, vfmaq_f64(vmulq_f64(ej, ej), ei, ei))
+		} else {
+			let e = vminq_f64(ei, ej);
+			vmulq_f64(e, e)
+		};
+		let r2 = vfmaq_f64(vfmaq_f64(vfmaq_f64(eps2, dx, dx), dy, dy), dz, dz);
+		let f = vdivq_f64(vld1q_f64(soa.m.as_ptr().add(j)), vmulq_f64(r2, vsqrtq_f64(r2)));
+		let f = vbslq_f64(vcgtq_f64(r2, zero), f, zero);
+		ax = vfmaq_f64(ax, f, dx);
+		ay = vfmaq_f64(ay, f, dy);
+		az = vfmaq_f64(az, f, dz);
+		j += 2;
+	}
+	let mut acc = row_scalar(soa, i, j);
+	acc[0] += vaddvq_f64(ax);
+	acc[1] += vaddvq_f64(ay);
+	acc[2] += vaddvq_f64(az);
+	acc
+}
+
+fn row(soa: &Soa, i: usize, isa: Isa) -> [f64; 3] {
+	// available() is checked once in acceleration(), which makes these calls sound
+	match isa {
+		#[cfg(target_arch = "x86_64")]
+		Isa::Avx512 => unsafe { row_avx512(soa, i) },
+		#[cfg(target_arch = "x86_64")]
+		Isa::Avx2 => unsafe { row_avx2(soa, i) },
+		#[cfg(target_arch = "aarch64")]
+		Isa::Neon => unsafe { row_neon(soa, i) },
+		_ => row_scalar(soa, i, 0),
+	}
+}
+
+/*
+ Computes all accelerations with the vector kernel, in f64 whatever R is.
+ Falls back to the scalar row loop when the CPU lacks isa. Returns how long
+ each thread was busy, in seconds, like ::acceleration.
+ */
+pub fn acceleration<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>, isa: Isa) -> Vec<f64> {
+	let isa = if isa.available() { isa } else { Isa::Scalar };
+	let n = s.len();
+	let eps: Vec<f64> = p.star_eps(s).iter().map(|x| x.to_f64()).collect();
+	let soa = Soa {
+		x: s.iter().map(|x| x.r[0].to_f64()).collect(),
+		y: s.iter().map(|x| x.r[1].to_f64()).collect(),
+		z: s.iter().map(|x| x.r[2].to_f64()).collect(),
+		m: s.iter().map(|x| x.m.to_f64()).collect(),
+		e: eps,
+		mean: p.softening == Softening::Mean,
+	};
+
+	let threads = p.threads.max(1);
+	let mut busy: Vec<f64> = vec![0.0; threads];
+	let mut rows: Vec<Vec<[f64; 3]>> = vec![vec![]; threads];
+	if threads == 1 {
+		let clock = Clock::start();
+		rows[0] = (0..n).map(|i| row(&soa, i, isa)).collect();
+		busy[0] = clock.seconds();
+	} else {
+		let soa = &soa;
+		thread::scope(|scope| {
+			let handles: Vec<_> = (0..threads).map(|thread_index| scope.spawn(move || {
+				let clock = Clock::start();
+				let thread_start = n * thread_index / threads;
+				let thread_end = n * (thread_index + 1) / threads;
+				let acc: Vec<[f64; 3]> = (thread_start..thread_end).map(|i| row(soa, i, isa)).collect();
+				(acc, clock.seconds())
+			})).collect();
+			for (thread_index, handle) in handles.into_iter().enumerate() {
+				let (acc, seconds) = handle.join().expect("Thread failure, RIP");
+				rows[thread_index] = acc;
+				busy[thread_index] = seconds;
+			}
+		});
+	}
+
+	for (si, a) in rows.iter().flatten().enumerate() {
+		for i in 0..3 {
+			s[si].a[i] = R::from_f64(a[i]);
+		}
+	}
+	busy
+}
@@ -0,0 +1,648 @@
+/*
+ A running system: the stars, the settings and the clock, stepped with the
+ same predictor-corrector leapfrog as the command line driver.
+ */
+use std::fmt;
+
+use central::Central;
+use collisions;
+use collisions::{Collisions, Outcome};
+use columns::Columns;
+use cosmology::Cosmology;
+use drag::Drag;
+use external;
+use external::ExternalPotential;
+use fixed::Fixed;
+use massloss::{MassEvolution, MassLoss};
+use metadata::Metadata;
+use observer::Observer;
+use planar;
+use pn::PostNewtonian;
+use real::c;
+use regularize::Regularization;
+use rng::Rng;
+use solver;
+use solver::{Direct, ForceSolver};
+use tidal;
+use tidal::Tidal;
+use timing::{Clock, Phase, Timers};
+use timestep::Timestep;
+use track::Tracks;
+use {check_dim, energies, masses, pairs, update_positions, update_velocities, NBodyError, Params, Real, Star};
+
+/*
+ Energy bookkeeping without the O(N^2) potential sum. The kinetic energy is
+ O(N) anyway; the potential changes by minus the work the forces do, which
+ over a step is sum m (r' - r).(a + a')/2 by the trapezoidal rule (second
+ order, like the integrator). Every resync steps the potential is recomputed
+ exactly so the estimate cannot drift away.
+ */
+pub struct EnergyTracker<R = f64> {
+	pub resync: usize,
+	potential: R,
+	last_sync: usize,
+}
+
+pub struct Simulation<R = f64> {
+	pub s: Vec<Star<R>>,
+	pub p: Params<R>,
+	pub t: R,
+	pub steps: usize,
+	pub solver: Box<dyn ForceSolver<R>>,
+	// Events reported by the solver (e.g. switches), with the time they happened
+	pub events: Vec<(R, String)>,
+	pub energy_tracker: Option<EnergyTracker<R>>,
+	// Extra input columns, carried along for the output and ignored otherwise
+	pub columns: Columns,
+	// Analytic potentials acting on every star, see set_external()
+	pub external: Vec<Box<dyn ExternalPotential>>,
+	// Angular velocity of a frame rotating about z, for the Coriolis term
+	pub rotating: Option<f64>,
+	// Unsoftened, possibly sub-cycled central object, see set_central()
+	pub central: Option<Central>,
+	// Kepler drift for the closest bound pair, see regularize.rs
+	pub regularization: Option<Regularization>,
+	// Merging or bouncing of stars that touch, see collisions.rs
+	pub collisions: Option<Collisions>,
+	// Post-Newtonian terms between compact stars, see pn.rs
+	pub post_newtonian: Option<PostNewtonian>,
+	// Drag on every star, applied like the Coriolis term
+	pub drag: Option<Drag>,
+	// Expanding background for comoving coordinates, see cosmology.rs
+	pub cosmology: Option<Cosmology>,
+	// Stars held in place, see set_fixed()
+	pub fixed: Option<Fixed>,
+	// Masses changing with time, see set_mass_loss()
+	pub mass_loss: Option<MassEvolution>,
+	// Adaptive step size, applied to p.dt by adapt(); see timestep.rs
+	pub timestep: Option<Timestep>,
+	// Wall-clock time per phase, see timing.rs
+	pub timers: Timers,
+	// Called after every step and at every snapshot, see observe()
+	pub observers: Vec<Box<dyn Observer<R>>>,
+	// Trajectory files of selected stars; written by the driver, renumbered here on merges
+	pub tracks: Option<Tracks>,
+	// Provenance of the run, written into checkpoints
+	pub metadata: Option<Metadata>,
+	// Busy seconds per thread and wall seconds of the last force evaluation
+	thread_busy: Vec<f64>,
+	force_wall: f64,
+}
+
+/*
+ Snapshot of the state of a simulation, for tests and debugging.
+ */
+pub struct Stats {
+	pub n: usize,
+	pub mass: f64,
+	pub t: f64,
+	pub steps: usize,
+	pub dt: f64,
+	// Both 0 with fewer than 2 stars, when there is no pair
+	pub min_separation: f64,
+	pub max_separation: f64,
+	// min, median and max of the per-star step sizes the masses module would pick; 0 without pairs
+	pub dt_distribution: (f64, f64, f64),
+	// Depth of the force tree; None for direct summation
+	pub tree_depth: Option<usize>,
+	// Busy fraction of every force thread during the last evaluation
+	pub thread_utilization: Vec<f64>,
+}
+
+impl<R: Real> Simulation<R> {
+	pub fn new(s: Vec<Star<R>>, p: Params<R>) -> Simulation<R> {
+		Simulation::with_solver(s, p, Box::new(Direct))
+	}
+
+	// Panics where try_with_solver() returns an error
+	pub fn with_solver(s: Vec<Star<R>>, p: Params<R>, solver: Box<dyn ForceSolver<R>>) -> Simulation<R> {
+		Simulation::try_with_solver(s, p, solver).unwrap_or_else(|e| panic!("{}", e))
+	}
+
+	pub fn try_new(s: Vec<Star<R>>, p: Params<R>) -> Result<Simulation<R>, NBodyError> {
+		Simulation::try_with_solver(s, p, Box::new(Direct))
+	}
+
+	/*
+	 Fails if the stars have fewer components than p.dim needs (see
+	 check_dim), or if p.dim > 3 with a solver other than direct summation,
+	 the only one with kernels beyond 3D.
+	 */
+	pub fn try_with_solver(s: Vec<Star<R>>, p: Params<R>, solver: Box<dyn ForceSolver<R>>) -> Result<Simulation<R>, NBodyError> {
+		check_dim(&s, &p)?;
+		if p.dim > 3 && solver.name() != "direct" {
+			return Err(NBodyError::Config(format!("The {} solver is 3D only, {} dimensions need direct summation", solver.name(), p.dim)));
+		}
+		let mut sim = Simulation { s: s, p: p, t: R::zero(), steps: 0, solver: solver, events: vec![], energy_tracker: None, columns: Columns::default(), external: vec![], rotating: None, central: None, regularization: None, collisions: None, post_newtonian: None, drag: None, cosmology: None, fixed: None, mass_loss: None, timestep: None, timers: Timers::default(), observers: vec![], tracks: None, metadata: None, thread_busy: vec![], force_wall: 0.0 };
+		sim.forces();
+		Ok(sim)
+	}
+
+	fn forces(&mut self) {
+		let clock = Clock::start();
+		self.thread_busy = self.solver.accelerations(&mut self.s, &self.p);
+		external::add_accelerations(&mut self.s, &self.external);
+		if let Some(x) = self.central {
+			x.unsoften(&mut self.s, &self.p);
+		}
+		if let Some(ref x) = self.post_newtonian {
+			x.add_accelerations(&mut self.s);
+		}
+		if let Some(ref x) = self.fixed {
+			x.hold(&mut self.s);
+		}
+		// Motion in the z = 0 plane, see set_planar()
+		if self.p.dim == 2 {
+			planar::confine(&mut self.s);
+		}
+		self.force_wall = clock.seconds();
+		self.timers.add(Phase::Forces, self.force_wall);
+		for event in self.solver.events() {
+			self.events.push((self.t, event));
+		}
+	}
+
+	pub fn step(&mut self) {
+		let clock = Clock::start();
+		let forces = self.timers.run.get(Phase::Forces);
+		let half = 0.5*self.p.dt.to_f64();
+		if let Some(omega) = self.rotating {
+			tidal::coriolis(&mut self.s, omega, half);
+		}
+		if let Some(x) = self.drag {
+			x.apply(&mut self.s, half);
+		}
+		let mut regularized = None;
+		if let Some(ref mut x) = self.regularization {
+			if let Some(event) = x.update(&self.s) {
+				self.events.push((self.t, event));
+			}
+			regularized = x.pair;
+		}
+		// None when the energy tracker has to resync
+		let work = match (self.central, regularized) {
+			(Some(x), _) if x.substeps > 1 => Some(self.subcycled_step(x)),
+			(_, Some(pair)) => {
+				self.regularized_step(pair);
+				None
+			},
+			_ if self.cosmology.is_some() => {
+				self.comoving_step();
+				None
+			},
+			_ => {
+				update_positions(&mut self.s, &self.p);
+				self.forces();
+				let work = if self.energy_tracker.is_some() { self.work() } else { R::zero() };
+				update_velocities(&mut self.s, &self.p);
+				Some(work)
+			},
+		};
+		if let Some(x) = self.drag {
+			x.apply(&mut self.s, half);
+		}
+		if let Some(omega) = self.rotating {
+			tidal::coriolis(&mut self.s, omega, half);
+		}
+		self.t += self.p.dt;
+		self.steps += 1;
+		let collided = self.collide();
+		let lost = self.lose_mass();
+
+		let mut resync = None;
+		if let Some(ref mut tracker) = self.energy_tracker {
+			tracker.potential -= work.unwrap_or(R::zero());
+			if work.is_none() || collided || lost || self.steps - tracker.last_sync >= tracker.resync {
+				resync = Some(tracker.resync);
+			}
+		}
+		if let Some(every) = resync {
+			self.track_energy_every(every);
+		}
+		let spent = clock.seconds() - (self.timers.run.get(Phase::Forces) - forces);
+		self.timers.add(Phase::Integration, spent.max(0.0));
+		self.timers.step();
+		for x in self.observers.iter_mut() {
+			x.on_step(self.t, &self.s);
+		}
+	}
+
+	// Relative errors of the solver's forces on k random stars against direct summation, see solver::force_errors()
+	pub fn verify_forces(&mut self, k: usize, rng: &mut Rng) -> Vec<f64> {
+		let clock = Clock::start();
+		let x = solver::force_errors(&mut *self.solver, &self.s, &self.p, k, rng);
+		self.timers.add(Phase::Diagnostics, clock.seconds());
+		x
+	}
+
+	/*
+	 Sets p.dt to the adaptive step size for the next step, if there is one.
+	 Separate from step() so the caller can still shorten the step, e.g. to
+	 end on an output time.
+	 */
+	pub fn adapt(&mut self) {
+		if let Some(x) = self.timestep {
+			let clock = Clock::start();
+			self.p.dt = x.dt(&self.s, &self.p);
+			self.timers.add(Phase::Integration, clock.seconds());
+		}
+	}
+
+	/*
+	 Resolves collisions after a step. Merging removes stars, so the central
+	 object and the regularized pair are renumbered and the forces
+	 recomputed. Returns whether anything collided.
+	 */
+	fn collide(&mut self) -> bool {
+		let done = match self.collisions {
+			Some(ref mut x) => x.resolve(&mut self.s, &mut self.columns),
+			None => return false,
+		};
+		let mut merged = false;
+		for (outcome, msg) in done.iter() {
+			self.events.push((self.t, msg.clone()));
+			if let Outcome::Merged(i, j) = *outcome {
+				merged = true;
+				if let Some(ref mut x) = self.central {
+					x.index = if x.index == j { i } else { collisions::shift(x.index, j) };
+				}
+				if let Some(ref mut x) = self.regularization {
+					x.pair = match x.pair {
+						Some((a, b)) if a != i && a != j && b != i && b != j => Some((collisions::shift(a, j), collisions::shift(b, j))),
+						_ => None,
+					};
+				}
+				if let Some(ref mut x) = self.post_newtonian {
+					x.merged(i, j);
+				}
+				if let Some(ref mut x) = self.fixed {
+					x.merged(i, j);
+				}
+				if let Some(ref mut x) = self.mass_loss {
+					x.merged(i, j);
+				}
+				if let Some(ref mut x) = self.tracks {
+					x.merged(i, j);
+				}
+			}
+		}
+		if merged {
+			self.forces();
+		} else if let Some(ref x) = self.fixed {
+			// A bounce must not set a fixed star moving
+			x.hold(&mut self.s);
+		}
+		!done.is_empty()
+	}
+
+	/*
+	 Updates the masses for the new time, recomputes the forces and adds the
+	 energy change to the mass-loss adjustment. Returns whether any mass
+	 changed.
+	 */
+	fn lose_mass(&mut self) -> bool {
+		let t = self.t.to_f64();
+		if !self.mass_loss.as_ref().map_or(false, |x| x.due(t)) {
+			return false;
+		}
+		let before = self.energies()[0];
+		if let Some(ref mut x) = self.mass_loss {
+			x.apply(&mut self.s, t);
+		}
+		self.forces();
+		let after = self.energies()[0];
+		if let Some(ref mut x) = self.mass_loss {
+			x.adjustment += (after - before).to_f64();
+		}
+		true
+	}
+
+	/*
+	 A step with the star-hole forces sub-cycled, see central.rs. Returns the
+	 work done by the forces when energy is tracked, by the same trapezoidal
+	 rule as work() over the actual displacements.
+	 */
+	fn subcycled_step(&mut self, central: Central) -> R {
+		let half: R = c::<R>(0.5)*self.p.dt;
+		let old: Vec<Vec<R>> = if self.energy_tracker.is_some() { self.s.iter().map(|x| x.r.clone()).collect() } else { vec![] };
+		let fast = central.accelerations(&self.s, None);
+		for (i, star) in self.s.iter_mut().enumerate() {
+			for k in 0..3 {
+				star.a0[k] = star.a[k];
+				star.v[k] += half*(star.a[k] - fast[i][k]);
+			}
+		}
+		central.subcycle(&mut self.s, self.p.dt);
+		self.forces();
+		let fast = central.accelerations(&self.s, None);
+		let mut w = R::zero();
+		for (i, star) in self.s.iter_mut().enumerate() {
+			for k in 0..3 {
+				if !old.is_empty() {
+					w += star.m*(star.r[k] - old[i][k])*c::<R>(0.5)*(star.a0[k] + star.a[k]);
+				}
+				star.v[k] += half*(star.a[k] - fast[i][k]);
+				star.a0[k] = star.a[k];
+			}
+		}
+		w
+	}
+
+	/*
+	 A step with the pair (i, j) regularized, see regularize.rs. The pair's
+	 softened mutual force is taken out of the kicks and replaced by the
+	 Kepler drift.
+	 */
+	fn regularized_step(&mut self, (i, j): (usize, usize)) {
+		let reg = self.regularization.expect("regularized_step needs a regularization");
+		self.kick_without(i, j);
+		for star in self.s.iter_mut() {
+			for k in 0..3 {
+				star.a0[k] = star.a[k];
+			}
+		}
+		reg.drift(&mut self.s, self.p.dt);
+		self.forces();
+		self.kick_without(i, j);
+		for star in self.s.iter_mut() {
+			for k in 0..3 {
+				star.a0[k] = star.a[k];
+			}
+		}
+	}
+
+	/*
+	 A kick-drift-kick step in comoving coordinates, see cosmology.rs. The
+	 kicks change the momentum a^2 v by g times the integral of 1/a over
+	 half the step, the drift moves by the momentum times the integral of
+	 1/a^2 over the whole of it.
+	 */
+	fn comoving_step(&mut self) {
+		let mut x = self.cosmology.expect("comoving_step needs a cosmology");
+		let half = 0.5*self.p.dt.to_f64();
+		let (middle, kick0, drift0) = x.advance(x.a, half);
+		let (end, kick1, drift1) = x.advance(middle, half);
+		let (a0, kick0, kick1, drift) = (R::from_f64(x.a), R::from_f64(kick0), R::from_f64(kick1), R::from_f64(drift0 + drift1));
+		for star in self.s.iter_mut() {
+			for k in 0..3 {
+				star.a0[k] = star.a[k];
+				star.v[k] = a0*a0*star.v[k] + kick0*star.a[k];
+				star.r[k] += drift*star.v[k];
+			}
+		}
+		self.forces();
+		let a1 = R::from_f64(end);
+		for star in self.s.iter_mut() {
+			for k in 0..3 {
+				star.v[k] = (star.v[k] + kick1*star.a[k])/(a1*a1);
+				star.a0[k] = star.a[k];
+			}
+		}
+		x.a = end;
+		self.cosmology = Some(x);
+	}
+
+	// Half kick with the accelerations less the mutual force of i and j
+	fn kick_without(&mut self, i: usize, j: usize) {
+		let half: R = c::<R>(0.5)*self.p.dt;
+		let (ai, aj) = Regularization::pair_accelerations(&self.s, i, j, Some(&self.p));
+		for star in self.s.iter_mut() {
+			for k in 0..3 {
+				star.v[k] += half*star.a[k];
+			}
+		}
+		for k in 0..3 {
+			self.s[i].v[k] -= half*ai[k];
+			self.s[j].v[k] -= half*aj[k];
+		}
+	}
+
+	/*
+	 Work done by the forces over the step just taken. Called between the
+	 force evaluation and the velocity update, when v is still the old
+	 velocity, a0 the old and a the new acceleration.
+	 */
+	fn work(&self) -> R {
+		let dt = self.p.dt;
+		let half: R = c(0.5);
+		let mut w = R::zero();
+		for star in &self.s {
+			for i in 0..self.p.dim {
+				let dr = dt*star.v[i] + half*dt*dt*star.a0[i];
+				w += star.m*dr*half*(star.a0[i] + star.a[i]);
+			}
+		}
+		w
+	}
+
+	// Sets the external potentials and recomputes the accelerations with them
+	pub fn set_external(&mut self, external: Vec<Box<dyn ExternalPotential>>) {
+		self.external = external;
+		self.forces();
+		if let Some(every) = self.energy_tracker.as_ref().map(|x| x.resync) {
+			self.track_energy_every(every);
+		}
+	}
+
+	/*
+	 Puts the system in the frame co-rotating with a circular galactic orbit:
+	 adds the tidal field to the external potentials and switches on the
+	 Coriolis term. Energies are then Jacobi energies.
+	 */
+	pub fn set_tidal(&mut self, tidal: Tidal) {
+		let mut external = std::mem::take(&mut self.external);
+		external.push(Box::new(tidal));
+		self.rotating = Some(tidal.omega);
+		self.set_external(external);
+	}
+
+	/*
+	 Flags a central object that is never softened against, sub-cycled when
+	 central.substeps > 1, and recomputes the accelerations.
+	 */
+	pub fn set_central(&mut self, central: Central) {
+		self.central = Some(central);
+		self.forces();
+		if let Some(every) = self.energy_tracker.as_ref().map(|x| x.resync) {
+			self.track_energy_every(every);
+		}
+	}
+
+	// Adds post-Newtonian terms between the flagged stars and recomputes the accelerations
+	pub fn set_post_newtonian(&mut self, x: PostNewtonian) {
+		self.post_newtonian = Some(x);
+		self.forces();
+	}
+
+	/*
+	 Holds the stars of x in place from now on, see fixed.rs, and recomputes
+	 the accelerations. The sub-cycled central object and the regularized
+	 pair move stars by their own rules and do not know about fixed stars.
+	 */
+	pub fn set_fixed(&mut self, x: Fixed) {
+		self.fixed = Some(x);
+		self.forces();
+	}
+
+	/*
+	 Confines the stars to the z = 0 plane from now on, see planar.rs:
+	 projects them onto it, switches the kernels to 2D and recomputes the
+	 accelerations. p.dim == 2 is all that marks a planar run, so stars
+	 already in the plane can also be started with it set.
+	 */
+	pub fn set_planar(&mut self) {
+		planar::flatten(&mut self.s);
+		self.p.dim = 2;
+		self.forces();
+	}
+
+	// Lets the masses follow model from now on, see massloss.rs
+	pub fn set_mass_loss(&mut self, model: Box<dyn MassLoss>) {
+		self.mass_loss = Some(MassEvolution::new(model, &self.s, self.t.to_f64()));
+	}
+
+	// Energy change due to mass loss so far, to add to the initial energy for dE
+	pub fn mass_loss_adjustment(&self) -> f64 {
+		self.mass_loss.as_ref().map_or(0.0, |x| x.adjustment)
+	}
+
+	/*
+	 Regularizes the closest pair when it is bound and closer than radius,
+	 see regularize.rs. Not combined with a sub-cycled central object, which
+	 takes precedence.
+	 */
+	pub fn regularize(&mut self, radius: f64) {
+		self.regularization = Some(Regularization::new(radius));
+	}
+
+	// Registers an observer for the rest of the run
+	pub fn observe(&mut self, observer: Box<dyn Observer<R>>) {
+		self.observers.push(observer);
+	}
+
+	// Hands the current state and its energies e to the observers' on_snapshot
+	pub fn snapshot(&mut self, e: &[R]) {
+		for x in self.observers.iter_mut() {
+			x.on_snapshot(self.t, &self.s, e);
+		}
+	}
+
+	/*
+	 Gravitational potential at arbitrary points, e.g. a grid for contour
+	 plots, from the stars through the active solver plus the external
+	 potentials. The central object is softened like any other star here.
+	 */
+	pub fn potential_at(&self, points: &[[f64; 3]]) -> Vec<f64> {
+		self.solver.field(&self.s, &self.p, points).into_iter().zip(points.iter())
+			.map(|((phi, _), &x)| phi + self.external.iter().map(|e| e.potential(x)).sum::<f64>()).collect()
+	}
+
+	// Acceleration a test particle would feel at each of points, see potential_at()
+	pub fn acceleration_at(&self, points: &[[f64; 3]]) -> Vec<[f64; 3]> {
+		self.solver.field(&self.s, &self.p, points).into_iter().zip(points.iter()).map(|((_, mut a), &x)| {
+			for e in &self.external {
+				let ae = e.acceleration(x);
+				for k in 0..3 {
+					a[k] += ae[k];
+				}
+			}
+			a
+		}).collect()
+	}
+
+	// [E, T, W], with the energy in the external potentials counted in W
+	pub fn energies(&self) -> Vec<R> {
+		let e = self.solver.energies(&self.s, &self.p).unwrap_or_else(|| energies(&self.s, &self.p));
+		let mut e = external::with_energy(e, &self.s, &self.external);
+		if let Some(x) = self.central {
+			let w = x.energy_correction(&self.s, &self.p);
+			e[0] += w;
+			e[2] += w;
+		}
+		if let Some(x) = self.regularization {
+			let w = x.energy_correction(&self.s, &self.p);
+			e[0] += w;
+			e[2] += w;
+		}
+		e
+	}
+
+	/*
+	 Switches on incremental energy tracking with an exact resync every
+	 resync steps (starting with one now).
+	 */
+	pub fn track_energy_every(&mut self, resync: usize) {
+		let e = self.energies();
+		self.energy_tracker = Some(EnergyTracker { resync: resync.max(1), potential: e[2], last_sync: self.steps });
+	}
+
+	// The first star with a non-finite position or velocity, if any
+	pub fn first_non_finite(&self) -> Option<usize> {
+		self.s.iter().position(|x| x.r.iter().chain(x.v.iter()).any(|x| !x.is_finite()))
+	}
+
+	// [E, T, W] like energies(), with W from the tracker when there is one
+	pub fn tracked_energies(&self) -> Vec<R> {
+		match self.energy_tracker {
+			Some(ref tracker) => {
+				let mut kinetic = R::zero();
+				for star in &self.s {
+					kinetic += c::<R>(0.5)*star.m*star.v[..self.p.dim].iter().fold(R::zero(), |v2, x| v2 + x.powi(2));
+				}
+				vec![kinetic + tracker.potential, kinetic, tracker.potential]
+			},
+			None => self.energies(),
+		}
+	}
+
+	pub fn stats(&self) -> Stats {
+		let s = &self.s;
+		let (mut rmin2, mut rmax2) = (R::zero(), R::zero());
+		let mut dt_distribution = (0.0, 0.0, 0.0);
+		if s.len() >= 2 {
+			rmin2 = R::infinity();
+			pairs::for_each_pair(s, |_, _, _, r2| {
+				rmin2 = rmin2.min(r2);
+				rmax2 = rmax2.max(r2);
+			});
+			let mut dts: Vec<f64> = masses::timescales(s).iter().map(|x| masses::ETA*x.to_f64()).collect();
+			dts.sort_by(|a, b| a.partial_cmp(b).expect("NaN timescale"));
+			dt_distribution = (dts[0], dts[dts.len()/2], dts[dts.len() - 1]);
+		}
+
+		let wall = self.force_wall;
+		Stats {
+			n: s.len(),
+			mass: s.iter().map(|x| x.m.to_f64()).sum(),
+			t: self.t.to_f64(),
+			steps: self.steps,
+			dt: self.p.dt.to_f64(),
+			min_separation: rmin2.sqrt().to_f64(),
+			max_separation: rmax2.sqrt().to_f64(),
+			dt_distribution: dt_distribution,
+			tree_depth: self.solver.tree_depth(),
+			thread_utilization: self.thread_busy.iter().map(|x| if wall > 0.0 { x/wall } else { 0.0 }).collect(),
+		}
+	}
+}
+
+impl fmt::Display for Stats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "stars:        {} (total mass {})", self.n, self.mass)?;
+		writeln!(f, "time:         t = {} after {} steps of dt = {}", self.t, self.steps, self.dt)?;
+		if self.n < 2 {
+			writeln!(f, "separations:  - (no pairs)")?;
+			writeln!(f, "per-star dt:  - (no pairs)")?;
+		} else {
+			writeln!(f, "separations:  min {:e}, max {:e}", self.min_separation, self.max_separation)?;
+			writeln!(f, "per-star dt:  min {:e}, median {:e}, max {:e}",
+				self.dt_distribution.0, self.dt_distribution.1, self.dt_distribution.2)?;
+		}
+		match self.tree_depth {
+			Some(d) => writeln!(f, "tree depth:   {}", d)?,
+			None => writeln!(f, "tree depth:   - (direct summation)")?,
+		}
+		let util: Vec<String> = self.thread_utilization.iter().map(|x| format!("{:.0}%", 100.0*x)).collect();
+		write!(f, "threads:      {}", util.join(" "))
+	}
+}
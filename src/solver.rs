@@ -0,0 +1,350 @@
+/*
+ Force solvers a Simulation can be driven by. Direct is the O(N^2) pair loop
+ in acceleration(), Tree the Barnes-Hut octree in tree.rs, and Auto switches
+ between the two during the run: direct while the number of active (massive)
+ stars is small or the system is so clustered that the tree gets deep and
+ loses its advantage, the tree otherwise. The solvers keep no state that the
+ other needs (the tree at most keeps its last build for reuse), so a switch
+ is just a different call on the next step.
+ */
+use std::fmt;
+
+use real::c;
+use rng::Rng;
+use timing::Clock;
+use tree;
+use tree::Opening;
+use {acceleration, Params, Real, Star};
+
+pub trait ForceSolver<R: Real> {
+	fn name(&self) -> &'static str;
+
+	// Fills in the accelerations, returns the busy seconds per thread
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64>;
+
+	fn tree_depth(&self) -> Option<usize> {
+		None
+	}
+
+	// [E, T, W] if the solver computes them itself, as the MPI one does over all ranks
+	fn energies(&self, _s: &Vec<Star<R>>, _p: &Params<R>) -> Option<Vec<R>> {
+		None
+	}
+
+	// Events since the last call, e.g. solver switches
+	fn events(&mut self) -> Vec<String> {
+		vec![]
+	}
+
+	// Potential and acceleration of the stars at arbitrary points, see field_at()
+	fn field(&self, s: &Vec<Star<R>>, p: &Params<R>, points: &[[f64; 3]]) -> Vec<(f64, [f64; 3])> {
+		field_at(s, p, points)
+	}
+}
+
+/*
+ Potential and acceleration of the stars at each of points, summed directly.
+ A point is softened against a star with that star's own softening length,
+ as a massless star would be.
+ */
+pub fn field_at<R: Real>(s: &Vec<Star<R>>, p: &Params<R>, points: &[[f64; 3]]) -> Vec<(f64, [f64; 3])> {
+	let eps = p.star_eps(s);
+	points.iter().map(|x| {
+		let x = [R::from_f64(x[0]), R::from_f64(x[1]), R::from_f64(x[2])];
+		let mut phi = R::zero();
+		let mut a = [R::zero(); 3];
+		for (j, star) in s.iter().enumerate() {
+			let d = [star.r[0] - x[0], star.r[1] - x[1], star.r[2] - x[2]];
+			let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p.pair_eps2(eps[j], eps[j]);
+			let r = r2.sqrt();
+			phi -= star.m/r;
+			for k in 0..3 {
+				a[k] += star.m*d[k]/(r2*r);
+			}
+		}
+		(phi.to_f64(), [a[0].to_f64(), a[1].to_f64(), a[2].to_f64()])
+	}).collect()
+}
+
+pub struct Direct;
+
+impl<R: Real> ForceSolver<R> for Direct {
+	fn name(&self) -> &'static str {
+		"direct"
+	}
+
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		acceleration(s, p)
+	}
+}
+
+// Default opening angle
+pub static THETA: f64 = 0.5;
+
+pub struct Tree<R = f64> {
+	pub theta: R,
+	pub opening: Opening,
+	// Cells as point mass plus quadrupole rather than point mass alone
+	pub quadrupole: bool,
+	// Refresh the last tree until a star has drifted this many leaf sides, see tree::Tree::refresh(); None builds every time
+	pub reuse: Option<R>,
+	// Full builds and refreshes so far
+	pub builds: usize,
+	pub refreshes: usize,
+	depth: usize,
+	last: Option<tree::Tree<R>>,
+}
+
+impl<R: Real> Tree<R> {
+	// Geometric opening and monopoles, the classic Barnes-Hut tree
+	pub fn new(theta: R) -> Tree<R> {
+		Tree { theta: theta, opening: Opening::Geometric, quadrupole: false, reuse: None, builds: 0, refreshes: 0, depth: 0, last: None }
+	}
+
+	// With room to move in when it is going to be refreshed
+	fn build(&self, s: &Vec<Star<R>>) -> tree::Tree<R> {
+		let mut x = tree::Tree::build_padded(s, if self.reuse.is_some() { tree::PAD } else { 0.0 });
+		x.opening = self.opening;
+		x.quadrupole = self.quadrupole;
+		x
+	}
+}
+
+impl<R: Real> ForceSolver<R> for Tree<R> {
+	fn name(&self) -> &'static str {
+		"tree"
+	}
+
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		let drift = match self.reuse {
+			Some(x) => x,
+			None => {
+				let (busy, depth) = tree::acceleration(s, p, self.theta, self.opening, self.quadrupole);
+				self.builds += 1;
+				self.depth = depth;
+				return busy;
+			},
+		};
+		let clock = Clock::start();
+		let refreshed = match self.last {
+			Some(ref mut x) => x.refresh(s, drift),
+			None => false,
+		};
+		if refreshed {
+			self.refreshes += 1;
+		} else {
+			let x = self.build(s);
+			self.last = Some(x);
+			self.builds += 1;
+		}
+		let setup = clock.seconds();
+		let tree = self.last.as_ref().expect("The tree was just built or refreshed");
+		let mut busy = tree.accelerations(s, p, self.theta);
+		busy[0] += setup;
+		self.depth = tree.depth;
+		busy
+	}
+
+	fn tree_depth(&self) -> Option<usize> {
+		Some(self.depth)
+	}
+
+	fn field(&self, s: &Vec<Star<R>>, p: &Params<R>, points: &[[f64; 3]]) -> Vec<(f64, [f64; 3])> {
+		let tree = self.build(s);
+		let eps = p.star_eps(s);
+		let mut stack = vec![];
+		points.iter().map(|x| {
+			let (phi, a) = tree.field(&[R::from_f64(x[0]), R::from_f64(x[1]), R::from_f64(x[2])], p, &eps, self.theta, &mut stack);
+			(phi.to_f64(), [a[0].to_f64(), a[1].to_f64(), a[2].to_f64()])
+		}).collect()
+	}
+}
+
+// Below this many active stars Auto uses direct summation
+pub static AUTO_MIN_N: usize = 2048;
+// Deeper trees than this mean heavy clustering; Auto goes back to direct
+pub static AUTO_MAX_DEPTH: usize = 24;
+// Force evaluations in direct mode between probe trees
+pub static AUTO_PROBE_EVERY: usize = 64;
+
+pub struct Auto<R = f64> {
+	pub tree: Tree<R>,
+	pub min_n: usize,
+	pub max_depth: usize,
+	pub probe_every: usize,
+	// Trees built in direct mode only to measure the depth
+	pub probes: usize,
+	using_tree: Option<bool>,
+	evaluations: usize,
+	// Evaluation and depth of the last probe
+	probe: Option<(usize, usize)>,
+	events: Vec<String>,
+}
+
+impl<R: Real> Auto<R> {
+	pub fn new(theta: R) -> Auto<R> {
+		Auto { tree: Tree::new(theta), min_n: AUTO_MIN_N, max_depth: AUTO_MAX_DEPTH, probe_every: AUTO_PROBE_EVERY, probes: 0, using_tree: None, evaluations: 0, probe: None, events: vec![] }
+	}
+
+	/*
+	 Tree when there are enough active stars and the tree stays shallow. The
+	 thresholds have some hysteresis so the solver does not flip every step
+	 near the boundary. In tree mode the depth is that of the tree solver's
+	 last build; in direct mode a probe tree is built when the active count
+	 rises to min_n and then every probe_every evaluations.
+	 */
+	fn want_tree(&mut self, s: &Vec<Star<R>>) -> (bool, String) {
+		self.evaluations += 1;
+		let active = s.iter().filter(|x| x.m > R::zero()).count();
+		let tree = self.using_tree == Some(true);
+		let min_n = if tree { self.min_n*3/4 } else { self.min_n };
+		if active < min_n {
+			self.probe = None;
+			return (false, format!("{} active stars", active));
+		}
+		let depth = match self.probe {
+			_ if tree => self.tree.depth,
+			Some((at, depth)) if self.evaluations - at < self.probe_every => depth,
+			_ => {
+				let depth = tree::Tree::build(s).depth;
+				self.probes += 1;
+				self.probe = Some((self.evaluations, depth));
+				depth
+			},
+		};
+		let max_depth = if tree { self.max_depth } else { self.max_depth.saturating_sub(2) };
+		if depth > max_depth {
+			return (false, format!("tree depth {} with {} active stars", depth, active));
+		}
+		(true, format!("{} active stars, tree depth {}", active, depth))
+	}
+}
+
+impl<R: Real> ForceSolver<R> for Auto<R> {
+	fn name(&self) -> &'static str {
+		match self.using_tree {
+			Some(true) => "tree",
+			_ => "direct",
+		}
+	}
+
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		let (tree, why) = self.want_tree(s);
+		match self.using_tree {
+			Some(x) if x == tree => {},
+			Some(_) => self.events.push(format!("solver switch to {} ({})", if tree { "tree" } else { "direct" }, why)),
+			None => self.events.push(format!("solver starts as {} ({})", if tree { "tree" } else { "direct" }, why)),
+		}
+		self.using_tree = Some(tree);
+		if tree {
+			self.tree.accelerations(s, p)
+		} else {
+			acceleration(s, p)
+		}
+	}
+
+	fn tree_depth(&self) -> Option<usize> {
+		if self.using_tree == Some(true) { self.tree.tree_depth() } else { None }
+	}
+
+	fn events(&mut self) -> Vec<String> {
+		self.events.drain(..).collect()
+	}
+
+	// With whichever solver the last force evaluation used
+	fn field(&self, s: &Vec<Star<R>>, p: &Params<R>, points: &[[f64; 3]]) -> Vec<(f64, [f64; 3])> {
+		if self.using_tree == Some(true) { self.tree.field(s, p, points) } else { field_at(s, p, points) }
+	}
+}
+
+// Solver by name: direct, tree or auto
+pub fn by_name<R: Real>(name: &str, theta: f64) -> Option<Box<dyn ForceSolver<R>>> {
+	by_name_with(name, theta, Opening::Geometric, false, None)
+}
+
+// Same, with the tree's opening criterion, multipole order and reuse drift
+pub fn by_name_with<R: Real>(name: &str, theta: f64, opening: Opening, quadrupole: bool, reuse: Option<f64>) -> Option<Box<dyn ForceSolver<R>>> {
+	let mut tree = Tree::new(c::<R>(theta));
+	tree.opening = opening;
+	tree.quadrupole = quadrupole;
+	tree.reuse = reuse.map(c::<R>);
+	match name {
+		"direct" => Some(Box::new(Direct)),
+		"tree" => Some(Box::new(tree)),
+		"auto" => {
+			let mut x = Auto::new(c::<R>(theta));
+			x.tree = tree;
+			Some(Box::new(x))
+		},
+		_ => None,
+	}
+}
+
+// Stars per check when --verify-forces gives none
+pub static VERIFY_SAMPLE: usize = 100;
+
+/*
+ Relative force errors |a - a_direct|/|a_direct| of a solver for a random
+ sample of k stars, the reference summed directly in double precision for
+ just those stars. The solver runs on a copy, so the stars keep their
+ accelerations, and only the pair forces are compared: external fields and
+ other extra terms are in neither.
+ */
+pub fn force_errors<R: Real>(solver: &mut dyn ForceSolver<R>, s: &Vec<Star<R>>, p: &Params<R>, k: usize, rng: &mut Rng) -> Vec<f64> {
+	let n = s.len();
+	let mut sample: Vec<usize> = (0..n).collect();
+	for i in 0..k.min(n) {
+		let j = i + (rng.next_u64() % (n - i) as u64) as usize;
+		sample.swap(i, j);
+	}
+	sample.truncate(k.min(n));
+	let mut approx = s.clone();
+	solver.accelerations(&mut approx, p);
+	let eps: Vec<f64> = p.star_eps(s).iter().map(|x| x.to_f64()).collect();
+	let p64: Params<f64> = p.convert();
+	sample.iter().map(|&i| {
+		let mut a = [0.0; 3];
+		for (j, star) in s.iter().enumerate() {
+			if j == i {
+				continue;
+			}
+			let d: Vec<f64> = (0..3).map(|k| star.r[k].to_f64() - s[i].r[k].to_f64()).collect();
+			let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p64.pair_eps2(eps[i], eps[j]);
+			let f = star.m.to_f64()/(r2*r2.sqrt());
+			for k in 0..3 {
+				a[k] += f*d[k];
+			}
+		}
+		let diff: f64 = (0..3).map(|k| (approx[i].a[k].to_f64() - a[k]).powi(2)).sum();
+		let norm: f64 = a.iter().map(|x| x*x).sum();
+		if norm > 0.0 { (diff/norm).sqrt() } else { diff.sqrt() }
+	}).collect()
+}
+
+// The distribution of force_errors(), as quantiles
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ErrorSummary {
+	pub count: usize,
+	pub median: f64,
+	pub p90: f64,
+	pub p99: f64,
+	pub max: f64,
+	pub rms: f64,
+}
+
+impl ErrorSummary {
+	pub fn new(errors: &[f64]) -> ErrorSummary {
+		let mut x = errors.to_vec();
+		x.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+		let quantile = |q: f64| if x.is_empty() { 0.0 } else { x[((q*x.len() as f64) as usize).min(x.len() - 1)] };
+		let rms = if x.is_empty() { 0.0 } else { (x.iter().map(|e| e*e).sum::<f64>()/x.len() as f64).sqrt() };
+		ErrorSummary { count: x.len(), median: quantile(0.5), p90: quantile(0.9), p99: quantile(0.99), max: x.last().cloned().unwrap_or(0.0), rms: rms }
+	}
+}
+
+// "median 1.2e-4, 90% 3.1e-4, 99% 8.0e-4, max 1.1e-3, rms 2.0e-4 over 100 stars"
+impl fmt::Display for ErrorSummary {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "median {:.2e}, 90% {:.2e}, 99% {:.2e}, max {:.2e}, rms {:.2e} over {} stars", self.median, self.p90, self.p99, self.max, self.rms, self.count)
+	}
+}
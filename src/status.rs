@@ -0,0 +1,122 @@
+/*
+ How a run ended, as an exit code and as status.json, so workflow managers
+ can branch on the outcome without parsing the log.
+ */
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use NBodyError;
+
+pub static EXIT_SUCCESS: i32 = 0;
+pub static EXIT_CONFIG: i32 = 2;
+pub static EXIT_NUMERICAL: i32 = 3;
+pub static EXIT_WALLTIME: i32 = 4;
+pub static EXIT_INPUT: i32 = 5;
+pub static EXIT_IO: i32 = 6;
+// 128 + SIGINT, as shells report a Ctrl-C
+pub static EXIT_INTERRUPTED: i32 = 130;
+
+pub enum Outcome {
+	Success,
+	ConfigError(String),
+	// The input could not be read
+	InputError(String),
+	// An output file could not be written
+	IoError(String),
+	NumericalFailure(String),
+	// Stopped at the wall-clock limit after writing a checkpoint
+	Walltime,
+	// Stopped by SIGINT or SIGTERM after writing a checkpoint
+	Interrupted,
+}
+
+impl Outcome {
+	pub fn exit_code(&self) -> i32 {
+		match *self {
+			Outcome::Success => EXIT_SUCCESS,
+			Outcome::ConfigError(_) => EXIT_CONFIG,
+			Outcome::InputError(_) => EXIT_INPUT,
+			Outcome::IoError(_) => EXIT_IO,
+			Outcome::NumericalFailure(_) => EXIT_NUMERICAL,
+			Outcome::Walltime => EXIT_WALLTIME,
+			Outcome::Interrupted => EXIT_INTERRUPTED,
+		}
+	}
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Outcome::Success => "success",
+			Outcome::ConfigError(_) => "config_error",
+			Outcome::InputError(_) => "input_error",
+			Outcome::IoError(_) => "io_error",
+			Outcome::NumericalFailure(_) => "numerical_failure",
+			Outcome::Walltime => "walltime",
+			Outcome::Interrupted => "interrupted",
+		}
+	}
+
+	pub fn reason(&self) -> &str {
+		match *self {
+			Outcome::ConfigError(ref x) | Outcome::InputError(ref x) | Outcome::IoError(ref x) | Outcome::NumericalFailure(ref x) => x,
+			Outcome::Walltime => "wall-clock limit reached, checkpoint written",
+			Outcome::Interrupted => "interrupted by a signal, checkpoint written",
+			Outcome::Success => "",
+		}
+	}
+}
+
+impl From<NBodyError> for Outcome {
+	fn from(error: NBodyError) -> Outcome {
+		let msg = error.to_string();
+		match error {
+			NBodyError::Io(..) => Outcome::IoError(msg),
+			NBodyError::Parse { .. } => Outcome::InputError(format!("invalid input, {}", msg)),
+			NBodyError::Config(_) => Outcome::ConfigError(msg),
+			NBodyError::Solver(_) => Outcome::NumericalFailure(msg),
+		}
+	}
+}
+
+pub struct Status {
+	pub outcome: Outcome,
+	pub t: f64,
+	pub steps: usize,
+	pub de: f64,
+	pub wall_seconds: f64,
+	pub outputs: Vec<String>,
+}
+
+pub fn json_string(s: &str) -> String {
+	let mut out = String::from("\"");
+	for ch in s.chars() {
+		match ch {
+			'"' => out += "\\\"",
+			'\\' => out += "\\\\",
+			'\n' => out += "\\n",
+			c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+	out
+}
+
+// JSON has no NaN or infinity
+pub fn json_number(x: f64) -> String {
+	if x.is_finite() { format!("{:e}", x) } else { String::from("null") }
+}
+
+impl Status {
+	pub fn to_json(&self) -> String {
+		let outputs: Vec<String> = self.outputs.iter().map(|x| json_string(x)).collect();
+		format!("{{\n  \"status\": {},\n  \"exit_code\": {},\n  \"reason\": {},\n  \"t\": {},\n  \"steps\": {},\n  \"dE\": {},\n  \"wall_seconds\": {},\n  \"outputs\": [{}]\n}}\n",
+			json_string(self.outcome.name()), self.outcome.exit_code(), json_string(self.outcome.reason()),
+			json_number(self.t), self.steps, json_number(self.de), json_number(self.wall_seconds), outputs.join(", "))
+	}
+
+	pub fn write(&self, path: &Path) -> io::Result<()> {
+		File::create(path)?.write_all(self.to_json().as_bytes())
+	}
+}
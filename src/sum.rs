@@ -0,0 +1,35 @@
+/*
+ Neumaier (improved Kahan) compensated summation. Used when Params::compensated
+ is set, so the rounding error of long sums does not show up as energy drift.
+ */
+use Real;
+
+/*
+ Adds x to *sum, collecting the lost low-order bits in *c. The final result is
+ *sum + *c.
+ */
+pub fn neumaier_add<R: Real>(sum: &mut R, c: &mut R, x: R) {
+	let t = *sum + x;
+	if sum.abs() >= x.abs() {
+		*c += (*sum - t) + x;
+	} else {
+		*c += (x - t) + *sum;
+	}
+	*sum = t;
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Neumaier<R = f64> {
+	sum: R,
+	c: R,
+}
+
+impl<R: Real> Neumaier<R> {
+	pub fn add(&mut self, x: R) {
+		neumaier_add(&mut self.sum, &mut self.c, x);
+	}
+
+	pub fn value(&self) -> R {
+		self.sum + self.c
+	}
+}
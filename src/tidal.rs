@@ -0,0 +1,104 @@
+/*
+ Linearized galactic tidal field (Hill's approximation) for a cluster on a
+ circular orbit of angular frequency omega. The run then works in the frame
+ that co-rotates with the orbit: x points away from the galactic centre, y
+ along the orbit, z out of the plane. Per unit mass
+
+   a_x = 2 omega v_y + (4 omega^2 - kappa^2) x
+   a_y = -2 omega v_x
+   a_z = -nu^2 z
+
+ with kappa the epicyclic and nu the vertical frequency. The terms in x and z
+ (tidal plus centrifugal) are an ExternalPotential; the Coriolis term does no
+ work and is applied by Simulation as a rotation of the velocities over half
+ a step before and after each leapfrog step, which keeps the scheme
+ time-symmetric. The conserved energy is then the Jacobi energy, what
+ energies() reports.
+
+ "--tidal omega=W[,kappa=K][,nu=N]": kappa and nu default to omega, a point
+ mass galaxy (a_x = 3 omega^2 x); for a flat rotation curve kappa = sqrt(2)
+ omega. Escapers are not removed and drift off along y.
+ */
+use external::ExternalPotential;
+use {Real, Star};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tidal {
+	pub omega: f64,
+	pub kappa: f64,
+	pub nu: f64,
+}
+
+impl Tidal {
+	// Point mass galaxy
+	pub fn kepler(omega: f64) -> Tidal {
+		Tidal { omega: omega, kappa: omega, nu: omega }
+	}
+
+	// Parses "omega=W,kappa=K,nu=N", kappa and nu optional
+	pub fn parse(spec: &str) -> Result<Tidal, String> {
+		let (mut omega, mut kappa, mut nu) = (None, None, None);
+		for item in spec.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+			let mut kv = item.splitn(2, '=');
+			let name = kv.next().unwrap_or("").trim();
+			let value = kv.next().and_then(|x| x.trim().parse::<f64>().ok()).ok_or(format!("Bad parameter '{}' in --tidal {}", item, spec))?;
+			if !(value > 0.0) {
+				return Err(format!("{} must be positive, got {}", name, value));
+			}
+			match name {
+				"omega" => omega = Some(value),
+				"kappa" => kappa = Some(value),
+				"nu" => nu = Some(value),
+				_ => return Err(format!("Unknown parameter '{}' for --tidal, use omega, kappa and nu", name)),
+			}
+		}
+		let omega = omega.ok_or(format!("--tidal needs omega=, got '{}'", spec))?;
+		let kappa = kappa.unwrap_or(omega);
+		// kappa = 2 omega is a uniform density core, where there is no tidal pull
+		if kappa >= 2.0*omega {
+			return Err(format!("--tidal needs kappa < 2 omega, got kappa = {} and omega = {}", kappa, omega));
+		}
+		Ok(Tidal { omega: omega, kappa: kappa, nu: nu.unwrap_or(omega) })
+	}
+
+	// Coefficient of x in a_x, 4 omega^2 - kappa^2
+	pub fn strength(&self) -> f64 {
+		4.0*self.omega*self.omega - self.kappa*self.kappa
+	}
+
+	/*
+	 Tidal radius (Jacobi radius) of a cluster of mass m, where the tidal
+	 pull along x balances the cluster's gravity: (m/strength)^(1/3).
+	 */
+	pub fn tidal_radius(&self, m: f64) -> f64 {
+		(m/self.strength()).cbrt()
+	}
+}
+
+impl ExternalPotential for Tidal {
+	fn name(&self) -> &'static str {
+		"tidal"
+	}
+
+	fn potential(&self, r: [f64; 3]) -> f64 {
+		0.5*(-self.strength()*r[0]*r[0] + self.nu*self.nu*r[2]*r[2])
+	}
+
+	fn acceleration(&self, r: [f64; 3]) -> [f64; 3] {
+		[self.strength()*r[0], 0.0, -self.nu*self.nu*r[2]]
+	}
+}
+
+/*
+ The Coriolis acceleration -2 omega x v over a time dt: a rotation of the
+ velocities in the x-y plane by -2 omega dt, exact for that term alone.
+ */
+pub fn coriolis<R: Real>(s: &mut Vec<Star<R>>, omega: f64, dt: f64) {
+	let angle = 2.0*omega*dt;
+	let (c, sn) = (R::from_f64(angle.cos()), R::from_f64(angle.sin()));
+	for star in s.iter_mut() {
+		let (vx, vy) = (star.v[0], star.v[1]);
+		star.v[0] = c*vx + sn*vy;
+		star.v[1] = c*vy - sn*vx;
+	}
+}
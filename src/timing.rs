@@ -0,0 +1,127 @@
+/*
+ Where the wall-clock time of a run goes: force evaluations, the rest of the
+ integration (kicks, drifts and everything else a step does), energy
+ diagnostics, and writing files. Simulation times the first two, the driver
+ the others. Totals are kept for the whole run and for the current interval,
+ so the report can come at the end and, with --timing, at every diagnostic.
+ */
+use std::fmt;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+/*
+ A stopwatch. wasm32-unknown-unknown has no clock in std (Instant::now()
+ panics there), so in the browser it always reads zero and the timings stay
+ empty rather than taking the simulation down.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+	#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+	start: Instant,
+}
+
+impl Clock {
+	#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+	pub fn start() -> Clock {
+		Clock { start: Instant::now() }
+	}
+
+	#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+	pub fn start() -> Clock {
+		Clock {}
+	}
+
+	// Seconds since start()
+	#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+	pub fn seconds(&self) -> f64 {
+		self.start.elapsed().as_secs_f64()
+	}
+
+	#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+	pub fn seconds(&self) -> f64 {
+		0.0
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Phase {
+	Forces,
+	Integration,
+	Diagnostics,
+	Io,
+}
+
+pub const PHASES: [Phase; 4] = [Phase::Forces, Phase::Integration, Phase::Diagnostics, Phase::Io];
+
+impl Phase {
+	pub fn name(self) -> &'static str {
+		match self {
+			Phase::Forces => "forces",
+			Phase::Integration => "integration",
+			Phase::Diagnostics => "diagnostics",
+			Phase::Io => "I/O",
+		}
+	}
+}
+
+// Seconds per phase and the steps they cover
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Times {
+	pub seconds: [f64; 4],
+	pub steps: usize,
+}
+
+impl Times {
+	pub fn get(&self, phase: Phase) -> f64 {
+		self.seconds[phase as usize]
+	}
+
+	pub fn total(&self) -> f64 {
+		self.seconds.iter().sum()
+	}
+}
+
+// "forces 1.2 s (80.0%, 1.2 ms/step), ..."
+impl fmt::Display for Times {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let total = self.total();
+		for (n, &phase) in PHASES.iter().enumerate() {
+			let x = self.get(phase);
+			let share = if total > 0.0 { 100.0*x/total } else { 0.0 };
+			let per_step = if self.steps > 0 { 1e3*x/self.steps as f64 } else { 0.0 };
+			write!(f, "{}{} {:.3} s ({:.1}%, {:.3} ms/step)", if n > 0 { ", " } else { "" }, phase.name(), x, share, per_step)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Timers {
+	pub run: Times,
+	pub interval: Times,
+}
+
+impl Timers {
+	pub fn add(&mut self, phase: Phase, seconds: f64) {
+		self.run.seconds[phase as usize] += seconds;
+		self.interval.seconds[phase as usize] += seconds;
+	}
+
+	pub fn step(&mut self) {
+		self.run.steps += 1;
+		self.interval.steps += 1;
+	}
+
+	// Runs f and adds its time to phase
+	pub fn time<T, F: FnOnce() -> T>(&mut self, phase: Phase, f: F) -> T {
+		let clock = Clock::start();
+		let x = f();
+		self.add(phase, clock.seconds());
+		x
+	}
+
+	// The current interval, which then starts over
+	pub fn take_interval(&mut self) -> Times {
+		std::mem::take(&mut self.interval)
+	}
+}
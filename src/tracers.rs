@@ -0,0 +1,75 @@
+/*
+ Massless test particles. A star with m = 0 feels the others but pulls on
+ nothing, so it only ever needs the field of the massive stars: with M
+ massive stars and T tracers the forces cost M^2/2 + T M pair terms instead
+ of (M + T)^2/2, which is most of the work gone when the tracers outnumber
+ the stars (tidal streams, phase-space maps of a fixed cluster). Tracers are
+ integrated with the same steps as everyone else.
+ */
+use std::thread;
+
+use timing::Clock;
+use {acceleration, Params, Real, Star};
+
+// Indices of the massive stars and of the tracers
+pub fn split<R: Real>(s: &Vec<Star<R>>) -> (Vec<usize>, Vec<usize>) {
+	(0..s.len()).partition(|&i| s[i].m != R::zero())
+}
+
+/*
+ Accelerations with the tracers left out as sources. The massive stars go
+ through acceleration() among themselves, with whatever kernel p asks for,
+ and the tracers sum the pull of the massive stars, split evenly over
+ p.threads. Softening lengths are those of the whole system. Returns the
+ busy seconds per thread of both parts.
+ */
+pub fn accelerations<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+	let (massive, tracers) = split(s);
+	let eps = p.star_eps(s);
+	let mut sources: Vec<Star<R>> = massive.iter().map(|&i| s[i].clone()).collect();
+	let mut busy = acceleration(&mut sources, p);
+	for (&i, x) in massive.iter().zip(sources.iter()) {
+		s[i].a = x.a.clone();
+	}
+
+	let field = |lo: usize, hi: usize| {
+		let clock = Clock::start();
+		let a: Vec<[R; 3]> = tracers[lo..hi].iter().map(|&i| {
+			let mut a = [R::zero(); 3];
+			for (&j, source) in massive.iter().zip(sources.iter()) {
+				let d = [source.r[0] - s[i].r[0], source.r[1] - s[i].r[1], source.r[2] - s[i].r[2]];
+				let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p.pair_eps2(eps[i], eps[j]);
+				let f = source.m/(r2*r2.sqrt());
+				for k in 0..3 {
+					a[k] += f*d[k];
+				}
+			}
+			a
+		}).collect();
+		(a, clock.seconds())
+	};
+	let threads = p.threads.max(1).min(tracers.len().max(1));
+	let mut parts = vec![];
+	if threads == 1 {
+		parts.push(field(0, tracers.len()));
+	} else {
+		let (field, t) = (&field, tracers.len());
+		thread::scope(|scope| {
+			let handles: Vec<_> = (0..threads).map(|x| scope.spawn(move || field(t*x/threads, t*(x + 1)/threads))).collect();
+			for handle in handles {
+				parts.push(handle.join().expect("Thread failure, RIP"));
+			}
+		});
+	}
+	if busy.len() < parts.len() {
+		busy.resize(parts.len(), 0.0);
+	}
+	let mut rows = tracers.iter();
+	for (x, (a, seconds)) in parts.into_iter().enumerate() {
+		busy[x] += seconds;
+		for (a, &i) in a.iter().zip(rows.by_ref()) {
+			s[i].a = vec![a[0], a[1], a[2]];
+		}
+	}
+	busy
+}
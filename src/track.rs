@@ -0,0 +1,86 @@
+/*
+ Trajectories of a few selected stars at every step, for orbit analysis
+ without writing full snapshots that often. --track 0,5,17 writes
+ DIR/track_0.csv, DIR/track_5.csv and DIR/track_17.csv, each with the
+ columns t,x,y,z,vx,vy,vz, starting with the initial state. The numbers are
+ positions in the input; when a tracked star is merged into another by a
+ collision its file goes on with the merged star.
+ */
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use collisions;
+use {Real, Star};
+
+pub static HEADER: &str = "t,x,y,z,vx,vy,vz";
+
+// Parses "0,5,17" into star indices below n
+pub fn parse(spec: &str, n: usize) -> Result<Vec<usize>, String> {
+	let mut indices = vec![];
+	for item in spec.split(',').filter(|x| !x.is_empty()) {
+		let i: usize = item.trim().parse().map_err(|_| format!("--track needs star indices, got '{}'", item))?;
+		if i >= n {
+			return Err(format!("--track {}: there are only {} stars", i, n));
+		}
+		if !indices.contains(&i) {
+			indices.push(i);
+		}
+	}
+	if indices.is_empty() {
+		return Err(String::from("--track needs at least one star index"));
+	}
+	Ok(indices)
+}
+
+struct Track {
+	// Where the star is now, after any merges
+	index: usize,
+	file: BufWriter<File>,
+}
+
+pub struct Tracks {
+	tracks: Vec<Track>,
+}
+
+impl Tracks {
+	// Creates track_I.csv in dir for every index
+	pub fn create(dir: &Path, indices: &[usize]) -> io::Result<Tracks> {
+		let mut tracks = vec![];
+		for &i in indices {
+			let mut file = BufWriter::new(File::create(dir.join(name(i)))?);
+			writeln!(file, "{}", HEADER)?;
+			tracks.push(Track { index: i, file: file });
+		}
+		Ok(Tracks { tracks: tracks })
+	}
+
+	// One line per tracked star for the state at time t
+	pub fn write<R: Real>(&mut self, t: f64, s: &Vec<Star<R>>) -> io::Result<()> {
+		for x in self.tracks.iter_mut() {
+			let star = &s[x.index];
+			writeln!(x.file, "{},{},{},{},{},{},{}", t, star.r[0], star.r[1], star.r[2], star.v[0], star.v[1], star.v[2])?;
+		}
+		Ok(())
+	}
+
+	// Star j was merged into star i and removed
+	pub fn merged(&mut self, i: usize, j: usize) {
+		for x in self.tracks.iter_mut() {
+			x.index = if x.index == j { i } else { collisions::shift(x.index, j) };
+		}
+	}
+
+	pub fn finish(&mut self) -> io::Result<()> {
+		for x in self.tracks.iter_mut() {
+			x.file.flush()?;
+		}
+		Ok(())
+	}
+}
+
+// The file of the star at input position i
+pub fn name(i: usize) -> String {
+	format!("track_{}.csv", i)
+}
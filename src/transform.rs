@@ -0,0 +1,178 @@
+/*
+ Operations on a set of stars: rotations about the origin, shifts, bulk
+ velocity changes and rescaling of masses and lengths. Rotations turn
+ positions and velocities alike.
+
+ "nbabel transform OP... < input > output" applies operations in the order
+ given:
+   --rotate A,B,G          Euler angles in degrees (z-x-z)
+   --rotate-axis X,Y,Z,D   D degrees about the axis (X,Y,Z), right-handed
+   --shift X,Y,Z           added to the positions
+   --velocity X,Y,Z        added to the velocities
+   --scale-mass F          masses times F
+   --scale-length F        positions times F
+ Rescaling keeps G = 1 and the dynamical state: velocities are multiplied by
+ sqrt(mass factor/length factor), so the virial ratio and the orbits'
+ shapes stay the same and times scale by sqrt(length^3/mass).
+ */
+use std::io;
+use std::io::{Read, Write};
+use std::process;
+
+use columns::Columns;
+use {parse_stars, write_stars_with, Star};
+
+pub type Matrix = [[f64; 3]; 3];
+
+/*
+ Rotation by Euler angles in the z-x-z convention (radians): alpha about z,
+ then beta about the new x, then gamma about the new z.
+ */
+pub fn euler(alpha: f64, beta: f64, gamma: f64) -> Matrix {
+	let (ca, sa, cb, sb, cg, sg) = (alpha.cos(), alpha.sin(), beta.cos(), beta.sin(), gamma.cos(), gamma.sin());
+	[
+		[ca*cg - sa*cb*sg, -ca*sg - sa*cb*cg, sa*sb],
+		[sa*cg + ca*cb*sg, -sa*sg + ca*cb*cg, -ca*sb],
+		[sb*sg, sb*cg, cb],
+	]
+}
+
+/*
+ Rotation by angle (radians) about axis, right-handed (Rodrigues' formula).
+ The axis need not be normalized.
+ */
+pub fn axis_angle(axis: [f64; 3], angle: f64) -> Matrix {
+	let norm = (axis[0]*axis[0] + axis[1]*axis[1] + axis[2]*axis[2]).sqrt();
+	let (x, y, z) = (axis[0]/norm, axis[1]/norm, axis[2]/norm);
+	let (c, s) = (angle.cos(), angle.sin());
+	let t = 1.0 - c;
+	[
+		[c + x*x*t, x*y*t - z*s, x*z*t + y*s],
+		[y*x*t + z*s, c + y*y*t, y*z*t - x*s],
+		[z*x*t - y*s, z*y*t + x*s, c + z*z*t],
+	]
+}
+
+fn apply(m: &Matrix, x: &Vec<f64>) -> Vec<f64> {
+	(0..3).map(|i| m[i][0]*x[0] + m[i][1]*x[1] + m[i][2]*x[2]).collect()
+}
+
+pub fn rotate(s: &mut Vec<Star>, m: &Matrix) {
+	for star in s.iter_mut() {
+		star.r = apply(m, &star.r);
+		star.v = apply(m, &star.v);
+	}
+}
+
+pub fn shift(s: &mut Vec<Star>, d: [f64; 3]) {
+	for star in s.iter_mut() {
+		for k in 0..3 {
+			star.r[k] += d[k];
+		}
+	}
+}
+
+pub fn boost(s: &mut Vec<Star>, v: [f64; 3]) {
+	for star in s.iter_mut() {
+		for k in 0..3 {
+			star.v[k] += v[k];
+		}
+	}
+}
+
+/*
+ Masses times mass, positions times length and velocities times
+ sqrt(mass/length), which keeps G = 1 and the dynamical state.
+ */
+pub fn scale(s: &mut Vec<Star>, mass: f64, length: f64) {
+	let velocity = (mass/length).sqrt();
+	for star in s.iter_mut() {
+		star.m *= mass;
+		for k in 0..3 {
+			star.r[k] *= length;
+			star.v[k] *= velocity;
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Op {
+	Rotate(Matrix),
+	Shift([f64; 3]),
+	Boost([f64; 3]),
+	// Mass and length factors, see scale()
+	Scale(f64, f64),
+}
+
+// Applies the operations in order
+pub fn apply_all(s: &mut Vec<Star>, ops: &[Op]) {
+	for op in ops {
+		match *op {
+			Op::Rotate(ref m) => rotate(s, m),
+			Op::Shift(d) => shift(s, d),
+			Op::Boost(v) => boost(s, v),
+			Op::Scale(mass, length) => scale(s, mass, length),
+		}
+	}
+}
+
+fn parse_numbers(text: &str, count: usize) -> Result<Vec<f64>, String> {
+	let x: Result<Vec<f64>, _> = text.split(',').map(|x| x.trim().parse::<f64>()).collect();
+	match x {
+		Ok(x) if x.len() == count => Ok(x),
+		_ => Err(format!("Expected {} comma-separated numbers, got '{}'", count, text)),
+	}
+}
+
+// Parses "x,y,z"
+pub fn parse_vector(text: &str) -> Result<[f64; 3], String> {
+	let x = parse_numbers(text, 3)?;
+	Ok([x[0], x[1], x[2]])
+}
+
+// Parses one command line operation and its value
+pub fn parse_op(flag: &str, value: &str) -> Result<Op, String> {
+	let positive = |x: f64| if x > 0.0 { Ok(x) } else { Err(format!("{} must be positive, got {}", flag, x)) };
+	match flag {
+		"--rotate" => {
+			let x = parse_vector(value)?;
+			Ok(Op::Rotate(euler(x[0].to_radians(), x[1].to_radians(), x[2].to_radians())))
+		},
+		"--rotate-axis" => {
+			let x = parse_numbers(value, 4)?;
+			if x[0] == 0.0 && x[1] == 0.0 && x[2] == 0.0 {
+				return Err(String::from("--rotate-axis needs a non-zero axis"));
+			}
+			Ok(Op::Rotate(axis_angle([x[0], x[1], x[2]], x[3].to_radians())))
+		},
+		"--shift" => Ok(Op::Shift(parse_vector(value)?)),
+		"--velocity" => Ok(Op::Boost(parse_vector(value)?)),
+		"--scale-mass" => Ok(Op::Scale(positive(value.parse().map_err(|_| format!("Bad factor '{}'", value))?)?, 1.0)),
+		"--scale-length" => Ok(Op::Scale(1.0, positive(value.parse().map_err(|_| format!("Bad factor '{}'", value))?)?)),
+		_ => Err(format!("Unknown operation {}", flag)),
+	}
+}
+
+pub fn main(args: &[String]) {
+	let usage = "Usage: nbabel transform [--rotate A,B,G] [--rotate-axis X,Y,Z,DEG] [--shift X,Y,Z] [--velocity X,Y,Z] [--scale-mass F] [--scale-length F]... < input > output";
+	let mut ops = vec![];
+	let mut it = args.iter();
+	while let Some(flag) = it.next() {
+		let value = it.next().unwrap_or_else(|| panic!("{} needs a value\n{}", flag, usage));
+		ops.push(parse_op(flag, value).unwrap_or_else(|msg| panic!("{}\n{}", msg, usage)));
+	}
+
+	let mut text = String::new();
+	io::stdin().read_to_string(&mut text).expect("Could not read input");
+	let mut s: Vec<Star> = parse_stars(&text).unwrap_or_else(|x| {
+		error!("Invalid input, {}", x);
+		process::exit(x.exit_code());
+	});
+	let columns = Columns::read(&text).unwrap_or_else(|msg| panic!("{}", msg));
+	apply_all(&mut s, &ops);
+
+	let stdout = io::stdout();
+	let mut out = io::BufWriter::new(stdout.lock());
+	writeln!(out, "# nbabel transform {}", args.join(" ")).expect("Could not write output");
+	write_stars_with(&mut out, &s, &columns).expect("Could not write output");
+}
@@ -0,0 +1,524 @@
+/*
+ Barnes-Hut octree. Cells are opened when they look larger than theta times
+ their distance from the star, otherwise the whole cell acts as a point mass
+ at its center of mass. Forces cost O(N log N) instead of O(N^2), at a
+ relative error of roughly theta^2 per interaction; pairs inside a leaf and
+ cells that contain the star itself are always summed directly.
+
+ Two settings trade speed for accuracy beyond theta. The opening criterion
+ is either the classic geometric one (bh: cell side < theta d) or the bmax
+ one of Salmon & Warren (bmax: the largest distance of a member from the
+ centre of mass < theta d), which does not accept lopsided cells whose mass
+ sits near a corner close to the star. With quadrupole moments the cells
+ act as point mass plus quadrupole, and the error per interaction drops to
+ about theta^3 (the dipole vanishes about the centre of mass).
+
+ Stars move little in one step, so instead of building a new tree every
+ time, refresh() can sort them back into the cells of the last one and
+ update the moments bottom-up. The cells keep their boxes, so the walk is as
+ exact as after a build; what degrades is the balance of the tree, and a
+ full build is due once any star has drifted too far from where the last
+ build put it (see solver::Tree::reuse).
+ */
+use std::thread;
+
+use real::c;
+use timing::Clock;
+use {Params, Real, Star};
+
+// Stars per leaf
+pub static LEAF: usize = 8;
+// Deeper than this, coincident stars just share a leaf
+static MAX_DEPTH: usize = 48;
+// Room around the stars in a tree that is going to be refreshed, as a fraction of its size
+pub static PAD: f64 = 1.0/32.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Opening {
+	// Cell side against theta times the distance to the centre of mass
+	Geometric,
+	// Farthest member from the centre of mass against theta times the distance
+	Bmax,
+}
+
+impl Opening {
+	pub fn parse(name: &str) -> Option<Opening> {
+		match name {
+			"bh" => Some(Opening::Geometric),
+			"bmax" => Some(Opening::Bmax),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Opening::Geometric => "bh",
+			Opening::Bmax => "bmax",
+		}
+	}
+}
+
+struct Node<R> {
+	center: [R; 3],
+	half: R,
+	mass: R,
+	com: [R; 3],
+	// Traceless quadrupole about com, xx yy zz xy xz yz
+	quad: [R; 6],
+	bmax: R,
+	children: Vec<usize>,
+	stars: Vec<usize>,
+	level: usize,
+	parent: usize,
+}
+
+pub struct Tree<R = f64> {
+	nodes: Vec<Node<R>>,
+	// Copies of the positions and masses, contiguous for the walk
+	pos: Vec<[R; 3]>,
+	mass: Vec<R>,
+	pub depth: usize,
+	// How the walk treats distant cells, Geometric and monopole after build()
+	pub opening: Opening,
+	pub quadrupole: bool,
+	// The massive stars, their positions at the last build, the side of the leaf each went into and the leaf each is in now, for refresh()
+	massive: Vec<usize>,
+	origin: Vec<[R; 3]>,
+	reach: Vec<R>,
+	leaf: Vec<usize>,
+}
+
+// Audit every refresh against a full build, as acceleration() audits the pair loop
+const AUDIT: bool = cfg!(any(debug_assertions, feature = "paranoid"));
+
+// Which child box of a cell centred on center holds r, one bit per axis
+fn octant<R: Real>(center: &[R; 3], r: &[R; 3]) -> usize {
+	(0..3).filter(|&k| r[k] >= center[k]).fold(0, |o, k| o | 1 << k)
+}
+
+// Acceleration at d from a cell with mass m and quadrupole q at its centre of mass, r2 the softened d^2
+fn cell_acceleration<R: Real>(a: &mut [R; 3], m: R, q: Option<&[R; 6]>, d: &[R; 3], r2: R) {
+	let f = m/(r2*r2.sqrt());
+	for k in 0..3 {
+		a[k] += f*d[k];
+	}
+	if let Some(q) = q {
+		let inv = R::one()/r2.sqrt();
+		let inv3 = inv*inv*inv;
+		// a = M d/r^3 - Q d/r^5 + 5/2 (d.Q.d) d/r^7 for d from the point to the cell
+		let qd = [q[0]*d[0] + q[3]*d[1] + q[4]*d[2], q[3]*d[0] + q[1]*d[1] + q[5]*d[2], q[4]*d[0] + q[5]*d[1] + q[2]*d[2]];
+		let dqd = d[0]*qd[0] + d[1]*qd[1] + d[2]*qd[2];
+		let inv5 = inv3*inv*inv;
+		let radial = c::<R>(2.5)*dqd*inv5*inv*inv;
+		for k in 0..3 {
+			a[k] += radial*d[k] - qd[k]*inv5;
+		}
+	}
+}
+
+// The quadrupole part of the potential of a cell at d, -1/2 (d.Q.d)/r^5
+fn cell_potential<R: Real>(q: &[R; 6], d: &[R; 3], r2: R) -> R {
+	let dqd = q[0]*d[0]*d[0] + q[1]*d[1]*d[1] + q[2]*d[2]*d[2] + c::<R>(2.0)*(q[3]*d[0]*d[1] + q[4]*d[0]*d[2] + q[5]*d[1]*d[2]);
+	-c::<R>(0.5)*dqd/(r2*r2*r2.sqrt())
+}
+
+// Centre of child box o of a cell centred on center, quarter being half the child's side
+fn child_center<R: Real>(center: &[R; 3], quarter: R, o: usize) -> [R; 3] {
+	let mut sub = *center;
+	for k in 0..3 {
+		sub[k] += if o & (1 << k) != 0 { quarter } else { -quarter };
+	}
+	sub
+}
+
+impl<R: Real> Tree<R> {
+	pub fn build(s: &Vec<Star<R>>) -> Tree<R> {
+		Tree::build_padded(s, 0.0)
+	}
+
+	// With the root box larger than the stars by the fraction pad, so stars near its faces can move before refresh() gives up
+	pub fn build_padded(s: &Vec<Star<R>>, pad: f64) -> Tree<R> {
+		// Massless tracers pull on nothing, so only the massive stars go into the cells
+		let massive: Vec<usize> = (0..s.len()).filter(|&i| s[i].m != R::zero()).collect();
+		let mut lo = [R::infinity(); 3];
+		let mut hi = [-R::infinity(); 3];
+		for star in massive.iter().map(|&i| &s[i]) {
+			for k in 0..3 {
+				lo[k] = lo[k].min(star.r[k]);
+				hi[k] = hi[k].max(star.r[k]);
+			}
+		}
+		let pos: Vec<[R; 3]> = s.iter().map(|x| [x.r[0], x.r[1], x.r[2]]).collect();
+		let mut tree = Tree {
+			nodes: vec![],
+			origin: pos.clone(),
+			pos: pos,
+			mass: s.iter().map(|x| x.m).collect(),
+			depth: 0,
+			opening: Opening::Geometric,
+			quadrupole: false,
+			massive: massive.clone(),
+			reach: vec![R::zero(); s.len()],
+			leaf: vec![0; s.len()],
+		};
+		if massive.is_empty() {
+			return tree;
+		}
+		let half: R = c::<R>(0.5);
+		let center = [half*(lo[0] + hi[0]), half*(lo[1] + hi[1]), half*(lo[2] + hi[2])];
+		let size = (hi[0] - lo[0]).max(hi[1] - lo[1]).max(hi[2] - lo[2]);
+		// A little slack so stars on the upper faces are inside
+		let mut extent = half*size*c::<R>(1.0 + 1e-6 + pad);
+		if extent == R::zero() {
+			extent = R::one();
+		}
+		tree.add(massive, center, extent, 0, 0);
+		tree
+	}
+
+	fn add(&mut self, stars: Vec<usize>, center: [R; 3], half: R, depth: usize, parent: usize) -> usize {
+		self.depth = self.depth.max(depth);
+		let (mass, com, quad, bmax) = self.moments(&stars, center);
+		let idx = self.nodes.len();
+		self.nodes.push(Node { center: center, half: half, mass: mass, com: com, quad: quad, bmax: bmax, children: vec![], stars: vec![], level: depth, parent: parent });
+		if stars.len() <= LEAF || depth >= MAX_DEPTH {
+			for &i in &stars {
+				self.reach[i] = c::<R>(2.0)*half;
+				self.leaf[i] = idx;
+			}
+			self.nodes[idx].stars = stars;
+			return idx;
+		}
+
+		let mut octants: Vec<Vec<usize>> = vec![vec![]; 8];
+		for &i in &stars {
+			octants[octant(&center, &self.pos[i])].push(i);
+		}
+		let quarter = c::<R>(0.5)*half;
+		let mut children = vec![];
+		for (o, list) in octants.into_iter().enumerate() {
+			if list.is_empty() {
+				continue;
+			}
+			children.push(self.add(list, child_center(&center, quarter, o), quarter, depth + 1, idx));
+		}
+		self.nodes[idx].children = children;
+		idx
+	}
+
+	// Mass, centre of mass, quadrupole and bmax of the given stars; an empty cell sits at center
+	fn moments(&self, stars: &[usize], center: [R; 3]) -> (R, [R; 3], [R; 6], R) {
+		let mut mass = R::zero();
+		let mut com = [R::zero(); 3];
+		for &i in stars {
+			mass += self.mass[i];
+			for k in 0..3 {
+				com[k] += self.mass[i]*self.pos[i][k];
+			}
+		}
+		if mass > R::zero() {
+			for k in 0..3 {
+				com[k] /= mass;
+			}
+		} else {
+			com = center;
+		}
+		let mut quad = [R::zero(); 6];
+		let mut bmax2 = R::zero();
+		for &i in stars {
+			let y = [self.pos[i][0] - com[0], self.pos[i][1] - com[1], self.pos[i][2] - com[2]];
+			let (m, y2) = (self.mass[i], y[0]*y[0] + y[1]*y[1] + y[2]*y[2]);
+			let three: R = c(3.0);
+			for k in 0..3 {
+				quad[k] += m*(three*y[k]*y[k] - y2);
+			}
+			quad[3] += m*three*y[0]*y[1];
+			quad[4] += m*three*y[0]*y[2];
+			quad[5] += m*three*y[1]*y[2];
+			bmax2 = bmax2.max(y2);
+		}
+		(mass, com, quad, bmax2.sqrt())
+	}
+
+	/*
+	 Sorts the stars of s back into the cells of the last build: a massive
+	 star that left its leaf climbs to the first cell that still holds it and
+	 walks down from there into the leaf whose box holds it now, opening a
+	 new leaf where it lands in an octant that was empty, and the moments are
+	 recomputed bottom-up. Inner cells combine their
+	 children's moments, with bmax bounded by theirs rather than measured,
+	 which can only open more cells. Returns false if a full build is due
+	 instead: the stars or the massive ones changed, a star left the root
+	 box, one has moved more than drift leaf sides since the build, or a
+	 leaf filled up with more than 4 LEAF stars. The tree must not be walked
+	 after a false.
+	 */
+	pub fn refresh(&mut self, s: &Vec<Star<R>>, drift: R) -> bool {
+		let massive = (0..s.len()).filter(|&i| s[i].m != R::zero());
+		if s.len() != self.pos.len() || self.nodes.is_empty() || !massive.eq(self.massive.iter().cloned()) {
+			return false;
+		}
+		for &i in &self.massive {
+			let r = [s[i].r[0], s[i].r[1], s[i].r[2]];
+			let moved = (0..3).fold(R::zero(), |x, k| x + (r[k] - self.origin[i][k]).powi(2));
+			if moved > (drift*self.reach[i]).powi(2) || !self.contains(&self.nodes[0], &r) {
+				return false;
+			}
+		}
+		for (i, star) in s.iter().enumerate() {
+			self.pos[i] = [star.r[0], star.r[1], star.r[2]];
+			self.mass[i] = star.m;
+		}
+
+		// Most stars are still in their leaf; the others climb to a cell that holds them and walk down from there
+		for x in 0..self.massive.len() {
+			let i = self.massive[x];
+			let mut idx = self.leaf[i];
+			if self.contains(&self.nodes[idx], &self.pos[i]) {
+				continue;
+			}
+			self.nodes[idx].stars.retain(|&j| j != i);
+			while !self.contains(&self.nodes[idx], &self.pos[i]) {
+				idx = self.nodes[idx].parent;
+			}
+			while !self.nodes[idx].children.is_empty() {
+				let (center, quarter) = (self.nodes[idx].center, c::<R>(0.5)*self.nodes[idx].half);
+				let o = octant(&center, &self.pos[i]);
+				idx = match self.nodes[idx].children.iter().find(|&&j| octant(&center, &self.nodes[j].center) == o) {
+					Some(&j) => j,
+					None => {
+						let level = self.nodes[idx].level + 1;
+						let leaf = self.nodes.len();
+						self.nodes.push(Node { center: child_center(&center, quarter, o), half: quarter, mass: R::zero(), com: center, quad: [R::zero(); 6], bmax: R::zero(), children: vec![], stars: vec![], level: level, parent: idx });
+						self.nodes[idx].children.push(leaf);
+						self.depth = self.depth.max(level);
+						leaf
+					},
+				};
+			}
+			self.nodes[idx].stars.push(i);
+			self.leaf[i] = idx;
+		}
+
+		// Children come after their parents, so going backwards every cell sees finished children
+		for idx in (0..self.nodes.len()).rev() {
+			let moments = if self.nodes[idx].children.is_empty() {
+				let node = &self.nodes[idx];
+				if node.stars.len() > 4*LEAF && node.level < MAX_DEPTH {
+					return false;
+				}
+				self.moments(&node.stars, node.center)
+			} else {
+				self.combine(idx)
+			};
+			let node = &mut self.nodes[idx];
+			node.mass = moments.0;
+			node.com = moments.1;
+			node.quad = moments.2;
+			node.bmax = moments.3;
+		}
+		if AUDIT {
+			let fresh = Tree::build(s);
+			let (a, b) = (&self.nodes[0], &fresh.nodes[0]);
+			let scale = b.mass*b.half;
+			let off = (0..3).fold(R::zero(), |x, k| x.max((a.com[k] - b.com[k]).abs()));
+			if !((a.mass - b.mass).abs() <= c::<R>(64.0*R::epsilon())*b.mass && off*a.mass <= c::<R>(1e3*R::epsilon())*scale) {
+				panic!("Tree refresh audit failed: root mass {:?} and centre {:?}, a full build gives {:?} and {:?}", a.mass.to_f64(), a.com.iter().map(|x| x.to_f64()).collect::<Vec<f64>>(), b.mass.to_f64(), b.com.iter().map(|x| x.to_f64()).collect::<Vec<f64>>());
+			}
+		}
+		true
+	}
+
+	// Moments of an inner cell from those of its children, by the parallel axis theorem
+	fn combine(&self, idx: usize) -> (R, [R; 3], [R; 6], R) {
+		let node = &self.nodes[idx];
+		let mut mass = R::zero();
+		let mut com = [R::zero(); 3];
+		for child in node.children.iter().map(|&j| &self.nodes[j]) {
+			mass += child.mass;
+			for k in 0..3 {
+				com[k] += child.mass*child.com[k];
+			}
+		}
+		if mass > R::zero() {
+			for k in 0..3 {
+				com[k] /= mass;
+			}
+		} else {
+			return (R::zero(), node.center, [R::zero(); 6], R::zero());
+		}
+		let mut quad = [R::zero(); 6];
+		let mut bmax = R::zero();
+		let three: R = c(3.0);
+		for child in node.children.iter().map(|&j| &self.nodes[j]).filter(|x| x.mass > R::zero()) {
+			let y = [child.com[0] - com[0], child.com[1] - com[1], child.com[2] - com[2]];
+			let (m, y2) = (child.mass, y[0]*y[0] + y[1]*y[1] + y[2]*y[2]);
+			for k in 0..3 {
+				quad[k] += child.quad[k] + m*(three*y[k]*y[k] - y2);
+			}
+			quad[3] += child.quad[3] + m*three*y[0]*y[1];
+			quad[4] += child.quad[4] + m*three*y[0]*y[2];
+			quad[5] += child.quad[5] + m*three*y[1]*y[2];
+			bmax = bmax.max(y2.sqrt() + child.bmax);
+		}
+		(mass, com, quad, bmax)
+	}
+
+	fn contains(&self, node: &Node<R>, r: &[R; 3]) -> bool {
+		(0..3).all(|k| (r[k] - node.center[k]).abs() <= node.half)
+	}
+
+	// Whether node may act as a whole on a point at squared distance d2 from its centre of mass
+	fn accept(&self, node: &Node<R>, x: &[R; 3], d2: R, theta2: R) -> bool {
+		let size = match self.opening {
+			Opening::Geometric => c::<R>(2.0)*node.half,
+			Opening::Bmax => node.bmax,
+		};
+		size*size < theta2*d2 && !self.contains(node, x)
+	}
+
+	/*
+	 Acceleration of star i. stack is scratch space, passed in so the walk
+	 does not allocate for every star.
+	 */
+	pub fn acceleration(&self, i: usize, p: &Params<R>, eps: &Vec<R>, theta: R, stack: &mut Vec<usize>) -> [R; 3] {
+		let mut a = [R::zero(); 3];
+		if self.nodes.is_empty() {
+			return a;
+		}
+		let ri = &self.pos[i];
+		let theta2 = theta*theta;
+		stack.clear();
+		stack.push(0);
+		while let Some(idx) = stack.pop() {
+			let node = &self.nodes[idx];
+			if node.children.is_empty() {
+				for &j in &node.stars {
+					if j == i {
+						continue;
+					}
+					let rj = &self.pos[j];
+					let d = [rj[0] - ri[0], rj[1] - ri[1], rj[2] - ri[2]];
+					let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p.pair_eps2(eps[i], eps[j]);
+					let f = self.mass[j]/(r2*r2.sqrt());
+					for k in 0..3 {
+						a[k] += f*d[k];
+					}
+				}
+				continue;
+			}
+			let d = [node.com[0] - ri[0], node.com[1] - ri[1], node.com[2] - ri[2]];
+			let d2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+			if self.accept(node, ri, d2, theta2) {
+				let q = if self.quadrupole { Some(&node.quad) } else { None };
+				cell_acceleration(&mut a, node.mass, q, &d, d2 + p.pair_eps2(eps[i], eps[i]));
+			} else {
+				stack.extend(node.children.iter().cloned());
+			}
+		}
+		a
+	}
+
+	/*
+	 Potential and acceleration at a point x that need not be a star, e.g. a
+	 test particle. Stars are softened with their own length, cells with
+	 p.eps.
+	 */
+	pub fn field(&self, x: &[R; 3], p: &Params<R>, eps: &Vec<R>, theta: R, stack: &mut Vec<usize>) -> (R, [R; 3]) {
+		let mut phi = R::zero();
+		let mut a = [R::zero(); 3];
+		if self.nodes.is_empty() {
+			return (phi, a);
+		}
+		let theta2 = theta*theta;
+		stack.clear();
+		stack.push(0);
+		while let Some(idx) = stack.pop() {
+			let node = &self.nodes[idx];
+			let mut add = |m: R, d: [R; 3], r2: R| {
+				let r = r2.sqrt();
+				phi -= m/r;
+				for k in 0..3 {
+					a[k] += m*d[k]/(r2*r);
+				}
+			};
+			if node.children.is_empty() {
+				for &j in &node.stars {
+					let rj = &self.pos[j];
+					let d = [rj[0] - x[0], rj[1] - x[1], rj[2] - x[2]];
+					add(self.mass[j], d, d[0]*d[0] + d[1]*d[1] + d[2]*d[2] + p.pair_eps2(eps[j], eps[j]));
+				}
+				continue;
+			}
+			let d = [node.com[0] - x[0], node.com[1] - x[1], node.com[2] - x[2]];
+			let d2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+			if self.accept(node, x, d2, theta2) {
+				let r2 = d2 + p.pair_eps2(p.eps, p.eps);
+				add(node.mass, d, r2);
+				if self.quadrupole {
+					phi += cell_potential(&node.quad, &d, r2);
+					cell_acceleration(&mut a, R::zero(), Some(&node.quad), &d, r2);
+				}
+			} else {
+				stack.extend(node.children.iter().cloned());
+			}
+		}
+		(phi, a)
+	}
+
+	/*
+	 Accelerations of all stars of s from this tree, which has to be built or
+	 refreshed from s. Returns the busy seconds per thread.
+	 */
+	pub fn accelerations(&self, s: &mut Vec<Star<R>>, p: &Params<R>, theta: R) -> Vec<f64> {
+		let eps = p.star_eps(s);
+		let n = s.len();
+		let threads = p.threads.max(1);
+		let mut busy = vec![0.0; threads];
+		let mut rows: Vec<Vec<[R; 3]>> = vec![vec![]; threads];
+		if threads == 1 {
+			let clock = Clock::start();
+			let mut stack = vec![];
+			rows[0] = (0..n).map(|i| self.acceleration(i, p, &eps, theta, &mut stack)).collect();
+			busy[0] = clock.seconds();
+		} else {
+			let (tree, eps) = (self, &eps);
+			thread::scope(|scope| {
+				let handles: Vec<_> = (0..threads).map(|thread_index| scope.spawn(move || {
+					let clock = Clock::start();
+					let mut stack = vec![];
+					let acc: Vec<[R; 3]> = (n*thread_index/threads..n*(thread_index + 1)/threads)
+						.map(|i| tree.acceleration(i, p, eps, theta, &mut stack)).collect();
+					(acc, clock.seconds())
+				})).collect();
+				for (thread_index, handle) in handles.into_iter().enumerate() {
+					let (acc, seconds) = handle.join().expect("Thread failure, RIP");
+					rows[thread_index] = acc;
+					busy[thread_index] = seconds;
+				}
+			});
+		}
+		for (si, a) in rows.iter().flatten().enumerate() {
+			for k in 0..3 {
+				s[si].a[k] = a[k];
+			}
+		}
+		busy
+	}
+}
+
+/*
+ Tree accelerations for all stars, rows split evenly over p.threads, walked
+ with the given opening criterion and with or without quadrupoles. Returns
+ the busy seconds per thread, the first entry including the tree build.
+ */
+pub fn acceleration<R: Real>(s: &mut Vec<Star<R>>, p: &Params<R>, theta: R, opening: Opening, quadrupole: bool) -> (Vec<f64>, usize) {
+	let clock = Clock::start();
+	let mut tree = Tree::build(s);
+	tree.opening = opening;
+	tree.quadrupole = quadrupole;
+	let build = clock.seconds();
+	let mut busy = tree.accelerations(s, p, theta);
+	busy[0] += build;
+	(busy, tree.depth)
+}
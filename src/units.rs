@@ -0,0 +1,144 @@
+/*
+ Physical units for input and output. The user declares the units the input
+ is written in, "--units MASS,LENGTH,VELOCITY" such as "Msun,pc,km/s". The
+ integrator keeps masses and lengths in those units and picks the velocity
+ (and so the time) unit that makes G = 1:
+
+   V = sqrt(G M / L), T = L / V
+
+ so only the input velocities need converting. Times and energies are
+ reported back in Myr and erg; checkpoints are written in the input units so
+ they can be fed back with the same --units. With --normalize the internal
+ units become the N-body units of the system instead (rescaled()).
+
+ Mass units: kg, g, Msun, Mjup, Mearth. Length units: m, cm, km, Rsun, au,
+ pc, kpc. Velocity units: m/s, cm/s, km/s, au/yr, pc/Myr, or "nbody" for
+ velocities that already are in the G = 1 unit.
+ */
+use std::fmt;
+
+use constants::Constants;
+use {Real, Star};
+
+// Jupiter and Earth mass parameters (IAU 2015 B3 nominal), m^3 s^-2
+static GM_JUPITER: f64 = 1.2668653e17;
+static GM_EARTH: f64 = 3.986004e14;
+// IAU 2015 B3 nominal solar radius, m
+static RSUN: f64 = 6.957e8;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Units {
+	pub names: Vec<String>,
+	// Internal mass and length units, kg and m
+	pub mass: f64,
+	pub length: f64,
+	// Mass, length and velocity units of the input, kg, m and m/s
+	pub input: [f64; 3],
+	pub constants: Constants,
+}
+
+impl Units {
+	pub fn parse(spec: &str, constants: Constants) -> Result<Units, String> {
+		let names: Vec<String> = spec.split(',').map(|x| x.trim().to_string()).collect();
+		if names.len() != 3 {
+			return Err(format!("--units needs MASS,LENGTH,VELOCITY, got '{}'", spec));
+		}
+		let k = &constants;
+		let mass = match names[0].as_str() {
+			"kg" => 1.0,
+			"g" => 1e-3,
+			"Msun" => k.msun,
+			"Mjup" => GM_JUPITER/k.g,
+			"Mearth" => GM_EARTH/k.g,
+			x => return Err(format!("Unknown mass unit '{}', use kg, g, Msun, Mjup or Mearth", x)),
+		};
+		let length = match names[1].as_str() {
+			"m" => 1.0,
+			"cm" => 1e-2,
+			"km" => 1e3,
+			"Rsun" => RSUN,
+			"au" => k.au,
+			"pc" => k.pc,
+			"kpc" => 1e3*k.pc,
+			x => return Err(format!("Unknown length unit '{}', use m, cm, km, Rsun, au, pc or kpc", x)),
+		};
+		let mut units = Units { names: names.clone(), mass: mass, length: length, input: [mass, length, 1.0], constants: constants };
+		units.input[2] = match names[2].as_str() {
+			"m/s" => 1.0,
+			"cm/s" => 1e-2,
+			"km/s" => 1e3,
+			"au/yr" => k.au/k.year,
+			"pc/Myr" => k.pc/(1e6*k.year),
+			"nbody" => units.velocity(),
+			x => return Err(format!("Unknown velocity unit '{}', use m/s, cm/s, km/s, au/yr, pc/Myr or nbody", x)),
+		};
+		Ok(units)
+	}
+
+	// Internal velocity unit in m/s, the one that makes G = 1
+	pub fn velocity(&self) -> f64 {
+		self.constants.velocity_unit(self.length, self.mass)
+	}
+
+	// Internal time unit in s
+	pub fn time(&self) -> f64 {
+		self.constants.time_unit(self.length, self.mass)
+	}
+
+	// Internal energy unit in J
+	pub fn energy(&self) -> f64 {
+		self.mass*self.velocity().powi(2)
+	}
+
+	pub fn myr(&self, t: f64) -> f64 {
+		t*self.time()/(1e6*self.constants.year)
+	}
+
+	pub fn erg(&self, e: f64) -> f64 {
+		e*self.energy()*1e7
+	}
+
+	// Internal time t in the G = 1 time unit of the input masses and lengths
+	pub fn input_time(&self, t: f64) -> f64 {
+		t*self.time()/self.constants.time_unit(self.input[1], self.input[0])
+	}
+
+	// The same physical units with the internal mass and length units scaled
+	pub fn rescaled(&self, mass: f64, length: f64) -> Units {
+		let mut x = self.clone();
+		x.mass *= mass;
+		x.length *= length;
+		x
+	}
+
+	// Converts input values to internal ones
+	pub fn to_internal<R: Real>(&self, s: &mut Vec<Star<R>>) {
+		let (fm, fr, fv) = (R::from_f64(self.input[0]/self.mass), R::from_f64(self.input[1]/self.length), R::from_f64(self.input[2]/self.velocity()));
+		for star in s.iter_mut() {
+			star.m *= fm;
+			for k in 0..3 {
+				star.r[k] *= fr;
+				star.v[k] *= fv;
+			}
+		}
+	}
+
+	// A copy of s with masses, positions and velocities in the input units
+	pub fn to_input<R: Real>(&self, s: &Vec<Star<R>>) -> Vec<Star<R>> {
+		let (fm, fr, fv) = (R::from_f64(self.mass/self.input[0]), R::from_f64(self.length/self.input[1]), R::from_f64(self.velocity()/self.input[2]));
+		s.iter().map(|x| Star {
+			m: x.m*fm,
+			r: x.r.iter().map(|&y| y*fr).collect(),
+			v: x.v.iter().map(|&y| y*fv).collect(),
+			a: x.a.clone(),
+			a0: x.a0.clone(),
+		}).collect()
+	}
+}
+
+impl fmt::Display for Units {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "mass {:e} kg, length {:e} m, velocity {:e} m/s, time {:e} Myr, energy {:e} erg",
+			self.mass, self.length, self.velocity(), self.myr(1.0), self.erg(1.0))
+	}
+}
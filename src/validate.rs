@@ -0,0 +1,100 @@
+/*
+ A look over the input before it is parsed, reporting every suspicious row
+ rather than only the first bad one: rows that are not numbers, rows with
+ fewer than eight columns or a different number of columns than the first
+ row, NaN or infinite values, negative masses (zero is fine, a test
+ particle), stars at the same position (an infinite force without
+ softening) and ids used twice (negative ids mean none).
+
+ The run logs them as warnings and goes on where it can; with --strict any
+ of them stops it before it starts.
+ */
+use std::collections::HashMap;
+use std::fmt;
+
+use NBodyError;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Issue {
+	// Counting from 1
+	pub line: usize,
+	pub column: usize,
+	pub message: String,
+}
+
+impl fmt::Display for Issue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+	}
+}
+
+impl Issue {
+	pub fn to_error(&self) -> NBodyError {
+		NBodyError::parse(self.line, self.column, self.message.clone())
+	}
+}
+
+// Words of a line with the column each starts at
+fn words(line: &str) -> Vec<(usize, &str)> {
+	line.split_whitespace().map(|x| (line[..x.as_ptr() as usize - line.as_ptr() as usize].chars().count() + 1, x)).collect()
+}
+
+pub fn validate(text: &str) -> Vec<Issue> {
+	let mut issues = vec![];
+	let mut width = None;
+	let mut ids: HashMap<&str, usize> = HashMap::new();
+	let mut positions: HashMap<[u64; 3], usize> = HashMap::new();
+	for (number, line) in text.split('\n').enumerate() {
+		if line.trim().is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let line_no = number + 1;
+		let mut issue = |column: usize, message: String| issues.push(Issue { line: line_no, column: column, message: message });
+		let w = words(line);
+		let end = line.trim_end().chars().count() + 1;
+		if w.len() < 8 {
+			issue(end, format!("expected 8 columns (id m x y z vx vy vz), found {}", w.len()));
+		} else if let Some(first) = width {
+			if w.len() != first {
+				issue(end, format!("{} columns where the first row has {}", w.len(), first));
+			}
+		}
+		width = width.or(Some(w.len()));
+
+		let mut values: Vec<Option<f64>> = vec![];
+		for &(column, word) in w.iter().take(8) {
+			match word.parse::<f64>() {
+				Ok(x) if x.is_nan() => issue(column, String::from("NaN")),
+				Ok(x) if x.is_infinite() => issue(column, format!("'{}' is not finite", word)),
+				Ok(_) => {},
+				Err(_) => issue(column, format!("'{}' is not a number", word)),
+			}
+			values.push(word.parse::<f64>().ok().filter(|x| x.is_finite()));
+		}
+		// The reference inputs give every star id -1, i.e. none
+		if let Some(&(column, id)) = w.first().filter(|x| !x.1.starts_with('-')) {
+			if let Some(&first) = ids.get(id) {
+				issue(column, format!("id {} was already used on line {}", id, first));
+			} else {
+				ids.insert(id, line_no);
+			}
+		}
+		if let (Some(&Some(m)), Some(&(column, _))) = (values.get(1), w.get(1)) {
+			if m < 0.0 {
+				issue(column, format!("negative mass {}", m));
+			}
+		}
+		if values.len() >= 5 {
+			if let (Some(x), Some(y), Some(z)) = (values[2], values[3], values[4]) {
+				// +0 and -0 are the same place
+				let key = [(x + 0.0).to_bits(), (y + 0.0).to_bits(), (z + 0.0).to_bits()];
+				if let Some(&first) = positions.get(&key) {
+					issue(w[2].0, format!("same position as the star on line {}", first));
+				} else {
+					positions.insert(key, line_no);
+				}
+			}
+		}
+	}
+	issues
+}
@@ -0,0 +1,205 @@
+/*
+ Short-range forces through Verlet neighbour lists, for strongly softened
+ systems whose interesting physics is local (--solver verlet --cutoff RC).
+ Only pairs closer than RC interact, through the softened potential shifted
+ by its value at RC,
+
+   phi(r) = -1/sqrt(r^2 + eps^2) + 1/sqrt(RC^2 + eps^2)   for r < RC,
+
+ so the energy is continuous where a pair leaves the cutoff and dE stays a
+ measure of accuracy; the force jumps there by the softened pull at RC, which
+ is small when RC is several softening lengths. The pull of everything
+ further away is simply missing: this is not gravity, it is gravity's short
+ end.
+
+ The list holds every pair closer than RC + skin and is built on a hashed
+ chaining mesh of cells of that side, O(N) in open space. No pair can get
+ from outside RC + skin to inside RC before one of its stars has moved half
+ the skin, so the list is reused until the largest displacement since the
+ build exceeds skin/2, and every force evaluation costs the listed pairs
+ only, O(N) for a fixed number of neighbours.
+ */
+use std::collections::HashMap;
+use std::thread;
+
+use solver::ForceSolver;
+use timing::Clock;
+use {Params, Real, Star};
+
+pub struct Verlet<R = f64> {
+	pub cutoff: R,
+	pub skin: R,
+	// Lists built so far
+	pub builds: usize,
+	pairs: Vec<(usize, usize)>,
+	// Positions at the last build
+	origin: Vec<[R; 3]>,
+}
+
+// The skin when none is given, as a fraction of the cutoff
+pub static SKIN: f64 = 0.2;
+
+fn position<R: Real>(x: &Star<R>) -> [R; 3] {
+	[x.r[0], x.r[1], x.r[2]]
+}
+
+// Every pair i < j closer than reach, from a chaining mesh of cells of side reach
+pub fn pairs_within<R: Real>(s: &Vec<Star<R>>, reach: R) -> Vec<(usize, usize)> {
+	let width = reach.to_f64();
+	let cell = |x: &Star<R>| {
+		let c: Vec<i64> = (0..3).map(|k| (x.r[k].to_f64()/width).floor() as i64).collect();
+		(c[0], c[1], c[2])
+	};
+	let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+	for (i, x) in s.iter().enumerate() {
+		cells.entry(cell(x)).or_insert_with(Vec::new).push(i);
+	}
+	let reach2 = reach*reach;
+	let mut list = vec![];
+	for (i, x) in s.iter().enumerate() {
+		let c = cell(x);
+		for dx in -1..2 {
+			for dy in -1..2 {
+				for dz in -1..2 {
+					if let Some(members) = cells.get(&(c.0 + dx, c.1 + dy, c.2 + dz)) {
+						for &j in members.iter().filter(|&&j| j > i) {
+							let r2 = (0..3).fold(R::zero(), |r2, k| r2 + (x.r[k] - s[j].r[k]).powi(2));
+							if r2 < reach2 {
+								list.push((i, j));
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+	list.sort();
+	list
+}
+
+impl<R: Real> Verlet<R> {
+	pub fn new(cutoff: R, skin: R) -> Verlet<R> {
+		Verlet { cutoff: cutoff, skin: skin, builds: 0, pairs: vec![], origin: vec![] }
+	}
+
+	// Whether no star has moved half the skin since the last build
+	fn valid(&self, s: &Vec<Star<R>>) -> bool {
+		let limit = (R::from_f64(0.5)*self.skin).powi(2);
+		self.origin.len() == s.len() && s.iter().zip(self.origin.iter()).all(|(x, r)| (0..3).fold(R::zero(), |d, k| d + (x.r[k] - r[k]).powi(2)) <= limit)
+	}
+
+	// The pairs closer than the cutoff and their separations r_i - r_j, from the list
+	fn close<'a>(&'a self, s: &'a Vec<Star<R>>, list: &'a [(usize, usize)]) -> impl Iterator<Item = (usize, usize, [R; 3], R)> + 'a {
+		let rc2 = self.cutoff*self.cutoff;
+		list.iter().filter_map(move |&(i, j)| {
+			let d = [s[i].r[0] - s[j].r[0], s[i].r[1] - s[j].r[1], s[i].r[2] - s[j].r[2]];
+			let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+			if r2 < rc2 { Some((i, j, d, r2)) } else { None }
+		})
+	}
+}
+
+impl<R: Real> ForceSolver<R> for Verlet<R> {
+	fn name(&self) -> &'static str {
+		"verlet"
+	}
+
+	/*
+	 Rebuilds the list if it went stale, then sums the listed pairs in equal
+	 slices on p.threads threads, each into its own buffer; the buffers are
+	 added in thread order, so the result does not depend on scheduling.
+	 */
+	fn accelerations(&mut self, s: &mut Vec<Star<R>>, p: &Params<R>) -> Vec<f64> {
+		let clock = Clock::start();
+		if !self.valid(s) {
+			self.pairs = pairs_within(s, self.cutoff + self.skin);
+			self.origin = s.iter().map(position).collect();
+			self.builds += 1;
+		}
+		let build = clock.seconds();
+		let eps = p.star_eps(s);
+		let n = s.len();
+		let threads = p.threads.max(1).min(self.pairs.len().max(1));
+		let sum = |lo: usize, hi: usize| {
+			let clock = Clock::start();
+			let mut a = vec![[R::zero(); 3]; n];
+			for (i, j, d, r2) in self.close(s, &self.pairs[lo..hi]) {
+				let r2 = r2 + p.pair_eps2(eps[i], eps[j]);
+				let f = R::one()/(r2*r2.sqrt());
+				for k in 0..3 {
+					a[i][k] -= s[j].m*f*d[k];
+					a[j][k] += s[i].m*f*d[k];
+				}
+			}
+			(a, clock.seconds())
+		};
+		let total = self.pairs.len();
+		let parts: Vec<(Vec<[R; 3]>, f64)> = if threads == 1 {
+			vec![sum(0, total)]
+		} else {
+			let sum = &sum;
+			thread::scope(|scope| {
+				let handles: Vec<_> = (0..threads).map(|t| scope.spawn(move || sum(total*t/threads, total*(t + 1)/threads))).collect();
+				handles.into_iter().map(|x| x.join().expect("Thread failure, RIP")).collect()
+			})
+		};
+		let mut busy = vec![];
+		for star in s.iter_mut() {
+			star.a = vec![R::zero(); 3];
+		}
+		for (a, seconds) in parts {
+			for (star, a) in s.iter_mut().zip(a.iter()) {
+				for k in 0..3 {
+					star.a[k] += a[k];
+				}
+			}
+			busy.push(seconds);
+		}
+		busy[0] += build;
+		busy
+	}
+
+	// [E, T, W] with the shifted, truncated potential
+	fn energies(&self, s: &Vec<Star<R>>, p: &Params<R>) -> Option<Vec<R>> {
+		let eps = p.star_eps(s);
+		let fresh;
+		let list = if self.valid(s) {
+			&self.pairs
+		} else {
+			fresh = pairs_within(s, self.cutoff);
+			&fresh
+		};
+		let half = R::from_f64(0.5);
+		let kinetic = s.iter().fold(R::zero(), |e, x| e + half*x.m*(x.v[0]*x.v[0] + x.v[1]*x.v[1] + x.v[2]*x.v[2]));
+		let rc2 = self.cutoff*self.cutoff;
+		let potential = self.close(s, list).fold(R::zero(), |w, (i, j, _, r2)| {
+			let e2 = p.pair_eps2(eps[i], eps[j]);
+			w - s[i].m*s[j].m*(R::one()/(r2 + e2).sqrt() - R::one()/(rc2 + e2).sqrt())
+		});
+		Some(vec![kinetic + potential, kinetic, potential])
+	}
+
+	// The same truncated field at arbitrary points, from every star within the cutoff
+	fn field(&self, s: &Vec<Star<R>>, p: &Params<R>, points: &[[f64; 3]]) -> Vec<(f64, [f64; 3])> {
+		let eps = p.star_eps(s);
+		let rc2 = (self.cutoff*self.cutoff).to_f64();
+		points.iter().map(|x| {
+			let mut phi = 0.0;
+			let mut a = [0.0; 3];
+			for (j, star) in s.iter().enumerate() {
+				let d: Vec<f64> = (0..3).map(|k| star.r[k].to_f64() - x[k]).collect();
+				let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+				if r2 >= rc2 {
+					continue;
+				}
+				let e2 = p.pair_eps2(eps[j], eps[j]).to_f64();
+				let (m, r) = (star.m.to_f64(), (r2 + e2).sqrt());
+				phi -= m*(1.0/r - 1.0/(rc2 + e2).sqrt());
+				for k in 0..3 {
+					a[k] += m*d[k]/(r*r*r);
+				}
+			}
+			(phi, a)
+		}).collect()
+	}
+}
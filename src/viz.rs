@@ -0,0 +1,103 @@
+/*
+ A window showing the stars live as the run goes, through kiss3d. The mouse
+ turns (left button), pans (right button) and zooms (wheel) the camera; Space
+ pauses and resumes the run, S or the right arrow does one step while paused
+ and R puts the camera back. Stars are colored by their specific energy:
+ the most bound ones blue, marginally bound ones white, escapers red.
+
+ The run waits while paused. Closing the window only closes the viewer, the
+ run itself goes on.
+ */
+use std::time::Instant;
+
+use kiss3d::camera::ArcBall;
+use kiss3d::event::{Action, Key, WindowEvent};
+use kiss3d::nalgebra::{Point2, Point3};
+use kiss3d::text::Font;
+use kiss3d::window::Window;
+
+use diagnostics;
+use {Params, Real, Star};
+
+// Seconds between frames while running; steps in between are not drawn
+const FRAME: f64 = 1.0/60.0;
+
+fn camera() -> ArcBall {
+	ArcBall::new(Point3::new(0.0, 0.0, 6.0), Point3::origin())
+}
+
+// Blue for the deepest bound star through white at e = 0, red when unbound
+fn color(e: f64, deepest: f64) -> Point3<f32> {
+	if e >= 0.0 {
+		return Point3::new(1.0, 0.3, 0.3);
+	}
+	let x = if deepest < 0.0 { (e/deepest).min(1.0) as f32 } else { 0.0 };
+	Point3::new(1.0 - 0.7*x, 1.0 - 0.5*x, 1.0)
+}
+
+pub struct Viewer {
+	window: Window,
+	camera: ArcBall,
+	paused: bool,
+	single_step: bool,
+	drawn: Option<Instant>,
+}
+
+impl Viewer {
+	pub fn open() -> Viewer {
+		let mut window = Window::new("nbabel");
+		window.set_background_color(0.0, 0.0, 0.0);
+		window.set_point_size(3.0);
+		Viewer { window: window, camera: camera(), paused: false, single_step: false, drawn: None }
+	}
+
+	/*
+	 Draws the stars at time t, at most every FRAME seconds, and handles the
+	 keys; while paused it keeps drawing until the run may go on. Returns
+	 false once the window has been closed.
+	 */
+	pub fn show<R: Real>(&mut self, s: &Vec<Star<R>>, p: &Params<R>, t: f64) -> bool {
+		if !self.paused && self.drawn.map_or(false, |x| x.elapsed().as_secs_f64() < FRAME) {
+			return true;
+		}
+		let e = diagnostics::specific_energies(s, p);
+		let deepest = e.iter().fold(0.0, |x: f64, y| x.min(y.to_f64()));
+		let points: Vec<(Point3<f32>, Point3<f32>)> = s.iter().zip(e.iter()).map(|(x, e)| {
+			(Point3::new(x.r[0].to_f64() as f32, x.r[1].to_f64() as f32, x.r[2].to_f64() as f32), color(e.to_f64(), deepest))
+		}).collect();
+		let font = Font::default();
+		loop {
+			for x in points.iter() {
+				self.window.draw_point(&x.0, &x.1);
+			}
+			let label = format!("t = {:.4}  N = {}{}", t, s.len(), if self.paused { "  (paused: Space resumes, S steps)" } else { "" });
+			self.window.draw_text(&label, &Point2::new(10.0, 10.0), 40.0, &font, &Point3::new(0.8, 0.8, 0.8));
+			if !self.window.render_with_camera(&mut self.camera) {
+				return false;
+			}
+			self.drawn = Some(Instant::now());
+			self.keys();
+			if !self.paused {
+				return true;
+			}
+			if self.single_step {
+				self.single_step = false;
+				return true;
+			}
+		}
+	}
+
+	fn keys(&mut self) {
+		for event in self.window.events().iter() {
+			match event.value {
+				WindowEvent::Key(Key::Space, Action::Press, _) => self.paused = !self.paused,
+				WindowEvent::Key(Key::S, Action::Press, _) | WindowEvent::Key(Key::Right, Action::Press, _) => {
+					self.paused = true;
+					self.single_step = true;
+				},
+				WindowEvent::Key(Key::R, Action::Press, _) => self.camera = camera(),
+				_ => (),
+			}
+		}
+	}
+}
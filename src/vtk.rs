@@ -0,0 +1,160 @@
+/*
+ Snapshots for ParaView: one VTK PolyData file (.vtp) per diagnostic step
+ and a collection file, snapshots.pvd, that lists them with their times, so
+ ParaView opens the whole run as a time series. Each snapshot has the
+ positions as points (one vertex cell per star, so they render without a
+ filter) and point data arrays id (see columns.rs), mass, velocity, speed
+ and potential (the softened potential of all other stars), all in N-body
+ units. Fields
+ adds optional ones: density, the Casertano-Hut estimate of
+ diagnostics::local_densities(), the specific kinetic and total energy
+ of every star, negative energy meaning bound to the cluster, and the
+ acceleration the integrator last used with the jerk of the pair forces
+ (diagnostics::jerks()).
+
+ A run's metadata (see metadata.rs) goes into the FieldData of every
+ snapshot as one string array per item, which ParaView lists in its
+ Information panel.
+
+ The files are ASCII XML: larger than the binary encodings but readable and
+ with no dependencies. The .pvd is rewritten after every snapshot, so an
+ interrupted run leaves a complete series behind.
+ */
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use columns::Columns;
+use diagnostics;
+use metadata::Metadata;
+use {Params, Real, Star};
+
+fn array<W: Write, I: Iterator<Item = f64>>(w: &mut W, name: &str, components: usize, values: I) -> io::Result<()> {
+	writeln!(w, "<DataArray type=\"Float64\" Name=\"{}\" NumberOfComponents=\"{}\" format=\"ascii\">", name, components)?;
+	for (i, x) in values.enumerate() {
+		write!(w, "{}{}", x, if (i + 1) % components == 0 { "\n" } else { " " })?;
+	}
+	writeln!(w, "</DataArray>")
+}
+
+// Point data beyond the standard arrays
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Fields {
+	// Neighbour count for a density array
+	pub density: Option<usize>,
+	// kinetic and energy arrays, per unit mass
+	pub energies: bool,
+	// acceleration and jerk arrays
+	pub accelerations: bool,
+}
+
+// The stars as one .vtp file
+pub fn write_snapshot<R: Real, W: Write>(w: &mut W, s: &Vec<Star<R>>, p: &Params<R>) -> io::Result<()> {
+	write_snapshot_with(w, s, p, &Fields::default())
+}
+
+// Same, with the optional arrays in fields
+pub fn write_snapshot_with<R: Real, W: Write>(w: &mut W, s: &Vec<Star<R>>, p: &Params<R>, fields: &Fields) -> io::Result<()> {
+	write_snapshot_meta(w, s, p, fields, &Columns::default(), None)
+}
+
+// Same, with the ids of columns and the metadata of the run as field data
+pub fn write_snapshot_meta<R: Real, W: Write>(w: &mut W, s: &Vec<Star<R>>, p: &Params<R>, fields: &Fields, columns: &Columns, metadata: Option<&Metadata>) -> io::Result<()> {
+	let n = s.len();
+	let phi = diagnostics::potentials(s, p);
+	writeln!(w, "<?xml version=\"1.0\"?>\n<VTKFile type=\"PolyData\" version=\"0.1\" byte_order=\"LittleEndian\">\n<PolyData>")?;
+	if let Some(x) = metadata {
+		// ASCII string arrays are the byte values, each string ending in a 0
+		writeln!(w, "<FieldData>")?;
+		for (key, value) in x.pairs() {
+			writeln!(w, "<DataArray type=\"String\" Name=\"{}\" NumberOfTuples=\"1\" format=\"ascii\">", key)?;
+			let bytes: Vec<String> = value.bytes().chain(Some(0)).map(|b| b.to_string()).collect();
+			writeln!(w, "{}\n</DataArray>", bytes.join(" "))?;
+		}
+		writeln!(w, "</FieldData>")?;
+	}
+	writeln!(w, "<Piece NumberOfPoints=\"{}\" NumberOfVerts=\"{}\" NumberOfLines=\"0\" NumberOfStrips=\"0\" NumberOfPolys=\"0\">", n, n)?;
+	writeln!(w, "<Points>")?;
+	array(w, "position", 3, s.iter().flat_map(|x| x.r[..3].iter().map(|x| x.to_f64()).collect::<Vec<f64>>()))?;
+	writeln!(w, "</Points>\n<PointData Scalars=\"mass\" Vectors=\"velocity\">")?;
+	writeln!(w, "<DataArray type=\"UInt64\" Name=\"id\" format=\"ascii\">")?;
+	for i in 0..n {
+		writeln!(w, "{}", columns.id(i))?;
+	}
+	writeln!(w, "</DataArray>")?;
+	array(w, "mass", 1, s.iter().map(|x| x.m.to_f64()))?;
+	array(w, "velocity", 3, s.iter().flat_map(|x| x.v[..3].iter().map(|x| x.to_f64()).collect::<Vec<f64>>()))?;
+	array(w, "speed", 1, s.iter().map(|x| (x.v[0].powi(2) + x.v[1].powi(2) + x.v[2].powi(2)).sqrt().to_f64()))?;
+	array(w, "potential", 1, phi.iter().map(|x| x.to_f64()))?;
+	if let Some(k) = fields.density {
+		array(w, "density", 1, diagnostics::local_densities(s, k).iter().map(|x| x.to_f64()))?;
+	}
+	if fields.energies {
+		let kinetic: Vec<f64> = s.iter().map(|x| 0.5*(x.v[0].powi(2) + x.v[1].powi(2) + x.v[2].powi(2)).to_f64()).collect();
+		array(w, "kinetic", 1, kinetic.iter().cloned())?;
+		array(w, "energy", 1, kinetic.iter().zip(phi.iter()).map(|(k, phi)| k + phi.to_f64()))?;
+	}
+	if fields.accelerations {
+		array(w, "acceleration", 3, s.iter().flat_map(|x| x.a[..3].iter().map(|x| x.to_f64()).collect::<Vec<f64>>()))?;
+		array(w, "jerk", 3, diagnostics::jerks(s, p).iter().flat_map(|x| x.iter().map(|x| x.to_f64()).collect::<Vec<f64>>()))?;
+	}
+	writeln!(w, "</PointData>\n<Verts>\n<DataArray type=\"Int64\" Name=\"connectivity\" format=\"ascii\">")?;
+	for i in 0..n {
+		writeln!(w, "{}", i)?;
+	}
+	writeln!(w, "</DataArray>\n<DataArray type=\"Int64\" Name=\"offsets\" format=\"ascii\">")?;
+	for i in 0..n {
+		writeln!(w, "{}", i + 1)?;
+	}
+	writeln!(w, "</DataArray>\n</Verts>\n</Piece>\n</PolyData>\n</VTKFile>")
+}
+
+/*
+ The snapshots of a run in DIR/vtk. Files are numbered in the order they are
+ written, snapshot_000000.vtp onwards.
+ */
+pub struct Series {
+	dir: PathBuf,
+	pub times: Vec<f64>,
+	// Optional arrays in every snapshot
+	pub fields: Fields,
+	pub metadata: Option<Metadata>,
+}
+
+impl Series {
+	pub fn new(out_dir: &Path) -> io::Result<Series> {
+		let dir = out_dir.join("vtk");
+		fs::create_dir_all(&dir)?;
+		Ok(Series { dir: dir, times: vec![], fields: Fields::default(), metadata: None })
+	}
+
+	fn name(n: usize) -> String {
+		format!("snapshot_{:06}.vtp", n)
+	}
+
+	// Adds the stars at time t, unless t is already the last snapshot
+	pub fn write<R: Real>(&mut self, t: f64, s: &Vec<Star<R>>, p: &Params<R>) -> io::Result<()> {
+		self.write_with(t, s, p, &Columns::default())
+	}
+
+	// Same, with the ids of columns
+	pub fn write_with<R: Real>(&mut self, t: f64, s: &Vec<Star<R>>, p: &Params<R>, columns: &Columns) -> io::Result<()> {
+		if self.times.last() == Some(&t) {
+			return Ok(());
+		}
+		let mut f = BufWriter::new(File::create(self.dir.join(Series::name(self.times.len())))?);
+		write_snapshot_meta(&mut f, s, p, &self.fields, columns, self.metadata.as_ref())?;
+		f.flush()?;
+		self.times.push(t);
+
+		let mut f = BufWriter::new(File::create(self.dir.join("snapshots.pvd"))?);
+		writeln!(f, "<?xml version=\"1.0\"?>\n<VTKFile type=\"Collection\" version=\"0.1\" byte_order=\"LittleEndian\">\n<Collection>")?;
+		for (n, t) in self.times.iter().enumerate() {
+			writeln!(f, "<DataSet timestep=\"{}\" group=\"\" part=\"0\" file=\"{}\"/>", t, Series::name(n))?;
+		}
+		writeln!(f, "</Collection>\n</VTKFile>")?;
+		f.flush()
+	}
+}
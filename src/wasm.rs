@@ -0,0 +1,97 @@
+/*
+ Bindings for running the integrator in a browser, built with
+ `wasm-pack build --target web --features wasm`; demo/ has a page that draws
+ a cluster with them. There are no threads on wasm32-unknown-unknown, so the
+ simulation always runs its force loop serially on the calling thread, and
+ there is no clock, so the timings stay zero.
+
+ Arrays cross into JavaScript as Float64Arrays: masses are n doubles,
+ positions and velocities 3n, x y z of each star in turn. Everything is in
+ N-body units (G = 1).
+ */
+use wasm_bindgen::prelude::*;
+
+use generate;
+use rng::Rng;
+use {Params, Simulation, Star};
+
+#[wasm_bindgen]
+pub struct Demo {
+	sim: Simulation<f64>,
+}
+
+fn params(dt: f64, eps: f64) -> Result<Params, String> {
+	if !(dt > 0.0) {
+		return Err(format!("Time step must be positive, got {}", dt));
+	}
+	if !(eps >= 0.0) {
+		return Err(format!("Softening must not be negative, got {}", eps));
+	}
+	let mut p = Params::default();
+	p.dt = dt;
+	p.eps = eps;
+	p.threads = 1;
+	Ok(p)
+}
+
+#[wasm_bindgen]
+impl Demo {
+	// A King model (W0 = 6) of n stars drawn from seed
+	#[wasm_bindgen(constructor)]
+	pub fn new(n: usize, seed: u32, dt: f64, eps: f64) -> Result<Demo, String> {
+		if n < 2 {
+			return Err(format!("Need at least 2 stars, got {}", n));
+		}
+		let p = params(dt, eps)?;
+		let s = generate::king(n, 6.0, &mut Rng::new(seed as u64));
+		Ok(Demo { sim: Simulation::new(s, p) })
+	}
+
+	// Stars given by masses m, positions r and velocities v
+	pub fn from_particles(m: &[f64], r: &[f64], v: &[f64], dt: f64, eps: f64) -> Result<Demo, String> {
+		let n = m.len();
+		if r.len() != 3*n || v.len() != 3*n {
+			return Err(format!("Expected {} positions and velocities for {} masses, got {} and {}", 3*n, n, r.len(), v.len()));
+		}
+		let p = params(dt, eps)?;
+		let s = (0..n).map(|i| Star {
+			m: m[i],
+			r: r[3*i..3*i + 3].to_vec(),
+			v: v[3*i..3*i + 3].to_vec(),
+			a: vec![0.0; 3],
+			a0: vec![0.0; 3],
+		}).collect();
+		Ok(Demo { sim: Simulation::new(s, p) })
+	}
+
+	pub fn step(&mut self, steps: usize) {
+		for _ in 0..steps {
+			self.sim.step();
+		}
+	}
+
+	pub fn positions(&self) -> Vec<f64> {
+		self.sim.s.iter().flat_map(|x| x.r[..3].to_vec()).collect()
+	}
+
+	pub fn velocities(&self) -> Vec<f64> {
+		self.sim.s.iter().flat_map(|x| x.v[..3].to_vec()).collect()
+	}
+
+	pub fn masses(&self) -> Vec<f64> {
+		self.sim.s.iter().map(|x| x.m).collect()
+	}
+
+	pub fn count(&self) -> usize {
+		self.sim.s.len()
+	}
+
+	pub fn time(&self) -> f64 {
+		self.sim.t
+	}
+
+	// Total energy, to watch the drift
+	pub fn energy(&self) -> f64 {
+		self.sim.energies()[0]
+	}
+}
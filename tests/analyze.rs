@@ -0,0 +1,33 @@
+extern crate nbabel;
+
+use std::fs::File;
+
+use nbabel::*;
+use nbabel::analyze;
+use nbabel::diagnostics;
+use nbabel::generate;
+use nbabel::rng::Rng;
+
+// The streamed summary agrees with the in-memory diagnostics
+#[test]
+fn streaming_matches_in_memory() {
+	let s = generate::king(1000, 6.0, &mut Rng::new(3));
+	let path = std::env::temp_dir().join(format!("nbabel-analyze-{}.txt", std::process::id()));
+	write_stars(&mut File::create(&path).unwrap(), &s).unwrap();
+	let x = analyze::summarize(path.to_str().unwrap(), &diagnostics::FRACTIONS, true);
+	std::fs::remove_file(&path).unwrap();
+	let x = x.unwrap();
+
+	assert_eq!(x.n, 1000);
+	let e = energies(&s, &Params::default());
+	assert!((x.kinetic - e[1]).abs() < 1e-9);
+	let (w, _, _, bound) = x.full.unwrap();
+	assert!((w - e[2]).abs() < 1e-9);
+	assert_eq!(bound, diagnostics::bound_count(&s));
+
+	let com = diagnostics::center_of_mass(&s);
+	let exact = diagnostics::lagrangian_radii(&s, &com, &diagnostics::FRACTIONS);
+	for (r, exact) in x.radii.iter().zip(exact.iter()) {
+		assert!(r >= exact && r/exact - 1.0 < 0.003, "{} vs {}", r, exact);
+	}
+}
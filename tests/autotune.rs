@@ -0,0 +1,36 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::autotune::{self, Key, Tuning};
+use nbabel::generate;
+use nbabel::rng::Rng;
+
+#[test]
+fn cache_keeps_one_line_per_key() {
+	let path = std::env::temp_dir().join(format!("nbabel-autotune-{}", std::process::id()));
+	let _ = std::fs::remove_file(&path);
+	let key = Key::new("direct", "f64", 3000);
+	assert_eq!(key.n, 4096);
+	assert_eq!(autotune::load(&path, &key), None);
+
+	autotune::save(&path, &key, Tuning { threads: 2, tile: 128 }).unwrap();
+	let other = Key::new("tree", "f64", 3000);
+	autotune::save(&path, &other, Tuning { threads: 4, tile: 64 }).unwrap();
+	autotune::save(&path, &key, Tuning { threads: 8, tile: 32 }).unwrap();
+	assert_eq!(autotune::load(&path, &key), Some(Tuning { threads: 8, tile: 32 }));
+	assert_eq!(autotune::load(&path, &other), Some(Tuning { threads: 4, tile: 64 }));
+	assert_eq!(autotune::load(&path, &Key::new("direct", "f32", 3000)), None);
+	let text = std::fs::read_to_string(&path).unwrap();
+	assert_eq!(text.lines().filter(|x| !x.starts_with('#')).count(), 2);
+	std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn tuning_picks_a_measured_setting() {
+	let s = generate::plummer(300, &mut Rng::new(5));
+	let x = autotune::tune(&s, &Params::default(), "direct", 0.5);
+	assert!(x.threads >= 1 && x.threads <= thread_count());
+	assert!([32, 64, 128, 256].contains(&x.tile));
+	assert_eq!(pairs::tile_size(), x.tile);
+	pairs::set_tile_size(pairs::BLOCK);
+}
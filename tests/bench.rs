@@ -0,0 +1,32 @@
+extern crate nbabel;
+
+use nbabel::bench::{recommend, thread_counts, Row};
+
+#[test]
+fn thread_lists_and_ranges() {
+	assert_eq!(thread_counts("1..16"), Some(vec![1, 2, 4, 8, 16]));
+	assert_eq!(thread_counts("1..12"), Some(vec![1, 2, 4, 8, 12]));
+	assert_eq!(thread_counts("6, 2,2..4"), Some(vec![2, 4, 6]));
+	assert_eq!(thread_counts("3"), Some(vec![3]));
+	for bad in &["0", "0..4", "8..2", "two", ""] {
+		assert_eq!(thread_counts(bad), None, "{}", bad);
+	}
+}
+
+// Fewest threads within 10% of the fastest time, per N in measured order
+#[test]
+fn recommendations_leave_useless_cores_free() {
+	let row = |solver: &str, n: usize, threads: usize, seconds: f64| Row { solver: String::from(solver), n: n, threads: threads, seconds: seconds };
+	let rows = vec![
+		row("direct", 4096, 1, 4.0),
+		row("direct", 4096, 4, 1.0),
+		row("direct", 4096, 8, 0.95),
+		row("tree", 4096, 1, 2.0),
+		row("tree", 4096, 4, 0.6),
+		row("tree", 4096, 8, 0.5),
+		row("direct", 256, 1, 0.01),
+		row("direct", 256, 2, 0.0105),
+	];
+	let picks = recommend(&rows);
+	assert_eq!(picks, vec![row("tree", 4096, 8, 0.5), row("direct", 256, 1, 0.01)]);
+}
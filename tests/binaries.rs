@@ -0,0 +1,48 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::binaries::{find_pairs, Catalog};
+
+fn star(m: f64, r: [f64; 3], v: [f64; 3]) -> Star {
+	Star { m: m, r: r.to_vec(), v: v.to_vec(), a: vec![0.0; 3], a0: vec![0.0; 3] }
+}
+
+// Two circular binaries of separation 0.01 and 0.02, far apart, and a lone star
+fn system() -> Vec<Star> {
+	let v1 = (1.0f64/0.01).sqrt()/2.0;
+	let v2 = (1.0f64/0.02).sqrt()/2.0;
+	vec![
+		star(0.5, [0.0, 0.0, 0.0], [0.0, -v1, 0.0]),
+		star(0.5, [0.01, 0.0, 0.0], [0.0, v1, 0.0]),
+		star(0.5, [5.0, 0.0, 0.0], [0.0, -v2, 0.0]),
+		star(0.5, [5.02, 0.0, 0.0], [0.0, v2, 0.0]),
+		star(0.1, [-5.0, 3.0, 0.0], [0.0, 0.0, 0.0]),
+	]
+}
+
+#[test]
+fn finds_mutual_bound_pairs() {
+	let found = find_pairs(&system());
+	assert_eq!(found.len(), 2);
+	assert_eq!((found[0].i, found[0].j), (0, 1));
+	assert!((found[0].a - 0.01).abs() < 1e-9 && found[0].e < 1e-6);
+	assert!((found[1].a - 0.02).abs() < 1e-9);
+}
+
+#[test]
+fn statistics_follow_formation_and_disruption() {
+	let mut s = system();
+	let mut catalog = Catalog::new(None).unwrap();
+	catalog.update(0.0, &s).unwrap();
+	let x = catalog.statistics();
+	assert_eq!((x.formed, x.disrupted, x.bound, x.exchanges), (2, 0, 2, 0));
+	assert!((x.min_a - 0.01).abs() < 1e-9);
+
+	// The wide binary is blown apart
+	s[3].v[1] = 10.0;
+	catalog.update(1.0, &s).unwrap();
+	let x = catalog.statistics();
+	assert_eq!((x.formed, x.disrupted, x.bound), (2, 1, 1));
+	assert!((x.median_a - 0.01).abs() < 1e-9);
+	assert!(x.to_string().starts_with("2 formed, 1 disrupted, 1 bound, 0 exchanges; bound: a min 1.0000e-2"));
+}
@@ -0,0 +1,20 @@
+extern crate nbabel;
+
+use nbabel::cadence::Cadence;
+
+// Log times are the multiples of every, also from a time that is one of them up to rounding
+#[test]
+fn next_log_time() {
+	let mut c = Cadence::fixed(10);
+	assert_eq!(c.next_time(0.0), None);
+	c.every = Some(0.1);
+	assert!((c.next_time(0.0).unwrap() - 0.1).abs() < 1e-15);
+	assert!((c.next_time(0.05).unwrap() - 0.1).abs() < 1e-15);
+	assert!((c.next_time(0.1).unwrap() - 0.2).abs() < 1e-15);
+	assert!((c.next_time(0.30000000000000004).unwrap() - 0.4).abs() < 1e-15);
+	assert!((c.next_time(0.29999999999999993).unwrap() - 0.4).abs() < 1e-15);
+	// A step interval set by hand goes back to counting steps
+	c.set_interval(5);
+	assert_eq!(c.next_time(0.0), None);
+	assert_eq!(c.interval, 5);
+}
@@ -0,0 +1,52 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::central::Central;
+
+fn system() -> Vec<Star> {
+	// A hole, a star on a tight circular orbit about it and two far away
+	read_stars("0 1 0 0 0 0 0 0\n1 1e-6 0.05 0 0 0 4.47213595499958 0\n2 1e-3 3 0 0 0 0.57735 0\n3 1e-3 -3 0.5 0 0 -0.57 0\n")
+}
+
+#[test]
+fn never_softened() {
+	let mut p = Params::default();
+	p.eps = 0.1;
+	let mut sim = Simulation::new(system(), p);
+	assert!(sim.s[1].a[0] > -100.0);
+	sim.set_central(Central::pick("heaviest", &sim.s).unwrap());
+	assert!((sim.s[1].a[0] + 1.0/0.0025).abs() < 1.0, "a = {}", sim.s[1].a[0]);
+	// So are the potential energies; the pairs without the hole hardly feel the softening
+	let exact = energies(&sim.s, &Params::default());
+	assert!((sim.energies()[2] - exact[2]).abs() < 1e-9);
+}
+
+// The tight orbit has a period of 0.07; sub-cycling resolves it at dt = 0.01
+#[test]
+fn subcycling() {
+	let de = |substeps: usize| {
+		let mut p = Params::default();
+		p.dt = 0.01;
+		let mut sim = Simulation::new(system(), p);
+		let mut central = Central::new(0);
+		central.substeps = substeps;
+		sim.set_central(central);
+		let e0 = sim.energies()[0];
+		for _ in 0..100 {
+			sim.step();
+		}
+		((sim.energies()[0] - e0)/e0).abs()
+	};
+	let (plain, sub) = (de(1), de(50));
+	assert!(sub < 1e-4, "dE = {}", sub);
+	assert!(sub < 0.01*plain, "dE = {} vs {}", sub, plain);
+}
+
+#[test]
+fn pick() {
+	let s = system();
+	assert_eq!(Central::pick("heaviest", &s).unwrap().index, 0);
+	assert_eq!(Central::pick("2", &s).unwrap(), Central::new(2));
+	assert!(Central::pick("4", &s).is_err());
+	assert!(Central::pick("bh", &s).is_err());
+}
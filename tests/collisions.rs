@@ -0,0 +1,56 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::collisions::{Collisions, Mode, Outcome};
+use nbabel::columns::Columns;
+
+static PAIR: &'static str = "# columns: id m x y z vx vy vz radius\n0 1 -1 0 0 2 0.1 0 0.1\n1 3 1 0 0 -1 0 0 0.2\n2 1 0 5 0 0 0 0 0.1\n";
+
+#[test]
+fn head_on_merger() {
+	let s: Vec<Star> = read_stars(PAIR);
+	let columns = Columns::read(PAIR).unwrap();
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	let mut sim = Simulation::new(s, p);
+	sim.columns = columns;
+	sim.collisions = Some(Collisions::new(Mode::Merge, 1.0, 3, &sim.columns, 0.0));
+	let momentum = |s: &Vec<Star>| -> Vec<f64> { (0..3).map(|k| s.iter().map(|x| x.m*x.v[k]).sum()).collect() };
+	let p0 = momentum(&sim.s);
+	while sim.s.len() == 3 && sim.t < 2.0 {
+		sim.step();
+	}
+	assert_eq!(sim.s.len(), 2);
+	assert_eq!(sim.s[0].m, 4.0);
+	assert!(sim.events.iter().any(|x| x.1.contains("merged")));
+	let p1 = momentum(&sim.s);
+	assert!((0..3).all(|k| (p1[k] - p0[k]).abs() < 1e-12));
+	let radius = (0.1f64.powi(3) + 0.2f64.powi(3)).cbrt();
+	assert!((sim.columns.value(0, "radius").unwrap() - radius).abs() < 1e-12);
+	assert_eq!(sim.columns.value(1, "radius"), Some(0.1));
+	assert_eq!(sim.columns.rows.len(), 2);
+}
+
+#[test]
+fn bounces() {
+	let text = "0 1 -0.05 0 0 1 0 0\n1 1 0.05 0 0 -1 0 0\n";
+	for &(restitution, speed) in &[(1.0, 1.0), (0.5, 0.5)] {
+		let mut s: Vec<Star> = read_stars(text);
+		let mut c = Collisions::new(Mode::Bounce, restitution, 2, &Columns::default(), 0.1);
+		let done = c.resolve(&mut s, &mut Columns::default());
+		assert_eq!(done[0].0, Outcome::Bounced(0, 1));
+		assert!((s[0].v[0] + speed).abs() < 1e-15 && (s[1].v[0] - speed).abs() < 1e-15);
+		// Moving apart now, so no second bounce
+		assert!(c.resolve(&mut s, &mut Columns::default()).is_empty());
+	}
+}
+
+#[test]
+fn modes() {
+	assert_eq!(Mode::parse("merge"), Some(Mode::Merge));
+	assert_eq!(Mode::parse("off"), Some(Mode::Off));
+	assert_eq!(Mode::parse("stick"), None);
+	let mut s: Vec<Star> = read_stars(PAIR);
+	let mut c = Collisions::new(Mode::Off, 1.0, 3, &Columns::default(), 10.0);
+	assert!(c.resolve(&mut s, &mut Columns::default()).is_empty());
+}
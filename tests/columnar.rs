@@ -0,0 +1,73 @@
+#![cfg(feature = "parquet")]
+extern crate nbabel;
+extern crate parquet;
+
+use std::fs::File;
+
+use nbabel::*;
+use nbabel::columnar::Parquet;
+use nbabel::columns::Columns;
+use nbabel::diagnostics::{History, FRACTIONS};
+use nbabel::metadata::Metadata;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::Field;
+
+#[test]
+fn snapshots_and_diagnostics_read_back() {
+	let dir = std::env::temp_dir().join(format!("nbabel-parquet-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let text = "# columns: id m x y z vx vy vz age\n0 0.5 -0.5 0 0 0 -0.5 0 3\n1 0.5 0.5 0 0 0 0.5 0 7\n";
+	let columns = Columns::read(text).unwrap();
+	let meta = Metadata::new(text.as_bytes(), &[], &[]);
+	let mut sim = Simulation::new(read_stars::<f64>(text), Params::default());
+	let mut history = History::new(&FRACTIONS, None).unwrap();
+	let mut out = Parquet::new(&dir, &columns, Some(&meta), false).unwrap();
+	let mut x = 0.0;
+	for _ in 0..3 {
+		x = sim.s[1].r[0];
+		let e = sim.energies();
+		history.record(sim.t, &e, &e, &sim.s).unwrap();
+		out.write(sim.t, sim.steps, &sim.s, &sim.p, &columns).unwrap();
+		out.write(sim.t, sim.steps, &sim.s, &sim.p, &columns).unwrap();
+		sim.step();
+	}
+	out.finish(Some(&history)).unwrap();
+
+	let reader = SerializedFileReader::new(File::open(dir.join("snapshots.parquet")).unwrap()).unwrap();
+	let file = reader.metadata().file_metadata();
+	assert_eq!((file.num_rows(), reader.num_row_groups()), (6, 3));
+	let sha = file.key_value_metadata().unwrap().iter().find(|x| x.key == "input_sha256").and_then(|x| x.value.clone());
+	assert_eq!(sha, Some(meta.input_sha256.clone()));
+	let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|x| x.unwrap()).collect();
+	let names: Vec<&String> = rows[0].get_column_iter().map(|x| x.0).collect();
+	assert_eq!(names, vec!["t", "step", "id", "m", "x", "y", "z", "vx", "vy", "vz", "age"]);
+	let last: Vec<Field> = rows[5].get_column_iter().map(|x| x.1.clone()).collect();
+	assert_eq!(last[1], Field::ULong(2));
+	assert_eq!(last[2], Field::ULong(1));
+	assert_eq!(last[4], Field::Double(x));
+	assert_eq!(last[10], Field::Double(7.0));
+
+	let reader = SerializedFileReader::new(File::open(dir.join("diagnostics.parquet")).unwrap()).unwrap();
+	assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+	assert_eq!(reader.metadata().file_metadata().schema_descr().num_columns(), 12 + FRACTIONS.len());
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn accelerations_and_jerks() {
+	let dir = std::env::temp_dir().join(format!("nbabel-parquet-jerk-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let sim = Simulation::new(read_stars::<f64>("0 0.5 -0.5 0 0 0 -0.5 0\n1 0.5 0.5 0 0 0 0.5 0\n"), Params::default());
+	let mut out = Parquet::new(&dir, &Columns::default(), None, true).unwrap();
+	out.write(sim.t, sim.steps, &sim.s, &sim.p, &Columns::default()).unwrap();
+	out.finish(None).unwrap();
+	let reader = SerializedFileReader::new(File::open(dir.join("snapshots.parquet")).unwrap()).unwrap();
+	let row = reader.get_row_iter(None).unwrap().next().unwrap().unwrap();
+	let names: Vec<&String> = row.get_column_iter().map(|x| x.0).collect();
+	assert_eq!(names, vec!["t", "step", "id", "m", "x", "y", "z", "vx", "vy", "vz", "ax", "ay", "az", "jx", "jy", "jz"]);
+	let values: Vec<Field> = row.get_column_iter().map(|x| x.1.clone()).collect();
+	// Pulled towards the other star at 0.5/1^2, the pull turning with the relative velocity 1
+	assert_eq!(values[10], Field::Double(0.5));
+	assert_eq!(values[14], Field::Double(0.5));
+	std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,75 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::checkpoint;
+use nbabel::columns::Columns;
+
+static INPUT: &'static str = "# columns: id m x y z vx vy vz feh age
+0 0.5 1 0 0 0 0.5 0 -0.25 12
+1 0.5 -1 0 0 0 -0.5 0 0.1 7
+";
+
+// Extra columns survive a run and a checkpoint untouched
+#[test]
+fn extra_columns_round_trip() {
+	let columns = Columns::read(INPUT).unwrap();
+	assert_eq!(columns.names, vec!["feh", "age"]);
+	assert_eq!(columns.value(1, "age"), Some(7.0));
+
+	let mut sim = Simulation::new(read_stars(INPUT), Params::default());
+	sim.columns = columns.clone();
+	for _ in 0..10 {
+		sim.step();
+	}
+	let path = std::env::temp_dir().join(format!("nbabel-columns-{}.txt", std::process::id()));
+	checkpoint::write(&path, &sim).unwrap();
+	let text = std::fs::read_to_string(&path).unwrap();
+	std::fs::remove_file(&path).unwrap();
+
+	assert_eq!(Columns::read(&text).unwrap(), columns);
+	let (s, t, steps) = checkpoint::read::<f64>(&text).unwrap();
+	assert_eq!((s.len(), steps), (2, 10));
+	assert!(t > 0.0 && s[0].r[0] == sim.s[0].r[0]);
+}
+
+#[test]
+fn unnamed_and_bad_columns() {
+	let columns = Columns::read("0 1 0 0 0 0 0 0 3\n").unwrap();
+	assert_eq!(columns.names, vec!["col8"]);
+	assert!(Columns::read("0 1 0 0 0 0 0 0 old\n").is_err());
+	assert!(Columns::read("0 1 0 0 0 0 0 0 1\n1 1 1 0 0 0 0 0\n").is_err());
+	assert!(Columns::read("0 1 0 0 0 0 0 0\n").unwrap().is_empty());
+}
+
+// Ids stay with their stars through a merge, and a species column may hold words
+#[test]
+fn ids_and_species() {
+	let text = "# columns: id m x y z vx vy vz species\n\
+		40 1 -1 0 0 0 0 0 dm\n\
+		-1 1 0 0 0 0 0 0 disk\n\
+		17 1 0.001 0 0 -1 0 0 disk\n";
+	let columns = Columns::read(text).unwrap();
+	assert_eq!(columns.ids, vec![40, 1, 17]);
+	assert_eq!((columns.species(0), columns.species(2)), (Some("dm"), Some("disk")));
+	assert!(Columns::read("0 1 0 0 0 0 0 0 dm\n").is_err());
+
+	let mut p = Params::default();
+	p.dt = 1e-4;
+	p.eps = 0.1;
+	let mut sim = Simulation::new(read_stars(text), p);
+	sim.columns = columns;
+	sim.collisions = Some(collisions::Collisions::new(collisions::Mode::Merge, 1.0, 3, &sim.columns, 0.01));
+	sim.step();
+	assert_eq!(sim.s.len(), 2);
+	let mut out = vec![];
+	write_stars_with(&mut out, &sim.s, &sim.columns).unwrap();
+	let out = String::from_utf8(out).unwrap();
+	let ids: Vec<&str> = out.lines().skip(1).map(|x| x.split_whitespace().next().unwrap()).collect();
+	assert_eq!(ids, vec!["40", "1"]);
+	assert!(out.lines().last().unwrap().ends_with(" disk"));
+
+	// Putting two copies together moves the second one's ids up
+	let mut twice = Columns::read(text).unwrap();
+	twice.extend(Columns::read(text).unwrap(), 3, 3);
+	assert_eq!(twice.ids, vec![40, 1, 17, 81, 42, 58]);
+}
@@ -0,0 +1,46 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::compose::{self, Part};
+use nbabel::generate;
+use nbabel::rng::Rng;
+use nbabel::transform;
+
+fn temp_file(name: &str, s: &Vec<Star>) -> String {
+	let path = std::env::temp_dir().join(format!("nbabel-compose-{}-{}.txt", std::process::id(), name));
+	write_stars(&mut std::fs::File::create(&path).unwrap(), s).unwrap();
+	path.to_str().unwrap().to_string()
+}
+
+// Two clusters keep their internal energies and end up where they were sent
+#[test]
+fn two_cluster_collision() {
+	let cluster = generate::king(200, 5.0, &mut Rng::new(2));
+	let a = temp_file("a", &cluster);
+	let b = temp_file("b", &cluster);
+	let parts = vec![
+		Part { path: a.clone(), rotation: [0.0; 3], shift: [-4.0, 0.0, 0.0], velocity: [0.25, 0.0, 0.0] },
+		Part { path: b.clone(), rotation: [30.0, 60.0, 90.0], shift: [4.0, 1.0, 0.0], velocity: [-0.25, 0.0, 0.0] },
+	];
+	let result = compose::compose(&parts);
+	std::fs::remove_file(&a).unwrap();
+	std::fs::remove_file(&b).unwrap();
+	let (s, columns) = result.unwrap();
+	assert_eq!(s.len(), 400);
+	assert!(columns.is_empty());
+
+	let p = Params::default();
+	let e0 = energies(&cluster, &p);
+	for (k, part) in parts.iter().enumerate() {
+		let mut half: Vec<Star> = s[200*k..200*(k + 1)].iter()
+			.map(|x| Star { m: x.m, r: x.r.clone(), v: x.v.clone(), a: vec![0.0; 3], a0: vec![0.0; 3] }).collect();
+		let com = diagnostics::center_of_mass(&half);
+		for i in 0..3 {
+			assert!((com[i] - part.shift[i]).abs() < 1e-9, "part {} centre {:?}", k, com);
+		}
+		let v = part.velocity;
+		transform::boost(&mut half, [-v[0], -v[1], -v[2]]);
+		let e = energies(&half, &p);
+		assert!((e[0] - e0[0]).abs() < 1e-9, "part {}: E = {} instead of {}", k, e[0], e0[0]);
+	}
+}
@@ -0,0 +1,165 @@
+extern crate nbabel;
+
+use nbabel::convert::*;
+
+static INPUT: &'static str = "# nbabel checkpoint t = 2.5e-1 steps = 250
+# columns: id m x y z vx vy vz feh age
+-1 0.5 1.5 -2 0.25 0.1 -0.2 0.3 -0.5 12
+7 0.25 -1 0.5 3 0 0.125 -1 0.02 1e3
+";
+
+#[test]
+fn text_csv_and_json_keep_everything() {
+	let x = read_text(INPUT).unwrap();
+	assert_eq!((x.t, x.steps), (0.25, 250));
+	assert_eq!(x.ids, vec!["-1", "7"]);
+	assert_eq!(x.columns.names, vec!["feh", "age"]);
+
+	let mut text = vec![];
+	write_csv(&mut text, &x).unwrap();
+	let y = read_csv(&String::from_utf8(text).unwrap()).unwrap();
+	let mut text = vec![];
+	write_json(&mut text, &y).unwrap();
+	let z = read_json(&String::from_utf8(text).unwrap()).unwrap();
+	let mut text = vec![];
+	write_text(&mut text, &z).unwrap();
+	let w = read_text(&String::from_utf8(text).unwrap()).unwrap();
+	for y in [y, z, w].iter() {
+		assert_eq!((y.t, y.steps), (0.25, 250));
+		assert_eq!(y.ids, x.ids);
+		assert_eq!(y.columns, x.columns);
+		for (a, b) in x.s.iter().zip(y.s.iter()) {
+			assert_eq!((a.m, &a.r, &a.v), (b.m, &b.r, &b.v));
+		}
+	}
+}
+
+#[test]
+fn binary_formats_round_trip_in_single_precision() {
+	let mut x = read_text(INPUT).unwrap();
+	x.ids = vec![String::from("3"), String::from("9")];
+	let gadget = read_gadget(&write_gadget(&x)).unwrap();
+	assert_eq!(gadget.ids, x.ids);
+	let tipsy = read_tipsy(&write_tipsy(&x)).unwrap();
+	assert_eq!(tipsy.ids, vec!["0", "1"]);
+	for y in [gadget, tipsy].iter() {
+		assert_eq!(y.t, 0.25);
+		assert!(y.columns.is_empty());
+		for (a, b) in x.s.iter().zip(y.s.iter()) {
+			assert_eq!(a.m, b.m);
+			for k in 0..3 {
+				assert!((a.r[k] - b.r[k]).abs() < 1e-6 && (a.v[k] - b.v[k]).abs() < 1e-6);
+			}
+		}
+	}
+	assert!(read_gadget(&write_gadget(&x)[..100]).is_err());
+}
+
+#[test]
+fn formats_by_name_and_extension() {
+	assert_eq!(Format::parse("tipsy"), Some(Format::Tipsy));
+	assert_eq!(Format::parse("fits"), None);
+	assert_eq!(Format::of_path("run/out.h5"), Format::Hdf5);
+	assert_eq!(Format::of_path("snap.std"), Format::Tipsy);
+	assert_eq!(Format::of_path("input2k"), Format::Text);
+	assert_eq!(Format::of_path("dat.10"), Format::Nbody);
+	assert_eq!(Format::of_path("cluster.dyn"), Format::Starlab);
+	assert!(read_json("{\"stars\": [{\"m\": 1}]}").is_err());
+}
+
+// A star and a binary whose members sit relative to it, as kira writes them
+static STARLAB: &'static str = "(Particle
+  N = 3
+(Log
+  ===>  makeplummer -n 3
+)Log
+(Dynamics
+  system_time  =  0.5
+  m  =  1
+  r  =  0 0 0
+  v  =  0 0 0
+)Dynamics
+(Particle
+  i = 1
+  N = 1
+(Dynamics
+  m  =  0.5
+  r  =  -1 0 0
+  v  =  0 -0.5 0
+)Dynamics
+)Particle
+(Particle
+  name = (2,3)
+  N = 2
+(Dynamics
+  m  =  0.5
+  r  =  1 0 0
+  v  =  0 0.5 0
+)Dynamics
+(Particle
+  i = 2
+(Dynamics
+  m = 0.25
+  r = 0 1D-2 0
+  v = 0.1 0 0
+)Dynamics
+)Particle
+(Particle
+  i = 3
+  name = 7
+(Dynamics
+  m = 0.25
+  r = 0 -1D-2 0
+  v = -0.1 0 0
+)Dynamics
+)Particle
+)Particle
+)Particle
+";
+
+#[test]
+fn starlab_trees() {
+	assert_eq!(detect(STARLAB), Format::Starlab);
+	let x = read_starlab(STARLAB).unwrap();
+	assert_eq!(x.t, 0.5);
+	assert_eq!(x.ids, vec!["1", "2", "7"]);
+	assert_eq!(x.s.iter().map(|x| x.m).collect::<Vec<f64>>(), vec![0.5, 0.25, 0.25]);
+	assert_eq!((&x.s[1].r, &x.s[1].v), (&vec![1.0, 0.01, 0.0], &vec![0.1, 0.5, 0.0]));
+	assert_eq!((&x.s[2].r, &x.s[2].v), (&vec![1.0, -0.01, 0.0], &vec![-0.1, 0.5, 0.0]));
+
+	let mut text = vec![];
+	write_starlab(&mut text, &x).unwrap();
+	let y = read_starlab(&String::from_utf8(text).unwrap()).unwrap();
+	assert_eq!((y.t, &y.ids), (x.t, &x.ids));
+	for (a, b) in x.s.iter().zip(y.s.iter()) {
+		assert_eq!((a.m, &a.r, &a.v), (b.m, &b.r, &b.v));
+	}
+	assert!(read_starlab("(Particle\n(Dynamics\n  m = 1\n)Dynamics\n").is_err());
+	assert!(read_starlab("(Particle\n(Dynamics\n  r = 1 2\n)Dynamics\n)Particle\n").is_err());
+}
+
+#[test]
+fn nbody_input() {
+	let plain = "1.0 0 0 0 0 0 0\n 1.0D-3 1 0 0 0 1 0\n";
+	assert_eq!(detect(plain), Format::Nbody);
+	assert_eq!(detect(INPUT), Format::Text);
+	let x = read_nbody(plain).unwrap();
+	assert_eq!((x.t, &x.ids), (0.0, &vec![String::from("1"), String::from("2")]));
+	assert_eq!((x.s[1].m, &x.s[1].v), (1e-3, &vec![0.0, 1.0, 0.0]));
+
+	// The ACS tutorials put N and the time first
+	let tutorial = "2\n0.75\n1.0 0 0 0 0 0 0\n1.0e-3 1 0 0 0 1 0\n";
+	assert_eq!(detect(tutorial), Format::Nbody);
+	let y = read_nbody(tutorial).unwrap();
+	assert_eq!(y.t, 0.75);
+	assert_eq!(y.s[1].r, x.s[1].r);
+	assert!(read_nbody("3\n0\n1 0 0 0 0 0 0\n").is_err());
+	assert!(read_nbody("1 0 0 0 0 0\n").is_err());
+
+	let mut text = vec![];
+	write_nbody(&mut text, &x).unwrap();
+	let z = read_nbody(&String::from_utf8(text).unwrap()).unwrap();
+	for (a, b) in x.s.iter().zip(z.s.iter()) {
+		assert_eq!((a.m, &a.r, &a.v), (b.m, &b.r, &b.v));
+	}
+}
@@ -0,0 +1,94 @@
+extern crate nbabel;
+
+use std::f64::consts::PI;
+
+use nbabel::*;
+use nbabel::cosmology::Cosmology;
+use nbabel::ewald::Periodic;
+use nbabel::pm::Pm;
+
+fn einstein_de_sitter(h0: f64) -> Cosmology {
+	let mut x = Cosmology::parse("omega_m=1,omega_lambda=0").unwrap();
+	x.h0 = Some(h0);
+	x
+}
+
+// Matter alone expands as a = (1 + 3 H0 t/2)^(2/3), and the step integrals follow it
+#[test]
+fn expansion() {
+	let x = einstein_de_sitter(2.0);
+	assert!((x.scale_at(1.0) - 4f64.powf(2.0/3.0)).abs() < 1e-9);
+	let (a, kick, drift) = x.advance(1.0, 0.1);
+	let exact = |t: f64| 1.0 + 3.0*t;
+	assert!((a - exact(0.1).powf(2.0/3.0)).abs() < 1e-9);
+	assert!((kick - (exact(0.1).powf(1.0/3.0) - 1.0)).abs() < 1e-9);
+	assert!((drift + exact(0.1).powf(-1.0/3.0) - 1.0).abs() < 1e-8);
+	// A cosmological constant alone expands exponentially
+	let mut x = Cosmology::parse("omega_m=1e-12,omega_lambda=1,h0=0.5,z=1").unwrap();
+	assert_eq!(x.a, 0.5);
+	x.a = x.scale_at(2.0);
+	assert!((x.a - 0.5*1f64.exp()).abs() < 1e-6);
+	assert!((x.redshift() - (2.0/1f64.exp() - 1.0)).abs() < 1e-6);
+
+	assert!(Cosmology::parse("omega_m=0.3").is_err());
+	assert!(Cosmology::parse("omega_m=0.3,omega_lambda=0.7,w=1").is_err());
+	assert!(Cosmology::parse("omega_m=0,omega_lambda=0.7").is_err());
+	assert!((Cosmology::parse("omega_m=1,omega_lambda=0").unwrap().matching_h0(1.0, 1.0) - (8.0*PI/3.0).sqrt()).abs() < 1e-12);
+}
+
+// A lone star feels no force and its peculiar velocity decays as 1/a^2
+#[test]
+fn hubble_drag() {
+	let mut p = Params::default();
+	p.dt = 0.01;
+	let star = Star { m: 1.0, r: vec![0.0; 3], v: vec![0.1, 0.0, 0.0], a: vec![0.0; 3], a0: vec![0.0; 3] };
+	let mut sim = Simulation::with_solver(vec![star], p, Box::new(Periodic::new(1.0)));
+	sim.cosmology = Some(einstein_de_sitter(1.0));
+	for _ in 0..100 {
+		sim.step();
+	}
+	let a = sim.cosmology.unwrap().a;
+	assert!((a - 2.5f64.powf(2.0/3.0)).abs() < 1e-9);
+	assert!((sim.s[0].v[0]*a*a - 0.1).abs() < 1e-12);
+	// The distance covered is 0.1 times the integral of 1/a^2 from t = 0 to 1
+	assert!((sim.s[0].r[0] - 0.1*2.0*(1.0 - 2.5f64.powf(-1.0/3.0))).abs() < 1e-9);
+}
+
+// A small plane wave of the growing mode grows like a in a matter dominated box
+#[test]
+fn linear_growth() {
+	let (side, amplitude) = (8, 0.005);
+	let mut cosmology = Cosmology::parse("omega_m=1,omega_lambda=0").unwrap();
+	let h0 = cosmology.matching_h0(1.0, 1.0);
+	cosmology.h0 = Some(h0);
+	let mut s = vec![];
+	let mut wave = vec![];
+	for i in 0..side {
+		for j in 0..side {
+			for k in 0..side {
+				let q = [-0.5 + (i as f64 + 0.5)/side as f64, -0.5 + (j as f64 + 0.5)/side as f64, -0.5 + (k as f64 + 0.5)/side as f64];
+				// Zel'dovich: the displacement grows as a, so the velocity is H0 times it at a = 1
+				let psi = amplitude*(2.0*PI*q[0]).sin();
+				s.push(Star { m: 1.0/(side*side*side) as f64, r: vec![q[0] + psi, q[1], q[2]], v: vec![h0*psi, 0.0, 0.0], a: vec![0.0; 3], a0: vec![0.0; 3] });
+				wave.push((q[0], psi));
+			}
+		}
+	}
+	let mut p = Params::default();
+	p.dt = 0.005;
+	let mut sim = Simulation::with_solver(s, p, Box::new(Pm::new(1.0, 16, false)));
+	sim.cosmology = Some(cosmology);
+	for _ in 0..40 {
+		sim.step();
+	}
+	let a = sim.cosmology.unwrap().a;
+	assert!(a > 1.4);
+	let (mut num, mut den) = (0.0, 0.0);
+	for (star, &(q, psi)) in sim.s.iter().zip(wave.iter()) {
+		let d = ewald::nearest(&[star.r[0] - q, 0.0, 0.0], 1.0)[0];
+		num += d*psi;
+		den += psi*psi;
+	}
+	assert!((num/den/a - 1.0).abs() < 0.03, "growth {} at a = {}", num/den, a);
+	assert!(sim.s.iter().all(|x| x.v[1].abs() < 1e-10 && x.v[2].abs() < 1e-10));
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "sqlite")]
+extern crate nbabel;
+extern crate rusqlite;
+
+use nbabel::*;
+use nbabel::database::Database;
+use nbabel::diagnostics::{History, FRACTIONS};
+use nbabel::metadata::Metadata;
+
+#[test]
+fn runs_share_one_database() {
+	let path = std::env::temp_dir().join(format!("nbabel-runs-{}.db", std::process::id()));
+	let _ = std::fs::remove_file(&path);
+	let text = "0 0.5 -0.5 0 0 0 -0.5 0\n1 0.5 0.5 0 0 0 0.5 0\n";
+	for (run, steps) in [("a", 3), ("b", 5)].iter() {
+		let meta = Metadata::new(text.as_bytes(), &[], &[]);
+		let mut sim = Simulation::new(read_stars::<f64>(text), Params::default());
+		let mut history = History::new(&FRACTIONS, None).unwrap();
+		let mut db = Database::open(&path, run, &meta, 2).unwrap();
+		for _ in 0..*steps {
+			let e = sim.energies();
+			history.record(sim.t, &e, &e, &sim.s).unwrap();
+			db.samples(&history.samples, &history.fractions).unwrap();
+			db.timings(sim.steps, sim.t, 0.0, &sim.timers.run).unwrap();
+			sim.step();
+		}
+		db.finish(sim.steps, sim.t, "success", 1.0).unwrap();
+	}
+
+	let c = rusqlite::Connection::open(&path).unwrap();
+	let count = |sql: &str| -> i64 { c.query_row(sql, [], |x| x.get(0)).unwrap() };
+	assert_eq!(count("select count(*) from runs where outcome = 'success'"), 2);
+	assert_eq!(count("select count(*) from diagnostics where run_id = 'b'"), 5);
+	assert_eq!(count("select count(*) from lagrangian where run_id = 'a'"), 3*FRACTIONS.len() as i64);
+	assert_eq!(count("select count(*) from timings"), 8);
+	assert_eq!(count("select steps from runs where run_id = 'b'"), 5);
+	std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,49 @@
+extern crate nbabel;
+
+use nbabel::*;
+
+fn run() -> Vec<Star> {
+	let text: String = std::fs::read_to_string("input/input2k").unwrap().lines().take(256).collect::<Vec<_>>().join("\n");
+	let mut p = Params::default();
+	p.deterministic = true;
+	let mut sim = Simulation::new(read_stars(&text), p);
+	for _ in 0..20 {
+		sim.step();
+	}
+	sim.s
+}
+
+#[test]
+fn deterministic_runs_are_bitwise_identical() {
+	let first = run();
+	for _ in 0..3 {
+		let again = run();
+		for (x, y) in first.iter().zip(again.iter()) {
+			for i in 0..3 {
+				assert_eq!(x.r[i].to_bits(), y.r[i].to_bits());
+				assert_eq!(x.v[i].to_bits(), y.v[i].to_bits());
+			}
+		}
+	}
+}
+
+// The threaded energy sum agrees with the serial one and does not depend on scheduling
+#[test]
+fn parallel_energies() {
+	let text: String = std::fs::read_to_string("input/input2k").unwrap().lines().take(500).collect::<Vec<_>>().join("\n");
+	let s: Vec<Star> = read_stars(&text);
+	let mut p = Params::default();
+	p.threads = 1;
+	let serial = energies(&s, &p);
+	p.threads = 4;
+	let first = energies(&s, &p);
+	for k in 0..3 {
+		assert!((first[k] - serial[k]).abs() < 1e-12*serial[k].abs());
+	}
+	for _ in 0..5 {
+		let again = energies(&s, &p);
+		for k in 0..3 {
+			assert_eq!(first[k].to_bits(), again[k].to_bits());
+		}
+	}
+}
@@ -0,0 +1,104 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::rng::Rng;
+
+fn star(m: f64, r: Vec<f64>, v: Vec<f64>) -> Star {
+	let d = r.len();
+	Star { m: m, r: r, v: v, a: vec![0.0; d], a0: vec![0.0; d] }
+}
+
+// In the plane the 2D kernels give the 3D results to the last bit, on any thread count
+#[test]
+fn planar_matches_3d() {
+	let mut s = generate::uniform_disc(100, 0.5, &mut Rng::new(2));
+	for &threads in &[1, 3] {
+		for &mixed in &[false, true] {
+			let mut p = Params::default();
+			p.eps = 0.01;
+			p.threads = threads;
+			p.deterministic = true;
+			p.mixed = mixed;
+			let mut flat = p.clone();
+			flat.dim = 2;
+			let mut x = s.clone();
+			acceleration(&mut s, &p);
+			acceleration(&mut x, &flat);
+			assert!(s.iter().zip(x.iter()).all(|(a, b)| a.a == b.a));
+			assert_eq!(energies(&s, &p), energies(&x, &flat));
+		}
+	}
+}
+
+// Two stars on a circular orbit in the x-w plane of 4D space stay on it
+#[test]
+fn four_dimensions() {
+	let mut p = Params::default();
+	p.dim = 4;
+	p.dt = 1e-3;
+	// Separation 1, total mass 1: each star circles at radius 1/2 with speed 1/2
+	let s = vec![star(0.5, vec![0.0, 0.0, 0.0, 0.5], vec![0.5, 0.0, 0.0, 0.0]), star(0.5, vec![0.0, 0.0, 0.0, -0.5], vec![-0.5, 0.0, 0.0, 0.0])];
+	let mut sim = Simulation::new(s, p);
+	assert_eq!(sim.s[0].a, vec![0.0, 0.0, 0.0, -0.5]);
+	let e0 = sim.energies();
+	assert!((e0[0] + 0.125).abs() < 1e-15);
+	// Half an orbit, period 2 pi
+	for _ in 0..3142 {
+		sim.step();
+	}
+	assert!(sim.s[0].r[3] < -0.4999 && sim.s[0].r[1].abs() + sim.s[0].r[2].abs() == 0.0);
+	let e = sim.energies();
+	assert!(((e[0] - e0[0])/e0[0]).abs() < 1e-6);
+}
+
+// The drift and kick leave components past dim alone
+#[test]
+fn integrator_components() {
+	let mut s = vec![star(1.0, vec![0.0; 3], vec![1.0, 1.0, 1.0])];
+	let mut p = Params::default();
+	p.dim = 2;
+	p.dt = 0.1;
+	update_positions(&mut s, &p);
+	assert_eq!(s[0].r, vec![0.1, 0.1, 0.0]);
+}
+
+// Stars with too few components or a 3D-only solver are an error, not a panic in a kernel
+#[test]
+fn checked_components() {
+	let text = "0 0.5 -0.5 0 0 0 -0.5 0\n1 0.5 0.5 0 0 0 0.5 0\n";
+	let mut p = Params::default();
+	p.dim = 4;
+	assert!(Simulation::try_new(read_stars::<f64>(text), p.clone()).is_err());
+	let s = vec![star(0.5, vec![0.0, 0.0, 0.0, 0.5], vec![0.0; 4]), star(0.5, vec![0.0, 0.0, 0.0, -0.5], vec![0.0; 4])];
+	assert!(Simulation::try_new(s.clone(), p.clone()).is_ok());
+	assert!(Simulation::try_with_solver(s.clone(), p.clone(), solver::by_name("tree", 0.5).unwrap()).is_err());
+	p.dim = 5;
+	assert!(Simulation::try_new(s, p.clone()).is_err());
+	p.dim = 2;
+	assert!(Simulation::try_new(read_stars::<f64>(text), p).is_ok());
+}
+
+// Fixed stars and the incremental energy see the fourth component
+#[test]
+fn four_dimensional_tracking() {
+	let mut p = Params::default();
+	p.dim = 4;
+	p.dt = 1e-3;
+	let s = vec![star(0.5, vec![0.5, 0.0, 0.0, 0.0], vec![0.0, 0.0, 0.0, 0.5]), star(0.5, vec![-0.5, 0.0, 0.0, 0.0], vec![0.0, 0.0, 0.0, -0.5]), star(1e-3, vec![0.0, 2.0, 0.0, 0.0], vec![0.0, 0.0, 0.0, 0.3])];
+	let mut sim = Simulation::new(s.clone(), p.clone());
+	sim.track_energy_every(1000);
+	for _ in 0..100 {
+		sim.step();
+	}
+	let (e, tracked) = (sim.energies(), sim.tracked_energies());
+	assert_eq!(e[1], tracked[1]);
+	assert!(((e[0] - tracked[0])/e[0]).abs() < 1e-9);
+
+	let mut sim = Simulation::new(s, p);
+	sim.set_fixed(fixed::Fixed { stars: vec![2] });
+	for _ in 0..100 {
+		sim.step();
+	}
+	assert_eq!(sim.s[2].r, vec![0.0, 2.0, 0.0, 0.0]);
+	assert!(sim.s[2].v.iter().all(|&x| x == 0.0));
+}
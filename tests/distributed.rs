@@ -0,0 +1,35 @@
+#![cfg(feature = "mpi")]
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::distributed::{finalize, init, size, Direct};
+use nbabel::solver::ForceSolver;
+
+fn stars(n: usize) -> Vec<Star> {
+	read_stars(&std::fs::read_to_string("input/input2k").unwrap().lines().take(n).collect::<Vec<_>>().join("\n"))
+}
+
+// MPI can only be started once per process, so this is the only test; run it under mpirun with any number of ranks
+#[test]
+fn matches_local_direct() {
+	init().unwrap();
+	let mut p = Params::default();
+	p.threads = 2;
+	let mut local = stars(500);
+	acceleration(&mut local, &p);
+	let mut s = stars(500);
+	let busy = Direct.accelerations(&mut s, &p);
+	assert_eq!(busy.len(), 1);
+	for (x, y) in local.iter().zip(s.iter()) {
+		for k in 0..3 {
+			assert!((x.a[k] - y.a[k]).abs() <= 1e-12*x.a[k].abs().max(1.0), "{} ranks: {} vs {}", size(), x.a[k], y.a[k]);
+		}
+	}
+
+	let expected = energies(&s, &p);
+	let e = Direct.energies(&s, &p).unwrap();
+	for k in 0..3 {
+		assert!((e[k] - expected[k]).abs() < 1e-12*expected[k].abs());
+	}
+	finalize();
+}
@@ -0,0 +1,57 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::drag::{self, Drag};
+
+// A lone star under linear drag slows down as exp(-k t) and stops at x = v0/k
+#[test]
+fn linear_decay() {
+	let s: Vec<Star> = read_stars("0 1 0 0 0 2 0 0\n");
+	let mut p = Params::default();
+	p.dt = 0.01;
+	let mut sim = Simulation::new(s, p);
+	sim.drag = Some(Drag::Linear(0.5));
+	for _ in 0..1000 {
+		sim.step();
+	}
+	let expected = 2.0*(-0.5*sim.t).exp();
+	assert!(((sim.s[0].v[0] - expected)/expected).abs() < 1e-9, "v = {}", sim.s[0].v[0]);
+	assert!((sim.s[0].r[0] - 4.0*(1.0 - (-0.5*sim.t).exp())).abs() < 1e-4, "x = {}", sim.s[0].r[0]);
+}
+
+#[test]
+fn error_function() {
+	assert!(drag::erf(0.0).abs() < 2e-7);
+	assert!((drag::erf(0.5) - 0.5204998778).abs() < 2e-7);
+	assert!((drag::erf(-1.0) + 0.8427007929).abs() < 2e-7);
+	assert!((drag::erf(3.0) - 0.9999779095).abs() < 2e-7);
+}
+
+// Friction points against the motion, is stronger for heavier stars and fades at high speed
+#[test]
+fn friction() {
+	let x = Drag::friction("rho=1,sigma=1").unwrap();
+	assert_eq!(x, Drag::Friction { rho: 1.0, sigma: 1.0, ln_lambda: 3.0 });
+	let a = x.acceleration(1.0, [0.0, 1.0, 0.0]);
+	assert!(a[0] == 0.0 && a[1] < 0.0 && a[2] == 0.0);
+	assert!(x.acceleration(2.0, [0.0, 1.0, 0.0])[1] < 2.0*a[1]*0.999);
+	assert!(x.acceleration(1.0, [0.0, 10.0, 0.0])[1] > a[1]);
+	assert_eq!(x.acceleration(1.0, [0.0; 3]), [0.0; 3]);
+
+	let s: Vec<Star> = read_stars("0 0.1 0 0 0 1 0 0\n");
+	let mut sim = Simulation::new(s, Params::default());
+	sim.drag = Some(x);
+	for _ in 0..100 {
+		sim.step();
+	}
+	assert!(sim.s[0].v[0] > 0.0 && sim.s[0].v[0] < 1.0);
+}
+
+#[test]
+fn bad_specs() {
+	assert!(Drag::friction("rho=1").is_err());
+	assert!(Drag::friction("rho=1,sigma=0").is_err());
+	assert!(Drag::friction("rho=1,sigma=1,mu=2").is_err());
+	assert!(Drag::friction("rho=x,sigma=1").is_err());
+	assert_eq!(Drag::friction("sigma=2,rho=1,lnL=5").unwrap(), Drag::Friction { rho: 1.0, sigma: 2.0, ln_lambda: 5.0 });
+}
@@ -0,0 +1,49 @@
+extern crate nbabel;
+
+use std::io;
+
+use nbabel::*;
+use nbabel::checkpoint;
+use nbabel::status::{self, Outcome};
+
+fn parse_error(text: &str) -> (usize, usize, String) {
+	match parse_stars::<f64>(text) {
+		Err(NBodyError::Parse { line, column, message }) => (line, column, message),
+		x => panic!("expected a parse error, got {:?}", x.map(|s| s.len())),
+	}
+}
+
+// Lines and columns count from 1, comments and blank lines included
+#[test]
+fn where_parsing_failed() {
+	assert_eq!(parse_error("# stars\n\n0 1 0 0 x 0 0 0\n"), (3, 9, String::from("'x' is not a number")));
+	assert_eq!(parse_error("0 1 0 0 0 0 0 0\n1  -2 0 0 0 0 0 0\n").0, 2);
+	assert_eq!(parse_error("0 1 0 0 0 0 0 0\n1  -2 0 0 0 0 0 0\n").1, 4);
+	assert_eq!(parse_error("0 1 0 0 inf 0 0 0\n").1, 9);
+	assert_eq!(parse_error("0 1 0 0 0\n"), (1, 10, String::from("expected 8 columns (id m x y z vx vy vz), found 5")));
+	let error = parse_stars::<f64>("0 1 0 0 x 0 0 0\n").err().unwrap();
+	assert_eq!(error.to_string(), "line 1, column 9: 'x' is not a number");
+	assert_eq!(error.exit_code(), status::EXIT_INPUT);
+}
+
+#[test]
+fn checkpoint_header() {
+	match checkpoint::read::<f64>("# nbabel checkpoint t = x steps = 3\n0 1 0 0 0 0 0 0\n") {
+		Err(NBodyError::Parse { line: 1, column: 25, .. }) => {},
+		x => panic!("{:?}", x.map(|x| x.1)),
+	}
+}
+
+#[test]
+fn outcomes() {
+	let io = NBodyError::io("Could not write diagnostics", io::Error::new(io::ErrorKind::Other, "disk full"));
+	assert_eq!(io.to_string(), "Could not write diagnostics: disk full");
+	assert_eq!(io.exit_code(), status::EXIT_IO);
+	let outcome = Outcome::from(io);
+	assert_eq!((outcome.name(), outcome.exit_code()), ("io_error", status::EXIT_IO));
+	let outcome = Outcome::from(NBodyError::Config(String::from("--binaries needs --out")));
+	assert_eq!((outcome.name(), outcome.reason()), ("config_error", "--binaries needs --out"));
+	assert_eq!(Outcome::from(NBodyError::Solver(String::from("tree overflow"))).exit_code(), status::EXIT_NUMERICAL);
+	let msg: String = NBodyError::parse(2, 3, String::from("bad")).into();
+	assert_eq!(msg, "line 2, column 3: bad");
+}
@@ -0,0 +1,70 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::ewald::{ewald, Ewald, Periodic};
+use nbabel::rng::Rng;
+
+// The table reproduces the lattice constants: the Madelung energy and the vanishing pull half a box away
+#[test]
+fn lattice_symmetry() {
+	let x = Ewald::new(2.0);
+	assert!((x.potential_correction([0.0; 3]) - 2.837297/2.0).abs() < 1e-5);
+	// At the face centre the nearest copy's pull -d/|d|^3 is cancelled exactly
+	let a = x.acceleration_correction([1.0, 0.0, 0.0]);
+	assert!((a[0] - 1.0).abs() < 1e-9 && a[1].abs() < 1e-12 && a[2].abs() < 1e-12);
+	assert_eq!(x.acceleration_correction([-1.0, 0.0, 0.0])[0], -a[0]);
+	let (_, corner) = ewald([1.0, 1.0, 1.0], 2.0);
+	assert!(corner.iter().all(|c| c.abs() < 1e-12));
+}
+
+// Between grid points the interpolation stays close to the Ewald sums
+#[test]
+fn interpolation() {
+	let x = Ewald::new(1.0);
+	let mut rng = Rng::new(3);
+	for _ in 0..200 {
+		let d = [rng.uniform() - 0.5, rng.uniform() - 0.5, rng.uniform() - 0.5];
+		let r = (d[0]*d[0] + d[1]*d[1] + d[2]*d[2]).sqrt();
+		let (phi, a) = ewald(d, 1.0);
+		let ac = x.acceleration_correction(d);
+		let error: f64 = (0..3).map(|k| (ac[k] - d[k]/r.powi(3) - a[k]).powi(2)).sum();
+		assert!(error.sqrt()*r*r < 1e-3, "{:?}", d);
+		assert!((x.potential_correction(d) - 1.0/r - phi).abs() < 1e-3);
+	}
+}
+
+// Stars pull each other across the boundary, leave the box on one side and come back on the other
+#[test]
+fn across_the_boundary() {
+	let star = |x: f64, v: f64| Star { m: 0.5, r: vec![x, 0.0, 0.0], v: vec![v, 0.0, 0.0], a: vec![0.0; 3], a0: vec![0.0; 3] };
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	let sim = Simulation::with_solver(vec![star(0.45, 0.0), star(-0.45, 0.0)], p.clone(), Box::new(Periodic::new(1.0)));
+	assert!(sim.s[0].a[0] > 40.0 && sim.s[1].a[0] < -40.0);
+	assert!((sim.s[0].a[0] + sim.s[1].a[0]).abs() < 1e-12);
+
+	let mut sim = Simulation::with_solver(vec![star(0.499, 1.0), star(0.0, 0.0)], p, Box::new(Periodic::new(1.0)));
+	for _ in 0..10 {
+		sim.step();
+	}
+	assert!(sim.s[0].r[0] < -0.45);
+}
+
+// A cold uniform cube clumps up while the periodic energy is conserved
+#[test]
+fn energy_conservation() {
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	p.eps = 0.02;
+	let mut sim = Simulation::with_solver(generate::cube(64, 1.0, &mut Rng::new(1)), p, Box::new(Periodic::new(1.0)));
+	let e0 = sim.energies();
+	for _ in 0..300 {
+		sim.step();
+	}
+	let e = sim.energies();
+	assert!(e[1] > 1e-3);
+	assert!((e[0] - e0[0]).abs() < 1e-3*e[1]);
+	assert!(sim.s.iter().all(|x| x.r.iter().all(|&r| r >= -0.5 && r < 0.5)));
+	let momentum: f64 = (0..3).map(|k| sim.s.iter().map(|x| x.m*x.v[k]).sum::<f64>().powi(2)).sum();
+	assert!(momentum.sqrt() < 1e-10);
+}
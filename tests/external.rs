@@ -0,0 +1,51 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::external;
+
+// Every acceleration is minus the gradient of its potential
+#[test]
+fn accelerations_match_potentials() {
+	for spec in &["point:m=2", "harmonic:omega=0.7", "log:v0=1.5,rc=0.3,q=0.8", "nfw:rs=2,rho0=0.05"] {
+		let x = external::parse(spec).unwrap();
+		let r = [0.4, -1.1, 0.9];
+		let a = x.acceleration(r);
+		for k in 0..3 {
+			let h = 1e-6;
+			let (mut lo, mut hi) = (r, r);
+			lo[k] -= h;
+			hi[k] += h;
+			let gradient = (x.potential(hi) - x.potential(lo))/(2.0*h);
+			assert!((a[k] + gradient).abs() < 1e-7, "{}: {} vs {}", spec, a[k], -gradient);
+		}
+	}
+	let nfw = external::parse("nfw:rs=1,rho0=1").unwrap();
+	assert!(nfw.potential([0.0; 3]).is_finite() && nfw.acceleration([0.0; 3]) == [0.0; 3]);
+}
+
+#[test]
+fn bad_specs() {
+	assert!(external::parse("plummer:a=1").is_err());
+	assert!(external::parse("nfw:rs=10").is_err());
+	assert!(external::parse("nfw:rs=10,rho0=1,c=3").is_err());
+	assert!(external::parse("harmonic:omega=-1").is_err());
+	assert!(external::parse("log:v0=1,rc=x").is_err());
+}
+
+// A test star on a circular orbit in a logarithmic halo keeps its radius and energy
+#[test]
+fn circular_orbit_in_halo() {
+	let v0 = 1.0;
+	let s: Vec<Star> = read_stars(&format!("0 1e-12 5 0 0 0 {} 0\n", v0*5.0/(0.01f64 + 25.0).sqrt()));
+	let mut p = Params::default();
+	p.dt = 0.01;
+	let mut sim = Simulation::new(s, p);
+	sim.set_external(vec![external::parse("log:v0=1,rc=0.1").unwrap()]);
+	let e0 = sim.energies();
+	for _ in 0..3000 {
+		sim.step();
+	}
+	let r = (sim.s[0].r[0].powi(2) + sim.s[0].r[1].powi(2)).sqrt();
+	assert!((r - 5.0).abs() < 1e-3, "r = {}", r);
+	assert!(((sim.energies()[0] - e0[0])/e0[0]).abs() < 1e-6);
+}
@@ -0,0 +1,83 @@
+extern crate nbabel;
+
+use std::ptr;
+
+use nbabel::ffi::*;
+
+// Two equal stars on a circular orbit, driven only through the C interface
+#[test]
+fn circular_binary() {
+	let m = [0.5, 0.5];
+	let r = [-0.5, 0.0, 0.0, 0.5, 0.0, 0.0];
+	let v = [0.0, -0.5, 0.0, 0.0, 0.5, 0.0];
+	unsafe {
+		let sim = nbabel_create(2, m.as_ptr(), r.as_ptr(), v.as_ptr(), 1e-3, 0.0);
+		assert!(!sim.is_null());
+		assert_eq!(nbabel_count(sim), 2);
+		let mut e0 = [0.0; 3];
+		assert_eq!(nbabel_energy(sim, e0.as_mut_ptr()), NBABEL_OK);
+		assert!((e0[0] + 0.125).abs() < 1e-15);
+
+		// Half a period
+		assert_eq!(nbabel_step(sim, 3142), NBABEL_OK);
+		assert!((nbabel_time(sim) - 3142.0*1e-3).abs() < 1e-9);
+		let mut out_r = [0.0; 6];
+		let mut out_a = [0.0; 6];
+		assert_eq!(nbabel_get_state(sim, 2, ptr::null_mut(), out_r.as_mut_ptr(), ptr::null_mut(), out_a.as_mut_ptr()), NBABEL_OK);
+		assert!((out_r[0] - 0.5).abs() < 1e-3 && (out_r[3] + 0.5).abs() < 1e-3, "{:?}", out_r);
+		assert!((out_a[0] + 0.5).abs() < 1e-2, "{:?}", out_a);
+		let mut e = [0.0; 3];
+		nbabel_energy(sim, e.as_mut_ptr());
+		assert!(((e[0] - e0[0])/e0[0]).abs() < 1e-6);
+
+		// New stars, same clock
+		let m = [1.0];
+		let r = [0.0; 3];
+		assert_eq!(nbabel_set_particles(sim, 1, m.as_ptr(), r.as_ptr(), ptr::null()), NBABEL_OK);
+		assert_eq!(nbabel_count(sim), 1);
+		assert!((nbabel_time(sim) - 3142.0*1e-3).abs() < 1e-9);
+		assert_eq!(nbabel_get_state(sim, 2, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut()), NBABEL_EINVAL);
+		nbabel_destroy(sim);
+	}
+}
+
+#[test]
+fn bad_arguments() {
+	let m = [1.0];
+	let r = [0.0; 3];
+	unsafe {
+		assert!(nbabel_create(1, ptr::null(), r.as_ptr(), ptr::null(), 1e-3, 0.0).is_null());
+		assert!(nbabel_create(1, m.as_ptr(), r.as_ptr(), ptr::null(), 0.0, 0.0).is_null());
+		assert!(nbabel_create(1, m.as_ptr(), r.as_ptr(), ptr::null(), 1e-3, -1.0).is_null());
+		assert_eq!(nbabel_step(ptr::null_mut(), 1), NBABEL_ENULL);
+		assert_eq!(nbabel_energy(ptr::null(), ptr::null_mut()), NBABEL_ENULL);
+		assert_eq!(nbabel_count(ptr::null()), 0);
+		nbabel_destroy(ptr::null_mut());
+	}
+}
+
+#[test]
+fn kernel() {
+	let m = [1.0, 2.0];
+	let r = [0.0, 0.0, 0.0, 0.0, 2.0, 0.0];
+	let mut a = [0.0; 6];
+	unsafe {
+		assert_eq!(nbabel_accelerations(2, m.as_ptr(), r.as_ptr(), 0.0, a.as_mut_ptr()), NBABEL_OK);
+	}
+	assert_eq!(a, [0.0, 0.5, 0.0, 0.0, -0.25, 0.0]);
+}
+
+// The header declares every function the library exports, and nothing else
+#[test]
+fn header_matches() {
+	let source = std::fs::read_to_string("src/ffi.rs").unwrap();
+	let header = std::fs::read_to_string("include/nbabel.h").unwrap();
+	let exported: Vec<&str> = source.lines().filter_map(|x| x.strip_prefix("pub unsafe extern \"C\" fn ")).map(|x| &x[..x.find('(').unwrap()]).collect();
+	let declared: Vec<&str> = header.lines().filter(|x| !x.starts_with('#') && !x.starts_with(' ') && !x.starts_with("/*")).filter_map(|x| x.find("nbabel_").map(|i| &x[i..])).map(|x| &x[..x.find('(').unwrap()]).collect();
+	assert_eq!(exported.len(), 9);
+	assert_eq!(exported, declared);
+	for &(name, value) in [("NBABEL_OK", NBABEL_OK), ("NBABEL_ENULL", NBABEL_ENULL), ("NBABEL_EINVAL", NBABEL_EINVAL), ("NBABEL_EPANIC", NBABEL_EPANIC)].iter() {
+		let text = if value < 0 { format!("#define {} ({})", name, value) } else { format!("#define {} {}", name, value) };
+		assert!(header.contains(&text), "{}", text);
+	}
+}
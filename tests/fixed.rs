@@ -0,0 +1,39 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::columns::Columns;
+use nbabel::fixed::Fixed;
+
+// The flagged star stays put while pulling the others around, and the energy is conserved
+#[test]
+fn anchored() {
+	let text = "# columns: id m x y z vx vy vz fixed\n\
+		0 1 0 0 0 0.3 0 0 1\n\
+		1 0.001 1 0 0 0 1 0 0\n\
+		2 0.001 -1.5 0 0 0 -0.8 0 0\n";
+	let x = Fixed::new(3, &Columns::read(text).unwrap());
+	assert_eq!(x.stars, vec![0]);
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	let mut sim = Simulation::new(parse_stars(text).unwrap(), p);
+	sim.set_fixed(x);
+	let e0 = sim.energies();
+	for _ in 0..3142 {
+		sim.step();
+	}
+	assert!(sim.s[0].r.iter().chain(sim.s[0].v.iter()).all(|&x| x == 0.0));
+	// Half an orbit for the inner star
+	assert!(sim.s[1].r[0] < -0.99);
+	let e = sim.energies();
+	assert!(((e[0] - e0[0])/e0[0]).abs() < 1e-6);
+}
+
+// Merging keeps the flags with their stars
+#[test]
+fn merged() {
+	let mut x = Fixed { stars: vec![1, 4] };
+	x.merged(2, 4);
+	assert_eq!(x.stars, vec![1, 2]);
+	x.merged(0, 1);
+	assert_eq!(x.stars, vec![0, 1]);
+}
@@ -0,0 +1,31 @@
+extern crate nbabel;
+
+use nbabel::checkpoint;
+use nbabel::columns::Columns;
+use nbabel::dd::DoubleDouble;
+use nbabel::fuzz;
+
+// The structured harness over a fixed set of seeds
+#[test]
+fn random_systems_keep_invariants() {
+	for k in 0..300u32 {
+		let data = k.to_le_bytes();
+		let (s, p) = fuzz::random_system(&data);
+		if let Err(msg) = fuzz::check(s, p, 4) {
+			panic!("input {:?}: {}", data, msg);
+		}
+	}
+}
+
+// Malformed input is an error, never a panic
+#[test]
+fn bad_input_is_rejected() {
+	for text in &["0 1 2", "0 1 0 0 0 0 0 x", "0 -1 0 0 0 0 0 0", "0 1 0 0 0 0 0 NaN", "0 1 0 0 0 inf 0 0",
+		"\u{0}\u{ff}", "# nbabel checkpoint t = x steps = 1\n", "# nbabel checkpoint t = 1 steps = -1\n"] {
+		assert!(checkpoint::read::<f64>(text).is_err(), "{:?}", text);
+		assert!(checkpoint::read::<DoubleDouble>(text).is_err(), "{:?}", text);
+		let _ = Columns::read(text);
+	}
+	let (s, t, steps) = checkpoint::read::<f64>("# nbabel checkpoint t\n\n0\t1 0 0 0 0 0 0\n").unwrap();
+	assert_eq!((s.len(), t, steps), (1, 0.0, 0));
+}
@@ -0,0 +1,136 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::generate;
+use nbabel::imf::Imf;
+use nbabel::rng::Rng;
+
+// Concentrations log10(r_t/r_0) from King (1966)
+#[test]
+fn king_tidal_radius() {
+	for &(w0, c) in &[(3.0, 0.67), (6.0, 1.26), (9.0, 2.12)] {
+		let profile = generate::king_profile(w0);
+		let rt = *profile.r.last().unwrap();
+		assert!((rt.log10() - c).abs() < 0.02, "W0 = {}: c = {}", w0, rt.log10());
+	}
+}
+
+#[test]
+fn king_model_is_in_nbody_units() {
+	let s = generate::king(500, 6.0, &mut Rng::new(7));
+	assert_eq!(s.len(), 500);
+	let mass: f64 = s.iter().map(|x| x.m).sum();
+	assert!((mass - 1.0).abs() < 1e-12);
+	let e = energies(&s, &Params::default());
+	assert!((e[0] + 0.25).abs() < 1e-9 && (e[1] - 0.25).abs() < 1e-9);
+}
+
+#[test]
+fn uniform_sphere_virial_ratio() {
+	for &q in &[0.0, 0.5] {
+		let s = generate::uniform(800, q, &mut Rng::new(11));
+		let e = energies(&s, &Params::default());
+		assert!((e[2] + 0.5).abs() < 1e-9, "W = {}", e[2]);
+		assert!((e[1] - 0.25*q).abs() < 1e-9, "T = {}", e[1]);
+		let rmax = s.iter().map(|x| (x.r[0]*x.r[0] + x.r[1]*x.r[1] + x.r[2]*x.r[2]).sqrt()).fold(0.0, f64::max);
+		assert!(rmax < 1.4, "rmax = {}", rmax);
+	}
+}
+
+// Exact Kepler orbit: the energy is -m1 m2/2a and after one period the pair is back at apocentre
+#[test]
+fn binary_returns_after_one_period() {
+	let (a, e) = (1.0, 0.5);
+	let s = generate::binary(a, e, 0.5);
+	let (m1, m2) = (s[0].m, s[1].m);
+	let mut p = Params::default();
+	p.dt = 1e-4;
+	p.threads = 1;
+	assert!((energies(&s, &p)[0] + m1*m2/(2.0*a)).abs() < 1e-12);
+	let start: Vec<f64> = (0..3).map(|k| s[1].r[k] - s[0].r[k]).collect();
+
+	let mut sim = Simulation::new(s, p);
+	let steps = (generate::period(1.0, a)/1e-4).round() as usize;
+	for _ in 0..steps {
+		sim.step();
+	}
+	let end: Vec<f64> = (0..3).map(|k| sim.s[1].r[k] - sim.s[0].r[k]).collect();
+	let miss = ((end[0] - start[0]).powi(2) + (end[1] - start[1]).powi(2) + (end[2] - start[2]).powi(2)).sqrt();
+	assert!(miss < 1e-3*a, "missed the starting point by {}", miss);
+}
+
+#[test]
+fn tertiary_orbits_the_inner_binary() {
+	let s = generate::add_tertiary(generate::binary(1.0, 0.0, 1.0), 0.1, 20.0, 0.3, 0.5);
+	assert_eq!(s.len(), 3);
+	let inner = [0.5*(s[0].r[0] + s[1].r[0]), 0.5*(s[0].r[1] + s[1].r[1]), 0.5*(s[0].r[2] + s[1].r[2])];
+	let d = ((s[2].r[0] - inner[0]).powi(2) + (s[2].r[1] - inner[1]).powi(2) + (s[2].r[2] - inner[2]).powi(2)).sqrt();
+	assert!((d - 20.0*1.3).abs() < 1e-9, "apocentre distance {}", d);
+	let momentum: f64 = (0..3).map(|k| s.iter().map(|x| x.m*x.v[k]).sum::<f64>().abs()).sum();
+	assert!(momentum < 1e-12);
+}
+
+// The planets' osculating orbits match the mean elements, and the Earth is back after a year
+#[test]
+fn solar_system_periods() {
+	let s = generate::solar_system();
+	let names = generate::solar_system_names();
+	assert_eq!(s.len(), 9);
+	assert_eq!(names[3], "Earth");
+	for &(k, a, e) in &[(1, 0.38709927, 0.20563593), (3, 1.00000261, 0.01671123), (5, 5.20288700, 0.04838624), (8, 30.06992276, 0.00859048)] {
+		let (a2, e2, _) = binaries::two_body(&s, 0, k);
+		assert!((a2/a - 1.0).abs() < 1e-9 && (e2 - e).abs() < 1e-9, "{}: a = {}, e = {}", names[k], a2, e2);
+	}
+
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	p.threads = 1;
+	let heliocentric = |s: &Vec<Star>| -> Vec<f64> { (0..3).map(|k| s[3].r[k] - s[0].r[k]).collect() };
+	let start = heliocentric(&s);
+	let mut sim = Simulation::new(s, p);
+	let year = generate::period(1.0 + sim.s[3].m, 1.00000261);
+	while sim.t < year - 0.5e-3 {
+		sim.step();
+	}
+	let end = heliocentric(&sim.s);
+	let miss = ((end[0] - start[0]).powi(2) + (end[1] - start[1]).powi(2) + (end[2] - start[2]).powi(2)).sqrt();
+	assert!(miss < 2e-3, "Earth misses its starting point by {} au", miss);
+}
+
+#[test]
+fn imf_sampling() {
+	let salpeter = Imf::salpeter(0.1, 100.0);
+	let expected = ((100f64.powf(-0.35) - 0.1f64.powf(-0.35))/-0.35)/((100f64.powf(-1.35) - 0.1f64.powf(-1.35))/-1.35);
+	assert!((salpeter.mean()/expected - 1.0).abs() < 1e-12);
+
+	let mut rng = Rng::new(5);
+	for imf in &[salpeter, Imf::kroupa(0.08, 100.0), Imf::kroupa(0.01, 1.0)] {
+		let draws: Vec<f64> = (0..200000).map(|_| imf.sample(&mut rng)).collect();
+		let mean = draws.iter().sum::<f64>()/draws.len() as f64;
+		assert!((mean/imf.mean() - 1.0).abs() < 0.03, "sample mean {} vs {}", mean, imf.mean());
+	}
+	assert!(Imf::by_name("kroupa", 1.0, 0.5).is_err() && Imf::by_name("chabrier", 0.1, 1.0).is_err());
+}
+
+#[test]
+fn imf_cluster_is_in_nbody_units() {
+	let mut rng = Rng::new(9);
+	let mut s = generate::king(400, 5.0, &mut rng);
+	generate::apply_imf(&mut s, &Imf::kroupa(0.08, 100.0), &mut rng);
+	let mass: f64 = s.iter().map(|x| x.m).sum();
+	assert!((mass - 1.0).abs() < 1e-12);
+	assert!(s.iter().any(|x| x.m > 10.0*s[0].m) || s.iter().any(|x| x.m < 0.1*s[0].m));
+	let e = energies(&s, &Params::default());
+	assert!((e[2] + 0.5).abs() < 1e-9 && (e[1] - 0.25).abs() < 1e-9);
+}
+
+// Half-mass radius of a Plummer sphere in N-body units is 0.769 (scale radius 3 pi/16)
+#[test]
+fn plummer_sphere_half_mass_radius() {
+	let s = generate::plummer(4000, &mut Rng::new(3));
+	let e = energies(&s, &Params::default());
+	assert!((e[0] + 0.25).abs() < 1e-9 && (e[1] - 0.25).abs() < 1e-9);
+	let mut r: Vec<f64> = s.iter().map(|x| (x.r[0]*x.r[0] + x.r[1]*x.r[1] + x.r[2]*x.r[2]).sqrt()).collect();
+	r.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	assert!((r[2000] - 0.769).abs() < 0.05, "r_h = {}", r[2000]);
+}
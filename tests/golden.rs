@@ -0,0 +1,67 @@
+extern crate nbabel;
+
+use std::fs;
+
+use nbabel::*;
+
+/*
+ Golden runs: the 16- and 128-body fixtures in tests/golden are stepped a
+ fixed number of times, serially and on several threads, and the final
+ positions, velocities and dE are compared with the stored references. A
+ change in the physics or in the parallel reduction shows up here first.
+ After a deliberate change, NBABEL_BLESS=1 cargo test --test golden writes
+ new references; review their diff before committing them.
+ */
+
+// Relative to the size of the value, or absolute for values below one
+const TOLERANCE: f64 = 1e-10;
+const DE_TOLERANCE: f64 = 1e-12;
+
+fn run(n: usize, steps: usize, threads: usize) -> (Vec<Star>, f64) {
+	let mut p = Params::default();
+	p.threads = threads;
+	let mut sim = Simulation::new(read_stars(&fs::read_to_string(format!("tests/golden/input{}", n)).unwrap()), p);
+	let e0 = sim.energies()[0];
+	for _ in 0..steps {
+		sim.step();
+	}
+	let de = (sim.energies()[0] - e0)/e0;
+	(sim.s, de)
+}
+
+fn close(a: f64, b: f64, tolerance: f64) -> bool {
+	(a - b).abs() <= tolerance*a.abs().max(b.abs()).max(1.0)
+}
+
+fn check(n: usize, steps: usize) {
+	let path = format!("tests/golden/reference{}", n);
+	if std::env::var("NBABEL_BLESS").is_ok() {
+		let (s, de) = run(n, steps, 1);
+		let mut text = format!("# nbabel golden: {} steps, dE = {:e}\n", steps, de).into_bytes();
+		write_stars(&mut text, &s).unwrap();
+		fs::write(&path, text).unwrap();
+	}
+	let text = fs::read_to_string(&path).unwrap();
+	let expected_de: f64 = text.lines().next().and_then(|x| x.rsplit("dE = ").next()).unwrap().parse().unwrap();
+	let expected = read_stars::<f64>(&text);
+	for &threads in [1, 2, 4].iter() {
+		let (s, de) = run(n, steps, threads);
+		assert!(close(de, expected_de, DE_TOLERANCE), "N = {}, {} threads: dE = {:e}, expected {:e}", n, threads, de, expected_de);
+		for (i, (x, y)) in s.iter().zip(expected.iter()).enumerate() {
+			for k in 0..3 {
+				assert!(close(x.r[k], y.r[k], TOLERANCE), "N = {}, {} threads: star {} r[{}] = {}, expected {}", n, threads, i, k, x.r[k], y.r[k]);
+				assert!(close(x.v[k], y.v[k], TOLERANCE), "N = {}, {} threads: star {} v[{}] = {}, expected {}", n, threads, i, k, x.v[k], y.v[k]);
+			}
+		}
+	}
+}
+
+#[test]
+fn golden16() {
+	check(16, 200);
+}
+
+#[test]
+fn golden128() {
+	check(128, 100);
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "http")]
+extern crate nbabel;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+
+use nbabel::http::{route, status_json, Api, Request, Response};
+use nbabel::timing::Times;
+
+#[test]
+fn routes() {
+	assert_eq!(route("GET", "/status"), Ok(Request::Status));
+	assert_eq!(route("GET", "/snapshot/"), Ok(Request::Snapshot));
+	assert_eq!(route("POST", "/pause"), Ok(Request::Pause));
+	assert_eq!(route("POST", "/resume"), Ok(Request::Resume));
+	assert_eq!(route("POST", "/cadence?steps=25"), Ok(Request::Cadence(25)));
+	assert_eq!(route("POST", "/status").unwrap_err().status, 405);
+	assert_eq!(route("GET", "/pause").unwrap_err().status, 405);
+	assert_eq!(route("POST", "/cadence?steps=0").unwrap_err().status, 400);
+	assert_eq!(route("POST", "/cadence").unwrap_err().status, 400);
+	assert_eq!(route("GET", "/").unwrap_err().status, 404);
+}
+
+#[test]
+fn status() {
+	let mut times = Times::default();
+	times.seconds = [1.5, 0.25, 0.25, 0.0];
+	times.steps = 100;
+	let json = status_json(0.5, 100, &[-0.25, 0.25, -0.5], 1e-6, 10, false, &times);
+	assert_eq!(json, "{\"t\": 5e-1, \"steps\": 100, \"E\": -2.5e-1, \"T\": 2.5e-1, \"W\": -5e-1, \"dE\": 1e-6, \"paused\": false, \"diagnostic_interval\": 10, \"steps_per_second\": 5e1, \"seconds\": {\"forces\": 1.5e0, \"integration\": 2.5e-1, \"diagnostics\": 2.5e-1, \"I/O\": 0e0}}");
+}
+
+fn ask(addr: SocketAddr, request: &str) -> thread::JoinHandle<String> {
+	let request = request.to_string();
+	thread::spawn(move || {
+		let mut stream = TcpStream::connect(addr).unwrap();
+		write!(stream, "{} HTTP/1.1\r\nHost: localhost\r\n\r\n", request).unwrap();
+		let mut text = String::new();
+		stream.read_to_string(&mut text).unwrap();
+		text
+	})
+}
+
+#[test]
+fn pause_and_answer() {
+	let mut api = Api::bind("127.0.0.1:0").unwrap();
+	let addr = api.local_addr();
+	let client = thread::spawn(move || {
+		let replies: Vec<String> = ["POST /pause", "GET /status", "POST /resume", "GET /nothing"].iter().map(|x| ask(addr, x).join().unwrap()).collect();
+		replies
+	});
+	// A status asked for while paused is answered before the resume
+	let mut answered = vec![];
+	while !client.is_finished() {
+		api.serve(|request, paused| {
+			answered.push((request, paused));
+			Response::json(String::from("{}"))
+		});
+	}
+	assert!(!api.paused);
+	assert_eq!(answered, vec![(Request::Status, true)]);
+	let replies = client.join().unwrap();
+	assert!(replies[0].ends_with("{\"paused\": true}"));
+	assert!(replies[1].starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 2\r\n"), "{}", replies[1]);
+	assert!(replies[2].ends_with("{\"paused\": false}"));
+	assert!(replies[3].starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", replies[3]);
+}
@@ -0,0 +1,32 @@
+extern crate nbabel;
+
+use nbabel::interrupt;
+use nbabel::status::{self, Outcome};
+
+#[cfg(unix)]
+extern "C" {
+	fn raise(signum: i32) -> i32;
+}
+
+// One test, as the flag is global to the process
+#[test]
+fn signal_sets_the_flag() {
+	interrupt::clear();
+	assert!(!interrupt::requested());
+	interrupt::request();
+	assert!(interrupt::requested());
+	interrupt::clear();
+
+	#[cfg(unix)]
+	{
+		interrupt::install();
+		unsafe {
+			raise(2);
+		}
+		assert!(interrupt::requested());
+		interrupt::clear();
+	}
+
+	let outcome = Outcome::Interrupted;
+	assert_eq!((outcome.name(), outcome.exit_code()), ("interrupted", status::EXIT_INTERRUPTED));
+}
@@ -0,0 +1,23 @@
+#[macro_use]
+extern crate nbabel;
+
+use nbabel::log;
+
+// The file gets what the verbosity lets through, progress lines included
+#[test]
+fn log_file_follows_verbosity() {
+	let path = std::env::temp_dir().join(format!("nbabel-log-{}.log", std::process::id()));
+	log::open(&path).unwrap();
+	log::set_verbosity(log::Level::Warn);
+	info!("hidden {}", 1);
+	progress!("hidden progress");
+	warn!("shown {}", 2);
+	log::set_verbosity(log::Level::Verbose);
+	verbose!("shown {}", 3);
+	debug!("hidden {}", 4);
+	progress!("shown progress");
+	log::close();
+	let text = std::fs::read_to_string(&path).unwrap();
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(text, "Warning: shown 2\nshown 3\nshown progress\n");
+}
@@ -0,0 +1,45 @@
+extern crate nbabel;
+
+use nbabel::*;
+
+fn star(m: f64, r: [f64; 3], v: [f64; 3]) -> Star {
+	Star { m: m, r: r.to_vec(), v: v.to_vec(), a: vec![0.0; 3], a0: vec![0.0; 3] }
+}
+
+// A 1e-6 "planet" on a circular orbit of radius 1 around a unit mass star
+#[test]
+fn planet_keeps_circular_orbit() {
+	let mp: f64 = 1e-6;
+	let vc = (1.0 + mp).sqrt();
+	let mut s = vec![
+		star(1.0, [-mp/(1.0 + mp), 0.0, 0.0], [0.0, -mp/(1.0 + mp)*vc, 0.0]),
+		star(mp, [1.0/(1.0 + mp), 0.0, 0.0], [0.0, vc/(1.0 + mp), 0.0]),
+	];
+
+	let mut p = Params::default();
+	p.eps = 0.1;
+	let warnings = masses::apply_defaults(&s, &mut p, false, false);
+	assert!(!warnings.is_empty());
+	assert_eq!(p.softening, Softening::Min);
+	assert!(p.dt < 0.02);
+
+	let e0 = energies(&s, &p);
+	acceleration(&mut s, &p);
+	let mut t = 0.0;
+	// Three orbits
+	while t < 6.0*std::f64::consts::PI {
+		update_positions(&mut s, &p);
+		acceleration(&mut s, &p);
+		update_velocities(&mut s, &p);
+		t += p.dt;
+
+		let mut r2 = 0.0;
+		for i in 0..3 {
+			r2 += (s[1].r[i] - s[0].r[i]).powi(2);
+		}
+		assert!((r2.sqrt() - 1.0).abs() < 1e-3, "orbit drifted to r = {} at t = {}", r2.sqrt(), t);
+	}
+
+	let e = energies(&s, &p);
+	assert!(((e[0] - e0[0])/e0[0]).abs() < 1e-6);
+}
@@ -0,0 +1,69 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::generate;
+use nbabel::massloss::{self, MassEvolution, MassLoss, Table, Wind};
+use nbabel::rng::Rng;
+
+#[test]
+fn wind() {
+	let x = Wind { tau: 2.0, floor: 0.5 };
+	assert_eq!(x.fraction(0.0), 1.0);
+	assert!((x.fraction(2.0) - (0.5 + 0.5*(-1f64).exp())).abs() < 1e-15);
+	assert!((x.fraction(1e3) - 0.5).abs() < 1e-15);
+}
+
+#[test]
+fn table() {
+	let x = Table::parse("# t fraction\n1 0.8\n0 1\n\n3 0.5\n").unwrap();
+	assert_eq!(x.points, vec![(0.0, 1.0), (1.0, 0.8), (3.0, 0.5)]);
+	assert_eq!(x.fraction(-1.0), 1.0);
+	assert!((x.fraction(0.5) - 0.9).abs() < 1e-15);
+	assert!((x.fraction(2.0) - 0.65).abs() < 1e-15);
+	assert_eq!(x.fraction(10.0), 0.5);
+	assert!(Table::parse("").is_err());
+	assert!(Table::parse("0 1 2\n").is_err());
+	assert!(Table::parse("0 0\n").is_err());
+	assert!(Table::parse("0 x\n").is_err());
+}
+
+#[test]
+fn bad_specs() {
+	assert!(massloss::parse("wind").is_err());
+	assert!(massloss::parse("wind:tau=0").is_err());
+	assert!(massloss::parse("wind:tau=1,floor=1").is_err());
+	assert!(massloss::parse("wind:tau=1,rate=2").is_err());
+	assert!(massloss::parse("table:/nonexistent/table.txt").is_err());
+	assert!(massloss::parse("supernova").is_err());
+	assert_eq!(massloss::parse("wind:tau=1,floor=0.2").unwrap().name(), "wind");
+}
+
+// A restarted run finds the same initial masses
+#[test]
+fn restart() {
+	let s: Vec<Star> = read_stars("0 0.5 0 0 0 0 0 0\n1 0.25 1 0 0 0 0 0\n");
+	let mut x = MassEvolution::new(Box::new(Wind { tau: 1.0, floor: 0.0 }), &s, 1f64.ln() + 2f64.ln());
+	assert!((x.initial[0] - 1.0).abs() < 1e-12 && (x.initial[1] - 0.5).abs() < 1e-12);
+	x.merged(0, 1);
+	assert!((x.initial[0] - 1.5).abs() < 1e-12 && x.initial.len() == 1);
+}
+
+// The masses follow the wind and dE, less the energy the wind carried off, stays small
+#[test]
+fn cluster_loses_mass() {
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	p.eps = 0.01;
+	let mut sim = Simulation::new(generate::king(100, 6.0, &mut Rng::new(5)), p);
+	let m0: f64 = sim.s.iter().map(|x| x.m).sum();
+	let e0 = sim.energies()[0];
+	sim.set_mass_loss(Box::new(Wind { tau: 0.5, floor: 0.0 }));
+	for _ in 0..300 {
+		sim.step();
+	}
+	let m: f64 = sim.s.iter().map(|x| x.m).sum();
+	assert!((m/m0 - (-sim.t/0.5).exp()).abs() < 1e-12);
+	let adjustment = sim.mass_loss_adjustment();
+	assert!(adjustment.abs() > 0.1*e0.abs());
+	assert!(((sim.energies()[0] - e0 - adjustment)/e0).abs() < 1e-4);
+}
@@ -0,0 +1,43 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::checkpoint;
+use nbabel::metadata::{self, Metadata};
+
+#[test]
+fn sha256_test_vectors() {
+	assert_eq!(metadata::sha256(b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+	assert_eq!(metadata::sha256(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+	// Two blocks of padding
+	assert_eq!(metadata::sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+}
+
+#[test]
+fn utc_dates() {
+	assert_eq!(metadata::utc(0), "1970-01-01T00:00:00Z");
+	assert_eq!(metadata::utc(951782400), "2000-02-29T00:00:00Z");
+	assert_eq!(metadata::utc(1792145445), "2026-10-16T10:10:45Z");
+}
+
+#[test]
+fn checkpoints_carry_the_metadata() {
+	let input = "0 0.5 -0.5 0 0 0 -0.5 0\n1 0.5 0.5 0 0 0 0.5 0\n";
+	let config = vec![(String::from("--dt"), String::from("0.001")), (String::from("--compensated"), String::new())];
+	let x = Metadata::new(input.as_bytes(), &[String::from("--dt"), String::from("1e-3")], &config);
+	assert_eq!(x.version, env!("CARGO_PKG_VERSION"));
+	assert_eq!(x.config, "--dt 0.001 --compensated");
+	assert_eq!(x.input_sha256, metadata::sha256(input.as_bytes()));
+
+	let mut sim = Simulation::new(read_stars::<f64>(input), Params::default());
+	sim.metadata = Some(x.clone());
+	sim.step();
+	let path = std::env::temp_dir().join(format!("nbabel-metadata-{}.txt", std::process::id()));
+	checkpoint::write(&path, &sim).unwrap();
+	let text = std::fs::read_to_string(&path).unwrap();
+	std::fs::remove_file(&path).unwrap();
+	assert!(text.starts_with("# nbabel checkpoint"));
+	assert_eq!(Metadata::read(&text), Some(x));
+	let (s, _, steps) = checkpoint::read::<f64>(&text).unwrap();
+	assert_eq!((s.len(), steps), (2, 1));
+	assert_eq!(Metadata::read(input), None);
+}
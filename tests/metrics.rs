@@ -0,0 +1,57 @@
+#![cfg(feature = "metrics")]
+extern crate nbabel;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use nbabel::metrics::{exposition, Gauges, Metrics};
+use nbabel::timing::Times;
+
+fn gauges() -> Gauges {
+	let mut times = Times::default();
+	times.seconds = [1.5, 0.25, 0.25, 0.0];
+	times.steps = 100;
+	Gauges { t: 0.5, steps: 100, stars: 2048, energy: -0.25, de: 1e-6, wall_seconds: 3.0, times: times }
+}
+
+#[test]
+fn text_format() {
+	let text = exposition(&gauges(), "0.1.0", "abc\"def", Some(4096));
+	assert!(text.contains("# TYPE nbabel_time gauge\nnbabel_time 0.5\n"));
+	assert!(text.contains("# TYPE nbabel_steps_total counter\nnbabel_steps_total 100\n"));
+	assert!(text.contains("nbabel_steps_per_second 50\n"));
+	assert!(text.contains("nbabel_stars 2048\n"));
+	assert!(text.contains("nbabel_energy -0.25\n"));
+	assert!(text.contains("nbabel_energy_error 0.000001\n"));
+	assert!(text.contains("nbabel_phase_seconds_total{phase=\"forces\"} 1.5\n"));
+	assert!(text.contains("nbabel_phase_seconds_total{phase=\"io\"} 0\n"));
+	assert!(text.contains("process_resident_memory_bytes 4096\n"));
+	assert!(text.contains("nbabel_build_info{version=\"0.1.0\",git=\"abc\\\"def\"} 1\n"));
+	assert!(!exposition(&gauges(), "0.1.0", "abc", None).contains("process_resident_memory_bytes"));
+	// Every sample line is "name[{labels}] value"
+	for line in text.lines().filter(|x| !x.starts_with('#')) {
+		let value = line.rsplit(' ').next().unwrap();
+		assert!(value.parse::<f64>().is_ok(), "{}", line);
+	}
+}
+
+fn get(addr: SocketAddr, path: &str) -> String {
+	let mut stream = TcpStream::connect(addr).unwrap();
+	write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+	let mut text = String::new();
+	stream.read_to_string(&mut text).unwrap();
+	text
+}
+
+#[test]
+fn scrape() {
+	let metrics = Metrics::bind("127.0.0.1:0", "0.1.0", "unknown").unwrap();
+	let addr = metrics.local_addr();
+	assert!(get(addr, "/metrics").contains("nbabel_steps_total 0\n"));
+	metrics.update(gauges());
+	let reply = get(addr, "/metrics");
+	assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+	assert!(reply.contains("Content-Type: text/plain; version=0.0.4\r\n"));
+	assert!(reply.contains("nbabel_steps_total 100\n"));
+	assert!(get(addr, "/status").starts_with("HTTP/1.1 404"));
+}
@@ -0,0 +1,93 @@
+extern crate nbabel;
+
+use nbabel::*;
+
+fn run(threads: usize) -> (Vec<Star>, Vec<f64>) {
+	let text: String = std::fs::read_to_string("input/input2k").unwrap().lines().take(100).collect::<Vec<_>>().join("\n");
+	let mut p = Params::default();
+	p.threads = threads;
+	let mut sim = Simulation::new(read_stars(&text), p);
+	for _ in 0..20 {
+		sim.step();
+	}
+	let e = sim.energies();
+	(sim.s, e)
+}
+
+// Serial and threaded execution share the kernel and only differ in summation order
+#[test]
+fn serial_matches_parallel() {
+	let (serial, es) = run(1);
+	for &threads in &[2, 3, 8] {
+		let (parallel, ep) = run(threads);
+		for (x, y) in serial.iter().zip(parallel.iter()) {
+			for i in 0..3 {
+				assert!((x.r[i] - y.r[i]).abs() < 1e-12);
+				assert!((x.v[i] - y.v[i]).abs() < 1e-12);
+			}
+		}
+		assert!(((es[0] - ep[0])/es[0]).abs() < 1e-12);
+	}
+}
+
+// Star counts that do not divide evenly, including fewer stars than threads
+#[test]
+fn any_star_count_with_any_thread_count() {
+	let lines: Vec<String> = std::fs::read_to_string("input/input2k").unwrap().lines().map(|x| x.to_string()).collect();
+	for &n in &[1, 7, 1001] {
+		let text = lines[..n].join("\n");
+		let mut reference: Vec<Star> = read_stars(&text);
+		assert_eq!(reference.len(), n);
+		let mut p = Params::default();
+		p.threads = 1;
+		acceleration(&mut reference, &p);
+		for star in &reference {
+			assert!(n == 1 || star.a.iter().any(|&x| x != 0.0));
+		}
+		for &threads in &[2, 3, 8, 16] {
+			for &simd in &[false, true] {
+				let mut s: Vec<Star> = read_stars(&text);
+				p.threads = threads;
+				p.simd = if simd { Some(simd::Isa::Scalar) } else { None };
+				acceleration(&mut s, &p);
+				for (x, y) in reference.iter().zip(s.iter()) {
+					for i in 0..3 {
+						assert!((x.a[i] - y.a[i]).abs() <= 1e-10*(1.0 + x.a[i].abs()), "N = {}, {} threads", n, threads);
+					}
+				}
+			}
+		}
+	}
+}
+
+// With softening the work-based potential follows the exact one closely between resyncs
+#[test]
+fn incremental_energy_tracks_exact() {
+	let text: String = std::fs::read_to_string("input/input2k").unwrap().lines().take(100).collect::<Vec<_>>().join("\n");
+	let mut p = Params::default();
+	p.eps = 0.01;
+	let mut sim = Simulation::new(read_stars(&text), p);
+	sim.track_energy_every(1000000);
+	for _ in 0..200 {
+		sim.step();
+	}
+	let exact = sim.energies();
+	let tracked = sim.tracked_energies();
+	assert_eq!(tracked[1], exact[1]);
+	assert!(((tracked[2] - exact[2])/exact[2]).abs() < 1e-5, "{:?} vs {:?}", tracked, exact);
+
+	sim.track_energy_every(1);
+	sim.step();
+	assert_eq!(sim.tracked_energies(), sim.energies());
+}
+
+// The health check reports the first star with a bad position or velocity
+#[test]
+fn non_finite_state_names_the_star() {
+	let star = |x: f64| Star { m: 1.0, r: vec![x, 0.0, 0.0], v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] };
+	let mut sim = Simulation::new(vec![star(-1.0), star(1.0), star(3.0)], Params::default());
+	assert_eq!(sim.first_non_finite(), None);
+	sim.s[1].v[2] = std::f64::NAN;
+	sim.s[2].r[0] = std::f64::INFINITY;
+	assert_eq!(sim.first_non_finite(), Some(1));
+}
@@ -0,0 +1,44 @@
+extern crate nbabel;
+
+use nbabel::diagnostics::local_densities;
+use nbabel::neighbors::KdTree;
+use nbabel::rng::Rng;
+use nbabel::Star;
+
+fn star(m: f64, r: [f64; 3]) -> Star {
+	Star { m: m, r: r.to_vec(), v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] }
+}
+
+#[test]
+fn neighbors_match_brute_force() {
+	let mut rng = Rng::new(7);
+	let s: Vec<Star> = (0..300).map(|_| star(1.0, [rng.uniform(), rng.uniform(), rng.uniform()])).collect();
+	let tree = KdTree::build(&s);
+	for i in (0..300).step_by(17) {
+		let mut all: Vec<(f64, usize)> = (0..300).filter(|&j| j != i).map(|j| {
+			let d2: f64 = (0..3).map(|k| (s[j].r[k] - s[i].r[k]).powi(2)).sum();
+			(d2, j)
+		}).collect();
+		all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+		let expected: Vec<usize> = all[..10].iter().map(|x| x.1).collect();
+		assert_eq!(tree.neighbors(i, 10), expected);
+	}
+	assert_eq!(tree.neighbors(0, 1000).len(), 299);
+}
+
+#[test]
+fn density_of_a_lattice() {
+	// Unit cubic lattice of unit masses: the 6 nearest are at distance 1
+	let mut s = vec![];
+	for x in 0..5 {
+		for y in 0..5 {
+			for z in 0..5 {
+				s.push(star(1.0, [x as f64, y as f64, z as f64]));
+			}
+		}
+	}
+	let rho = local_densities(&s, 6);
+	let centre = 2*25 + 2*5 + 2;
+	assert!((rho[centre] - 5.0/(4.0/3.0*std::f64::consts::PI)).abs() < 1e-12);
+	assert_eq!(local_densities(&s[..2].to_vec(), 6), vec![0.0; 2]);
+}
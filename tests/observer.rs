@@ -0,0 +1,55 @@
+extern crate nbabel;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use nbabel::*;
+use nbabel::observer::{Observer, Track};
+
+fn stars() -> Vec<Star> {
+	read_stars(&std::fs::read_to_string("input/input2k").unwrap().lines().take(64).collect::<Vec<_>>().join("\n"))
+}
+
+#[derive(Default)]
+struct Counter {
+	steps: usize,
+	snapshots: Vec<f64>,
+}
+
+impl Observer<f64> for Counter {
+	fn on_step(&mut self, _: f64, _: &Vec<Star>) {
+		self.steps += 1;
+	}
+
+	fn on_snapshot(&mut self, _: f64, _: &Vec<Star>, e: &[f64]) {
+		self.snapshots.push(e[0]);
+	}
+}
+
+#[test]
+fn observers_see_every_step_and_snapshot() {
+	let mut sim = Simulation::new(stars(), Params::default());
+	let track = Rc::new(RefCell::new(Track::new(3)));
+	let counter = Rc::new(RefCell::new(Counter::default()));
+	sim.observe(Box::new(track.clone()));
+	sim.observe(Box::new(counter.clone()));
+	let mut last = vec![];
+	for k in 1..=25 {
+		sim.step();
+		if k % 10 == 0 {
+			last = sim.energies();
+			sim.snapshot(&last);
+		}
+	}
+
+	let path = &track.borrow().path;
+	assert_eq!(path.len(), 25);
+	assert_eq!(path[24][0], sim.t);
+	for i in 0..3 {
+		assert_eq!(path[24][i + 1], sim.s[3].r[i]);
+	}
+	assert!(path[0][0] > 0.0 && path[0][0] < path[1][0]);
+	assert_eq!(counter.borrow().steps, 25);
+	assert_eq!(counter.borrow().snapshots.len(), 2);
+	assert_eq!(counter.borrow().snapshots[1], last[0]);
+}
@@ -0,0 +1,38 @@
+extern crate nbabel;
+
+use nbabel::orbit::{from_elements, to_elements, Elements};
+
+fn close(a: f64, b: f64) -> bool {
+	(a - b).abs() < 1e-9*b.abs().max(1.0)
+}
+
+#[test]
+fn bound_elements_round_trip() {
+	let x = Elements { a: 1.3, e: 0.6, i: 0.4, node: 1.1, peri: 2.5, mean_anomaly: 0.9 };
+	let (r, v) = from_elements(2.0, &x);
+	let y = to_elements(2.0, r, v);
+	for &(p, q) in [(x.a, y.a), (x.e, y.e), (x.i, y.i), (x.node, y.node), (x.peri, y.peri), (x.mean_anomaly, y.mean_anomaly)].iter() {
+		assert!(close(p, q), "{} != {}", p, q);
+	}
+	assert!(close(y.periapsis(), 1.3*0.4));
+	assert!(close(y.time_since_periapsis(2.0), 0.9/y.mean_motion(2.0)));
+}
+
+#[test]
+fn unbound_elements_round_trip() {
+	let x = Elements { a: -0.5, e: 1.8, i: 2.0, node: 0.3, peri: 4.0, mean_anomaly: -1.5 };
+	let (r, v) = from_elements(1.0, &x);
+	let y = to_elements(1.0, r, v);
+	for &(p, q) in [(x.a, y.a), (x.e, y.e), (x.i, y.i), (x.node, y.node), (x.peri, y.peri), (x.mean_anomaly, y.mean_anomaly)].iter() {
+		assert!(close(p, q), "{} != {}", p, q);
+	}
+	assert!(y.period(1.0).is_infinite() && y.time_since_periapsis(1.0) < 0.0);
+}
+
+#[test]
+fn circular_equatorial_orbit_is_measured_from_x() {
+	let y = to_elements(1.0, [0.0, 2.0, 0.0], [-(0.5f64).sqrt(), 0.0, 0.0]);
+	assert!(close(y.a, 2.0) && y.e < 1e-12 && y.i < 1e-12);
+	assert_eq!((y.node, y.peri), (0.0, 0.0));
+	assert!(close(y.mean_anomaly, std::f64::consts::FRAC_PI_2));
+}
@@ -0,0 +1,88 @@
+extern crate nbabel;
+
+use nbabel::*;
+
+fn line(n: usize) -> Vec<Star> {
+	(0..n).map(|i| Star { m: 1.0, r: vec![i as f64, 0.0, 0.0], v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] }).collect()
+}
+
+// Every i < j pair exactly once, also across tile borders
+#[test]
+fn visits_every_pair_once() {
+	for &n in &[0, 1, 2, 63, 64, 65, 200] {
+		let s = line(n);
+		let mut seen = vec![vec![0; n]; n];
+		pairs::for_each_pair(&s, |i, j, rij, r2| {
+			assert!(i < j);
+			assert_eq!(rij[0], i as f64 - j as f64);
+			assert_eq!(r2, rij[0]*rij[0]);
+			seen[i][j] += 1;
+		});
+		for i in 0..n {
+			for j in 0..n {
+				assert_eq!(seen[i][j], if i < j { 1 } else { 0 });
+			}
+		}
+	}
+}
+
+#[test]
+fn cutoff_skips_distant_pairs() {
+	let s = line(100);
+	let mut count = 0;
+	pairs::for_each_pair_within(&s, 2.5, |i, j, _, _| {
+		assert!(j - i <= 2);
+		count += 1;
+	});
+	assert_eq!(count, 99 + 98);
+}
+
+#[test]
+fn parallel_matches_serial() {
+	for &n in &[1, 7, 257, 1001] {
+		let s = line(n);
+		let mut serial = 0;
+		pairs::for_each_pair(&s, |_, _, _, _| serial += 1);
+		for threads in 1..17 {
+			let mut parallel = 0;
+			pairs::par_for_each_pair(&s, threads, 0usize, |acc, _, _, _, _| *acc += 1, |_, acc, _| parallel += acc);
+			assert_eq!(parallel, serial, "N = {}, {} threads", n, threads);
+		}
+	}
+}
+
+// Threads get contiguous row ranges with nearly equal pair counts
+#[test]
+fn partition_balances_pairs() {
+	for &(n, threads) in &[(0, 4), (1, 3), (10, 3), (1000, 8), (2048, 7)] {
+		let bounds = pairs::partition(n, threads);
+		assert_eq!(bounds.len(), threads + 1);
+		assert_eq!((bounds[0], bounds[threads]), (0, n));
+		let counts: Vec<usize> = (0..threads).map(|t| (bounds[t]..bounds[t + 1]).map(|i| n - 1 - i).sum()).collect();
+		let mean = n*n.saturating_sub(1)/2/threads;
+		for &c in &counts {
+			assert!((c as i64 - mean as i64).abs() <= n as i64, "{:?}", counts);
+		}
+	}
+}
+
+// Any block of rows on any number of threads gives the accelerations of the pair loop
+#[test]
+fn rows_match_acceleration() {
+	let mut s = read_stars::<f64>(&std::fs::read_to_string("input/input2k").unwrap().lines().take(300).collect::<Vec<_>>().join("\n"));
+	let mut p = Params::default();
+	p.eps = 0.01;
+	p.softening = Softening::Mean;
+	acceleration(&mut s, &p);
+	let eps = p.star_eps(&s);
+	for &(lo, hi, threads) in &[(0, 300, 1), (0, 300, 4), (100, 170, 3), (299, 300, 8), (5, 5, 2)] {
+		let (a, busy) = pairs::par_rows(&s, &eps, &p, lo, hi, threads);
+		assert_eq!(a.len(), hi - lo);
+		assert!(!busy.is_empty() && busy.len() <= threads);
+		for i in lo..hi {
+			for k in 0..3 {
+				assert!((a[i - lo][k] - s[i].a[k]).abs() <= 1e-12*s[i].a[k].abs().max(1.0));
+			}
+		}
+	}
+}
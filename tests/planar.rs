@@ -0,0 +1,93 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::external::ExternalPotential;
+use nbabel::rng::Rng;
+
+// A uniform pull towards -z, which would lift the stars out of any plane
+struct Sheet;
+
+impl ExternalPotential for Sheet {
+	fn name(&self) -> &'static str {
+		"sheet"
+	}
+
+	fn potential(&self, r: [f64; 3]) -> f64 {
+		0.1*r[2]
+	}
+
+	fn acceleration(&self, _: [f64; 3]) -> [f64; 3] {
+		[0.0, 0.0, -0.1]
+	}
+}
+
+// Stars off the plane are put on it, and stay there on every solver
+#[test]
+fn confined() {
+	let s = generate::uniform(64, 0.5, &mut Rng::new(3));
+	assert_eq!(planar::flatten(&mut s.clone()), 64);
+	for solver in &["direct", "tree"] {
+		let mut p = Params::default();
+		p.dt = 1e-3;
+		p.eps = 0.01;
+		let mut sim = Simulation::with_solver(s.clone(), p, solver::by_name(solver, 0.5).unwrap());
+		sim.set_external(vec![Box::new(Sheet)]);
+		sim.set_planar();
+		let e0 = sim.energies();
+		for _ in 0..200 {
+			sim.step();
+		}
+		assert!(sim.s.iter().all(|x| x.r[2] == 0.0 && x.v[2] == 0.0 && x.a[2] == 0.0), "{}", solver);
+		let e = sim.energies();
+		assert!(((e[0] - e0[0])/e0[0]).abs() < 1e-3, "{}", solver);
+	}
+}
+
+// The flat models are flat and in N-body units
+#[test]
+fn flat_models() {
+	let mut rng = Rng::new(7);
+	for s in vec![generate::uniform_disc(200, 0.5, &mut rng), generate::square(200, 2.0, &mut rng), generate::kuzmin(200, &mut rng)] {
+		assert!(s.iter().all(|x| x.r[2] == 0.0 && x.v[2] == 0.0));
+		let m: f64 = s.iter().map(|x| x.m).sum();
+		assert!((m - 1.0).abs() < 1e-12);
+	}
+	let e = energies(&generate::uniform_disc(200, 0.5, &mut rng), &Params::default());
+	assert!((e[2] + 0.5).abs() < 1e-9 && (e[1] - 0.125).abs() < 1e-9);
+}
+
+// Kuzmin disc stars start on circular orbits: the inward pull of the disc balances v^2/R
+#[test]
+fn kuzmin_rotation() {
+	let mut s = generate::kuzmin(4000, &mut Rng::new(11));
+	let mut p = Params::default();
+	p.eps = 0.02;
+	acceleration(&mut s, &p);
+	let (mut pull, mut needed) = (0.0, 0.0);
+	for x in s.iter() {
+		let radius = (x.r[0]*x.r[0] + x.r[1]*x.r[1]).sqrt();
+		if radius > 0.5 && radius < 2.0 {
+			pull -= (x.a[0]*x.r[0] + x.a[1]*x.r[1])/radius;
+			needed += (x.v[0]*x.v[0] + x.v[1]*x.v[1])/radius;
+		}
+		// Counter-clockwise about z
+		assert!(x.r[0]*x.v[1] - x.r[1]*x.v[0] >= 0.0);
+	}
+	assert!((pull/needed - 1.0).abs() < 0.05, "{}", pull/needed);
+}
+
+// Setting p.dim alone confines the accelerations too
+#[test]
+fn dim_alone() {
+	let mut s = generate::uniform_disc(32, 0.5, &mut Rng::new(4));
+	for x in s.iter_mut() {
+		x.a[2] = 1.0;
+	}
+	let mut p = Params::default();
+	p.dim = 2;
+	p.dt = 1e-3;
+	let mut sim = Simulation::new(s, p);
+	sim.set_external(vec![Box::new(Sheet)]);
+	sim.step();
+	assert!(sim.s.iter().all(|x| x.r[2] == 0.0 && x.v[2] == 0.0 && x.a[2] == 0.0));
+}
@@ -0,0 +1,39 @@
+#![cfg(feature = "plots")]
+extern crate nbabel;
+
+use std::fs;
+
+use nbabel::diagnostics::History;
+use nbabel::generate;
+use nbabel::plots::{self, Format};
+use nbabel::{Params, Simulation};
+
+#[test]
+fn formats() {
+	assert_eq!(Format::parse("png"), Some(Format::Png));
+	assert_eq!(Format::parse("svg"), Some(Format::Svg));
+	assert_eq!(Format::parse("pdf"), None);
+	assert_eq!(plots::names(Format::Png), vec!["energy.png", "dE.png", "lagrangian_radii.png", "n_bound.png"]);
+}
+
+#[test]
+fn end_of_run() {
+	let dir = std::env::temp_dir().join(format!("nbabel-plots-{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	let mut sim: Simulation<f64> = Simulation::new(generate::binary(1.0, 0.5, 1.0), Params::default());
+	let e0 = sim.energies();
+	let mut h = History::new(&[0.5], None).unwrap();
+	for _ in 0..20 {
+		h.record(sim.t, &sim.energies(), &e0, &sim.s).unwrap();
+		for _ in 0..10 {
+			sim.step();
+		}
+	}
+	for &format in [Format::Svg, Format::Png].iter() {
+		plots::write_all(&dir, &h, format).unwrap();
+		for name in plots::names(format) {
+			assert!(fs::metadata(dir.join(&name)).unwrap().len() > 0, "{}", name);
+		}
+	}
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,67 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::ewald::Periodic;
+use nbabel::pm::Pm;
+use nbabel::rng::Rng;
+use nbabel::solver::ForceSolver;
+
+fn median_error(s: &Vec<Star>, reference: &Vec<Star>) -> f64 {
+	let mut errors: Vec<f64> = s.iter().zip(reference.iter()).map(|(x, y)| {
+		let d: f64 = (0..3).map(|k| (x.a[k] - y.a[k]).powi(2)).sum();
+		let a: f64 = (0..3).map(|k| y.a[k].powi(2)).sum();
+		(d/a).sqrt()
+	}).collect();
+	errors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	errors[errors.len()/2]
+}
+
+// With the short-range pairs the mesh reproduces the Ewald forces and energy
+#[test]
+fn p3m_matches_ewald() {
+	let p = Params::default();
+	let s = generate::cube(200, 1.0, &mut Rng::new(1));
+	let mut reference = s.clone();
+	let mut ewald = Periodic::new(1.0);
+	ewald.accelerations(&mut reference, &p);
+	for &grid in &[16, 32] {
+		let mut x = s.clone();
+		let mut p3m = Pm::new(1.0, grid, true);
+		p3m.accelerations(&mut x, &p);
+		assert!(median_error(&x, &reference) < 2e-2, "grid {}: {}", grid, median_error(&x, &reference));
+		let (w, w0) = (ForceSolver::<f64>::energies(&p3m, &s, &p).unwrap()[2], ewald.energies(&s, &p).unwrap()[2]);
+		assert!((w - w0).abs() < 1e-3, "grid {}: W {} against {}", grid, w, w0);
+	}
+	assert_eq!(ForceSolver::<f64>::name(&Pm::new(1.0, 8, true)), "p3m");
+	assert_eq!(ForceSolver::<f64>::name(&Pm::new(1.0, 8, false)), "pm");
+}
+
+// Plain PM is smoothed over a few cells but gets the pull of distant stars right
+#[test]
+fn far_field() {
+	let p = Params::default();
+	let star = |x: f64| Star { m: 0.5, r: vec![x, 0.1, -0.2], v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] };
+	let mut reference = vec![star(-0.15), star(0.15)];
+	Periodic::new(1.0).accelerations(&mut reference, &p);
+	let mut s = reference.clone();
+	Pm::new(1.0, 32, false).accelerations(&mut s, &p);
+	assert!(median_error(&s, &reference) < 3e-2, "{}", median_error(&s, &reference));
+	assert!(s[0].a[0] > 0.0 && s[1].a[0] < 0.0);
+}
+
+// Stars on a lattice aligned with the mesh feel no force at all
+#[test]
+fn lattice() {
+	let p = Params::default();
+	let mut s = vec![];
+	for i in 0..4 {
+		for j in 0..4 {
+			for k in 0..4 {
+				let r = vec![-0.375 + 0.25*i as f64, -0.375 + 0.25*j as f64, -0.375 + 0.25*k as f64];
+				s.push(Star { m: 1.0/64.0, r: r, v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] });
+			}
+		}
+	}
+	Pm::new(1.0, 16, true).accelerations(&mut s, &p);
+	assert!(s.iter().all(|x| x.a.iter().all(|a| a.abs() < 1e-10)));
+}
@@ -0,0 +1,76 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::columns::Columns;
+use nbabel::generate;
+use nbabel::pn::PostNewtonian;
+
+fn orbit(e: f64) -> String {
+	let (r, v) = generate::kepler(1.0, 1.0, e, 0.0, 0.0);
+	format!("# columns: id m x y z vx vy vz pn\n0 1 0 0 0 0 0 0 1\n1 1e-6 {} {} {} {} {} {} 1\n", r[0], r[1], r[2], v[0], v[1], v[2])
+}
+
+// A test mass precesses by 6 pi m/(c^2 a (1 - e^2)) per orbit, measured at the
+// closest approaches
+#[test]
+fn periapsis_precession() {
+	let (e, c, orbits) = (0.5, 30.0, 20);
+	let text = orbit(e);
+	let run = |pn: bool| {
+		let mut p = Params::default();
+		p.dt = 1e-3;
+		let mut sim = Simulation::new(read_stars(&text), p);
+		if pn {
+			sim.set_post_newtonian(PostNewtonian::new(c, false, 2, &Columns::read(&text).unwrap()));
+		}
+		let distance = |s: &Vec<Star>| ((s[1].r[0] - s[0].r[0]).powi(2) + (s[1].r[1] - s[0].r[1]).powi(2)).sqrt();
+		let (mut passes, mut angle) = (0, 0.0);
+		// Starts at periapsis, which does not count
+		let (mut before, mut now) = (distance(&sim.s), distance(&sim.s));
+		let mut last = sim.s.clone();
+		while passes < orbits {
+			sim.step();
+			let next = distance(&sim.s);
+			if now < before && now < next {
+				passes += 1;
+				angle = (last[1].r[1] - last[0].r[1]).atan2(last[1].r[0] - last[0].r[0]);
+			}
+			before = now;
+			now = next;
+			last = sim.s.clone();
+		}
+		angle
+	};
+	let shift = run(true) - run(false);
+	let expected = orbits as f64*6.0*std::f64::consts::PI/(c*c*(1.0 - e*e));
+	assert!((shift/expected - 1.0).abs() < 0.03, "{} vs {}", shift, expected);
+}
+
+// On a circular orbit the radiation reaction takes out the Peters power
+#[test]
+fn radiation_power() {
+	let (m1, m2, r, c) = (0.3, 0.7, 2.0, 10.0);
+	let m = m1 + m2;
+	let eta = m1*m2/(m*m);
+	let (x, v) = ([r, 0.0, 0.0], [0.0, (m/r).sqrt(), 0.0]);
+	let conservative = PostNewtonian { c: c, radiation: false, flagged: vec![] }.relative(m, eta, x, v);
+	let full = PostNewtonian { c: c, radiation: true, flagged: vec![] }.relative(m, eta, x, v);
+	let power = eta*m*(0..3).map(|k| v[k]*(full[k] - conservative[k])).sum::<f64>();
+	let peters = -32.0/5.0*eta*eta*m.powi(5)/(r.powi(5)*c.powi(5));
+	assert!((power/peters - 1.0).abs() < 1e-12, "{} vs {}", power, peters);
+}
+
+// Only flagged pairs are corrected, and momentum is kept
+#[test]
+fn flagged_pairs() {
+	let text = "# columns: id m x y z vx vy vz pn\n0 0.4 0 0 0 0 0.3 0 1\n1 0.6 1 0 0 0 -0.2 0 1\n2 0.1 0 3 0 0 0 0 0\n";
+	let mut s: Vec<Star> = read_stars(text);
+	let x = PostNewtonian::new(5.0, true, 3, &Columns::read(text).unwrap());
+	assert_eq!(x.flagged, vec![0, 1]);
+	x.add_accelerations(&mut s);
+	assert!(s[2].a.iter().all(|&a| a == 0.0));
+	for k in 0..3 {
+		assert!((s[0].m*s[0].a[k] + s[1].m*s[1].a[k]).abs() < 1e-15);
+	}
+	assert!(s[0].a[0] != 0.0);
+}
@@ -0,0 +1,69 @@
+extern crate nbabel;
+
+use nbabel::*;
+
+fn plummer_like<R: Real>() -> Vec<Star<R>> {
+	read_stars(&std::fs::read_to_string("input/input2k").unwrap().lines().take(64).collect::<Vec<_>>().join("\n"))
+}
+
+fn run<R: Real>(steps: usize) -> Vec<f64> {
+	let mut sim: Simulation<R> = Simulation::new(plummer_like(), Params::default());
+	for _ in 0..steps {
+		sim.step();
+	}
+	sim.energies().iter().map(|x| x.to_f64()).collect()
+}
+
+// The same code path runs in both precisions and agrees to f32 accuracy
+#[test]
+fn f32_tracks_f64() {
+	let e32 = run::<f32>(50);
+	let e64 = run::<f64>(50);
+	for i in 0..3 {
+		assert!(((e32[i] - e64[i])/e64[i]).abs() < 1e-4, "{:?} vs {:?}", e32, e64);
+	}
+}
+
+#[test]
+fn double_double_arithmetic() {
+	use nbabel::dd::DoubleDouble;
+	let three = DoubleDouble::from_f64(3.0);
+	let third = DoubleDouble::one()/three;
+	assert!((third*three - DoubleDouble::one()).abs().to_f64() < 1e-30);
+
+	let two = DoubleDouble::from_f64(2.0);
+	let root = two.sqrt();
+	assert!((root*root - two).abs().to_f64() < 1e-30);
+	assert!((two.cbrt().powi(3) - two).abs().to_f64() < 1e-30);
+
+	// Parsing keeps digits that an f64 would round away
+	let x: DoubleDouble = "0.1".parse().unwrap();
+	assert!((x*DoubleDouble::from_f64(10.0) - DoubleDouble::one()).abs().to_f64() < 1e-31);
+	assert_eq!(format!("{:e}", x), "1.0000000000000000000000000000000e-1");
+}
+
+#[test]
+fn double_double_tracks_f64() {
+	let edd = run::<nbabel::dd::DoubleDouble>(20);
+	let e64 = run::<f64>(20);
+	for i in 0..3 {
+		assert!(((edd[i] - e64[i])/e64[i]).abs() < 1e-12, "{:?} vs {:?}", edd, e64);
+	}
+}
+
+// f32 pair terms with f64 accumulation stay within f32 rounding of the pure kernel
+#[test]
+fn mixed_kernel_matches_f64() {
+	let mut exact: Vec<Star> = plummer_like();
+	let mut mixed = exact.clone();
+	let mut p = Params::default();
+	acceleration(&mut exact, &p);
+	p.mixed = true;
+	acceleration(&mut mixed, &p);
+	for (x, y) in exact.iter().zip(mixed.iter()) {
+		let norm = (x.a[0]*x.a[0] + x.a[1]*x.a[1] + x.a[2]*x.a[2]).sqrt();
+		for i in 0..3 {
+			assert!((x.a[i] - y.a[i]).abs() < 1e-5*norm);
+		}
+	}
+}
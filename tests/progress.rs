@@ -0,0 +1,22 @@
+extern crate nbabel;
+
+use nbabel::progress::{self, Bar};
+
+#[test]
+fn durations() {
+	assert_eq!(progress::duration(12.4), "12s");
+	assert_eq!(progress::duration(192.0), "3m 12s");
+	assert_eq!(progress::duration(7500.0), "2h 05m");
+	assert_eq!(progress::duration(f64::INFINITY), "?");
+}
+
+// Halfway after 10 s and 500 steps: 50 steps/s and 10 s to go
+#[test]
+fn bar_line() {
+	let bar = Bar::new(1.0, 3.0, 100);
+	let line = bar.line(2.0, 600, 10.0);
+	assert!(line.starts_with(&format!("[{}{}]  50.0%", "#".repeat(15), ".".repeat(15))), "{}", line);
+	assert!(line.contains("50.0 steps/s") && line.ends_with("ETA 10s"), "{}", line);
+	assert!(bar.line(1.0, 100, 0.0).ends_with("ETA ?"));
+	assert!(bar.line(5.0, 100, 1.0).contains("100.0%"));
+}
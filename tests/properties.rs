@@ -0,0 +1,146 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::rng::Rng;
+
+/*
+ Property tests: every invariant is checked on many random small systems,
+ each from its own seed, which the failure message gives so the case can be
+ replayed with property(1, ..) from that seed.
+ */
+
+const CASES: u64 = 200;
+
+fn property<F: Fn(&mut Rng) -> Result<(), String>>(cases: u64, f: F) {
+	for seed in 0..cases {
+		if let Err(msg) = f(&mut Rng::new(seed)) {
+			panic!("seed {}: {}", seed, msg);
+		}
+	}
+}
+
+// 2 to 16 stars of total mass about 1 in a unit ball, with random velocities
+fn system(rng: &mut Rng) -> Vec<Star> {
+	let n = 2 + (rng.next_u64()%15) as usize;
+	(0..n).map(|_| {
+		let r = (0..3).map(|_| rng.range(-1.0, 1.0)).collect();
+		let v = (0..3).map(|_| 0.3*rng.normal()).collect();
+		Star { m: rng.range(0.1, 2.0)/n as f64, r: r, v: v, a: vec![0.0; 3], a0: vec![0.0; 3] }
+	}).collect()
+}
+
+fn params(eps: f64, dt: f64) -> Params {
+	let mut p = Params::default();
+	p.eps = eps;
+	p.dt = dt;
+	p
+}
+
+fn momentum(s: &Vec<Star>) -> [f64; 3] {
+	let mut x = [0.0; 3];
+	for star in s {
+		for k in 0..3 {
+			x[k] += star.m*star.v[k];
+		}
+	}
+	x
+}
+
+// Pairwise forces leave the total momentum unchanged: sum m a = 0 to rounding
+#[test]
+fn forces_conserve_momentum() {
+	property(CASES, |rng| {
+		let sim = Simulation::new(system(rng), params(0.0, 1e-3));
+		let scale: f64 = sim.s.iter().map(|x| x.m*(x.a[0].powi(2) + x.a[1].powi(2) + x.a[2].powi(2)).sqrt()).sum();
+		for k in 0..3 {
+			let total: f64 = sim.s.iter().map(|x| x.m*x.a[k]).sum();
+			if total.abs() > 1e-13*scale {
+				return Err(format!("sum m a[{}] = {:e} against {:e}", k, total, scale));
+			}
+		}
+		let p0 = momentum(&sim.s);
+		let mut sim = sim;
+		for _ in 0..10 {
+			sim.step();
+		}
+		let p1 = momentum(&sim.s);
+		for k in 0..3 {
+			if (p1[k] - p0[k]).abs() > 1e-12 {
+				return Err(format!("momentum[{}] went from {:e} to {:e}", k, p0[k], p1[k]));
+			}
+		}
+		Ok(())
+	});
+}
+
+// Two stars alone pull on each other equally and oppositely, along the line between them
+#[test]
+fn forces_are_antisymmetric() {
+	property(CASES, |rng| {
+		let mut s = system(rng);
+		s.truncate(2);
+		let sim = Simulation::new(s, params(0.0, 1e-3));
+		let (a, b) = (&sim.s[0], &sim.s[1]);
+		let d: Vec<f64> = (0..3).map(|k| b.r[k] - a.r[k]).collect();
+		let f = a.m*(a.a[0].powi(2) + a.a[1].powi(2) + a.a[2].powi(2)).sqrt();
+		for k in 0..3 {
+			if (a.m*a.a[k] + b.m*b.a[k]).abs() > 1e-14*f {
+				return Err(format!("m_0 a_0[{}] = {:e}, m_1 a_1[{}] = {:e}", k, a.m*a.a[k], k, b.m*b.a[k]));
+			}
+		}
+		// a_0 is parallel to d: their cross product vanishes
+		let cross = [a.a[1]*d[2] - a.a[2]*d[1], a.a[2]*d[0] - a.a[0]*d[2], a.a[0]*d[1] - a.a[1]*d[0]];
+		let size = (d[0].powi(2) + d[1].powi(2) + d[2].powi(2)).sqrt()*f/a.m;
+		if cross.iter().any(|x| x.abs() > 1e-14*size) {
+			return Err(format!("force not central: a_0 x d = {:?}", cross));
+		}
+		Ok(())
+	});
+}
+
+// With softening and a small step the energy error stays small: leapfrog has no secular drift
+#[test]
+fn energy_drift_is_bounded() {
+	property(CASES/4, |rng| {
+		let mut sim = Simulation::new(system(rng), params(0.1, 1e-4));
+		let e0 = sim.energies()[0];
+		let mut worst: f64 = 0.0;
+		for _ in 0..500 {
+			sim.step();
+			worst = worst.max(((sim.energies()[0] - e0)/e0).abs());
+		}
+		if worst > 1e-6 {
+			return Err(format!("|dE| reached {:e}", worst));
+		}
+		Ok(())
+	});
+}
+
+// Leapfrog is time-symmetric: forward, reverse the velocities, forward again, reverse
+#[test]
+fn time_reversal() {
+	property(CASES/4, |rng| {
+		let s = system(rng);
+		let start: Vec<(Vec<f64>, Vec<f64>)> = s.iter().map(|x| (x.r.clone(), x.v.clone())).collect();
+		let mut sim = Simulation::new(s, params(0.1, 1e-3));
+		for _ in 0..200 {
+			sim.step();
+		}
+		for star in sim.s.iter_mut() {
+			for k in 0..3 {
+				star.v[k] = -star.v[k];
+			}
+		}
+		for _ in 0..200 {
+			sim.step();
+		}
+		for (i, (star, &(ref r, ref v))) in sim.s.iter().zip(start.iter()).enumerate() {
+			for k in 0..3 {
+				if (star.r[k] - r[k]).abs() > 1e-9 || (star.v[k] + v[k]).abs() > 1e-9 {
+					return Err(format!("star {} came back to r {:?} v {:?}, started at r {:?} v {:?}", i, star.r, star.v, r, v));
+				}
+			}
+		}
+		Ok(())
+	});
+}
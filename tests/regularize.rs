@@ -0,0 +1,70 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::generate;
+use nbabel::regularize;
+
+fn close(a: &[f64], b: &[f64], tolerance: f64) -> bool {
+	a.iter().zip(b).all(|(x, y)| (x - y).abs() < tolerance)
+}
+
+// An e = 0.99 orbit comes back after a period, in one or in many drifts
+#[test]
+fn kepler_drift_period() {
+	let (r, v) = generate::kepler(1.0, 1.0, 0.99, 0.3, 0.2);
+	let (r0, v0) = ([r[0], r[1], r[2]], [v[0], v[1], v[2]]);
+	let period = generate::period(1.0, 1.0);
+	let (r1, v1) = regularize::kepler_drift(1.0, r0, v0, 3.0*period);
+	assert!(close(&r1, &r0, 1e-9) && close(&v1, &v0, 1e-8), "{:?} {:?}", r1, v1);
+	let (mut r2, mut v2) = (r0, v0);
+	for _ in 0..97 {
+		let x = regularize::kepler_drift(1.0, r2, v2, period/97.0);
+		r2 = x.0;
+		v2 = x.1;
+	}
+	assert!(close(&r2, &r0, 1e-9) && close(&v2, &v0, 1e-8), "{:?} {:?}", r2, v2);
+}
+
+// A hyperbolic passage keeps its energy and angular momentum
+#[test]
+fn kepler_drift_hyperbolic() {
+	let (r0, v0) = ([-10.0, 0.1, 0.0], [2.0, 0.0, 0.0]);
+	let (r, v) = regularize::kepler_drift(1.0, r0, v0, 10.0);
+	let energy = |r: [f64; 3], v: [f64; 3]| 0.5*(v[0]*v[0] + v[1]*v[1] + v[2]*v[2]) - 1.0/(r[0]*r[0] + r[1]*r[1] + r[2]*r[2]).sqrt();
+	let lz = |r: [f64; 3], v: [f64; 3]| r[0]*v[1] - r[1]*v[0];
+	assert!((energy(r, v) - energy(r0, v0)).abs() < 1e-10);
+	assert!((lz(r, v) - lz(r0, v0)).abs() < 1e-10);
+	let (mut r2, mut v2) = (r0, v0);
+	for _ in 0..100 {
+		let x = regularize::kepler_drift(1.0, r2, v2, 0.1);
+		r2 = x.0;
+		v2 = x.1;
+	}
+	assert!(close(&r2, &r, 1e-8) && close(&v2, &v, 1e-8), "{:?} vs {:?}", r2, r);
+	assert!((r[0]*r[0] + r[1]*r[1]).sqrt() > 9.0);
+}
+
+// A hard eccentric binary in a loose group, at a step far too coarse for its pericentre
+#[test]
+fn hard_binary() {
+	let run = |radius: Option<f64>| {
+		let mut s = generate::binary(0.01, 0.9, 1.0);
+		s.extend(read_stars::<f64>("2 0.1 2 0 0 0 0.3 0\n3 0.1 -2 1 0 0 -0.3 0\n4 0.1 0 -2 1 0.2 0 0\n"));
+		let mut p = Params::default();
+		p.dt = 1e-3;
+		let mut sim = Simulation::new(s, p);
+		if let Some(r) = radius {
+			sim.regularize(r);
+		}
+		let e0 = sim.energies()[0];
+		for _ in 0..500 {
+			sim.step();
+		}
+		(((sim.energies()[0] - e0)/e0).abs(), sim.events.len())
+	};
+	let (plain, _) = run(None);
+	let (regularized, events) = run(Some(0.05));
+	assert_eq!(events, 1);
+	assert!(regularized < 1e-6, "dE = {}", regularized);
+	assert!(regularized < 1e-3*plain, "dE = {} vs {}", regularized, plain);
+}
@@ -0,0 +1,77 @@
+extern crate nbabel;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::Duration;
+
+use nbabel::generate;
+use nbabel::serve;
+
+// The example of RFC 6455, section 1.3
+#[test]
+fn accept_key() {
+	assert_eq!(serve::accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+}
+
+#[test]
+fn frame_lengths() {
+	assert_eq!(serve::text_frame("hi"), vec![0x81, 2, b'h', b'i']);
+	let x = serve::text_frame(&"x".repeat(300));
+	assert_eq!(&x[..4], &[0x81, 126, 1, 44]);
+	assert_eq!(x.len(), 304);
+	let x = serve::text_frame(&"x".repeat(70000));
+	assert_eq!(&x[..10], &[0x81, 127, 0, 0, 0, 0, 0, 1, 0x11, 0x70]);
+}
+
+#[test]
+fn message() {
+	let s = generate::binary(1.0, 0.0, 1.0);
+	let text = serve::message(0.5, 500, &[-0.25, 0.125, -0.375], 1e-9, &s);
+	assert!(text.starts_with("{\"t\": 5e-1, \"steps\": 500, \"n\": 2, \"E\": -2.5e-1, \"T\": 1.25e-1, \"W\": -3.75e-1, \"dE\": 1e-9, \"positions\": ["), "{}", text);
+	// Seven fields before the positions, and six coordinates
+	assert_eq!(text.matches(',').count(), 7 + 5);
+	assert!(text.ends_with("]}"));
+}
+
+#[test]
+fn stream() {
+	let mut server = serve::Server::bind("ws://127.0.0.1:0", 1000.0).unwrap();
+	assert!(!server.due());
+	let mut client = TcpStream::connect(server.local_addr()).unwrap();
+	write!(client, "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").unwrap();
+	let mut reader = BufReader::new(client.try_clone().unwrap());
+	let mut lines = vec![];
+	loop {
+		let mut line = String::new();
+		reader.read_line(&mut line).unwrap();
+		if line.trim().is_empty() {
+			break;
+		}
+		lines.push(line.trim().to_string());
+	}
+	assert_eq!(lines[0], "HTTP/1.1 101 Switching Protocols");
+	assert!(lines.contains(&String::from("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")));
+
+	// The client list is filled in by the accepting thread
+	for _ in 0..100 {
+		if server.clients() == 1 {
+			break;
+		}
+		thread::sleep(Duration::from_millis(10));
+	}
+	assert!(server.due());
+	server.broadcast("{\"t\": 0}");
+	let mut frame = [0u8; 10];
+	reader.read_exact(&mut frame).unwrap();
+	assert_eq!(&frame[..2], &[0x81, 8]);
+	assert_eq!(&frame[2..], b"{\"t\": 0}");
+	assert!(!server.due());
+}
+
+#[test]
+fn bad_address() {
+	assert!(serve::Server::bind("http://127.0.0.1:0", 10.0).is_err());
+	assert!(serve::Server::bind("ws://127.0.0.1:0/path", 10.0).is_err());
+	assert!(serve::Server::bind("ws://127.0.0.1:0", 0.0).is_err());
+}
@@ -0,0 +1,40 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::simd::Isa;
+
+fn stars() -> Vec<Star> {
+	// 101 stars so every vector width leaves a remainder
+	read_stars(&std::fs::read_to_string("input/input2k").unwrap().lines().take(101).collect::<Vec<_>>().join("\n"))
+}
+
+// Every instruction set this CPU has agrees with the pair loop, for every softening rule
+#[test]
+fn vector_kernels_match_pair_loop() {
+	for &(eps, rule) in &[(0.0, Softening::Fixed), (0.01, Softening::Fixed), (0.01, Softening::Mean), (0.01, Softening::Min)] {
+		let mut p = Params::default();
+		p.eps = eps;
+		p.softening = rule;
+		let mut reference = stars();
+		acceleration(&mut reference, &p);
+
+		for &isa in &[Isa::Scalar, Isa::Avx2, Isa::Avx512, Isa::Neon] {
+			if !isa.available() {
+				continue;
+			}
+			for &threads in &[1, 3] {
+				let mut s = stars();
+				p.simd = Some(isa);
+				p.threads = threads;
+				acceleration(&mut s, &p);
+				p.simd = None;
+				p.threads = thread_count();
+				for (x, y) in reference.iter().zip(s.iter()) {
+					for i in 0..3 {
+						assert!((x.a[i] - y.a[i]).abs() <= 1e-10*(1.0 + x.a[i].abs()), "{} {:?}: {} vs {}", isa.name(), rule, x.a[i], y.a[i]);
+					}
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,127 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::solver::{Auto, ForceSolver, Tree};
+
+fn stars(n: usize) -> Vec<Star> {
+	read_stars(&std::fs::read_to_string("input/input2k").unwrap().lines().take(n).collect::<Vec<_>>().join("\n"))
+}
+
+// The tree tracks direct summation to about theta^2, and exactly at theta = 0
+#[test]
+fn tree_matches_direct() {
+	let p = Params::default();
+	let mut direct = stars(1000);
+	acceleration(&mut direct, &p);
+	for &(theta, tolerance) in &[(0.0, 1e-10), (0.5, 2e-2)] {
+		let mut s = stars(1000);
+		Tree::new(theta).accelerations(&mut s, &p);
+		let mut errors: Vec<f64> = direct.iter().zip(s.iter()).map(|(x, y)| {
+			let d: f64 = (0..3).map(|i| (x.a[i] - y.a[i]).powi(2)).sum();
+			let a: f64 = (0..3).map(|i| x.a[i].powi(2)).sum();
+			(d/a).sqrt()
+		}).collect();
+		errors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+		assert!(errors[errors.len()/2] < tolerance, "theta = {}: median error {}", theta, errors[errors.len()/2]);
+	}
+}
+
+// Auto starts direct for a small system and switches once enough stars are active
+#[test]
+fn auto_switches_on_active_count() {
+	let p = Params::default();
+	let mut auto: Auto = Auto::new(0.5);
+	auto.min_n = 100;
+	let mut s = stars(150);
+	auto.accelerations(&mut s, &p);
+	assert_eq!(auto.name(), "tree");
+	for star in s.iter_mut().take(100) {
+		star.m = 0.0;
+	}
+	auto.accelerations(&mut s, &p);
+	assert_eq!(auto.name(), "direct");
+	let events = auto.events();
+	assert_eq!(events.len(), 2);
+	assert!(events[1].starts_with("solver switch to direct"));
+}
+
+// Auto measures the depth on the tree it uses, and probes only now and then in direct mode
+#[test]
+fn auto_probes_sparingly() {
+	let p = Params::default();
+	let mut auto: Auto = Auto::new(0.5);
+	auto.min_n = 100;
+	let mut s = stars(150);
+	for _ in 0..5 {
+		auto.accelerations(&mut s, &p);
+	}
+	assert_eq!((auto.name(), auto.probes, auto.tree.builds), ("tree", 1, 5));
+
+	// Too deep for the tree: direct, with a fresh probe every probe_every evaluations
+	let mut auto: Auto = Auto::new(0.5);
+	auto.min_n = 100;
+	auto.max_depth = 0;
+	auto.probe_every = 4;
+	for _ in 0..9 {
+		auto.accelerations(&mut s, &p);
+	}
+	assert_eq!((auto.name(), auto.probes, auto.tree.builds), ("direct", 3, 0));
+	// Dropping below min_n and climbing back probes at once
+	let m = s[0].m;
+	s[0].m = 0.0;
+	s.truncate(100);
+	auto.accelerations(&mut s, &p);
+	s[0].m = m;
+	auto.accelerations(&mut s, &p);
+	assert_eq!(auto.probes, 4);
+}
+
+// Field points through the tree agree with direct summation, and with the point mass far out
+#[test]
+fn field_at_points() {
+	let p = Params::default();
+	let s = stars(1000);
+	let points = [[0.1, 0.2, -0.3], [1.5, 0.0, 0.0], [100.0, 0.0, 0.0]];
+	let direct = solver::field_at(&s, &p, &points);
+	let tree = Tree::new(0.5).field(&s, &p, &points);
+	for (x, y) in direct.iter().zip(tree.iter()) {
+		assert!(((x.0 - y.0)/x.0).abs() < 1e-2);
+	}
+	let mass: f64 = s.iter().map(|x| x.m).sum();
+	assert!((direct[2].0 + mass/100.0).abs() < 1e-2*mass/100.0);
+	assert!((direct[2].1[0] + mass/1e4).abs() < 1e-2*mass/1e4);
+
+	let sim = Simulation::new(vec![Star { m: 1.0, r: vec![0.0; 3], v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] }], p);
+	assert_eq!(sim.potential_at(&[[0.0, 2.0, 0.0]]), vec![-0.5]);
+	assert_eq!(sim.acceleration_at(&[[0.0, 2.0, 0.0]]), vec![[0.0, -0.25, 0.0]]);
+}
+
+// Quadrupoles make the tree more accurate at the same theta, with either opening criterion
+#[test]
+fn multipoles_and_opening() {
+	let p = Params::default();
+	let s = stars(1000);
+	let median = |solver: &mut dyn ForceSolver<f64>| solver::ErrorSummary::new(&solver::force_errors(solver, &s, &p, 200, &mut rng::Rng::new(1))).median;
+	let mut monopole = Tree::new(0.5);
+	let mut quadrupole = Tree::new(0.5);
+	quadrupole.quadrupole = true;
+	let (e1, e2) = (median(&mut monopole), median(&mut quadrupole));
+	assert!(e2 < 0.5*e1, "monopole {}, quadrupole {}", e1, e2);
+	let mut bmax = solver::by_name_with::<f64>("tree", 0.5, tree::Opening::Bmax, true, None).unwrap();
+	assert!(median(&mut *bmax) < 2e-2);
+	assert!(median(&mut solver::Direct) < 1e-12);
+	assert_eq!(tree::Opening::parse("bh"), Some(tree::Opening::Geometric));
+	assert_eq!(tree::Opening::parse("quad"), None);
+}
+
+// The checked stars are distinct, and the summary quantiles are order statistics
+#[test]
+fn error_summary() {
+	let p = Params::default();
+	let s = stars(10);
+	assert_eq!(solver::force_errors(&mut Tree::new(0.5), &s, &p, 50, &mut rng::Rng::new(2)).len(), 10);
+	let errors: Vec<f64> = (1..=100).rev().map(|x| x as f64).collect();
+	let x = solver::ErrorSummary::new(&errors);
+	assert_eq!((x.count, x.median, x.p90, x.p99, x.max), (100, 51.0, 91.0, 100.0, 100.0));
+	assert_eq!(format!("{}", solver::ErrorSummary::new(&[1e-3])), "median 1.00e-3, 90% 1.00e-3, 99% 1.00e-3, max 1.00e-3, rms 1.00e-3 over 1 stars");
+}
@@ -0,0 +1,46 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::solver::Tree;
+
+fn star(m: f64, x: f64) -> Star {
+	Star { m: m, r: vec![x, 0.0, 0.0], v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] }
+}
+
+// Three stars on a line at 0, 1 and 3
+#[test]
+fn known_system() {
+	let s = vec![star(1.0, 0.0), star(1.0, 1.0), star(2.0, 3.0)];
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	let mut sim = Simulation::new(s.clone(), p.clone());
+	sim.step();
+	let x = sim.stats();
+	assert_eq!((x.n, x.mass, x.steps, x.dt), (3, 4.0, 1, 1e-3));
+	assert!((x.t - 1e-3).abs() < 1e-15);
+	assert!((x.min_separation - 1.0).abs() < 1e-5 && (x.max_separation - 3.0).abs() < 1e-5);
+	// Pair timescales sqrt(r^3/(mi + mj)): sqrt(1/2), sqrt(8/3) and 3; each star takes its shortest
+	let (fast, slow) = (masses::ETA*0.5f64.sqrt(), masses::ETA*(8.0f64/3.0).sqrt());
+	let (min, median, max) = x.dt_distribution;
+	assert!((min - fast).abs() < 1e-6 && (median - fast).abs() < 1e-6 && (max - slow).abs() < 1e-6, "{:?}", x.dt_distribution);
+	assert_eq!(x.tree_depth, None);
+
+	// Up to tree::LEAF stars fit in the root, one more splits it
+	let tree = Simulation::with_solver(s, p.clone(), Box::new(Tree::new(0.5))).stats();
+	assert_eq!(tree.tree_depth, Some(0));
+	let line: Vec<Star> = (0..tree::LEAF + 1).map(|i| star(1.0, i as f64)).collect();
+	let tree = Simulation::with_solver(line, p, Box::new(Tree::new(0.5))).stats();
+	assert!(tree.tree_depth.is_some_and(|d| d >= 1), "{:?}", tree.tree_depth);
+}
+
+// A lone star has no pairs: separations and step sizes are 0, not infinite
+#[test]
+fn one_star() {
+	let x = Simulation::new(vec![star(1.0, 2.0)], Params::default()).stats();
+	assert_eq!((x.n, x.mass), (1, 1.0));
+	assert_eq!((x.min_separation, x.max_separation), (0.0, 0.0));
+	assert_eq!(x.dt_distribution, (0.0, 0.0, 0.0));
+	assert!(x.to_string().contains("separations:  - (no pairs)"));
+	let none = Simulation::<f64>::new(vec![], Params::default()).stats();
+	assert_eq!((none.n, none.min_separation, none.dt_distribution.2), (0, 0.0, 0.0));
+}
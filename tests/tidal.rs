@@ -0,0 +1,48 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::generate;
+use nbabel::rng::Rng;
+use nbabel::tidal::Tidal;
+
+// In a point mass galaxy a star at x moving at v_y = -3/2 omega x follows the shear;
+// the splitting only adds a small epicycle, of order (omega dt)^2
+#[test]
+fn shearing_orbit() {
+	let omega = 0.5;
+	let s: Vec<Star> = read_stars(&format!("0 1e-12 1 0 0 0 {} 0\n", -1.5*omega));
+	let mut p = Params::default();
+	p.dt = 0.01;
+	let mut sim = Simulation::new(s, p);
+	sim.set_tidal(Tidal::kepler(omega));
+	for _ in 0..1000 {
+		sim.step();
+	}
+	assert!((sim.s[0].r[0] - 1.0).abs() < 1e-4, "x = {}", sim.s[0].r[0]);
+	assert!((sim.s[0].r[1] + 1.5*omega*sim.t).abs() < 1e-3, "y = {}", sim.s[0].r[1]);
+}
+
+// A cluster well inside its tidal radius keeps its Jacobi energy
+#[test]
+fn jacobi_energy() {
+	let tidal = Tidal::parse("omega=0.1,kappa=0.1414").unwrap();
+	assert!(tidal.tidal_radius(1.0) > 3.0);
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	p.eps = 0.01;
+	let mut sim = Simulation::new(generate::king(100, 6.0, &mut Rng::new(3)), p);
+	sim.set_tidal(tidal);
+	let e0 = sim.energies();
+	for _ in 0..300 {
+		sim.step();
+	}
+	assert!(((sim.energies()[0] - e0[0])/e0[0]).abs() < 1e-4);
+}
+
+#[test]
+fn bad_specs() {
+	assert!(Tidal::parse("kappa=1").is_err());
+	assert!(Tidal::parse("omega=1,kappa=2").is_err());
+	assert!(Tidal::parse("omega=1,mu=2").is_err());
+	assert_eq!(Tidal::parse("omega=2").unwrap(), Tidal::kepler(2.0));
+}
@@ -0,0 +1,67 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::generate;
+use nbabel::timestep::{shortest, Timestep};
+
+// Relative energy errors of an e = 0.9 binary over the given time
+fn errors(symmetric: bool, tend: f64) -> Vec<f64> {
+	let mut p = Params::default();
+	p.dt = 0.1;
+	let mut sim = Simulation::new(generate::binary(1.0, 0.9, 1.0), p);
+	sim.timestep = Some(Timestep { eta: 0.02, max: 0.1, symmetric: symmetric });
+	let e0 = sim.energies()[0];
+	let mut x = vec![];
+	while sim.t < tend {
+		sim.adapt();
+		sim.step();
+		x.push(((sim.energies()[0] - e0)/e0).abs());
+	}
+	x
+}
+
+fn worst(x: &[f64]) -> f64 {
+	x.iter().cloned().fold(0.0, f64::max)
+}
+
+// The energy error grows from orbit to orbit with plain adaptive steps, not with symmetrized ones
+#[test]
+fn secular_drift() {
+	for &symmetric in [false, true].iter() {
+		let e = errors(symmetric, 200.0);
+		let n = e.len();
+		let (early, late) = (worst(&e[..n/10]), worst(&e[9*n/10..]));
+		if symmetric {
+			assert!(late < 1.01*early, "{:e} -> {:e}", early, late);
+		} else {
+			assert!(late > 1.5*early, "{:e} -> {:e}", early, late);
+		}
+	}
+}
+
+#[test]
+fn pair_timescale() {
+	let s = read_stars::<f64>("0 0.25 0 0 0 0 0 0\n1 0.75 1 0 0 0 0 0\n2 0 5 0 0 0 0 0\n");
+	let mut p = Params::default();
+	assert!((shortest(&s, &p) - 1.0).abs() < 1e-12);
+	p.eps = 1.0;
+	assert!((shortest(&s, &p) - 2f64.powf(0.75)).abs() < 1e-12);
+	assert_eq!(shortest(&s[..1].to_vec(), &p), std::f64::INFINITY);
+}
+
+// dt follows the pericentre passage and stays below the cap
+#[test]
+fn step_sizes() {
+	let mut p = Params::default();
+	p.dt = 0.05;
+	let mut sim = Simulation::new(generate::binary(1.0, 0.9, 1.0), p);
+	sim.timestep = Some(Timestep { eta: 0.02, max: 0.05, symmetric: true });
+	let mut sizes = vec![];
+	while sim.t < 7.0 {
+		sim.adapt();
+		sizes.push(sim.p.dt);
+		sim.step();
+	}
+	let (smallest, largest) = (sizes.iter().cloned().fold(1.0, f64::min), worst(&sizes));
+	assert!(largest <= 0.05 && smallest < 0.01, "{} {}", smallest, largest);
+}
@@ -0,0 +1,40 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::timing::{Phase, Times, Timers};
+
+#[test]
+fn report() {
+	let x = Times { seconds: [3.0, 0.5, 0.5, 0.0], steps: 100 };
+	assert_eq!(x.total(), 4.0);
+	assert_eq!(x.to_string(), "forces 3.000 s (75.0%, 30.000 ms/step), integration 0.500 s (12.5%, 5.000 ms/step), \
+		diagnostics 0.500 s (12.5%, 5.000 ms/step), I/O 0.000 s (0.0%, 0.000 ms/step)");
+	assert!(Times::default().to_string().starts_with("forces 0.000 s (0.0%, 0.000 ms/step)"));
+}
+
+#[test]
+fn intervals() {
+	let mut x = Timers::default();
+	x.add(Phase::Io, 1.0);
+	x.step();
+	let first = x.take_interval();
+	assert_eq!((first.get(Phase::Io), first.steps), (1.0, 1));
+	x.time(Phase::Diagnostics, || ());
+	x.step();
+	assert_eq!((x.interval.get(Phase::Io), x.interval.steps), (0.0, 1));
+	assert_eq!((x.run.get(Phase::Io), x.run.steps), (1.0, 2));
+}
+
+// The simulation times its own force evaluations and steps
+#[test]
+fn simulation_phases() {
+	let text: String = std::fs::read_to_string("input/input2k").unwrap().lines().take(200).collect::<Vec<_>>().join("\n");
+	let mut sim: Simulation = Simulation::new(read_stars(&text), Params::default());
+	for _ in 0..5 {
+		sim.step();
+	}
+	let run = sim.timers.run;
+	assert_eq!(run.steps, 5);
+	assert!(run.get(Phase::Forces) > 0.0 && run.get(Phase::Integration) > 0.0);
+	assert_eq!(run.get(Phase::Diagnostics) + run.get(Phase::Io), 0.0);
+}
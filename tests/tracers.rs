@@ -0,0 +1,70 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::rng::Rng;
+use nbabel::solver::{field_at, ForceSolver, Tree};
+
+// A cluster with every other star turned into a tracer, and the massive stars alone
+fn cluster() -> (Vec<Star>, Vec<Star>) {
+	let mut s = generate::plummer(200, &mut Rng::new(5));
+	for (i, star) in s.iter_mut().enumerate() {
+		if i % 3 != 0 {
+			star.m = 0.0;
+		}
+	}
+	let massive = s.iter().filter(|x| x.m > 0.0).cloned().collect();
+	(s, massive)
+}
+
+// Tracers feel the massive stars and leave them alone, on any number of threads
+#[test]
+fn forces() {
+	let (s, massive) = cluster();
+	let mut p = Params::default();
+	p.eps = 0.01;
+	for &threads in &[1, 3] {
+		p.threads = threads;
+		let mut x = s.clone();
+		let mut reference = massive.clone();
+		acceleration(&mut x, &p);
+		acceleration(&mut reference, &p);
+		let points: Vec<[f64; 3]> = x.iter().map(|x| [x.r[0], x.r[1], x.r[2]]).collect();
+		let field = field_at(&massive, &p, &points);
+		let mut j = 0;
+		for (i, star) in x.iter().enumerate() {
+			let expected = if star.m > 0.0 {
+				j += 1;
+				reference[j - 1].a.clone()
+			} else {
+				field[i].1.to_vec()
+			};
+			for k in 0..3 {
+				assert!((star.a[k] - expected[k]).abs() < 1e-10*(1.0 + expected[k].abs()), "star {} on {} threads", i, threads);
+			}
+		}
+	}
+	// The tree leaves them out of its cells too
+	let mut x = s.clone();
+	let mut reference = massive.clone();
+	Tree::new(0.5).accelerations(&mut x, &p);
+	Tree::new(0.5).accelerations(&mut reference, &p);
+	let mut tracers = x.iter().filter(|x| x.m > 0.0);
+	assert!(reference.iter().all(|y| tracers.next().unwrap().a == y.a));
+}
+
+// A tracer on a circular orbit around a single star stays on it, and the star stays put
+#[test]
+fn test_particle_orbit() {
+	let star = Star { m: 1.0, r: vec![0.0; 3], v: vec![0.0; 3], a: vec![0.0; 3], a0: vec![0.0; 3] };
+	let tracer = Star { m: 0.0, r: vec![1.0, 0.0, 0.0], v: vec![0.0, 1.0, 0.0], a: vec![0.0; 3], a0: vec![0.0; 3] };
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	let mut sim = Simulation::new(vec![star, tracer], p);
+	for _ in 0..3142 {
+		sim.step();
+	}
+	let r = sim.s[1].r.iter().map(|x| x*x).sum::<f64>().sqrt();
+	assert!((r - 1.0).abs() < 1e-6);
+	assert!(sim.s[1].r[0] < -0.999);
+	assert!(sim.s[0].r.iter().chain(sim.s[0].v.iter()).all(|&x| x == 0.0));
+}
@@ -0,0 +1,36 @@
+extern crate nbabel;
+
+use std::fs;
+
+use nbabel::*;
+use nbabel::track::{name, parse, Tracks, HEADER};
+
+#[test]
+fn parses_indices() {
+	assert_eq!(parse("0,5,17", 20), Ok(vec![0, 5, 17]));
+	assert_eq!(parse("3,3,", 20), Ok(vec![3]));
+	assert!(parse("20", 20).is_err());
+	assert!(parse("a", 20).is_err());
+	assert!(parse("", 20).is_err());
+}
+
+// One line per state, and a tracked star merged into another goes on as the merged star
+#[test]
+fn writes_and_follows_merges() {
+	let dir = std::env::temp_dir().join(format!("nbabel_track_{}", std::process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	let mut s: Vec<Star> = (0..4).map(|i| Star { m: 1.0, r: vec![i as f64, 0.0, 0.0], v: vec![0.0, i as f64, 0.0], a: vec![0.0; 3], a0: vec![0.0; 3] }).collect();
+	let mut tracks = Tracks::create(&dir, &[1, 3]).unwrap();
+	tracks.write(0.0, &s).unwrap();
+	// Star 1 merged into star 0, star 3 moves down to 2
+	tracks.merged(0, 1);
+	s.remove(1);
+	tracks.write(0.5, &s).unwrap();
+	tracks.finish().unwrap();
+
+	let one = fs::read_to_string(dir.join(name(1))).unwrap();
+	let three = fs::read_to_string(dir.join(name(3))).unwrap();
+	assert_eq!(one.lines().collect::<Vec<_>>(), vec![HEADER, "0,1,0,0,0,1,0", "0.5,0,0,0,0,0,0"]);
+	assert_eq!(three.lines().collect::<Vec<_>>(), vec![HEADER, "0,3,0,0,0,3,0", "0.5,3,0,0,0,3,0"]);
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,66 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::generate;
+use nbabel::rng::Rng;
+use nbabel::transform::{self, Op};
+
+#[test]
+fn euler_rotation_is_orthonormal() {
+	let m = transform::euler(0.3, 1.1, -2.0);
+	for i in 0..3 {
+		for j in 0..3 {
+			let dot: f64 = (0..3).map(|k| m[i][k]*m[j][k]).sum();
+			assert!((dot - if i == j { 1.0 } else { 0.0 }).abs() < 1e-14);
+		}
+	}
+}
+
+#[test]
+fn axis_angle_rotation() {
+	let m = transform::axis_angle([0.0, 0.0, 2.0], std::f64::consts::FRAC_PI_2);
+	let x = [m[0][0], m[1][0], m[2][0]];
+	assert!(x[0].abs() < 1e-15 && (x[1] - 1.0).abs() < 1e-15 && x[2].abs() < 1e-15);
+	let (a, b) = (transform::axis_angle([0.0, 0.0, 1.0], 0.7), transform::euler(0.7, 0.0, 0.0));
+	for i in 0..3 {
+		for j in 0..3 {
+			assert!((a[i][j] - b[i][j]).abs() < 1e-15);
+		}
+	}
+	assert!(transform::parse_op("--rotate-axis", "0,0,0,30").is_err());
+}
+
+// Rescaling keeps the virial ratio; energies scale as mass^2/length
+#[test]
+fn rescaling_keeps_the_dynamical_state() {
+	let mut s = generate::king(300, 6.0, &mut Rng::new(4));
+	let p = Params::default();
+	let e0 = energies(&s, &p);
+	let ops = [transform::parse_op("--scale-mass", "3").unwrap(), transform::parse_op("--scale-length", "0.5").unwrap(), Op::Shift([1.0, 2.0, 3.0])];
+	transform::apply_all(&mut s, &ops);
+	let e = energies(&s, &p);
+	assert!((diagnostics::virial_ratio(&e) - diagnostics::virial_ratio(&e0)).abs() < 1e-9);
+	assert!((e[0]/e0[0] - 9.0/0.5).abs() < 1e-9);
+	let com = diagnostics::center_of_mass(&s);
+	assert!((com[0] - 1.0).abs() < 1e-12 && (com[2] - 3.0).abs() < 1e-12);
+	assert!(transform::parse_op("--scale-length", "-1").is_err());
+}
+
+// Normalizing undoes a rescaling and reports it as the unit sizes
+#[test]
+fn normalize_to_henon_units() {
+	let mut s = generate::king(300, 6.0, &mut Rng::new(8));
+	transform::apply_all(&mut s, &[Op::Scale(5.0, 0.2), Op::Shift([3.0, 0.0, -1.0]), Op::Boost([0.0, 2.0, 0.0])]);
+	let scale = normalize::normalize(&mut s, &Params::default()).unwrap();
+	assert!((scale.mass/5.0 - 1.0).abs() < 1e-9 && (scale.length/0.2 - 1.0).abs() < 1e-9, "{}", scale);
+	assert!((scale.com[0] - 3.0).abs() < 1e-9 && (scale.vcom[1] - 2.0).abs() < 1e-9);
+	let e = energies(&s, &Params::default());
+	assert!((e[0] + 0.25).abs() < 1e-12 && (diagnostics::virial_ratio(&e) - 1.0).abs() < 1e-9);
+
+	let mut unbound = generate::king(50, 6.0, &mut Rng::new(8));
+	transform::apply_all(&mut unbound, &[Op::Scale(1.0, 100.0)]);
+	for star in unbound.iter_mut() {
+		star.v[0] *= 10.0;
+	}
+	assert!(normalize::normalize(&mut unbound, &Params::default()).is_err());
+}
@@ -0,0 +1,99 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::solver::{ForceSolver, Tree};
+
+fn stars(n: usize) -> Vec<Star> {
+	read_stars(&std::fs::read_to_string("input/input2k").unwrap().lines().take(n).collect::<Vec<_>>().join("\n"))
+}
+
+// Lets the stars drift along their velocities
+fn drift(s: &mut Vec<Star>, dt: f64) {
+	for star in s.iter_mut() {
+		for k in 0..3 {
+			star.r[k] += dt*star.v[k];
+		}
+	}
+}
+
+// Median relative error of the accelerations of s against a
+fn median_error(s: &Vec<Star>, a: &Vec<Star>) -> f64 {
+	let mut errors: Vec<f64> = s.iter().zip(a.iter()).map(|(x, y)| {
+		let d: f64 = (0..3).map(|i| (x.a[i] - y.a[i]).powi(2)).sum();
+		let a: f64 = (0..3).map(|i| y.a[i].powi(2)).sum();
+		(d/a).sqrt()
+	}).collect();
+	errors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+	errors[errors.len()/2]
+}
+
+// A refreshed tree gives the forces of a fresh build: exactly at theta = 0, as accurately at theta = 0.5
+#[test]
+fn refresh_matches_build() {
+	let p = Params::default();
+	let mut s = stars(1000);
+	for &quadrupole in &[false, true] {
+		let mut last = tree::Tree::build_padded(&s, tree::PAD);
+		last.quadrupole = quadrupole;
+		drift(&mut s, 0.01);
+		assert!(last.refresh(&s, 0.5));
+		let fresh = {
+			let mut x = tree::Tree::build(&s);
+			x.quadrupole = quadrupole;
+			x
+		};
+		let mut direct = s.clone();
+		acceleration(&mut direct, &p);
+		let (mut a, mut b) = (s.clone(), s.clone());
+		last.accelerations(&mut a, &p, 0.0);
+		assert!(median_error(&a, &direct) < 1e-10);
+		last.accelerations(&mut a, &p, 0.5);
+		fresh.accelerations(&mut b, &p, 0.5);
+		let (refreshed, built) = (median_error(&a, &direct), median_error(&b, &direct));
+		assert!(refreshed < 1.5*built, "quadrupole {}: refreshed {}, built {}", quadrupole, refreshed, built);
+	}
+}
+
+// Drifting too far, losing a star or turning one into a tracer calls for a full build
+#[test]
+fn rebuild_triggers() {
+	let s = stars(200);
+	let mut far = s.clone();
+	far[7].r[0] += 10.0;
+	assert!(!tree::Tree::build_padded(&s, tree::PAD).refresh(&far, 0.5));
+	let mut nudged = s.clone();
+	nudged[7].r[0] += 1e-6;
+	assert!(tree::Tree::build_padded(&s, tree::PAD).refresh(&nudged, 0.5));
+	assert!(!tree::Tree::build_padded(&s, tree::PAD).refresh(&nudged, 1e-12));
+	let mut fewer = s.clone();
+	fewer.pop();
+	assert!(!tree::Tree::build_padded(&s, tree::PAD).refresh(&fewer, 0.5));
+	let mut tracer = s.clone();
+	tracer[3].m = 0.0;
+	assert!(!tree::Tree::build_padded(&s, tree::PAD).refresh(&tracer, 0.5));
+}
+
+// A run with reuse mostly refreshes and follows the run that rebuilds every step
+#[test]
+fn reused_run() {
+	let mut p = Params::default();
+	p.dt = 1e-3;
+	let mut reuse = Tree::new(0.5);
+	reuse.reuse = Some(0.5);
+	let mut a = Simulation::with_solver(stars(500), p.clone(), Box::new(reuse));
+	let mut b = Simulation::with_solver(stars(500), p, Box::new(Tree::new(0.5)));
+	for _ in 0..50 {
+		a.step();
+		b.step();
+	}
+	let de = (a.energies()[0] - b.energies()[0]).abs()/b.energies()[0].abs();
+	assert!(de < 1e-4, "{}", de);
+	let mut x = Tree::new(0.5);
+	x.reuse = Some(0.5);
+	let mut s = stars(500);
+	for _ in 0..20 {
+		x.accelerations(&mut s, &Params::default());
+		drift(&mut s, 1e-3);
+	}
+	assert!(x.builds < 5 && x.builds + x.refreshes == 20, "{} builds, {} refreshes", x.builds, x.refreshes);
+}
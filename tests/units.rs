@@ -0,0 +1,37 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::constants::Constants;
+use nbabel::units::Units;
+
+// With Msun and au the time unit is about a year over 2 pi (a Julian year is
+// not quite the Earth's orbital period) and the Earth moves at about 1
+#[test]
+fn solar_units() {
+	let u = Units::parse("Msun,au,au/yr", Constants::default()).unwrap();
+	assert!((u.myr(2.0*std::f64::consts::PI)*1e6 - 1.0).abs() < 1e-4);
+	let mut s: Vec<Star> = read_stars("0 1 0 0 0 0 0 0\n1 3e-6 1 0 0 0 6.283185307179586 0\n");
+	u.to_internal(&mut s);
+	assert!((s[1].v[1] - 1.0).abs() < 1e-4 && s[1].r[0] == 1.0 && s[0].m == 1.0);
+	let back = u.to_input(&s);
+	assert!((back[1].v[1] - 2.0*std::f64::consts::PI).abs() < 1e-12);
+}
+
+// Rescaled internal units still convert back to the same input values
+#[test]
+fn rescaled_round_trip() {
+	let u = Units::parse("Msun,pc,km/s", Constants::default()).unwrap().rescaled(1e3, 2.0);
+	let mut s: Vec<Star> = read_stars("0 500 1 2 3 4 5 6\n1 700 -1 0 2 -3 1 0\n");
+	let input = s.clone();
+	u.to_internal(&mut s);
+	assert!((s[0].m - 0.5).abs() < 1e-15 && (s[0].r[2] - 1.5).abs() < 1e-15);
+	for (a, b) in u.to_input(&s).iter().zip(&input) {
+		for k in 0..3 {
+			assert!((a.r[k] - b.r[k]).abs() < 1e-12 && (a.v[k] - b.v[k]).abs() < 1e-12);
+		}
+	}
+	// About 14.9 Myr per N-body time unit for a solar mass and a parsec
+	assert!((Units::parse("Msun,pc,nbody", Constants::default()).unwrap().myr(1.0) - 14.91).abs() < 0.01);
+	assert!(Units::parse("Msun,pc", Constants::default()).is_err());
+	assert!(Units::parse("Msun,ly,km/s", Constants::default()).is_err());
+}
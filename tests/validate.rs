@@ -0,0 +1,40 @@
+extern crate nbabel;
+
+use nbabel::validate::{validate, Issue};
+
+fn issue(line: usize, column: usize, message: &str) -> Issue {
+	Issue { line: line, column: column, message: String::from(message) }
+}
+
+#[test]
+fn clean_inputs() {
+	for name in ["input/input2k", "input/input16k"].iter() {
+		let issues = validate(&std::fs::read_to_string(name).unwrap());
+		assert!(issues.is_empty(), "{}: {}", name, issues[0]);
+	}
+	assert!(validate("# a comment\n\n0 1 0 0 0 0 0 0 extra\n").is_empty());
+}
+
+// Every problem is reported, not only the first
+#[test]
+fn problems() {
+	let text = "0 1 0 0 0 0 0 0\n\
+		1 0 1 0 0 0 0 0\n\
+		2 -1 2 0 0 0 0 0\n\
+		0 1 3 0 0 0 0 0\n\
+		4 1 -0 0 0 0 0 0\n\
+		5 1 x 0 0 NaN inf 0\n\
+		6 1 9 9 9\n\
+		7 1 8 8 8 0 0 0 extra\n";
+	assert_eq!(validate(text), vec![
+		issue(3, 3, "negative mass -1"),
+		issue(4, 1, "id 0 was already used on line 1"),
+		issue(5, 5, "same position as the star on line 1"),
+		issue(6, 5, "'x' is not a number"),
+		issue(6, 11, "NaN"),
+		issue(6, 15, "'inf' is not finite"),
+		issue(7, 10, "expected 8 columns (id m x y z vx vy vz), found 5"),
+		issue(8, 22, "9 columns where the first row has 8"),
+	]);
+	assert_eq!(validate(text)[0].to_string(), "line 3, column 3: negative mass -1");
+}
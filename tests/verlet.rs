@@ -0,0 +1,87 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::rng::Rng;
+use nbabel::solver::ForceSolver;
+use nbabel::verlet::Verlet;
+
+// The truncated, softened accelerations summed over every pair
+fn truncated(s: &Vec<Star>, p: &Params, rc: f64) -> Vec<[f64; 3]> {
+	let mut a = vec![[0.0; 3]; s.len()];
+	for i in 0..s.len() {
+		for j in 0..s.len() {
+			let d: Vec<f64> = (0..3).map(|k| s[j].r[k] - s[i].r[k]).collect();
+			let r2 = d[0]*d[0] + d[1]*d[1] + d[2]*d[2];
+			if j == i || r2 >= rc*rc {
+				continue;
+			}
+			let r2 = r2 + p.eps*p.eps;
+			for k in 0..3 {
+				a[i][k] += s[j].m*d[k]/(r2*r2.sqrt());
+			}
+		}
+	}
+	a
+}
+
+// The listed pairs give the truncated forces on any thread count, and all of them with a long cutoff
+#[test]
+fn matches_truncated_sum() {
+	let s = generate::cube(400, 2.0, &mut Rng::new(4));
+	let mut p = Params::default();
+	p.eps = 0.05;
+	let expected = truncated(&s, &p, 0.3);
+	for &threads in &[1, 4] {
+		p.threads = threads;
+		let mut x = s.clone();
+		Verlet::new(0.3, 0.06).accelerations(&mut x, &p);
+		for (star, a) in x.iter().zip(expected.iter()) {
+			for k in 0..3 {
+				assert!((star.a[k] - a[k]).abs() < 1e-10*(1.0 + a[k].abs()));
+			}
+		}
+	}
+	let mut x = s.clone();
+	let mut direct = s.clone();
+	Verlet::new(10.0, 1.0).accelerations(&mut x, &p);
+	acceleration(&mut direct, &p);
+	assert!(x.iter().zip(direct.iter()).all(|(x, y)| (0..3).all(|k| (x.a[k] - y.a[k]).abs() < 1e-10*(1.0 + y.a[k].abs()))));
+}
+
+// The list is kept until a star has moved half the skin
+#[test]
+fn rebuilds() {
+	let mut s = generate::cube(200, 2.0, &mut Rng::new(9));
+	let p = Params::default();
+	let mut x = Verlet::new(0.3, 0.1);
+	x.accelerations(&mut s, &p);
+	s[5].r[0] += 0.04;
+	x.accelerations(&mut s, &p);
+	assert_eq!(x.builds, 1);
+	s[5].r[0] += 0.02;
+	x.accelerations(&mut s, &p);
+	assert_eq!(x.builds, 2);
+}
+
+// A strongly softened warm box conserves the energy of the shifted potential
+#[test]
+fn conserves_energy() {
+	let mut s = generate::cube(300, 2.0, &mut Rng::new(2));
+	let mut rng = Rng::new(3);
+	for star in s.iter_mut() {
+		star.v = vec![0.2*rng.normal(), 0.2*rng.normal(), 0.2*rng.normal()];
+	}
+	let mut p = Params::default();
+	p.eps = 0.1;
+	p.dt = 1e-3;
+	let mut sim = Simulation::with_solver(s, p, Box::new(Verlet::new(0.5, 0.1)));
+	let e0 = sim.energies();
+	for _ in 0..500 {
+		sim.step();
+	}
+	let e = sim.energies();
+	assert!(((e[0] - e0[0])/e0[0]).abs() < 1e-4, "{:?} {:?}", e0, e);
+	// The field at a point is the truncated one too
+	let far = sim.potential_at(&[[100.0, 0.0, 0.0]]);
+	assert_eq!(far, vec![0.0]);
+}
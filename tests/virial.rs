@@ -0,0 +1,41 @@
+extern crate nbabel;
+
+use nbabel::*;
+use nbabel::generate;
+use nbabel::rng::Rng;
+
+fn star(m: f64, x: f64, vy: f64) -> Star {
+	Star { m: m, r: vec![x, 0.0, 0.0], v: vec![0.0, vy, 0.0], a: vec![0.0; 3], a0: vec![0.0; 3] }
+}
+
+#[test]
+fn rescaled() {
+	let mut s = generate::plummer(200, &mut Rng::new(3));
+	let e = energies(&s, &Params::default());
+	diagnostics::virialize(&mut s, &e, 0.5).unwrap();
+	let e = energies(&s, &Params::default());
+	assert!((diagnostics::virial_ratio(&e) - 0.5).abs() < 1e-9);
+	diagnostics::virialize(&mut s, &e, 0.0).unwrap();
+	assert!(s.iter().all(|x| x.v.iter().all(|&v| v == 0.0)));
+}
+
+// A negative Q, a cold system and one without binding energy are errors, and leave the velocities alone
+#[test]
+fn rejected() {
+	let p = Params::default();
+	let mut s = vec![star(0.5, -0.5, 0.1), star(0.5, 0.5, -0.1)];
+	let e = energies(&s, &p);
+	assert!(diagnostics::virialize(&mut s, &e, -1.0).is_err());
+
+	let mut cold = vec![star(0.5, -0.5, 0.0), star(0.5, 0.5, 0.0)];
+	let e = energies(&cold, &p);
+	assert!(diagnostics::virialize(&mut cold, &e, 0.5).unwrap_err().contains("cold"));
+
+	let unbound = [vec![star(1.0, 0.0, 0.3)], vec![star(0.0, -0.5, 0.3), star(0.0, 0.5, -0.3)]];
+	for s in unbound.iter() {
+		let mut s = s.clone();
+		let e = energies(&s, &p);
+		assert!(diagnostics::virialize(&mut s, &e, 0.5).unwrap_err().contains("binding"));
+		assert!(s.iter().all(|x| x.v[1].abs() == 0.3));
+	}
+}
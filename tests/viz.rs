@@ -0,0 +1,31 @@
+extern crate nbabel;
+
+use nbabel::diagnostics::specific_energies;
+use nbabel::generate;
+use nbabel::rng::Rng;
+use nbabel::{energies, Params};
+
+// The energies the viewer colors by: v^2/2 less the mass of the other star at separation 1
+#[test]
+fn binary() {
+	let s = generate::binary(1.0, 0.0, 1.0);
+	let p = Params::default();
+	let e = specific_energies(&s, &p);
+	let v2 = |i: usize| s[i].v.iter().map(|x| x*x).sum::<f64>();
+	for i in 0..2 {
+		assert!((e[i] - (0.5*v2(i) - s[1 - i].m/1.0)).abs() < 1e-12, "{:?}", e);
+	}
+}
+
+// Mass-weighted, the potential parts count every pair twice
+#[test]
+fn sums_to_total() {
+	let s = generate::king(100, 6.0, &mut Rng::new(5));
+	let mut p = Params::default();
+	p.eps = 0.05;
+	let e = specific_energies(&s, &p);
+	let total = energies(&s, &p);
+	let weighted: f64 = s.iter().zip(e.iter()).map(|(x, e)| x.m*e).sum();
+	assert!((weighted - (total[1] + 2.0*total[2])).abs() < 1e-12);
+	assert!(e.iter().any(|&x| x < 0.0));
+}
@@ -0,0 +1,86 @@
+extern crate nbabel;
+
+use std::fs;
+
+use nbabel::generate;
+use nbabel::vtk;
+use nbabel::{Params, Simulation};
+
+// The numbers between <DataArray Name="name" ...> and </DataArray>
+fn values(text: &str, name: &str) -> Vec<f64> {
+	let start = text.find(&format!("Name=\"{}\"", name)).unwrap();
+	let body = &text[start..];
+	let body = &body[body.find('>').unwrap() + 1..body.find("</DataArray>").unwrap()];
+	body.split_whitespace().map(|x| x.parse().unwrap()).collect()
+}
+
+#[test]
+fn snapshot() {
+	let s = generate::binary(1.0, 0.0, 1.0);
+	let mut text = vec![];
+	vtk::write_snapshot(&mut text, &s, &Params::default()).unwrap();
+	let text = String::from_utf8(text).unwrap();
+	assert!(text.contains("NumberOfPoints=\"2\" NumberOfVerts=\"2\""));
+	assert_eq!(values(&text, "position"), vec![s[0].r[0], s[0].r[1], s[0].r[2], s[1].r[0], s[1].r[1], s[1].r[2]]);
+	assert_eq!(values(&text, "mass"), vec![0.5, 0.5]);
+	assert_eq!(values(&text, "velocity").len(), 6);
+	// Equal masses 1 apart, v = 1/2 each on a circular orbit
+	for x in values(&text, "speed") {
+		assert!((x - 0.5).abs() < 1e-12);
+	}
+	assert_eq!(values(&text, "potential"), vec![-0.5, -0.5]);
+	assert_eq!(values(&text, "connectivity"), vec![0.0, 1.0]);
+	assert_eq!(values(&text, "offsets"), vec![1.0, 2.0]);
+}
+
+#[test]
+fn optional_fields() {
+	let s = generate::binary(1.0, 0.0, 1.0);
+	let mut text = vec![];
+	vtk::write_snapshot_with(&mut text, &s, &Params::default(), &vtk::Fields { density: None, energies: true, accelerations: false }).unwrap();
+	let text = String::from_utf8(text).unwrap();
+	assert!(!text.contains("Name=\"density\""));
+	for x in values(&text, "kinetic") {
+		assert!((x - 0.125).abs() < 1e-12);
+	}
+	// Bound: 1/8 - 1/2
+	for x in values(&text, "energy") {
+		assert!((x + 0.375).abs() < 1e-12);
+	}
+}
+
+// On a circular orbit the acceleration turns at the orbital frequency, so |jerk| = omega |a|
+#[test]
+fn accelerations_and_jerks() {
+	let sim = Simulation::new(generate::binary(1.0, 0.0, 1.0), Params::default());
+	let mut text = vec![];
+	vtk::write_snapshot_with(&mut text, &sim.s, &sim.p, &vtk::Fields { density: None, energies: false, accelerations: true }).unwrap();
+	let text = String::from_utf8(text).unwrap();
+	let (a, j) = (values(&text, "acceleration"), values(&text, "jerk"));
+	assert_eq!((a.len(), j.len()), (6, 6));
+	for i in 0..2 {
+		let (a, j) = (&a[3*i..3*i + 3], &j[3*i..3*i + 3]);
+		let norm = |x: &[f64]| (x[0]*x[0] + x[1]*x[1] + x[2]*x[2]).sqrt();
+		assert!((norm(a) - 0.5).abs() < 1e-12 && (norm(j) - 0.5).abs() < 1e-12, "{:?} {:?}", a, j);
+		assert!((a[0]*j[0] + a[1]*j[1] + a[2]*j[2]).abs() < 1e-12);
+	}
+}
+
+#[test]
+fn series() {
+	let dir = std::env::temp_dir().join(format!("nbabel-vtk-{}", std::process::id()));
+	let s = generate::binary(1.0, 0.0, 1.0);
+	let p = Params::default();
+	let mut series = vtk::Series::new(&dir).unwrap();
+	series.write(0.0, &s, &p).unwrap();
+	series.write(0.5, &s, &p).unwrap();
+	// The final state coming right after a diagnostic at the same time
+	series.write(0.5, &s, &p).unwrap();
+	assert_eq!(series.times, vec![0.0, 0.5]);
+	let pvd = fs::read_to_string(dir.join("vtk/snapshots.pvd")).unwrap();
+	assert!(pvd.contains("<DataSet timestep=\"0\" group=\"\" part=\"0\" file=\"snapshot_000000.vtp\"/>"));
+	assert!(pvd.contains("<DataSet timestep=\"0.5\" group=\"\" part=\"0\" file=\"snapshot_000001.vtp\"/>"));
+	assert!(dir.join("vtk/snapshot_000001.vtp").exists());
+	assert!(!dir.join("vtk/snapshot_000002.vtp").exists());
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,73 @@
+extern crate nbabel;
+
+use nbabel::generate;
+use nbabel::pairs;
+use nbabel::rng::Rng;
+use nbabel::{acceleration, Params};
+
+// The browser has no threads: one thread must visit every pair on the calling thread
+#[test]
+fn single_thread_visits_all_pairs() {
+	let s = generate::king(50, 6.0, &mut Rng::new(3));
+	let caller = std::thread::current().id();
+	let mut seen = 0;
+	let mut calls = 0;
+	pairs::par_for_each_pair(&s, 1, 0usize, |n, _, _, _, _| {
+		assert_eq!(std::thread::current().id(), caller);
+		*n += 1;
+	}, |thread_index, n, _| {
+		assert_eq!(thread_index, 0);
+		seen += n;
+		calls += 1;
+	});
+	assert_eq!(calls, 1);
+	assert_eq!(seen, 50*49/2);
+}
+
+#[test]
+fn serial_forces_match_threaded() {
+	let mut a = generate::king(200, 6.0, &mut Rng::new(4));
+	let mut b = generate::king(200, 6.0, &mut Rng::new(4));
+	let mut p = Params::default();
+	p.eps = 0.01;
+	p.threads = 1;
+	acceleration(&mut a, &p);
+	p.threads = 4;
+	acceleration(&mut b, &p);
+	for (x, y) in a.iter().zip(b.iter()) {
+		for k in 0..3 {
+			assert!((x.a[k] - y.a[k]).abs() < 1e-12*(1.0 + x.a[k].abs()));
+		}
+	}
+}
+
+#[cfg(feature = "wasm")]
+mod demo {
+	use nbabel::wasm::Demo;
+
+	#[test]
+	fn king_cluster() {
+		let mut demo = Demo::new(64, 1, 1e-3, 0.01).unwrap();
+		assert_eq!(demo.count(), 64);
+		assert_eq!(demo.positions().len(), 192);
+		assert!((demo.masses().iter().sum::<f64>() - 1.0).abs() < 1e-12);
+		let e0 = demo.energy();
+		demo.step(100);
+		assert!((demo.time() - 0.1).abs() < 1e-9);
+		assert!(((demo.energy() - e0)/e0).abs() < 1e-4);
+	}
+
+	#[test]
+	fn from_particles() {
+		let demo = Demo::from_particles(&[0.5, 0.5], &[-0.5, 0.0, 0.0, 0.5, 0.0, 0.0], &[0.0, -0.5, 0.0, 0.0, 0.5, 0.0], 1e-3, 0.0).unwrap();
+		assert!((demo.energy() + 0.125).abs() < 1e-15);
+		assert_eq!(demo.velocities(), vec![0.0, -0.5, 0.0, 0.0, 0.5, 0.0]);
+	}
+
+	#[test]
+	fn bad_arguments() {
+		assert!(Demo::new(1, 1, 1e-3, 0.0).is_err());
+		assert!(Demo::new(10, 1, 0.0, 0.0).is_err());
+		assert!(Demo::from_particles(&[1.0], &[0.0; 2], &[0.0; 3], 1e-3, 0.0).is_err());
+	}
+}